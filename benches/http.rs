@@ -0,0 +1,164 @@
+//! Benchmarks for request parsing, routing and end-to-end throughput.
+//!
+//! Run with `cargo bench`, optionally followed by a filter such as `cargo bench -- parse`.
+//! Each benchmark runs for about a second after a short warm-up and reports the mean time
+//! per iteration, so compare runs on the same machine only.
+
+use std::hint::black_box;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use http_server::http::parser::parse;
+use http_server::testing::TestServer;
+use http_server::{Request, Response, Router, StatusCode};
+
+/// How long each benchmark is measured for.
+const MEASURE_FOR: Duration = Duration::from_secs(1);
+
+/// How long each benchmark runs before measuring starts.
+const WARM_UP_FOR: Duration = Duration::from_millis(200);
+
+const SIMPLE_REQUEST: &[u8] = b"GET /hello HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+const BROWSER_REQUEST: &[u8] = b"GET /users/42/posts?limit=20&offset=40 HTTP/1.1\r\n\
+    Host: example.com\r\n\
+    User-Agent: Mozilla/5.0 (X11; Linux x86_64; rv:124.0) Gecko/20100101 Firefox/124.0\r\n\
+    Accept: text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8\r\n\
+    Accept-Language: en-US,en;q=0.5\r\n\
+    Accept-Encoding: gzip, deflate, br\r\n\
+    Cookie: session=0123456789abcdef.fedcba9876543210; theme=dark\r\n\
+    Connection: keep-alive\r\n\r\n";
+
+fn main() {
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+    let enabled = |name: &str| filter.as_deref().is_none_or(|filter| name.contains(filter));
+
+    if enabled("parse/simple") {
+        bench("parse/simple", || parse(black_box(SIMPLE_REQUEST)).unwrap());
+    }
+    if enabled("parse/browser") {
+        bench("parse/browser", || {
+            parse(black_box(BROWSER_REQUEST)).unwrap()
+        });
+    }
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let router = router();
+    for (name, path) in [
+        ("route/static", "/hello"),
+        ("route/params", "/users/42/posts/7"),
+        ("route/not_found", "/missing/page"),
+    ] {
+        if enabled(name) {
+            bench(name, || {
+                let request = Request::builder().path(path).build();
+                runtime.block_on(router.oneshot(request))
+            });
+        }
+    }
+
+    if enabled("server/plaintext") {
+        let _guard = runtime.enter();
+        let server = TestServer::spawn(router).unwrap();
+        let mut connection = Connection::open(&server);
+        bench("server/plaintext", || connection.get("/hello"));
+        drop(connection);
+        runtime.block_on(server.shutdown()).unwrap();
+    }
+}
+
+/// Runs `f` repeatedly and prints the mean time per call.
+fn bench<T>(name: &str, mut f: impl FnMut() -> T) {
+    let warm_up = Instant::now();
+    while warm_up.elapsed() < WARM_UP_FOR {
+        black_box(f());
+    }
+
+    let mut iterations = 0u64;
+    let started = Instant::now();
+    while started.elapsed() < MEASURE_FOR {
+        // Check the clock every few calls, so it doesn't dominate fast benchmarks
+        for _ in 0..64 {
+            black_box(f());
+        }
+        iterations += 64;
+    }
+    let per_iteration = started.elapsed() / iterations as u32;
+    println!(
+        "{:<20} {:>12?}/iter {:>12.0} iter/s",
+        name,
+        per_iteration,
+        1.0 / per_iteration.as_secs_f64()
+    );
+}
+
+fn router() -> Router {
+    Router::new()
+        .get("/hello", plaintext)
+        .get("/users/:id", plaintext)
+        .get("/users/:id/posts", plaintext)
+        .get("/users/:id/posts/:post", plaintext)
+        .get("/static/*", plaintext)
+}
+
+async fn plaintext(_request: Request) -> Result<Response, String> {
+    let mut response = Response::new(StatusCode::OK);
+    response.set_content_type("text/plain");
+    response.set_body(b"Hello, World!".to_vec());
+    Ok(response)
+}
+
+/// A keep-alive connection sending one request at a time, as a load generator would.
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    line: String,
+}
+
+impl Connection {
+    fn open(server: &TestServer) -> Self {
+        let stream = TcpStream::connect(server.addr()).unwrap();
+        stream.set_nodelay(true).unwrap();
+        Connection {
+            writer: stream.try_clone().unwrap(),
+            reader: BufReader::new(stream),
+            line: String::new(),
+        }
+    }
+
+    /// Sends a `GET` request and reads the response.
+    ///
+    /// # Returns
+    ///
+    /// The length of the response body.
+    fn get(&mut self, path: &str) -> usize {
+        write!(
+            self.writer,
+            "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            path
+        )
+        .unwrap();
+
+        let mut content_length = 0;
+        loop {
+            self.line.clear();
+            self.reader.read_line(&mut self.line).unwrap();
+            let line = self.line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':')
+                && name.eq_ignore_ascii_case("Content-Length")
+            {
+                content_length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; content_length];
+        self.reader.read_exact(&mut body).unwrap();
+        body.len()
+    }
+}
@@ -0,0 +1,70 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory bundled into the binary when `HTTP_SERVER_EMBED_DIR` isn't set.
+const DEFAULT_EMBED_DIR: &str = "public";
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=HTTP_SERVER_EMBED_DIR");
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let embed_dir = manifest_dir
+        .join(env::var("HTTP_SERVER_EMBED_DIR").unwrap_or_else(|_| DEFAULT_EMBED_DIR.to_string()));
+    println!("cargo:rerun-if-changed={}", embed_dir.display());
+
+    let mut files = Vec::new();
+    if embed_dir.is_dir() {
+        collect_files(&embed_dir, &embed_dir, &mut files).expect("failed to read embed directory");
+    }
+    files.sort();
+
+    let mut out = String::from("pub static ASSETS: &[EmbeddedFile] = &[\n");
+    for (relative, path) in &files {
+        println!("cargo:rerun-if-changed={}", path.display());
+        let contents = fs::read(path).expect("failed to read embedded file");
+        out.push_str(&format!(
+            "    EmbeddedFile {{ path: {:?}, contents: include_bytes!({:?}), etag: \"\\\"{:016x}\\\"\" }},\n",
+            relative,
+            path.display().to_string(),
+            fnv1a(&contents)
+        ));
+    }
+    out.push_str("];\n");
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join("embedded_assets.rs"), out).expect("failed to write embedded assets");
+}
+
+/// Collects the files under `dir`, skipping hidden ones, with their `/`-separated paths
+/// relative to `root`.
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        println!("cargo:rerun-if-changed={}", path.display());
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if path.is_file() {
+            let relative = path
+                .strip_prefix(root)
+                .unwrap()
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push((relative, path));
+        }
+    }
+    Ok(())
+}
+
+/// Hashes file contents for the ETag, so it only changes when the contents do.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
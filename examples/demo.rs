@@ -1,30 +1,31 @@
-pub mod http;
-mod middleware;
-mod router;
-mod server;
-mod service;
+use http_server::health::Health;
+use http_server::server::{DrainControl, ServerMetrics};
+use http_server::static_files::StaticFiles;
+use http_server::{Request, Response, Router, StatusCode, new_server};
 
-use http::{Request, Response, StatusCode};
-use router::Router;
-use server::new_server;
-use std::fs;
-use std::path::Path;
+fn main() {
+    // Health endpoints report not ready once the server starts draining
+    let drain = DrainControl::new();
+    let health = Health::new().with_drain_control(drain.clone());
+    let metrics = ServerMetrics::new();
 
-#[tokio::main]
-async fn main() {
     // Create a router with routes
     let router = Router::new()
         .get("/", handle_index)
         .get("/hello", handle_hello)
         .get("/users/:id", handle_user)
         .post("/users", handle_create_user)
-        .get("/static/*", handle_static)
+        .get("/static/*", StaticFiles::new("public").handler())
+        .get("/metrics", metrics.handler())
         .set_not_found_handler(handle_not_found);
+    let router = health.routes(router);
 
-    // Create and start the server
-    let server = new_server("127.0.0.1:8080", router);
+    // Create and start the server on its own runtime
+    let server = new_server("127.0.0.1:8080", router)
+        .with_drain_control(drain)
+        .with_metrics(metrics);
 
-    if let Err(e) = server.listen() {
+    if let Err(e) = server.run() {
         eprintln!("Server error: {}", e);
     }
 }
@@ -77,41 +78,6 @@ async fn handle_create_user(_request: Request) -> Result<Response, String> {
     Ok(response)
 }
 
-async fn handle_static(request: Request) -> Result<Response, String> {
-    // Extract the file path from the wildcard
-    let path = request.path.strip_prefix("/static/").unwrap_or("");
-    let file_path = format!("public/{}", path);
-
-    // Try to read the file
-    match fs::read(&file_path) {
-        Ok(content) => {
-            let mut response = Response::new(StatusCode::OK);
-
-            // Set content type based on file extension
-            let content_type = match Path::new(&file_path).extension().and_then(|e| e.to_str()) {
-                Some("html") => "text/html",
-                Some("css") => "text/css",
-                Some("js") => "application/javascript",
-                Some("jpg") | Some("jpeg") => "image/jpeg",
-                Some("png") => "image/png",
-                Some("gif") => "image/gif",
-                _ => "application/octet-stream",
-            };
-
-            response.set_content_type(content_type);
-            response.set_body(content);
-            Ok(response)
-        }
-        Err(_) => {
-            // File not found
-            let mut response = Response::new(StatusCode::NotFound);
-            response.set_content_type("text/html");
-            response.set_body(b"<html><body><h1>404 - File Not Found</h1></body></html>".to_vec());
-            Ok(response)
-        }
-    }
-}
-
 async fn handle_not_found(_request: Request) -> Result<Response, String> {
     let mut response = Response::new(StatusCode::NotFound);
     response.set_content_type("text/html");
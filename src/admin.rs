@@ -0,0 +1,526 @@
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::{DateTime, Utc};
+
+use crate::crypto::{constant_time_eq, random_bytes, to_hex};
+use crate::http::{Request, Response, StatusCode};
+use crate::logging;
+use crate::middleware::host::HostLayer;
+use crate::router::{ErrorReport, RouteInfo, RouteTable, Router};
+use crate::server::{DrainControl, Server, ServerMetrics};
+use crate::static_files::html_escape;
+
+/// Boxed future returned by the dashboard handlers.
+type ResponseFuture = Pin<Box<dyn Future<Output = Result<Response, String>> + Send>>;
+
+/// Type alias for the reload action.
+type ReloadFn = dyn Fn() -> Result<(), String> + Send + Sync;
+
+/// How many recent errors the dashboard keeps.
+const MAX_ERRORS: usize = 20;
+
+/// How many routes each of the top routes tables lists.
+const TOP_ROUTES: usize = 10;
+
+/// How many unused CSRF tokens are kept; the oldest is forgotten when another page is served.
+const MAX_CSRF_TOKENS: usize = 64;
+
+/// How long a page's CSRF token can be used to post an action.
+const CSRF_TOKEN_LIFETIME: Duration = Duration::from_secs(10 * 60);
+
+/// A small HTML status page for operators, meant to be served on its own admin listener.
+///
+/// The page shows the server's uptime and connection and request gauges, the busiest and
+/// slowest routes, the most recent failed requests, and buttons to start draining and to
+/// run a reload action, when those are configured. It refreshes itself every few seconds.
+///
+/// The page offers no authentication, so bind its listener to a loopback or private address.
+/// Requests must name a loopback host (`localhost`, `127.0.0.1` or `[::1]`) or one added with
+/// [`allow_host`](AdminDashboard::allow_host) in their `Host` header, which keeps pages on
+/// other sites from reaching the dashboard through DNS rebinding. The buttons only act on
+/// same-origin posts carrying the token of a page the dashboard served.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Router, Server};
+/// # use http_server::admin::AdminDashboard;
+/// # use http_server::server::{DrainControl, ServerMetrics};
+/// # struct Config;
+/// # impl Config { fn reload(&self) -> Result<(), String> { Ok(()) } }
+/// # async fn example(router: Router, config: &'static Config) -> Result<(), String> {
+/// let metrics = ServerMetrics::new();
+/// let drain = DrainControl::new();
+/// let dashboard = AdminDashboard::new(metrics.clone())
+///     .with_drain_control(drain.clone())
+///     .on_reload(|| config.reload());
+/// let router = dashboard.watch(router);
+///
+/// tokio::spawn(dashboard.listen("127.0.0.1:9090").serve());
+/// Server::new("0.0.0.0:8080", router)
+///     .with_metrics(metrics)
+///     .with_drain_control(drain)
+///     .serve()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AdminDashboard {
+    metrics: ServerMetrics,
+    drain: Option<DrainControl>,
+    reload: Option<Arc<ReloadFn>>,
+    routes: Arc<Mutex<Option<RouteTable>>>,
+    errors: Arc<Mutex<VecDeque<(SystemTime, ErrorReport)>>>,
+    hosts: HostLayer,
+    /// CSRF tokens of served pages that haven't been used yet, with when they were issued.
+    csrf_tokens: Arc<Mutex<VecDeque<(Instant, String)>>>,
+}
+
+impl AdminDashboard {
+    /// Creates a dashboard showing the gauges in `metrics`, which should be attached to the
+    /// server with [`Server::with_metrics`].
+    pub fn new(metrics: ServerMetrics) -> Self {
+        AdminDashboard {
+            metrics,
+            drain: None,
+            reload: None,
+            routes: Arc::new(Mutex::new(None)),
+            errors: Arc::new(Mutex::new(VecDeque::new())),
+            hosts: HostLayer::new()
+                .allow("localhost")
+                .allow("127.0.0.1")
+                .allow("[::1]"),
+            csrf_tokens: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    /// Also serves requests for hosts matching `pattern`, e.g. the private address or internal
+    /// name the admin listener is reached by.
+    pub fn allow_host(mut self, pattern: &str) -> Self {
+        self.hosts = self.hosts.allow(pattern);
+        self
+    }
+
+    /// Adds a button that starts draining through `control`.
+    pub fn with_drain_control(mut self, control: DrainControl) -> Self {
+        self.drain = Some(control);
+        self
+    }
+
+    /// Adds a button that runs `reload`, e.g. to re-read configuration.
+    pub fn on_reload<F>(mut self, reload: F) -> Self
+    where
+        F: Fn() -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.reload = Some(Arc::new(reload));
+        self
+    }
+
+    /// Starts showing the route statistics and failed requests of `router`.
+    ///
+    /// Call it once all routes are registered; routes added afterwards aren't shown.
+    ///
+    /// # Returns
+    ///
+    /// The router, with an error hook that records failed requests for the dashboard.
+    pub fn watch(&self, router: Router) -> Router {
+        let errors = self.errors.clone();
+        let router = router.on_error(move |report| {
+            let mut errors = errors.lock().unwrap();
+            if errors.len() == MAX_ERRORS {
+                errors.pop_front();
+            }
+            errors.push_back((SystemTime::now(), report.clone()));
+        });
+        *self.routes.lock().unwrap() = Some(router.route_stats());
+        router
+    }
+
+    /// Returns a router serving the page at `/`, with the buttons posting to `/drain` and
+    /// `/reload`.
+    pub fn router(&self) -> Router {
+        let page = self.clone();
+        let drain = self.clone();
+        let reload = self.clone();
+        Router::new()
+            .get("/", move |req| page.page(&req))
+            .post("/drain", move |req| {
+                let control = drain.drain.clone();
+                drain.action(req, move || match control {
+                    Some(control) => {
+                        logging::info("admin", "Draining requested from the dashboard", &[]);
+                        control.drain();
+                        "draining"
+                    }
+                    None => "unavailable",
+                })
+            })
+            .post("/reload", move |req| {
+                let action = reload.reload.clone();
+                reload.action(req, move || match action {
+                    Some(action) => match action() {
+                        Ok(()) => {
+                            logging::info("admin", "Reloaded from the dashboard", &[]);
+                            "reloaded"
+                        }
+                        Err(e) => {
+                            logging::error("admin", "Reload failed", &[("error", &e)]);
+                            "reload-failed"
+                        }
+                    },
+                    None => "unavailable",
+                })
+            })
+    }
+
+    /// Creates the admin listener: a server on `address` serving the dashboard.
+    pub fn listen(&self, address: &str) -> Server<Router> {
+        Server::new(address, self.router())
+    }
+
+    fn page(&self, req: &Request) -> ResponseFuture {
+        if let Err(status) = self.hosts.check(req) {
+            return refuse(status);
+        }
+        let token = match self.issue_csrf_token() {
+            Ok(token) => token,
+            Err(e) => return Box::pin(async move { Err(e) }),
+        };
+        // Set by the buttons' redirects; only known values are shown
+        let notice = match req.query_param("done").map(|done| done.as_str()) {
+            Some("draining") => Some("Draining started."),
+            Some("reloaded") => Some("Reloaded."),
+            Some("reload-failed") => Some("Reload failed; see the server log."),
+            Some("unavailable") => Some("That action isn't configured."),
+            _ => None,
+        };
+        let mut response = Response::new(StatusCode::OK);
+        response.set_content_type("text/html; charset=utf-8");
+        response
+            .headers
+            .insert("Cache-Control".to_string(), "no-store".to_string());
+        response.set_body(self.render(notice, &token).into_bytes());
+        Box::pin(async move { Ok(response) })
+    }
+
+    /// Creates a CSRF token for a page, forgetting the oldest if too many are unused.
+    fn issue_csrf_token(&self) -> Result<String, String> {
+        let token = to_hex(&random_bytes(16)?);
+        let mut tokens = self.csrf_tokens.lock().unwrap();
+        if tokens.len() == MAX_CSRF_TOKENS {
+            tokens.pop_front();
+        }
+        tokens.push_back((Instant::now(), token.clone()));
+        Ok(token)
+    }
+
+    /// Uses up `token`, if the dashboard issued it and it hasn't expired.
+    fn redeem_csrf_token(&self, token: &str) -> bool {
+        let mut tokens = self.csrf_tokens.lock().unwrap();
+        tokens.retain(|(issued, _)| issued.elapsed() < CSRF_TOKEN_LIFETIME);
+        let found = tokens
+            .iter()
+            .position(|(_, issued)| constant_time_eq(issued.as_bytes(), token.as_bytes()));
+        found.and_then(|index| tokens.remove(index)).is_some()
+    }
+
+    /// Runs a button's action and sends the browser back to the page.
+    ///
+    /// The page has no authentication of its own, so the post must be for an allowed host,
+    /// come from the dashboard's own origin, and carry the token of a page it served.
+    fn action<F>(&self, req: Request, run: F) -> ResponseFuture
+    where
+        F: FnOnce() -> &'static str,
+    {
+        if let Err(status) = self.hosts.check(&req) {
+            return refuse(status);
+        }
+        let same_origin = match (req.header("Origin"), req.header("Host")) {
+            (Some(origin), Some(host)) => origin
+                .split_once("://")
+                .is_some_and(|(_, origin_host)| origin_host == host),
+            _ => false,
+        };
+        let token = form_field(&req.body, "csrf").unwrap_or_default();
+        if !same_origin || !self.redeem_csrf_token(&token) {
+            return refuse(StatusCode::Forbidden);
+        }
+
+        let outcome = run();
+        let mut response = Response::new(StatusCode::SeeOther);
+        response
+            .headers
+            .insert("Location".to_string(), format!("/?done={}", outcome));
+        response.set_body(Vec::new());
+        Box::pin(async move { Ok(response) })
+    }
+
+    /// Renders the status page, with `token` in the buttons' forms.
+    fn render(&self, notice: Option<&str>, token: &str) -> String {
+        let stats = self.metrics.snapshot();
+        let routes = self.routes.lock().unwrap().clone();
+        let errors: Vec<_> = self.errors.lock().unwrap().iter().rev().cloned().collect();
+
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+             <meta http-equiv=\"refresh\" content=\"5; url=/\"><title>Server status</title>\
+             <style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;\
+             margin-bottom:1.5em}td,th{border:1px solid #ccc;padding:.3em .6em;text-align:left}\
+             form{display:inline}</style></head><body><h1>Server status</h1>\n",
+        );
+        if let Some(notice) = notice {
+            let _ = writeln!(html, "<p><strong>{}</strong></p>", notice);
+        }
+
+        let _ = writeln!(
+            html,
+            "<p>Uptime: {}{}</p>",
+            stats
+                .uptime
+                .map_or_else(|| "not started".to_string(), format_duration),
+            if self.drain.as_ref().is_some_and(DrainControl::is_draining) {
+                " &mdash; <strong>draining</strong>"
+            } else {
+                ""
+            }
+        );
+
+        html.push_str("<h2>Connections and requests</h2>\n<table>\n");
+        for (name, value) in [
+            ("Open connections", stats.open_connections as u64),
+            ("Idle connections", stats.idle_connections as u64),
+            ("In-flight requests", stats.in_flight_requests as u64),
+            ("Accepted connections", stats.accepted_connections),
+            ("Rejected connections", stats.rejected_connections),
+            ("Requests", stats.requests),
+        ] {
+            let _ = writeln!(html, "<tr><th>{}</th><td>{}</td></tr>", name, value);
+        }
+        html.push_str("</table>\n");
+
+        if let Some(routes) = routes {
+            let mut info = routes.info();
+            info.sort_by_key(|route| Reverse(route.hits));
+            route_table(&mut html, "Top routes by traffic", &info);
+            info.retain(|route| route.mean_latency.is_some());
+            info.sort_by_key(|route| Reverse(route.mean_latency));
+            route_table(&mut html, "Slowest routes", &info);
+            let _ = writeln!(
+                html,
+                "<p>Requests matching no route: {}</p>",
+                routes.not_found_hits()
+            );
+        }
+
+        html.push_str("<h2>Recent errors</h2>\n");
+        if errors.is_empty() {
+            html.push_str("<p>None</p>\n");
+        } else {
+            html.push_str(
+                "<table>\n<tr><th>Time</th><th>Request</th><th>Route</th><th>Status</th>\
+                 <th>Error</th><th>Request ID</th></tr>\n",
+            );
+            for (time, report) in errors {
+                let _ = writeln!(
+                    html,
+                    "<tr><td>{}</td><td>{} {}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    DateTime::<Utc>::from(time).format("%Y-%m-%d %H:%M:%S"),
+                    report.method,
+                    html_escape(&report.path),
+                    html_escape(report.route.as_deref().unwrap_or("-")),
+                    report.status,
+                    html_escape(report.error.as_deref().unwrap_or("-")),
+                    html_escape(report.request_id.as_deref().unwrap_or("-")),
+                );
+            }
+            html.push_str("</table>\n");
+        }
+
+        if self.drain.is_some() || self.reload.is_some() {
+            let token = format!("<input type=\"hidden\" name=\"csrf\" value=\"{}\">", token);
+            html.push_str("<h2>Actions</h2>\n<p>");
+            if self.drain.is_some() {
+                let _ = write!(
+                    html,
+                    "<form method=\"post\" action=\"/drain\" \
+                     onsubmit=\"return confirm('Start draining?')\">{}\
+                     <button>Drain</button></form> ",
+                    token
+                );
+            }
+            if self.reload.is_some() {
+                let _ = write!(
+                    html,
+                    "<form method=\"post\" action=\"/reload\">{}<button>Reload</button></form>",
+                    token
+                );
+            }
+            html.push_str("</p>\n");
+        }
+
+        html.push_str("</body></html>\n");
+        html
+    }
+}
+
+/// Adds a table of up to `TOP_ROUTES` routes to the page.
+fn route_table(html: &mut String, title: &str, routes: &[RouteInfo]) {
+    let _ = writeln!(
+        html,
+        "<h2>{}</h2>\n<table>\n<tr><th>Route</th><th>Hits</th><th>Mean latency</th></tr>",
+        title
+    );
+    for route in routes.iter().take(TOP_ROUTES) {
+        let _ = writeln!(
+            html,
+            "<tr><td>{} {}</td><td>{}</td><td>{}</td></tr>",
+            route
+                .method
+                .as_ref()
+                .map_or_else(|| "*".to_string(), |method| method.to_string()),
+            html_escape(&route.pattern),
+            route.hits,
+            route.mean_latency.map_or_else(
+                || "-".to_string(),
+                |latency| format!("{:.2} ms", latency.as_secs_f64() * 1000.0)
+            ),
+        );
+    }
+    html.push_str("</table>\n");
+}
+
+/// Answers a refused request with a plain-text `status`.
+fn refuse(status: StatusCode) -> ResponseFuture {
+    let mut response = Response::new(status);
+    response.set_content_type("text/plain");
+    response.set_body(status.reason_phrase().as_bytes().to_vec());
+    Box::pin(async move { Ok(response) })
+}
+
+/// Returns the value of the field `name` in a form-encoded body, as sent.
+fn form_field(body: &[u8], name: &str) -> Option<String> {
+    String::from_utf8_lossy(body).split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then(|| value.to_string())
+    })
+}
+
+/// Formats a duration as days, hours, minutes and seconds, e.g. `2d 3h 4m 5s`.
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (days, hours, minutes) = (seconds / 86_400, seconds / 3600 % 24, seconds / 60 % 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}s", seconds % 60),
+        (0, 0, _) => format!("{}m {}s", minutes, seconds % 60),
+        (0, _, _) => format!("{}h {}m {}s", hours, minutes, seconds % 60),
+        _ => format!("{}d {}h {}m {}s", days, hours, minutes, seconds % 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Method;
+
+    fn dashboard() -> (AdminDashboard, DrainControl) {
+        let drain = DrainControl::new();
+        let dashboard = AdminDashboard::new(ServerMetrics::new()).with_drain_control(drain.clone());
+        (dashboard, drain)
+    }
+
+    /// Loads the page and returns the CSRF token in its forms.
+    async fn page_token(router: &Router) -> String {
+        let request = Request::builder().header("Host", "localhost:9090").build();
+        let response = router.oneshot(request).await;
+        assert_eq!(response.status_code, StatusCode::OK);
+        let html = String::from_utf8(response.body).unwrap();
+        let start = html.find("name=\"csrf\" value=\"").unwrap() + 19;
+        html[start..start + 32].to_string()
+    }
+
+    fn drain_request(host: &str, origin: Option<&str>, token: &str) -> Request {
+        let mut builder = Request::builder()
+            .method(Method::Post)
+            .path("/drain")
+            .header("Host", host)
+            .header("Content-Type", "application/x-www-form-urlencoded");
+        if let Some(origin) = origin {
+            builder = builder.header("Origin", origin);
+        }
+        builder.body(format!("csrf={}", token))
+    }
+
+    #[tokio::test]
+    async fn refuses_hosts_outside_the_allowlist() {
+        let (dashboard, _) = dashboard();
+        let router = dashboard.router();
+        let request = Request::builder().header("Host", "rebound.example").build();
+        assert_eq!(
+            router.oneshot(request).await.status_code,
+            StatusCode::MisdirectedRequest
+        );
+
+        let router = dashboard.allow_host("admin.internal").router();
+        let request = Request::builder()
+            .header("Host", "admin.internal:9090")
+            .build();
+        assert_eq!(router.oneshot(request).await.status_code, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn refuses_rebound_posts_even_with_matching_origin() {
+        let (dashboard, drain) = dashboard();
+        let router = dashboard.router();
+        let token = page_token(&router).await;
+        let request = drain_request(
+            "rebound.example:9090",
+            Some("http://rebound.example:9090"),
+            &token,
+        );
+        assert_eq!(
+            router.oneshot(request).await.status_code,
+            StatusCode::MisdirectedRequest
+        );
+        assert!(!drain.is_draining());
+    }
+
+    #[tokio::test]
+    async fn actions_need_origin_and_a_page_token() {
+        let (dashboard, drain) = dashboard();
+        let router = dashboard.router();
+        let token = page_token(&router).await;
+        let origin = Some("http://localhost:9090");
+
+        let without_origin = drain_request("localhost:9090", None, &token);
+        assert_eq!(
+            router.oneshot(without_origin).await.status_code,
+            StatusCode::Forbidden
+        );
+        let forged = drain_request("localhost:9090", origin, &"0".repeat(32));
+        assert_eq!(
+            router.oneshot(forged).await.status_code,
+            StatusCode::Forbidden
+        );
+        assert!(!drain.is_draining());
+
+        let valid = drain_request("localhost:9090", origin, &token);
+        assert_eq!(
+            router.oneshot(valid).await.status_code,
+            StatusCode::SeeOther
+        );
+        assert!(drain.is_draining());
+
+        // Tokens are single use
+        let replayed = drain_request("localhost:9090", origin, &token);
+        assert_eq!(
+            router.oneshot(replayed).await.status_code,
+            StatusCode::Forbidden
+        );
+    }
+}
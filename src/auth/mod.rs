@@ -0,0 +1,114 @@
+pub mod rbac;
+pub mod session;
+
+use serde_json::Value;
+
+use crate::http::Request;
+
+/// The authenticated identity behind a request.
+///
+/// Authentication middleware or handlers put it into the request's extensions once they know
+/// who the caller is; layers further in, like auditing and route guards, read it from there.
+/// Handlers that authenticate the caller themselves can also put it into the response's
+/// extensions so layers further out see it.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Request, auth::Principal};
+/// # let mut request = Request::builder().build();
+/// request.extensions.insert(Principal::new("user-42").with_role("admin"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Principal {
+    /// A stable identifier, e.g. a user ID or API key name.
+    pub id: String,
+    /// The roles granted to the principal, e.g. `admin`.
+    pub roles: Vec<String>,
+    /// The permissions granted to the principal, e.g. `users:write`.
+    pub permissions: Vec<String>,
+}
+
+impl Principal {
+    /// Creates a principal with the given identifier and no grants.
+    pub fn new(id: &str) -> Self {
+        Principal {
+            id: id.to_string(),
+            roles: Vec::new(),
+            permissions: Vec::new(),
+        }
+    }
+
+    /// Creates a principal from the claims of a token that has already been verified.
+    ///
+    /// The identifier is taken from `sub`, roles from a `roles` array, and permissions from a
+    /// `permissions` array and the space-separated `scope` string, as OAuth 2 access tokens
+    /// carry them.
+    ///
+    /// # Returns
+    ///
+    /// The principal, or `None` if the claims have no string `sub`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::{Request, auth::Principal};
+    /// # use serde_json::json;
+    /// # fn example(request: &mut Request) -> Option<()> {
+    /// let claims = json!({"sub": "user-42", "roles": ["admin"], "scope": "users:read"});
+    /// request.extensions.insert(Principal::from_claims(&claims)?);
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn from_claims(claims: &Value) -> Option<Self> {
+        let mut principal = Principal::new(claims["sub"].as_str()?);
+        principal.roles = strings(&claims["roles"]);
+        principal.permissions = strings(&claims["permissions"]);
+        if let Some(scope) = claims["scope"].as_str() {
+            principal
+                .permissions
+                .extend(scope.split_whitespace().map(str::to_string));
+        }
+        Some(principal)
+    }
+
+    /// Returns the principal a request was authenticated as, if any.
+    pub fn from_request(request: &Request) -> Option<&Principal> {
+        request.extensions.get::<Principal>()
+    }
+
+    /// Grants the principal a role.
+    pub fn with_role(mut self, role: &str) -> Self {
+        self.roles.push(role.to_string());
+        self
+    }
+
+    /// Grants the principal a permission.
+    pub fn with_permission(mut self, permission: &str) -> Self {
+        self.permissions.push(permission.to_string());
+        self
+    }
+
+    /// Returns whether the principal has been granted `role`.
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|granted| granted == role)
+    }
+
+    /// Returns whether the principal has been granted `permission`.
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|granted| granted == permission)
+    }
+}
+
+/// Collects the strings in a JSON array, ignoring anything else.
+fn strings(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,262 @@
+use crate::http::{Request, StatusCode};
+
+use super::Principal;
+
+/// The grants a route requires, declared with [`Router::require`](crate::router::Router::require).
+///
+/// A principal meets the requirement when it has at least one of the listed roles, if any are
+/// listed, and every listed permission.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::auth::rbac::Requirement;
+/// // Editors or admins who may also publish
+/// let requirement = Requirement::role("editor").or_role("admin").and_permission("publish");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Requirement {
+    /// Roles of which the principal needs at least one; empty if any role will do.
+    pub any_role: Vec<String>,
+    /// Permissions the principal needs all of.
+    pub all_permissions: Vec<String>,
+}
+
+impl Requirement {
+    /// Requires only that the request is authenticated.
+    pub fn authenticated() -> Self {
+        Requirement::default()
+    }
+
+    /// Requires the role `role`.
+    pub fn role(role: &str) -> Self {
+        Requirement::default().or_role(role)
+    }
+
+    /// Requires the permission `permission`.
+    pub fn permission(permission: &str) -> Self {
+        Requirement::default().and_permission(permission)
+    }
+
+    /// Accepts `role` as an alternative to the roles already listed.
+    pub fn or_role(mut self, role: &str) -> Self {
+        self.any_role.push(role.to_string());
+        self
+    }
+
+    /// Requires `permission` in addition to the permissions already listed.
+    pub fn and_permission(mut self, permission: &str) -> Self {
+        self.all_permissions.push(permission.to_string());
+        self
+    }
+
+    /// Returns whether `principal`'s grants meet the requirement.
+    pub fn is_met_by(&self, principal: &Principal) -> bool {
+        let has_role =
+            self.any_role.is_empty() || self.any_role.iter().any(|role| principal.has_role(role));
+        has_role
+            && self
+                .all_permissions
+                .iter()
+                .all(|permission| principal.has_permission(permission))
+    }
+}
+
+/// The outcome of an authorization check.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    /// The request may proceed to the handler.
+    Allow,
+    /// The request isn't authenticated; answered with `401 Unauthorized`.
+    Unauthenticated,
+    /// The principal lacks the required grants; answered with `403 Forbidden`.
+    Forbidden,
+}
+
+impl Decision {
+    /// Returns the status code a denied request is answered with, or `None` for `Allow`.
+    pub fn status_code(self) -> Option<StatusCode> {
+        match self {
+            Decision::Allow => None,
+            Decision::Unauthenticated => Some(StatusCode::Unauthorized),
+            Decision::Forbidden => Some(StatusCode::Forbidden),
+        }
+    }
+}
+
+/// Decides whether a request may reach a guarded route.
+///
+/// The router asks its policy, [`GrantsPolicy`] unless set with
+/// [`Router::policy`](crate::router::Router::policy), before calling the handler of any route
+/// with a requirement. Implement it for rules grants alone can't express, such as owners being
+/// allowed to edit their own resources. Closures taking the same arguments implement it too.
+pub trait Policy: Send + Sync {
+    /// Decides on a request to a route requiring `requirement`.
+    ///
+    /// # Arguments
+    ///
+    /// * `principal` - Who the request is authenticated as, if anyone.
+    /// * `request` - The request, with the route's path parameters.
+    /// * `requirement` - The grants the route declared.
+    fn authorize(
+        &self,
+        principal: Option<&Principal>,
+        request: &Request,
+        requirement: &Requirement,
+    ) -> Decision;
+}
+
+impl<F> Policy for F
+where
+    F: Fn(Option<&Principal>, &Request, &Requirement) -> Decision + Send + Sync,
+{
+    fn authorize(
+        &self,
+        principal: Option<&Principal>,
+        request: &Request,
+        requirement: &Requirement,
+    ) -> Decision {
+        self(principal, request, requirement)
+    }
+}
+
+/// The default policy: allows authenticated principals whose grants meet the requirement.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GrantsPolicy;
+
+impl Policy for GrantsPolicy {
+    fn authorize(
+        &self,
+        principal: Option<&Principal>,
+        _request: &Request,
+        requirement: &Requirement,
+    ) -> Decision {
+        match principal {
+            None => Decision::Unauthenticated,
+            Some(principal) if requirement.is_met_by(principal) => Decision::Allow,
+            Some(_) => Decision::Forbidden,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{Method, Response};
+    use crate::router::Router;
+
+    async fn ok(_request: Request) -> Result<Response, String> {
+        Ok(Response::new(StatusCode::OK))
+    }
+
+    fn router() -> Router {
+        Router::new()
+            .get("/public", ok)
+            .get("/profile", ok)
+            .require(Requirement::authenticated())
+            .post("/articles", ok)
+            .require(
+                Requirement::role("editor")
+                    .or_role("admin")
+                    .and_permission("publish"),
+            )
+    }
+
+    async fn status(
+        router: &Router,
+        method: Method,
+        path: &str,
+        principal: Option<Principal>,
+    ) -> u16 {
+        let mut builder = Request::builder().method(method).path(path);
+        if let Some(principal) = principal {
+            builder = builder.extension(principal);
+        }
+        router.oneshot(builder.build()).await.status_code as u16
+    }
+
+    #[test]
+    fn requirements_need_one_role_and_every_permission() {
+        let requirement = Requirement::role("editor")
+            .or_role("admin")
+            .and_permission("publish")
+            .and_permission("review");
+        let admin = Principal::new("a").with_role("admin");
+        assert!(!requirement.is_met_by(&admin));
+        assert!(
+            requirement.is_met_by(
+                &admin
+                    .clone()
+                    .with_permission("publish")
+                    .with_permission("review")
+            )
+        );
+        assert!(
+            !requirement.is_met_by(
+                &Principal::new("v")
+                    .with_role("viewer")
+                    .with_permission("publish")
+                    .with_permission("review")
+            )
+        );
+        assert!(Requirement::authenticated().is_met_by(&Principal::new("anyone")));
+    }
+
+    #[tokio::test]
+    async fn guarded_routes_refuse_missing_or_insufficient_grants() {
+        let router = router();
+        assert_eq!(status(&router, Method::Get, "/public", None).await, 200);
+        assert_eq!(status(&router, Method::Get, "/profile", None).await, 401);
+        let user = Principal::new("user");
+        assert_eq!(
+            status(&router, Method::Get, "/profile", Some(user.clone())).await,
+            200
+        );
+
+        assert_eq!(status(&router, Method::Post, "/articles", None).await, 401);
+        assert_eq!(
+            status(&router, Method::Post, "/articles", Some(user)).await,
+            403
+        );
+        let editor = Principal::new("editor").with_role("editor");
+        assert_eq!(
+            status(&router, Method::Post, "/articles", Some(editor.clone())).await,
+            403
+        );
+        let publisher = editor.with_permission("publish");
+        assert_eq!(
+            status(&router, Method::Post, "/articles", Some(publisher)).await,
+            200
+        );
+    }
+
+    #[tokio::test]
+    async fn custom_policies_decide_instead_of_grants() {
+        let router = Router::new()
+            .get("/users/:id", ok)
+            .require(Requirement::role("admin"))
+            .policy(
+                |principal: Option<&Principal>, request: &Request, requirement: &Requirement| {
+                    match principal {
+                        None => Decision::Unauthenticated,
+                        Some(p)
+                            if requirement.is_met_by(p) || request.param("id") == Some(&p.id) =>
+                        {
+                            Decision::Allow
+                        }
+                        Some(_) => Decision::Forbidden,
+                    }
+                },
+            );
+        let owner = Principal::new("42");
+        assert_eq!(
+            status(&router, Method::Get, "/users/42", Some(owner.clone())).await,
+            200
+        );
+        assert_eq!(
+            status(&router, Method::Get, "/users/7", Some(owner)).await,
+            403
+        );
+        assert_eq!(status(&router, Method::Get, "/users/7", None).await, 401);
+    }
+}
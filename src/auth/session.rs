@@ -0,0 +1,458 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::crypto::{constant_time_eq, from_hex, random_bytes, sha256, to_hex};
+use crate::http::cookie::{Cookie, SignedCookies};
+use crate::http::{Request, Response};
+use crate::logging;
+
+/// Name of the cookie holding remember-me tokens.
+const REMEMBER_COOKIE: &str = "remember";
+
+/// A logged-in session.
+struct Session {
+    user_id: String,
+    last_seen: Instant,
+}
+
+/// A remember-me token, stored by its selector with only a hash of its validator.
+struct RememberToken {
+    user_id: String,
+    validator_hash: [u8; 32],
+    expires: Instant,
+}
+
+/// Keeps track of who is logged in, with sessions identified by a signed cookie and optional
+/// long-lived remember-me tokens.
+///
+/// Logging in always starts a session with a new ID, so an attacker who planted a session
+/// cookie in the victim's browser beforehand (session fixation) isn't logged in with them.
+/// Call [`rotate`](Sessions::rotate) on other privilege changes for the same reason.
+///
+/// Remember-me tokens log users back in when their session has expired. Each is made of a
+/// selector, to look it up, and a validator, of which only a hash is stored. Tokens are
+/// replaced every time they're used; a token presented with the wrong validator is revoked,
+/// since that means it was copied.
+///
+/// Sessions and tokens are kept in memory, so they don't survive restarts and aren't shared
+/// between server instances.
+///
+/// # Examples
+///
+/// ```
+/// # use std::time::Duration;
+/// # use http_server::{Request, Response, StatusCode};
+/// # use http_server::auth::session::Sessions;
+/// # use http_server::http::cookie::SignedCookies;
+/// # struct User { id: String }
+/// # fn redirect_to_login() -> Response { Response::new(StatusCode::SeeOther) }
+/// # fn example(
+/// #     key: &[u8],
+/// #     request: &Request,
+/// #     user: &User,
+/// #     remember: bool,
+/// # ) -> Result<Response, String> {
+/// # let mut response = Response::new(StatusCode::OK);
+/// let sessions =
+///     Sessions::new(SignedCookies::new(key)?).remember_me(Duration::from_secs(30 * 86_400));
+///
+/// // In the login handler, once the password checks out
+/// sessions.login(request, &mut response, &user.id, remember)?;
+///
+/// // In other handlers
+/// let Some(user_id) = sessions.current_user(request, &mut response) else {
+///     return Ok(redirect_to_login());
+/// };
+/// # Ok(response)
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Sessions {
+    cookies: SignedCookies,
+    cookie_name: String,
+    idle_timeout: Duration,
+    remember_for: Option<Duration>,
+    secure: bool,
+    sessions: Arc<Mutex<HashMap<String, Session>>>,
+    remember: Arc<Mutex<HashMap<String, RememberToken>>>,
+}
+
+impl Sessions {
+    /// Creates a session store whose session cookie is signed by `cookies`.
+    ///
+    /// Sessions expire after 30 minutes without a request, and remember-me is off.
+    pub fn new(cookies: SignedCookies) -> Self {
+        Sessions {
+            cookies,
+            cookie_name: "session".to_string(),
+            idle_timeout: Duration::from_secs(30 * 60),
+            remember_for: None,
+            secure: false,
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            remember: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Names the session cookie `name` instead of `session`.
+    pub fn cookie_name(mut self, name: &str) -> Self {
+        self.cookie_name = name.to_string();
+        self
+    }
+
+    /// Ends sessions after `timeout` without a request.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Lets users ask to be remembered for `duration` when they log in.
+    pub fn remember_me(mut self, duration: Duration) -> Self {
+        self.remember_for = Some(duration);
+        self
+    }
+
+    /// Marks the cookies `Secure`, so they're only sent over HTTPS.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Logs `user_id` in, replacing any session the request had.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The login request.
+    /// * `response` - The response the session cookie is set in.
+    /// * `user_id` - The user who authenticated.
+    /// * `remember` - Whether to also issue a remember-me token, if remember-me is enabled.
+    ///
+    /// # Returns
+    ///
+    /// An error if no random session ID could be generated.
+    pub fn login(
+        &self,
+        request: &Request,
+        response: &mut Response,
+        user_id: &str,
+        remember: bool,
+    ) -> Result<(), String> {
+        if let Some(id) = self.session_id(request) {
+            self.sessions.lock().unwrap().remove(&id);
+        }
+        if let Some((selector, _)) = remember_cookie(request) {
+            self.remember.lock().unwrap().remove(selector);
+        }
+        self.start(response, user_id)?;
+        if remember && let Some(duration) = self.remember_for {
+            self.issue_token(response, user_id, duration)?;
+        }
+        Ok(())
+    }
+
+    /// Logs the request's user out, ending their session and revoking their remember-me token.
+    pub fn logout(&self, request: &Request, response: &mut Response) -> Result<(), String> {
+        if let Some(id) = self.session_id(request) {
+            self.sessions.lock().unwrap().remove(&id);
+        }
+        response.add_cookie(&self.cookie(Cookie::removal(&self.cookie_name)))?;
+        if let Some((selector, _)) = remember_cookie(request) {
+            self.remember.lock().unwrap().remove(selector);
+            response.add_cookie(&self.cookie(Cookie::removal(REMEMBER_COOKIE)))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the user the request is logged in as.
+    ///
+    /// When the session has expired but the request carries a valid remember-me token, the
+    /// user is logged back in: a new session is started and the token replaced, which is why
+    /// the response is needed.
+    pub fn current_user(&self, request: &Request, response: &mut Response) -> Option<String> {
+        if let Some(id) = self.session_id(request) {
+            let mut sessions = self.sessions.lock().unwrap();
+            match sessions.get_mut(&id) {
+                Some(session) if session.last_seen.elapsed() <= self.idle_timeout => {
+                    session.last_seen = Instant::now();
+                    return Some(session.user_id.clone());
+                }
+                Some(_) => {
+                    sessions.remove(&id);
+                }
+                None => {}
+            }
+        }
+
+        let (selector, validator) = remember_cookie(request)?;
+        let user_id = self.redeem_token(selector, validator)?;
+        let resumed = self.start(response, &user_id).and_then(|()| {
+            let duration = self.remember_for.unwrap_or_default();
+            self.issue_token(response, &user_id, duration)
+        });
+        match resumed {
+            Ok(()) => Some(user_id),
+            Err(e) => {
+                logging::error("session", "Failed to resume a session", &[("error", &e)]);
+                None
+            }
+        }
+    }
+
+    /// Moves the request's session to a new ID, e.g. after the user confirmed their password
+    /// to get elevated privileges.
+    ///
+    /// # Returns
+    ///
+    /// An error if the request has no live session or no random ID could be generated.
+    pub fn rotate(&self, request: &Request, response: &mut Response) -> Result<(), String> {
+        let user_id = self
+            .session_id(request)
+            .and_then(|id| self.sessions.lock().unwrap().remove(&id))
+            .map(|session| session.user_id)
+            .ok_or("No session to rotate")?;
+        self.start(response, &user_id)
+    }
+
+    /// Returns the verified ID in the request's session cookie.
+    fn session_id(&self, request: &Request) -> Option<String> {
+        self.cookies.get(request, &self.cookie_name)
+    }
+
+    /// Starts a session for `user_id` under a new ID, and sets its cookie.
+    fn start(&self, response: &mut Response, user_id: &str) -> Result<(), String> {
+        let id = to_hex(&random_bytes(16)?);
+        {
+            let mut sessions = self.sessions.lock().unwrap();
+            sessions.retain(|_, session| session.last_seen.elapsed() <= self.idle_timeout);
+            sessions.insert(
+                id.clone(),
+                Session {
+                    user_id: user_id.to_string(),
+                    last_seen: Instant::now(),
+                },
+            );
+        }
+        let cookie = self.cookie(Cookie::new(&self.cookie_name, &id));
+        self.cookies.add(response, cookie)
+    }
+
+    /// Issues a remember-me token for `user_id`, valid for `duration`, and sets its cookie.
+    fn issue_token(
+        &self,
+        response: &mut Response,
+        user_id: &str,
+        duration: Duration,
+    ) -> Result<(), String> {
+        let selector = to_hex(&random_bytes(12)?);
+        let validator = random_bytes(32)?;
+        {
+            let mut remember = self.remember.lock().unwrap();
+            remember.retain(|_, token| token.expires > Instant::now());
+            remember.insert(
+                selector.clone(),
+                RememberToken {
+                    user_id: user_id.to_string(),
+                    validator_hash: sha256(&validator),
+                    expires: Instant::now() + duration,
+                },
+            );
+        }
+        let value = format!("{}:{}", selector, to_hex(&validator));
+        response.add_cookie(&self.cookie(Cookie::new(REMEMBER_COOKIE, &value).max_age(duration)))
+    }
+
+    /// Checks and uses up a remember-me token.
+    ///
+    /// # Returns
+    ///
+    /// The token's user, or `None` if it's unknown, expired or has the wrong validator.
+    fn redeem_token(&self, selector: &str, validator: &str) -> Option<String> {
+        let token = self.remember.lock().unwrap().remove(selector)?;
+        if token.expires <= Instant::now() {
+            return None;
+        }
+        let validator = from_hex(validator)?;
+        if !constant_time_eq(&sha256(&validator), &token.validator_hash) {
+            logging::warn(
+                "session",
+                "Revoked a remember-me token presented with the wrong validator",
+                &[("user", &token.user_id)],
+            );
+            return None;
+        }
+        Some(token.user_id)
+    }
+
+    /// Applies the store's cookie settings.
+    fn cookie(&self, cookie: Cookie) -> Cookie {
+        cookie.secure(self.secure)
+    }
+}
+
+/// Splits the request's remember-me cookie into its selector and validator.
+fn remember_cookie(request: &Request) -> Option<(&str, &str)> {
+    request.cookie(REMEMBER_COOKIE)?.split_once(':')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::StatusCode;
+
+    fn sessions() -> Sessions {
+        Sessions::new(SignedCookies::new(&[3; 32]).unwrap()).remember_me(Duration::from_secs(3600))
+    }
+
+    /// Builds a request carrying the cookies `response` set, as a browser would send them.
+    fn follow_up(response: &Response) -> Request {
+        let cookies = response
+            .cookies
+            .iter()
+            .filter(|cookie| !cookie.contains("Max-Age=0"))
+            .filter_map(|cookie| cookie.split(';').next())
+            .collect::<Vec<_>>()
+            .join("; ");
+        Request::builder().header("Cookie", &cookies).build()
+    }
+
+    fn cookie_value<'a>(response: &'a Response, name: &str) -> &'a str {
+        response
+            .cookies
+            .iter()
+            .filter_map(|cookie| cookie.split(';').next()?.split_once('='))
+            .find(|(cookie, _)| *cookie == name)
+            .map(|(_, value)| value)
+            .unwrap()
+    }
+
+    #[test]
+    fn login_starts_a_session() {
+        let sessions = sessions();
+        let mut response = Response::new(StatusCode::OK);
+        sessions
+            .login(&Request::builder().build(), &mut response, "alice", false)
+            .unwrap();
+
+        let request = follow_up(&response);
+        let mut next = Response::new(StatusCode::OK);
+        assert_eq!(
+            sessions.current_user(&request, &mut next).as_deref(),
+            Some("alice")
+        );
+        assert!(
+            sessions
+                .current_user(&Request::builder().build(), &mut next)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn tampered_and_forged_session_cookies_are_ignored() {
+        let sessions = sessions();
+        let mut response = Response::new(StatusCode::OK);
+        sessions
+            .login(&Request::builder().build(), &mut response, "alice", false)
+            .unwrap();
+        let (id, mac) = cookie_value(&response, "session").split_once('.').unwrap();
+
+        let mut next = Response::new(StatusCode::OK);
+        let guessed = format!("session={}.{}", "0".repeat(id.len()), mac);
+        let request = Request::builder().header("Cookie", &guessed).build();
+        assert!(sessions.current_user(&request, &mut next).is_none());
+
+        // A correctly signed ID that was never issued isn't a session either
+        let forged = SignedCookies::new(&[3; 32])
+            .unwrap()
+            .sign(Cookie::new("session", "made-up"));
+        let request = Request::builder()
+            .header("Cookie", &format!("session={}", forged.value))
+            .build();
+        assert!(sessions.current_user(&request, &mut next).is_none());
+    }
+
+    #[test]
+    fn login_replaces_a_planted_session() {
+        let sessions = sessions();
+        let mut planted = Response::new(StatusCode::OK);
+        sessions
+            .login(&Request::builder().build(), &mut planted, "attacker", false)
+            .unwrap();
+        let victim_request = follow_up(&planted);
+
+        let mut response = Response::new(StatusCode::OK);
+        sessions
+            .login(&victim_request, &mut response, "victim", false)
+            .unwrap();
+        assert_ne!(
+            cookie_value(&planted, "session"),
+            cookie_value(&response, "session")
+        );
+        let mut next = Response::new(StatusCode::OK);
+        assert!(sessions.current_user(&victim_request, &mut next).is_none());
+    }
+
+    #[test]
+    fn logout_ends_the_session() {
+        let sessions = sessions();
+        let mut response = Response::new(StatusCode::OK);
+        sessions
+            .login(&Request::builder().build(), &mut response, "alice", true)
+            .unwrap();
+        let request = follow_up(&response);
+
+        let mut logout = Response::new(StatusCode::OK);
+        sessions.logout(&request, &mut logout).unwrap();
+        let mut next = Response::new(StatusCode::OK);
+        assert!(sessions.current_user(&request, &mut next).is_none());
+    }
+
+    #[test]
+    fn remember_me_tokens_are_single_use() {
+        let sessions = sessions().idle_timeout(Duration::ZERO);
+        let mut response = Response::new(StatusCode::OK);
+        sessions
+            .login(&Request::builder().build(), &mut response, "alice", true)
+            .unwrap();
+        let token = cookie_value(&response, REMEMBER_COOKIE).to_string();
+        let request = Request::builder()
+            .header("Cookie", &format!("remember={}", token))
+            .build();
+
+        // The session has expired, so the token logs the user back in and is replaced
+        let mut resumed = Response::new(StatusCode::OK);
+        assert_eq!(
+            sessions.current_user(&request, &mut resumed).as_deref(),
+            Some("alice")
+        );
+        assert_ne!(cookie_value(&resumed, REMEMBER_COOKIE), token);
+
+        let mut replayed = Response::new(StatusCode::OK);
+        assert!(sessions.current_user(&request, &mut replayed).is_none());
+    }
+
+    #[test]
+    fn a_token_with_the_wrong_validator_is_revoked() {
+        let sessions = sessions().idle_timeout(Duration::ZERO);
+        let mut response = Response::new(StatusCode::OK);
+        sessions
+            .login(&Request::builder().build(), &mut response, "alice", true)
+            .unwrap();
+        let token = cookie_value(&response, REMEMBER_COOKIE).to_string();
+        let (selector, _) = token.split_once(':').unwrap();
+
+        let stolen = Request::builder()
+            .header(
+                "Cookie",
+                &format!("remember={}:{}", selector, "00".repeat(32)),
+            )
+            .build();
+        let mut next = Response::new(StatusCode::OK);
+        assert!(sessions.current_user(&stolen, &mut next).is_none());
+
+        // The genuine token no longer works either
+        let genuine = Request::builder()
+            .header("Cookie", &format!("remember={}", token))
+            .build();
+        assert!(sessions.current_user(&genuine, &mut next).is_none());
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::fs_path::percent_decode;
+use crate::http::{Request, Response, StatusCode};
+use crate::mime;
+use crate::static_files::{
+    ByteRange, Multipart, accepts_encoding, byte_range, error_page, etag_matches,
+    redirect_to_directory,
+};
+
+/// Boxed future returned by [`EmbeddedFiles::handler`].
+type ResponseFuture = Pin<Box<dyn Future<Output = Result<Response, String>> + Send>>;
+
+/// A file compiled into the binary.
+#[derive(Debug)]
+pub struct EmbeddedFile {
+    /// The file's path relative to the embedded directory, with `/` separators.
+    pub path: &'static str,
+    /// The file's bytes.
+    pub contents: &'static [u8],
+    /// A strong `ETag` derived from the contents at build time.
+    pub etag: &'static str,
+}
+
+mod bundle {
+    use super::EmbeddedFile;
+
+    // Generated by build.rs from `public/`, or `HTTP_SERVER_EMBED_DIR` if set
+    include!(concat!(env!("OUT_DIR"), "/embedded_assets.rs"));
+}
+
+/// Serves files bundled into the binary at compile time, so a deployment needs no asset
+/// directory on disk.
+///
+/// The build script embeds every non-hidden file under `public/`, or the directory named by
+/// the `HTTP_SERVER_EMBED_DIR` environment variable at build time. Responses carry an `ETag`
+/// computed from the contents, so unchanged assets get `304 Not Modified`, and single byte
+/// ranges are honored, guarded by `If-Range`. Precompressed siblings such as `app.js.br` or
+/// `app.js.gz` in the bundle are sent to clients that accept them.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Router, embedded::EmbeddedFiles};
+/// let router = Router::new().get("/assets/*", EmbeddedFiles::bundled().handler());
+/// ```
+pub struct EmbeddedFiles {
+    files: HashMap<&'static str, &'static EmbeddedFile>,
+    index_file: Option<String>,
+}
+
+impl EmbeddedFiles {
+    /// Creates a service that serves the files bundled by the build script.
+    pub fn bundled() -> Self {
+        EmbeddedFiles::new(bundle::ASSETS)
+    }
+
+    /// Creates a service that serves `files`.
+    pub fn new(files: &'static [EmbeddedFile]) -> Self {
+        EmbeddedFiles {
+            files: files.iter().map(|file| (file.path, file)).collect(),
+            index_file: Some("index.html".to_string()),
+        }
+    }
+
+    /// Sets the file served for requests to a directory, `index.html` by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The index file name, or `None` to never serve one.
+    pub fn index_file(mut self, name: Option<&str>) -> Self {
+        self.index_file = name.map(str::to_string);
+        self
+    }
+
+    /// Returns the paths of the embedded files.
+    pub fn paths(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.files.keys().copied()
+    }
+
+    /// Turns the service into a handler for mounting on a router.
+    ///
+    /// On a wildcard route the path matched by `*` is served; otherwise the whole request
+    /// path is.
+    pub fn handler(self) -> impl Fn(Request) -> ResponseFuture + Send + Sync + 'static {
+        let files = Arc::new(self);
+        move |request| {
+            let files = files.clone();
+            Box::pin(async move { Ok(files.serve(&request)) })
+        }
+    }
+
+    /// Serves the embedded file `request` refers to.
+    pub fn serve(&self, request: &Request) -> Response {
+        let path = request
+            .param("*")
+            .map(String::as_str)
+            .unwrap_or(&request.path);
+        let Some(decoded) = percent_decode(path) else {
+            return error_page(StatusCode::BadRequest);
+        };
+        let mut path = decoded.trim_start_matches('/').to_string();
+
+        if path.is_empty() || path.ends_with('/') {
+            match &self.index_file {
+                Some(index) => path.push_str(index),
+                None => return error_page(StatusCode::NotFound),
+            }
+        } else if !self.files.contains_key(path.as_str())
+            && let Some(index) = &self.index_file
+            && self
+                .files
+                .contains_key(format!("{}/{}", path, index).as_str())
+        {
+            // Relative links in the index only work from a URL ending in a slash
+            return redirect_to_directory(request);
+        }
+
+        let Some(original) = self.files.get(path.as_str()) else {
+            return error_page(StatusCode::NotFound);
+        };
+        let (file, encoding) = self.variant(request, &path).unwrap_or((original, None));
+
+        let fresh = request
+            .header("If-None-Match")
+            .is_some_and(|if_none_match| etag_matches(if_none_match, file.etag));
+        let len = file.contents.len() as u64;
+        let range = match request.header("Range") {
+            // Only a strong, exact tag allows a range; the bundle has no modification dates
+            Some(range)
+                if !fresh
+                    && request
+                        .header("If-Range")
+                        .is_none_or(|tag| tag.trim() == file.etag) =>
+            {
+                byte_range(range, len)
+            }
+            _ => ByteRange::Full,
+        };
+
+        let status_code = match range {
+            _ if fresh => StatusCode::NotModified,
+            ByteRange::Full => StatusCode::OK,
+            ByteRange::Partial(_) | ByteRange::Multiple(_) => StatusCode::PartialContent,
+            ByteRange::Unsatisfiable => {
+                let mut response = error_page(StatusCode::RangeNotSatisfiable);
+                response
+                    .headers
+                    .insert("Content-Range".to_string(), format!("bytes */{}", len));
+                return response;
+            }
+        };
+        let mut response = Response::new(status_code);
+        response
+            .headers
+            .insert("ETag".to_string(), file.etag.to_string());
+        response
+            .headers
+            .insert("Vary".to_string(), "Accept-Encoding".to_string());
+        if let Some(encoding) = encoding {
+            response
+                .headers
+                .insert("Content-Encoding".to_string(), encoding.to_string());
+        }
+        if fresh {
+            return response;
+        }
+
+        let content_type = mime::from_path(original.path);
+        response.set_content_type(&content_type);
+        response
+            .headers
+            .insert("Accept-Ranges".to_string(), "bytes".to_string());
+        match range {
+            ByteRange::Partial(range) => {
+                response.headers.insert(
+                    "Content-Range".to_string(),
+                    format!("bytes {}-{}/{}", range.start, range.end - 1, len),
+                );
+                response.set_body(file.contents[range.start as usize..range.end as usize].to_vec());
+            }
+            ByteRange::Multiple(ranges) => {
+                let multipart = Multipart::new(ranges, &content_type, len);
+                response.set_content_type(&multipart.content_type());
+                response.set_body(multipart.to_bytes(file.contents));
+            }
+            _ => response.set_body(file.contents.to_vec()),
+        }
+        response
+    }
+
+    /// Finds a precompressed variant of `path` in the bundle that the client accepts.
+    fn variant(
+        &self,
+        request: &Request,
+        path: &str,
+    ) -> Option<(&'static EmbeddedFile, Option<&'static str>)> {
+        let accept_encoding = request.header("Accept-Encoding")?;
+        [("br", "br"), ("gzip", "gz")]
+            .into_iter()
+            .filter(|(encoding, _)| accepts_encoding(accept_encoding, encoding))
+            .find_map(|(encoding, extension)| {
+                let file = self.files.get(format!("{}.{}", path, extension).as_str())?;
+                Some((*file, Some(encoding)))
+            })
+    }
+}
@@ -0,0 +1,189 @@
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use tokio::fs;
+
+/// Reasons a requested path can't be resolved inside a root directory.
+#[derive(Debug)]
+pub enum PathError {
+    /// The path has a malformed `%XX` escape, a NUL byte, or isn't valid UTF-8 once decoded.
+    Malformed,
+    /// A segment is `..`, absolute, a drive prefix or contains a backslash, before or after
+    /// decoding.
+    Traversal,
+    /// The path exists but leads outside the root, through a symlink.
+    OutsideRoot,
+    /// The root or the path couldn't be canonicalized, usually because it doesn't exist.
+    Io(io::Error),
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathError::Malformed => write!(f, "Malformed path"),
+            PathError::Traversal => write!(f, "Path traversal attempt"),
+            PathError::OutsideRoot => write!(f, "Path leads outside the root"),
+            PathError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Resolves a request path to a file inside `root`.
+///
+/// The path is percent-decoded and checked segment by segment, so `..`, its encoded forms such
+/// as `%2e%2e`, encoded separators, NUL bytes and absolute paths are refused before the file
+/// system is touched. The result is then canonicalized and must still be inside the canonical
+/// root, which catches symlinks pointing out of it.
+///
+/// # Arguments
+///
+/// * `root` - The directory the path must stay inside.
+/// * `requested` - The URL path, relative to `root`; leading and repeated slashes are ignored.
+///
+/// # Returns
+///
+/// The canonical path of the file or directory, or why it can't be served.
+///
+/// # Examples
+///
+/// ```
+/// # use std::path::Path;
+/// # use http_server::fs_path::{self, PathError};
+/// # async fn example() -> Result<(), PathError> {
+/// let file = fs_path::resolve(Path::new("public"), "css/site.css").await?;
+/// assert!(fs_path::resolve(Path::new("public"), "../Cargo.toml").await.is_err());
+/// assert!(fs_path::resolve(Path::new("public"), "%2e%2e/Cargo.toml").await.is_err());
+/// # Ok(())
+/// # }
+/// ```
+pub async fn resolve(root: &Path, requested: &str) -> Result<PathBuf, PathError> {
+    let relative = relative(requested)?;
+    let root = fs::canonicalize(root).await.map_err(PathError::Io)?;
+    contain(&root, &root.join(relative)).await
+}
+
+/// Turns a request path into a relative file system path made of plain names only, without
+/// touching the file system.
+///
+/// # Returns
+///
+/// The relative path, empty for the root itself, or why the path was refused.
+pub fn relative(requested: &str) -> Result<PathBuf, PathError> {
+    let decoded = percent_decode(requested).ok_or(PathError::Malformed)?;
+
+    let mut relative = PathBuf::new();
+    for segment in decoded.split('/').filter(|segment| !segment.is_empty()) {
+        // A backslash is a separator on Windows, and a suspicious file name everywhere else
+        if segment.contains('\\') {
+            return Err(PathError::Traversal);
+        }
+        // Only plain names: no `..`, no absolute paths or drive prefixes smuggled in
+        let mut components = Path::new(segment).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) => relative.push(segment),
+            _ => return Err(PathError::Traversal),
+        }
+    }
+    Ok(relative)
+}
+
+/// Canonicalizes `path` and checks it's still inside `root`, which must be canonical.
+///
+/// # Returns
+///
+/// The canonical path, or why it can't be served.
+pub async fn contain(root: &Path, path: &Path) -> Result<PathBuf, PathError> {
+    let canonical = fs::canonicalize(path).await.map_err(PathError::Io)?;
+    if !canonical.starts_with(root) {
+        return Err(PathError::OutsideRoot);
+    }
+    Ok(canonical)
+}
+
+/// Decodes `%XX` escapes in a URL path.
+///
+/// # Returns
+///
+/// The decoded path, or `None` if an escape is malformed, decodes to a NUL byte, or the result
+/// isn't valid UTF-8.
+pub fn percent_decode(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    if decoded.contains(&0) {
+        return None;
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn traversal(requested: &str) -> bool {
+        matches!(relative(requested), Err(PathError::Traversal))
+    }
+
+    #[test]
+    fn keeps_plain_paths() {
+        assert_eq!(relative("css/site.css").unwrap(), Path::new("css/site.css"));
+        assert_eq!(
+            relative("//css///site.css").unwrap(),
+            Path::new("css/site.css")
+        );
+        assert_eq!(relative("/").unwrap(), Path::new(""));
+        assert_eq!(relative("my%20file.txt").unwrap(), Path::new("my file.txt"));
+    }
+
+    #[test]
+    fn refuses_traversal_in_any_encoding() {
+        assert!(traversal("../Cargo.toml"));
+        assert!(traversal("css/../../Cargo.toml"));
+        assert!(traversal("%2e%2e/Cargo.toml"));
+        assert!(traversal("%2E%2E%2FCargo.toml"));
+        assert!(traversal(".%2e/Cargo.toml"));
+        assert!(traversal("..%5cCargo.toml"));
+        assert!(traversal("css\\..\\..\\Cargo.toml"));
+        assert!(traversal("./Cargo.toml"));
+    }
+
+    #[test]
+    fn refuses_malformed_escapes_and_nul_bytes() {
+        assert!(matches!(relative("%zz"), Err(PathError::Malformed)));
+        assert!(matches!(relative("file%"), Err(PathError::Malformed)));
+        assert!(matches!(relative("file%00.txt"), Err(PathError::Malformed)));
+        assert!(matches!(relative("%ff%fe"), Err(PathError::Malformed)));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn refuses_symlinks_out_of_the_root() {
+        let base = std::env::temp_dir().join(format!("fs-path-test-{}", std::process::id()));
+        let root = base.join("public");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(base.join("secret.txt"), "secret").unwrap();
+        std::fs::write(root.join("index.html"), "hello").unwrap();
+        let _ = std::fs::remove_file(root.join("escape"));
+        std::os::unix::fs::symlink(base.join("secret.txt"), root.join("escape")).unwrap();
+
+        let served = resolve(&root, "index.html").await;
+        let escaped = resolve(&root, "escape").await;
+        let missing = resolve(&root, "missing.html").await;
+        std::fs::remove_dir_all(&base).unwrap();
+
+        assert!(served.unwrap().ends_with("public/index.html"));
+        assert!(matches!(escaped, Err(PathError::OutsideRoot)));
+        assert!(matches!(missing, Err(PathError::Io(_))));
+    }
+}
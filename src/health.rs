@@ -0,0 +1,255 @@
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use futures::future::join_all;
+use serde_json::{Map, Value, json};
+
+use crate::http::{Request, Response, StatusCode};
+use crate::router::Router;
+use crate::server::DrainControl;
+
+/// Boxed future returned by the health handlers.
+type ResponseFuture = Pin<Box<dyn Future<Output = Result<Response, String>> + Send>>;
+
+/// Type alias for registered check functions.
+type CheckFn = dyn Fn() -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> + Send + Sync;
+
+/// How long a check may run before it counts as failed, unless configured otherwise.
+const DEFAULT_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Which probes a check contributes to.
+#[derive(Clone, Copy, PartialEq)]
+enum Probe {
+    /// Whether the server should receive traffic, e.g. its database is reachable.
+    Readiness,
+    /// Whether the process is working at all, e.g. a worker thread hasn't died.
+    Liveness,
+}
+
+struct Check {
+    name: String,
+    probe: Probe,
+    check: Arc<CheckFn>,
+}
+
+/// A registry of health checks, served as `/healthz`, `/readyz` and `/livez`.
+///
+/// Application components register async checks at any time, e.g. once their connection
+/// pool is up. Each endpoint runs its checks concurrently and answers `200 OK` if all of them
+/// pass or `503 Service Unavailable` otherwise, with per-check details in a JSON body:
+///
+/// * `/livez` runs the liveness checks, so an orchestrator restarts the process only when it
+///   is truly stuck.
+/// * `/readyz` runs the readiness checks and also fails while the server is draining, so load
+///   balancers stop sending it traffic.
+/// * `/healthz` runs every check.
+///
+/// Clones share the same registry.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Request, Response, Router, StatusCode};
+/// # use http_server::health::Health;
+/// # use http_server::server::DrainControl;
+/// # #[derive(Clone)]
+/// # struct Pool;
+/// # impl Pool { async fn ping(&self) -> Result<(), std::io::Error> { Ok(()) } }
+/// # async fn handle_index(_req: Request) -> Result<Response, String> {
+/// #     Ok(Response::new(StatusCode::OK))
+/// # }
+/// # fn example(drain: DrainControl, pool: Pool) -> Router {
+/// let health = Health::new().with_drain_control(drain.clone());
+/// let db = pool.clone();
+/// health.readiness_check("database", move || {
+///     let db = db.clone();
+///     async move { db.ping().await.map_err(|e| e.to_string()) }
+/// });
+///
+/// let router = health.routes(Router::new().get("/", handle_index));
+/// # router
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Health {
+    checks: Arc<RwLock<Vec<Check>>>,
+    drain: Option<DrainControl>,
+    timeout: Duration,
+    started: Instant,
+}
+
+impl Health {
+    /// Creates an empty registry; with no checks, every endpoint reports healthy.
+    pub fn new() -> Self {
+        Health {
+            checks: Arc::new(RwLock::new(Vec::new())),
+            drain: None,
+            timeout: DEFAULT_CHECK_TIMEOUT,
+            started: Instant::now(),
+        }
+    }
+
+    /// Reports not ready while the server controlled by `drain` is draining.
+    pub fn with_drain_control(mut self, drain: DrainControl) -> Self {
+        self.drain = Some(drain);
+        self
+    }
+
+    /// Sets how long a check may run before it counts as failed, 5 seconds by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Registers a check that decides whether the server is ready for traffic.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The key the check's result is reported under.
+    /// * `check` - Returns `Ok(())` when healthy, or a description of the problem.
+    pub fn readiness_check<F, Fut>(&self, name: &str, check: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.register(name, Probe::Readiness, check);
+    }
+
+    /// Registers a check that decides whether the process is alive.
+    ///
+    /// Keep liveness checks to the process itself; a failing dependency should make the
+    /// server unready, not get it restarted.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The key the check's result is reported under.
+    /// * `check` - Returns `Ok(())` when healthy, or a description of the problem.
+    pub fn liveness_check<F, Fut>(&self, name: &str, check: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.register(name, Probe::Liveness, check);
+    }
+
+    fn register<F, Fut>(&self, name: &str, probe: Probe, check: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        let check = Arc::new(move || {
+            Box::pin(check()) as Pin<Box<dyn Future<Output = Result<(), String>> + Send>>
+        });
+        let mut checks = self.checks.write().unwrap();
+        // Registering a name again replaces the earlier check
+        checks.retain(|existing| existing.name != name);
+        checks.push(Check {
+            name: name.to_string(),
+            probe,
+            check,
+        });
+    }
+
+    /// Mounts `/healthz`, `/readyz` and `/livez` on `router`.
+    pub fn routes(&self, router: Router) -> Router {
+        router
+            .get(
+                "/healthz",
+                self.handler(&[Probe::Readiness, Probe::Liveness]),
+            )
+            .get("/readyz", self.handler(&[Probe::Readiness]))
+            .get("/livez", self.handler(&[Probe::Liveness]))
+    }
+
+    /// Returns a handler that runs every check, for mounting at a custom path.
+    pub fn health_handler(&self) -> impl Fn(Request) -> ResponseFuture + Send + Sync + 'static {
+        self.handler(&[Probe::Readiness, Probe::Liveness])
+    }
+
+    /// Returns a handler that runs the readiness checks, for mounting at a custom path.
+    pub fn readiness_handler(&self) -> impl Fn(Request) -> ResponseFuture + Send + Sync + 'static {
+        self.handler(&[Probe::Readiness])
+    }
+
+    /// Returns a handler that runs the liveness checks, for mounting at a custom path.
+    pub fn liveness_handler(&self) -> impl Fn(Request) -> ResponseFuture + Send + Sync + 'static {
+        self.handler(&[Probe::Liveness])
+    }
+
+    fn handler(
+        &self,
+        probes: &[Probe],
+    ) -> impl Fn(Request) -> ResponseFuture + Send + Sync + 'static {
+        let health = self.clone();
+        let probes = probes.to_vec();
+        move |_request| {
+            let health = health.clone();
+            let probes = probes.clone();
+            Box::pin(async move { Ok(health.report(&probes).await) })
+        }
+    }
+
+    /// Runs the checks for `probes` and builds the response.
+    async fn report(&self, probes: &[Probe]) -> Response {
+        let checks: Vec<(String, Arc<CheckFn>)> = self
+            .checks
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|check| probes.contains(&check.probe))
+            .map(|check| (check.name.clone(), check.check.clone()))
+            .collect();
+
+        let results = join_all(checks.into_iter().map(|(name, check)| async move {
+            let start = Instant::now();
+            let result = match tokio::time::timeout(self.timeout, check()).await {
+                Ok(result) => result,
+                Err(_) => Err(format!("timed out after {:?}", self.timeout)),
+            };
+            (name, result, start.elapsed())
+        }))
+        .await;
+
+        // Draining only affects readiness; a draining server is still alive
+        let draining = probes.contains(&Probe::Readiness)
+            && self.drain.as_ref().is_some_and(DrainControl::is_draining);
+        let mut healthy = !draining;
+        let mut details = Map::new();
+        for (name, result, elapsed) in results {
+            let mut detail = json!({
+                "status": if result.is_ok() { "pass" } else { "fail" },
+                "duration_ms": (elapsed.as_secs_f64() * 1_000_000.0).round() / 1000.0,
+            });
+            if let Err(e) = result {
+                healthy = false;
+                detail["error"] = Value::String(e);
+            }
+            details.insert(name, detail);
+        }
+
+        let body = json!({
+            "status": if healthy { "pass" } else { "fail" },
+            "draining": draining,
+            "uptime_seconds": self.started.elapsed().as_secs(),
+            "checks": details,
+        });
+        let mut response = Response::new(if healthy {
+            StatusCode::OK
+        } else {
+            StatusCode::ServiceUnavailable
+        });
+        response.set_content_type("application/json");
+        response
+            .headers
+            .insert("Cache-Control".to_string(), "no-store".to_string());
+        response.set_body(body.to_string().into_bytes());
+        response
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new()
+    }
+}
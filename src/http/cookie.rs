@@ -0,0 +1,383 @@
+use std::time::Duration;
+
+use super::header::validate_name;
+use super::{Request, Response};
+use crate::crypto::{constant_time_eq, from_hex, hmac_sha256, to_hex};
+
+/// Shortest key accepted for signing cookies, in bytes.
+const MIN_KEY_LEN: usize = 32;
+
+/// The `SameSite` attribute of a cookie.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SameSite {
+    /// Sent only with requests from the same site.
+    Strict,
+    /// Also sent when navigating to the site from elsewhere.
+    Lax,
+    /// Sent with cross-site requests too; browsers require `Secure` with it.
+    None,
+}
+
+/// A cookie to set in a response with [`Response::add_cookie`].
+///
+/// # Examples
+///
+/// ```
+/// # use std::time::Duration;
+/// # use http_server::{Response, StatusCode, http::cookie::Cookie};
+/// # fn main() -> Result<(), String> {
+/// # let mut response = Response::new(StatusCode::OK);
+/// let cookie = Cookie::new("theme", "dark").max_age(Duration::from_secs(30 * 86_400));
+/// response.add_cookie(&cookie)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    path: Option<String>,
+    domain: Option<String>,
+    max_age: Option<Duration>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+}
+
+impl Cookie {
+    /// Creates a session cookie for the whole site, hidden from scripts and sent with
+    /// `SameSite=Lax`.
+    pub fn new(name: &str, value: &str) -> Self {
+        Cookie {
+            name: name.to_string(),
+            value: value.to_string(),
+            path: Some("/".to_string()),
+            domain: None,
+            max_age: None,
+            secure: false,
+            http_only: true,
+            same_site: Some(SameSite::Lax),
+        }
+    }
+
+    /// Creates a cookie that makes the browser delete the cookie `name`.
+    pub fn removal(name: &str) -> Self {
+        Cookie::new(name, "").max_age(Duration::ZERO)
+    }
+
+    /// Limits the cookie to paths under `path`; `None` leaves it to the browser.
+    pub fn path(mut self, path: Option<&str>) -> Self {
+        self.path = path.map(str::to_string);
+        self
+    }
+
+    /// Shares the cookie with `domain` and its subdomains.
+    pub fn domain(mut self, domain: &str) -> Self {
+        self.domain = Some(domain.to_string());
+        self
+    }
+
+    /// Keeps the cookie for `max_age` instead of until the browser closes.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Sets whether the cookie is only sent over HTTPS.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Sets whether the cookie is hidden from scripts.
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    /// Sets the `SameSite` attribute; `None` leaves it to the browser.
+    pub fn same_site(mut self, same_site: Option<SameSite>) -> Self {
+        self.same_site = same_site;
+        self
+    }
+
+    /// Checks the cookie can be sent as is.
+    ///
+    /// # Returns
+    ///
+    /// An error if the name isn't a token, or the value or an attribute has characters that
+    /// aren't allowed, such as spaces, quotes, commas, semicolons or control characters.
+    pub fn validate(&self) -> Result<(), String> {
+        validate_name(&self.name)?;
+        if let Some(c) = self.value.chars().find(|&c| !is_cookie_char(c)) {
+            return Err(format!(
+                "Invalid character {:?} in the value of cookie {}",
+                c, self.name
+            ));
+        }
+        for attribute in [&self.path, &self.domain].into_iter().flatten() {
+            if attribute.chars().any(|c| c == ';' || c.is_control()) {
+                return Err(format!(
+                    "Invalid attribute {:?} for cookie {}",
+                    attribute, self.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Formats the cookie as a `Set-Cookie` header value.
+    pub fn to_header(&self) -> String {
+        let mut header = format!("{}={}", self.name, self.value);
+        if let Some(path) = &self.path {
+            header.push_str(&format!("; Path={}", path));
+        }
+        if let Some(domain) = &self.domain {
+            header.push_str(&format!("; Domain={}", domain));
+        }
+        if let Some(max_age) = self.max_age {
+            header.push_str(&format!("; Max-Age={}", max_age.as_secs()));
+        }
+        if self.secure {
+            header.push_str("; Secure");
+        }
+        if self.http_only {
+            header.push_str("; HttpOnly");
+        }
+        match self.same_site {
+            Some(SameSite::Strict) => header.push_str("; SameSite=Strict"),
+            Some(SameSite::Lax) => header.push_str("; SameSite=Lax"),
+            Some(SameSite::None) => header.push_str("; SameSite=None"),
+            None => {}
+        }
+        header
+    }
+}
+
+/// Iterates over the `name=value` pairs of a `Cookie` request header.
+pub fn parse(header: &str) -> impl Iterator<Item = (&str, &str)> {
+    header.split(';').filter_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        Some((name.trim(), value.trim().trim_matches('"')))
+    })
+}
+
+/// Cookies whose values are signed with a server secret, so clients can read them but not
+/// change them.
+///
+/// A signed value is the original value followed by a dot and the HMAC-SHA256 of the cookie's
+/// name and value, in hex. Binding the name means a value signed for one cookie isn't
+/// accepted in another. Values aren't encrypted, so don't put secrets in them.
+///
+/// Keys can be rotated without logging everyone out: sign with the new key and keep accepting
+/// the old one with [`previous_key`](SignedCookies::previous_key) until cookies signed with it
+/// have expired.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Request, Response, StatusCode, http::cookie::{Cookie, SignedCookies}};
+/// # fn main() -> Result<(), String> {
+/// # let (current_key, old_key) = ([7; 32], [3; 32]);
+/// # let (request, mut response) = (Request::builder().build(), Response::new(StatusCode::OK));
+/// let cookies = SignedCookies::new(&current_key)?.previous_key(&old_key)?;
+///
+/// cookies.add(&mut response, Cookie::new("flash", "saved"))?;
+///
+/// // On a later request; None if the cookie is missing or was tampered with
+/// let flash = cookies.get(&request, "flash");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SignedCookies {
+    /// The key signing new cookies, then older keys still accepted.
+    keys: Vec<Vec<u8>>,
+}
+
+impl SignedCookies {
+    /// Creates signed cookies keyed by `key`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - A secret of at least 32 random bytes, the same on every server instance.
+    ///
+    /// # Returns
+    ///
+    /// The signer, or an error if the key is too short.
+    pub fn new(key: &[u8]) -> Result<Self, String> {
+        check_key(key)?;
+        Ok(SignedCookies {
+            keys: vec![key.to_vec()],
+        })
+    }
+
+    /// Also accepts cookies signed with `key`, a key that has been rotated out.
+    ///
+    /// # Returns
+    ///
+    /// The signer, or an error if the key is too short.
+    pub fn previous_key(mut self, key: &[u8]) -> Result<Self, String> {
+        check_key(key)?;
+        self.keys.push(key.to_vec());
+        Ok(self)
+    }
+
+    /// Signs `cookie`'s value with the current key.
+    pub fn sign(&self, mut cookie: Cookie) -> Cookie {
+        let mac = hmac_sha256(
+            &self.keys[0],
+            message(&cookie.name, &cookie.value).as_bytes(),
+        );
+        cookie.value = format!("{}.{}", cookie.value, to_hex(&mac));
+        cookie
+    }
+
+    /// Checks a signed value sent for the cookie `name`.
+    ///
+    /// # Returns
+    ///
+    /// The original value, or `None` if the signature doesn't match any key.
+    pub fn verify(&self, name: &str, signed: &str) -> Option<String> {
+        let (value, mac) = signed.rsplit_once('.')?;
+        let mac = from_hex(mac)?;
+        let message = message(name, value);
+        self.keys
+            .iter()
+            .any(|key| constant_time_eq(&hmac_sha256(key, message.as_bytes()), &mac))
+            .then(|| value.to_string())
+    }
+
+    /// Returns the verified value of the cookie `name` sent with `request`.
+    pub fn get(&self, request: &Request, name: &str) -> Option<String> {
+        request
+            .cookies()
+            .filter(|(cookie, _)| *cookie == name)
+            .find_map(|(_, signed)| self.verify(name, signed))
+    }
+
+    /// Signs `cookie` and sets it in `response`.
+    ///
+    /// # Returns
+    ///
+    /// An error if the cookie is invalid; see [`Cookie::validate`].
+    pub fn add(&self, response: &mut Response, cookie: Cookie) -> Result<(), String> {
+        response.add_cookie(&self.sign(cookie))
+    }
+}
+
+/// Builds the message a cookie's MAC covers.
+fn message(name: &str, value: &str) -> String {
+    format!("{}={}", name, value)
+}
+
+fn check_key(key: &[u8]) -> Result<(), String> {
+    if key.len() < MIN_KEY_LEN {
+        return Err(format!(
+            "Cookie keys must be at least {} bytes, got {}",
+            MIN_KEY_LEN,
+            key.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Returns whether `c` may appear in a cookie value, as defined in RFC 6265.
+fn is_cookie_char(c: char) -> bool {
+    c.is_ascii_graphic() && !matches!(c, '"' | ',' | ';' | '\\')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [7; 32];
+    const OLD_KEY: [u8; 32] = [9; 32];
+
+    #[test]
+    fn signed_values_verify() {
+        let cookies = SignedCookies::new(&KEY).unwrap();
+        let signed = cookies.sign(Cookie::new("flash", "saved"));
+        assert!(signed.value.starts_with("saved."));
+        assert_eq!(
+            cookies.verify("flash", &signed.value).as_deref(),
+            Some("saved")
+        );
+    }
+
+    #[test]
+    fn tampered_values_are_rejected() {
+        let cookies = SignedCookies::new(&KEY).unwrap();
+        let signed = cookies.sign(Cookie::new("user", "alice")).value;
+        let (_, mac) = signed.rsplit_once('.').unwrap();
+
+        assert!(cookies.verify("user", &format!("admin.{}", mac)).is_none());
+        let mut flipped = mac.to_string();
+        flipped.replace_range(..1, if mac.starts_with('0') { "1" } else { "0" });
+        assert!(
+            cookies
+                .verify("user", &format!("alice.{}", flipped))
+                .is_none()
+        );
+        assert!(cookies.verify("user", "alice").is_none());
+        assert!(cookies.verify("user", "alice.").is_none());
+        // The signature covers the name, so it can't be replayed in another cookie
+        assert!(cookies.verify("role", &signed).is_none());
+    }
+
+    #[test]
+    fn rotated_keys_keep_verifying() {
+        let old = SignedCookies::new(&OLD_KEY).unwrap();
+        let signed_before = old.sign(Cookie::new("flash", "saved")).value;
+
+        let rotated = SignedCookies::new(&KEY)
+            .unwrap()
+            .previous_key(&OLD_KEY)
+            .unwrap();
+        assert_eq!(
+            rotated.verify("flash", &signed_before).as_deref(),
+            Some("saved")
+        );
+        // New cookies are signed with the current key only
+        let signed_after = rotated.sign(Cookie::new("flash", "saved")).value;
+        assert_ne!(signed_after, signed_before);
+        assert!(old.verify("flash", &signed_after).is_none());
+
+        // Once the old key is dropped, its cookies are no longer accepted
+        let current_only = SignedCookies::new(&KEY).unwrap();
+        assert!(current_only.verify("flash", &signed_before).is_none());
+    }
+
+    #[test]
+    fn short_keys_are_refused() {
+        assert!(SignedCookies::new(&[1; 31]).is_err());
+        assert!(
+            SignedCookies::new(&KEY)
+                .unwrap()
+                .previous_key(b"short")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn reads_the_signed_cookie_from_a_request() {
+        let cookies = SignedCookies::new(&KEY).unwrap();
+        let signed = cookies.sign(Cookie::new("flash", "saved")).value;
+        let request = Request::builder()
+            .header("Cookie", &format!("flash=forged.00; flash={}", signed))
+            .build();
+        assert_eq!(cookies.get(&request, "flash").as_deref(), Some("saved"));
+    }
+
+    #[test]
+    fn refuses_values_that_would_inject_attributes() {
+        assert!(
+            Cookie::new("a", "b; Domain=evil.example")
+                .validate()
+                .is_err()
+        );
+        assert!(Cookie::new("a b", "c").validate().is_err());
+        assert!(Cookie::new("a", "b").path(Some("/;x")).validate().is_err());
+        assert!(Cookie::new("a", "b").validate().is_ok());
+    }
+}
@@ -0,0 +1,62 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A map of values keyed by their type, for data attached to a request along the way.
+///
+/// Middleware and the server use it to hand things to handlers without new request fields,
+/// e.g. an authenticated user or an upgrade handle. Values are shared between clones of the
+/// request.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::Request;
+/// # let mut request = Request::builder().build();
+/// struct UserId(u64);
+///
+/// request.extensions.insert(UserId(7));
+/// let user = request.extensions.get::<UserId>();
+/// ```
+#[derive(Clone, Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        Extensions::default()
+    }
+
+    /// Stores `value`, replacing any earlier value of the same type.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.map.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    /// Returns the value of type `T`, if there is one.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>())
+    }
+
+    /// Removes the value of type `T`, returning whether there was one.
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> bool {
+        self.map.remove(&TypeId::of::<T>()).is_some()
+    }
+
+    /// Returns whether a value of type `T` is stored.
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.map.len())
+            .finish()
+    }
+}
@@ -0,0 +1,116 @@
+/// Checks that `name` is a valid header field name: one or more token characters.
+///
+/// # Returns
+///
+/// An error describing the problem if the name is empty or contains anything else, such as
+/// spaces, colons, CR or LF.
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Header name is empty".to_string());
+    }
+    match name.bytes().find(|&b| !is_token_byte(b)) {
+        Some(b) => Err(format!(
+            "Invalid character {:?} in header name {:?}",
+            b as char, name
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Checks that `value` is a valid header field value, which rules out response splitting.
+///
+/// # Returns
+///
+/// An error if the value contains CR, LF, NUL or another control character other than tab.
+pub fn validate_value(value: &str) -> Result<(), String> {
+    match value.bytes().find(|&b| is_forbidden_value_byte(b)) {
+        Some(b) => Err(format!("Invalid byte 0x{:02x} in header value", b)),
+        None => Ok(()),
+    }
+}
+
+/// Makes `value` safe to send by replacing each control character other than tab with a space.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::http::header::sanitize_value;
+/// assert_eq!(sanitize_value("text/html\r\nSet-Cookie: a=b"), "text/html  Set-Cookie: a=b");
+/// ```
+pub fn sanitize_value(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii() && is_forbidden_value_byte(c as u8) {
+                ' '
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Returns whether `b` may appear in a token, as defined in RFC 9110.
+fn is_token_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+/// Returns whether `b` is a control character not allowed in field values.
+fn is_forbidden_value_byte(b: u8) -> bool {
+    b.is_ascii_control() && b != b'\t'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{Response, StatusCode};
+
+    #[test]
+    fn validate_name_accepts_only_tokens() {
+        assert!(validate_name("X-Request-Id").is_ok());
+        for name in ["", "X Header", "X-Header:", "X-Header\r\n", "X-Héader"] {
+            assert!(validate_name(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn validate_value_rejects_cr_lf_and_controls() {
+        assert!(validate_value("text/html; charset=utf-8").is_ok());
+        assert!(validate_value("a\tb").is_ok());
+        assert!(validate_value("").is_ok());
+        for value in ["/\r\nSet-Cookie: a=b", "/\nX: y", "/\r", "a\0b", "a\x7fb"] {
+            assert!(validate_value(value).is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn sanitize_value_replaces_controls() {
+        assert_eq!(
+            sanitize_value("text/html\r\nSet-Cookie: a=b"),
+            "text/html  Set-Cookie: a=b"
+        );
+        assert_eq!(sanitize_value("a\tb\0c"), "a\tb c");
+        assert_eq!(sanitize_value("café"), "café");
+    }
+
+    #[test]
+    fn responses_refuse_injected_headers() {
+        let mut response = Response::new(StatusCode::SeeOther);
+        assert!(
+            response
+                .set_header("Location", "/next\r\nSet-Cookie: a=b")
+                .is_err()
+        );
+        assert!(!response.headers.contains_key("Location"));
+        assert!(response.set_header("Bad Name", "x").is_err());
+
+        response.set_content_type("text/plain\r\nX-Injected: 1");
+        response
+            .headers
+            .insert("Location".to_string(), "/next\r\nX-Injected: 1".to_string());
+        let head = String::from_utf8(response.head_bytes()).unwrap();
+        assert!(!head.contains("\r\nX-Injected"));
+        assert!(!head.contains("Location"));
+        assert!(head.contains("Content-Type: text/plain  X-Injected: 1\r\n"));
+    }
+}
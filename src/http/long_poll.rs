@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use tokio::sync::{broadcast, watch};
+
+use super::{Response, StatusCode};
+
+/// Parks a long-poll request until `wait` produces a value or `timeout` elapses.
+///
+/// If the client disconnects while waiting, the server drops the handler's future, which also
+/// drops `wait` and whatever it was subscribed to. Keep `timeout` below the server's
+/// `request_timeout`, which otherwise answers first with `503 Service Unavailable`.
+///
+/// # Arguments
+///
+/// * `timeout` - How long to wait before answering `204 No Content`.
+/// * `wait` - Resolves with the value to respond with, or `None` if no value will ever come,
+///   which is answered with `503 Service Unavailable`.
+/// * `respond` - Builds the response from the value.
+///
+/// # Examples
+///
+/// ```
+/// # use std::time::Duration;
+/// # use http_server::{Request, Response, StatusCode, http::long_poll::{changed, long_poll}};
+/// # use tokio::sync::watch;
+/// async fn handle_poll(_request: Request, mut updates: watch::Receiver<String>) -> Result<Response, String> {
+///     Ok(long_poll(Duration::from_secs(25), changed(&mut updates), |update| {
+///         let mut response = Response::new(StatusCode::OK);
+///         response.set_body(update.into_bytes());
+///         response
+///     })
+///     .await)
+/// }
+/// ```
+pub async fn long_poll<T, F, R>(timeout: Duration, wait: F, respond: R) -> Response
+where
+    F: Future<Output = Option<T>>,
+    R: FnOnce(T) -> Response,
+{
+    match tokio::time::timeout(timeout, wait).await {
+        Ok(Some(value)) => respond(value),
+        Ok(None) => {
+            let mut response = Response::new(StatusCode::ServiceUnavailable);
+            response.set_body(Vec::new());
+            response
+        }
+        Err(_) => {
+            let mut response = Response::new(StatusCode::NoContent);
+            response.set_body(Vec::new());
+            response
+        }
+    }
+}
+
+/// Waits for the next value sent on a watch channel.
+///
+/// # Returns
+///
+/// The new value, or `None` once the sender is dropped.
+pub async fn changed<T: Clone>(receiver: &mut watch::Receiver<T>) -> Option<T> {
+    receiver.changed().await.ok()?;
+    Some(receiver.borrow_and_update().clone())
+}
+
+/// Waits for the next message on a broadcast channel, skipping ahead if the receiver lagged.
+///
+/// # Returns
+///
+/// The message, or `None` once every sender is dropped.
+pub async fn next_message<T: Clone>(receiver: &mut broadcast::Receiver<T>) -> Option<T> {
+    loop {
+        match receiver.recv().await {
+            Ok(message) => return Some(message),
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
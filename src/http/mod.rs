@@ -1,9 +1,17 @@
 use std::fmt::Display;
+use std::str::FromStr;
 
+pub mod cookie;
+pub mod extensions;
+pub mod header;
+pub mod long_poll;
 pub mod parser;
 pub mod request;
 pub mod response;
+pub mod sse;
+pub mod timing;
 
+pub use extensions::Extensions;
 pub use request::Request;
 pub use response::Response;
 
@@ -20,23 +28,42 @@ pub enum Method {
     Patch,
 }
 
-impl From<&str> for Method {
-    fn from(s: &str) -> Self {
+impl FromStr for Method {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_uppercase().as_str() {
-            "GET" => Method::Get,
-            "POST" => Method::Post,
-            "PUT" => Method::Put,
-            "DELETE" => Method::Delete,
-            "HEAD" => Method::Head,
-            "CONNECT" => Method::Connect,
-            "OPTIONS" => Method::Options,
-            "TRACE" => Method::Trace,
-            "PATCH" => Method::Patch,
-            _ => panic!("Invalid method"),
+            "GET" => Ok(Method::Get),
+            "POST" => Ok(Method::Post),
+            "PUT" => Ok(Method::Put),
+            "DELETE" => Ok(Method::Delete),
+            "HEAD" => Ok(Method::Head),
+            "CONNECT" => Ok(Method::Connect),
+            "OPTIONS" => Ok(Method::Options),
+            "TRACE" => Ok(Method::Trace),
+            "PATCH" => Ok(Method::Patch),
+            _ => Err(format!("Unknown method: {:?}", s)),
         }
     }
 }
 
+impl Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let method = match self {
+            Method::Get => "GET",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Head => "HEAD",
+            Method::Connect => "CONNECT",
+            Method::Options => "OPTIONS",
+            Method::Trace => "TRACE",
+            Method::Patch => "PATCH",
+        };
+        write!(f, "{}", method)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Version {
     HTTP1_0,
@@ -68,17 +95,29 @@ impl Display for Version {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum StatusCode {
+    SwitchingProtocols = 101,
     OK = 200,
     Created = 201,
     Accepted = 202,
     NoContent = 204,
+    PartialContent = 206,
+    MovedPermanently = 301,
+    SeeOther = 303,
+    NotModified = 304,
+    PermanentRedirect = 308,
     BadRequest = 400,
     Unauthorized = 401,
     Forbidden = 403,
     NotFound = 404,
     MethodNotAllowed = 405,
+    RequestTimeout = 408,
+    PayloadTooLarge = 413,
+    RangeNotSatisfiable = 416,
+    MisdirectedRequest = 421,
+    UnprocessableEntity = 422,
+    TooManyRequests = 429,
     InternalServerError = 500,
     NotImplemented = 501,
     BadGateway = 502,
@@ -88,15 +127,27 @@ pub enum StatusCode {
 impl StatusCode {
     pub fn reason_phrase(&self) -> &str {
         match self {
+            StatusCode::SwitchingProtocols => "Switching Protocols",
             StatusCode::OK => "OK",
             StatusCode::Created => "Created",
             StatusCode::Accepted => "Accepted",
             StatusCode::NoContent => "No Content",
+            StatusCode::PartialContent => "Partial Content",
+            StatusCode::MovedPermanently => "Moved Permanently",
+            StatusCode::SeeOther => "See Other",
+            StatusCode::NotModified => "Not Modified",
+            StatusCode::PermanentRedirect => "Permanent Redirect",
             StatusCode::BadRequest => "Bad Request",
             StatusCode::Unauthorized => "Unauthorized",
             StatusCode::Forbidden => "Forbidden",
             StatusCode::NotFound => "Not Found",
             StatusCode::MethodNotAllowed => "Method Not Allowed",
+            StatusCode::RequestTimeout => "Request Timeout",
+            StatusCode::PayloadTooLarge => "Payload Too Large",
+            StatusCode::RangeNotSatisfiable => "Range Not Satisfiable",
+            StatusCode::MisdirectedRequest => "Misdirected Request",
+            StatusCode::UnprocessableEntity => "Unprocessable Entity",
+            StatusCode::TooManyRequests => "Too Many Requests",
             StatusCode::InternalServerError => "Internal Server Error",
             StatusCode::NotImplemented => "Not Implemented",
             StatusCode::BadGateway => "Bad Gateway",
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use super::{Method, Request, Version};
+use super::{Extensions, Method, Request, Version};
 
 /// Parses a raw HTTP request into a `Request` object.
 ///
@@ -25,45 +25,100 @@ pub fn parse(raw: &[u8]) -> Result<Request, String> {
     let request_line = lines.next().ok_or("Missing request line")?;
 
     let mut request_parts = request_line.split_whitespace();
-    let method = request_parts.next().ok_or("Missing method")?;
+    let method: Method = request_parts.next().ok_or("Missing method")?.parse()?;
     let path_with_query = request_parts.next().ok_or("Missing path")?;
     let version = request_parts.next().ok_or("Missing HTTP version")?;
 
-    // Parse path and query parameters
-    let (path, query) = if let Some(q_idx) = path_with_query.find('?') {
-        let path = &path_with_query[..q_idx];
-        let query_str = &path_with_query[q_idx + 1..];
-        let query = query_str
-            .split('&')
-            .filter_map(|pair| {
-                let mut split = pair.splitn(2, '=');
-                let key = split.next()?.to_string();
-                let value = split.next().unwrap_or("").to_string();
-                Some((key, value))
-            })
-            .collect();
-        (path.to_string(), query)
-    } else {
-        (path_with_query.to_string(), HashMap::new())
-    };
+    let (path, query, raw_query) = parse_target(path_with_query);
 
-    // Parse headers
-    let headers = lines
-        .filter_map(|line| {
-            let mut split = line.splitn(2, ':');
-            let key = split.next()?.trim().to_string();
-            let value = split.next()?.trim().to_string();
-            Some((key, value))
-        })
-        .collect();
+    // Parse headers, refusing anything that another server could frame differently
+    let mut headers = HashMap::new();
+    for line in lines {
+        if line.starts_with([' ', '\t']) {
+            return Err("Folded header lines aren't supported".to_string());
+        }
+        let (key, value) = line.split_once(':').ok_or("Malformed header line")?;
+        if key.is_empty() || key.contains(|c: char| c.is_ascii_whitespace()) {
+            return Err(format!("Invalid header name: {:?}", key));
+        }
+        if key.eq_ignore_ascii_case("Content-Length")
+            && headers
+                .keys()
+                .any(|name: &String| name.eq_ignore_ascii_case("Content-Length"))
+        {
+            return Err("Duplicate Content-Length header".to_string());
+        }
+        headers.insert(key.to_string(), value.trim().to_string());
+    }
 
     Ok(Request {
-        method: Method::from(method),
+        method,
         path,
         version: Version::from(version),
         headers,
         body: body_part.as_bytes().to_vec(),
         params: HashMap::new(), // Will be filled by the router
         query,
+        raw_query,
+        extensions: Extensions::new(),
     })
 }
+
+/// Splits a request target into its path, its query parameters and the raw query string.
+pub(super) fn parse_target(
+    path_with_query: &str,
+) -> (String, HashMap<String, String>, Option<String>) {
+    let Some((path, query_str)) = path_with_query.split_once('?') else {
+        return (path_with_query.to_string(), HashMap::new(), None);
+    };
+    let query = query_str
+        .split('&')
+        .filter_map(|pair| {
+            let mut split = pair.splitn(2, '=');
+            let key = split.next()?.to_string();
+            let value = split.next().unwrap_or("").to_string();
+            Some((key, value))
+        })
+        .collect();
+    (path.to_string(), query, Some(query_str.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_request_line_headers_and_query() {
+        let request =
+            parse(b"POST /users?limit=5&q HTTP/1.1\r\nHost: example.com\r\n\r\n").unwrap();
+        assert_eq!(request.method, Method::Post);
+        assert_eq!(request.path, "/users");
+        assert_eq!(request.version, Version::HTTP1_1);
+        assert_eq!(request.header("host").unwrap(), "example.com");
+        assert_eq!(request.query_param("limit").unwrap(), "5");
+        assert_eq!(request.query_param("q").unwrap(), "");
+        assert_eq!(request.raw_query.as_deref(), Some("limit=5&q"));
+    }
+
+    #[test]
+    fn rejects_unknown_methods() {
+        assert!(parse(b"FOO / HTTP/1.1\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn rejects_duplicate_content_length() {
+        let raw = b"POST / HTTP/1.1\r\nContent-Length: 4\r\ncontent-length: 40\r\n\r\n";
+        assert!(parse(raw).is_err());
+    }
+
+    #[test]
+    fn rejects_whitespace_before_colon() {
+        assert!(parse(b"GET / HTTP/1.1\r\nContent-Length : 4\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn rejects_folded_and_malformed_header_lines() {
+        assert!(parse(b"GET / HTTP/1.1\r\nX-A: 1\r\n  continued\r\n\r\n").is_err());
+        assert!(parse(b"GET / HTTP/1.1\r\nno colon here\r\n\r\n").is_err());
+    }
+}
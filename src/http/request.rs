@@ -1,6 +1,9 @@
 use std::collections::HashMap;
 
-use super::{Method, Version};
+use serde::Serialize;
+
+use super::parser::parse_target;
+use super::{Extensions, Method, Version, cookie};
 
 #[derive(Debug, Clone)]
 pub struct Request {
@@ -11,9 +14,34 @@ pub struct Request {
     pub body: Vec<u8>,
     pub params: HashMap<String, String>,
     pub query: HashMap<String, String>,
+    /// The query string as sent, without the leading `?`.
+    pub raw_query: Option<String>,
+    /// Values attached to the request by the server and middleware.
+    pub extensions: Extensions,
 }
 
 impl Request {
+    /// Starts building a request, e.g. for testing handlers with
+    /// [`Router::oneshot`](crate::router::Router::oneshot).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::{Method, Request};
+    /// # use serde_json::json;
+    /// # fn main() -> Result<(), String> {
+    /// let request = Request::builder()
+    ///     .method(Method::Post)
+    ///     .path("/users?limit=5")
+    ///     .header("Authorization", "Bearer test-token")
+    ///     .json(&json!({"name": "Ada"}))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder() -> RequestBuilder {
+        RequestBuilder::new()
+    }
+
     pub fn param(&self, key: &str) -> Option<&String> {
         self.params.get(key)
     }
@@ -21,4 +49,125 @@ impl Request {
     pub fn query_param(&self, key: &str) -> Option<&String> {
         self.query.get(key)
     }
+
+    /// Looks up a header by name, ignoring ASCII case.
+    pub fn header(&self, name: &str) -> Option<&String> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Iterates over the cookies sent with the request, as name and value pairs.
+    pub fn cookies(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.header("Cookie")
+            .map(|header| cookie::parse(header))
+            .into_iter()
+            .flatten()
+    }
+
+    /// Returns the value of the cookie `name`, if the request has one.
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies()
+            .find(|(cookie, _)| *cookie == name)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Builds a [`Request`] without going through the parser, created with [`Request::builder`].
+///
+/// The request starts out as `GET /` over HTTP/1.1 with no headers.
+#[derive(Debug, Clone)]
+pub struct RequestBuilder {
+    request: Request,
+}
+
+impl RequestBuilder {
+    fn new() -> Self {
+        RequestBuilder {
+            request: Request {
+                method: Method::Get,
+                path: "/".to_string(),
+                version: Version::HTTP1_1,
+                headers: HashMap::new(),
+                body: Vec::new(),
+                params: HashMap::new(),
+                query: HashMap::new(),
+                raw_query: None,
+                extensions: Extensions::new(),
+            },
+        }
+    }
+
+    /// Sets the request method.
+    pub fn method(mut self, method: Method) -> Self {
+        self.request.method = method;
+        self
+    }
+
+    /// Sets the request target, parsing its query string as the server would.
+    pub fn path(mut self, path_with_query: &str) -> Self {
+        let (path, query, raw_query) = parse_target(path_with_query);
+        self.request.path = path;
+        self.request.query = query;
+        self.request.raw_query = raw_query;
+        self
+    }
+
+    /// Sets the HTTP version.
+    pub fn version(mut self, version: Version) -> Self {
+        self.request.version = version;
+        self
+    }
+
+    /// Sets the header `name`, replacing any value set before.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.request
+            .headers
+            .retain(|key, _| !key.eq_ignore_ascii_case(name));
+        self.request
+            .headers
+            .insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Sets a path parameter, for calling a handler directly rather than through a router.
+    pub fn param(mut self, name: &str, value: &str) -> Self {
+        self.request
+            .params
+            .insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Attaches `value` to the request's extensions, e.g. a
+    /// [`Principal`](crate::auth::Principal) or a
+    /// [`ConnectInfo`](crate::server::ConnectInfo).
+    pub fn extension<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.request.extensions.insert(value);
+        self
+    }
+
+    /// Finishes the request with `body`, setting `Content-Length`.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Request {
+        self.request.body = body.into();
+        let length = self.request.body.len().to_string();
+        self.header("Content-Length", &length).request
+    }
+
+    /// Finishes the request with `value` serialized as its JSON body, setting `Content-Type`
+    /// and `Content-Length`.
+    ///
+    /// # Returns
+    ///
+    /// The request, or an error if `value` can't be serialized.
+    pub fn json<T: Serialize>(self, value: &T) -> Result<Request, String> {
+        let body =
+            serde_json::to_vec(value).map_err(|e| format!("Failed to serialize body: {}", e))?;
+        Ok(self.header("Content-Type", "application/json").body(body))
+    }
+
+    /// Finishes the request without a body.
+    pub fn build(self) -> Request {
+        self.request
+    }
 }
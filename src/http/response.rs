@@ -1,13 +1,61 @@
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, IoSlice, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 
-use super::{StatusCode, Version};
+use futures::Stream;
+
+use super::cookie::Cookie;
+use super::{Extensions, StatusCode, Version, header};
+use crate::logging;
 
 #[derive(Clone)]
 pub struct Response {
     pub version: Version,
     pub status_code: StatusCode,
     pub headers: HashMap<String, String>,
+    /// `Set-Cookie` header values, kept apart from `headers` since a response can set several.
+    pub cookies: Vec<String>,
     pub body: Vec<u8>,
+    /// A file sent after `body`, copied kernel-to-kernel where the platform allows it.
+    pub file: Option<Arc<File>>,
+    /// The byte range of `file` to send, or `None` for the whole file.
+    pub file_range: Option<Range<u64>>,
+    /// A body produced incrementally after `body`, sent with chunked transfer encoding, or as
+    /// is to HTTP/1.0 clients.
+    pub stream: Option<StreamBody>,
+    /// Values attached by handlers and middleware for layers further out; never sent.
+    pub extensions: Extensions,
+}
+
+/// Boxed stream of body chunks.
+type ChunkStream = Pin<Box<dyn Stream<Item = Vec<u8>> + Send>>;
+
+/// A response body produced incrementally, e.g. for server-sent events.
+///
+/// Clones share the same stream, which can only be consumed once.
+#[derive(Clone)]
+pub struct StreamBody {
+    inner: Arc<Mutex<Option<ChunkStream>>>,
+}
+
+impl StreamBody {
+    /// Wraps a stream of body chunks.
+    pub fn new<S>(stream: S) -> Self
+    where
+        S: Stream<Item = Vec<u8>> + Send + 'static,
+    {
+        StreamBody {
+            inner: Arc::new(Mutex::new(Some(Box::pin(stream)))),
+        }
+    }
+
+    /// Takes the stream out, leaving `None` for any other clone.
+    pub fn take(&self) -> Option<ChunkStream> {
+        self.inner.lock().unwrap().take()
+    }
 }
 
 impl Response {
@@ -33,7 +81,12 @@ impl Response {
             version: Version::HTTP1_1,
             status_code,
             headers,
+            cookies: Vec::new(),
             body: Vec::new(),
+            file: None,
+            file_range: None,
+            stream: None,
+            extensions: Extensions::new(),
         }
     }
 
@@ -48,46 +101,264 @@ impl Response {
             .insert("Content-Length".to_string(), self.body.len().to_string());
     }
 
+    /// Uses an open file as the body of the response and updates the "Content-Length" header.
+    ///
+    /// The file is streamed when the response is written instead of being read into memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The file to send, from its start.
+    ///
+    /// # Returns
+    ///
+    /// An error if the file's length couldn't be determined.
+    pub fn set_file(&mut self, file: File) -> io::Result<()> {
+        let len = file.metadata()?.len();
+        self.body = Vec::new();
+        self.file = Some(Arc::new(file));
+        self.file_range = None;
+        self.headers
+            .insert("Content-Length".to_string(), len.to_string());
+        Ok(())
+    }
+
+    /// Uses part of an open file as the body of the response and updates the "Content-Length"
+    /// header.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The file to send from.
+    /// * `range` - The byte offsets to send, which must lie within the file.
+    pub fn set_file_range(&mut self, file: File, range: Range<u64>) {
+        self.body = Vec::new();
+        self.file = Some(Arc::new(file));
+        self.headers.insert(
+            "Content-Length".to_string(),
+            (range.end - range.start).to_string(),
+        );
+        self.file_range = Some(range);
+    }
+
+    /// Uses a stream of chunks as the body of the response.
+    ///
+    /// The length isn't known up front, so the body is sent with chunked transfer encoding and
+    /// each chunk is written as soon as the stream yields it. HTTP/1.0 clients don't support
+    /// chunked encoding; they get the chunks as is, with the connection closed after the last.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The body chunks, in order.
+    pub fn set_stream<S>(&mut self, stream: S)
+    where
+        S: Stream<Item = Vec<u8>> + Send + 'static,
+    {
+        self.body = Vec::new();
+        self.stream = Some(StreamBody::new(stream));
+        self.headers.remove("Content-Length");
+        self.headers
+            .insert("Transfer-Encoding".to_string(), "chunked".to_string());
+    }
+
     /// Sets the "Content-Type" header of the response.
     ///
+    /// Control characters, which could end the header early, are replaced with spaces.
+    ///
     /// # Arguments
     ///
     /// * `content_type` - A string slice representing the MIME type of the response body.
     pub fn set_content_type(&mut self, content_type: &str) {
-        self.headers
-            .insert("Content-Type".to_string(), content_type.to_string());
+        self.headers.insert(
+            "Content-Type".to_string(),
+            header::sanitize_value(content_type),
+        );
     }
 
-    /// Converts the response to a vector of bytes suitable for sending over a network.
+    /// Sets a header, replacing any value it had.
+    ///
+    /// Use it rather than inserting into `headers` directly for values derived from the
+    /// request, such as a `Location` built from a query parameter: a value holding CR or LF
+    /// could otherwise add headers of its own, or a body.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The header's name, which must be a token.
+    /// * `value` - The header's value, which must not contain control characters other than tab.
     ///
     /// # Returns
     ///
-    /// A vector of bytes representing the entire HTTP response, including the status line,
-    /// headers, and body.
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut response = Vec::new();
+    /// An error, leaving the response unchanged, if the name or value is invalid.
+    pub fn set_header(&mut self, name: &str, value: &str) -> Result<(), String> {
+        header::validate_name(name)?;
+        header::validate_value(value)?;
+        self.headers.insert(name.to_string(), value.to_string());
+        Ok(())
+    }
+
+    /// Sets a cookie, in addition to any set before.
+    ///
+    /// # Returns
+    ///
+    /// An error, leaving the response unchanged, if the cookie is invalid; see
+    /// [`Cookie::validate`].
+    pub fn add_cookie(&mut self, cookie: &Cookie) -> Result<(), String> {
+        cookie.validate()?;
+        self.cookies.push(cookie.to_header());
+        Ok(())
+    }
+
+    /// Sets a header like [`set_header`](Response::set_header), for building a response in
+    /// one expression.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::{Response, StatusCode};
+    /// # fn main() -> Result<(), String> {
+    /// # let next = "/dashboard";
+    /// let response = Response::new(StatusCode::SeeOther).with_header("Location", next)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_header(mut self, name: &str, value: &str) -> Result<Self, String> {
+        self.set_header(name, value)?;
+        Ok(self)
+    }
+
+    /// Serializes the status line and headers, including the blank line that ends them.
+    ///
+    /// # Returns
+    ///
+    /// A vector of bytes holding everything in the response except the body.
+    pub fn head_bytes(&self) -> Vec<u8> {
+        // Sized for the status line and a typical set of headers, so most heads fit without
+        // growing
+        let mut head = Vec::with_capacity(256);
 
-        // Status line
-        let status_line = format!(
+        // Status line; writing to a `Vec` can't fail
+        let _ = write!(
+            head,
             "{} {} {}\r\n",
             self.version,
             self.status_code as u16,
             self.status_code.reason_phrase()
         );
-        response.extend_from_slice(status_line.as_bytes());
 
-        // Headers
-        for (key, value) in &self.headers {
-            let header_line = format!("{}: {}\r\n", key, value);
-            response.extend_from_slice(header_line.as_bytes());
+        // Headers; ones inserted into `headers` directly haven't been validated yet
+        let cookies = self.cookies.iter().map(|cookie| ("Set-Cookie", cookie));
+        for (key, value) in self
+            .headers
+            .iter()
+            .map(|(key, value)| (key.as_str(), value))
+            .chain(cookies)
+        {
+            if let Err(e) = header::validate_name(key).and(header::validate_value(value)) {
+                logging::warn(
+                    "http",
+                    "Dropped an invalid response header",
+                    &[("header", &key), ("error", &e)],
+                );
+                continue;
+            }
+            head.extend_from_slice(key.as_bytes());
+            head.extend_from_slice(b": ");
+            head.extend_from_slice(value.as_bytes());
+            head.extend_from_slice(b"\r\n");
         }
 
         // Empty line separating headers and body
-        response.extend_from_slice(b"\r\n");
+        head.extend_from_slice(b"\r\n");
 
-        // Body
-        response.extend_from_slice(&self.body);
+        head
+    }
 
+    /// Converts the response to a vector of bytes suitable for sending over a network.
+    ///
+    /// # Returns
+    ///
+    /// A vector of bytes representing the entire HTTP response, including the status line,
+    /// headers, and body. A file body is read into memory.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut response = self.head_bytes();
+        response.extend_from_slice(&self.body);
+        if let Some(file) = &self.file {
+            let (start, len) = self.file_span();
+            let mut file = &**file;
+            if file.seek(SeekFrom::Start(start)).is_ok() {
+                let _ = file.take(len).read_to_end(&mut response);
+            }
+        }
         response
     }
+
+    /// Writes the response to `writer` without copying the body into a single buffer.
+    ///
+    /// The head and body are handed to the writer together with vectored I/O, so large bodies
+    /// go out directly from where they are stored. A file body is then copied with
+    /// `std::io::copy`, which uses `sendfile`/`copy_file_range` on Linux when writing to a
+    /// socket and falls back to a buffered copy elsewhere.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The destination, typically a client connection.
+    ///
+    /// A stream body isn't written, since producing it needs an async runtime; send its
+    /// chunks with [`write_chunk`] afterwards.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let head = self.head_bytes();
+        let mut slices = [IoSlice::new(&head), IoSlice::new(&self.body)];
+        write_all_vectored(writer, &mut slices)?;
+
+        if let Some(file) = &self.file {
+            // Clones share the file, so seek rather than trust the current offset
+            let (start, len) = self.file_span();
+            let mut file = &**file;
+            file.seek(SeekFrom::Start(start))?;
+            io::copy(&mut file.take(len), writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the offset and length of the part of `file` that is sent.
+    fn file_span(&self) -> (u64, u64) {
+        match &self.file_range {
+            Some(range) => (range.start, range.end - range.start),
+            None => (0, u64::MAX),
+        }
+    }
+}
+
+/// Writes one chunk of a body sent with chunked transfer encoding.
+///
+/// # Arguments
+///
+/// * `writer` - The destination, typically a client connection.
+/// * `chunk` - The chunk to write; an empty chunk ends the body.
+pub fn write_chunk<W: Write>(writer: &mut W, chunk: &[u8]) -> io::Result<()> {
+    let size = format!("{:x}\r\n", chunk.len());
+    let mut slices = [
+        IoSlice::new(size.as_bytes()),
+        IoSlice::new(chunk),
+        IoSlice::new(b"\r\n"),
+    ];
+    write_all_vectored(writer, &mut slices)
+}
+
+/// Writes every slice in full, retrying partial and interrupted writes.
+fn write_all_vectored<W: Write>(writer: &mut W, slices: &mut [IoSlice<'_>]) -> io::Result<()> {
+    let mut slices = slices;
+    while !slices.is_empty() {
+        match writer.write_vectored(slices) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole response",
+                ));
+            }
+            Ok(written) => IoSlice::advance_slices(&mut slices, written),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
 }
@@ -0,0 +1,245 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use futures::{Stream, StreamExt};
+
+use super::{Request, Response, StatusCode};
+
+/// Returns the id of the last event a reconnecting client received, from the `Last-Event-ID`
+/// request header.
+pub fn last_event_id(request: &Request) -> Option<&str> {
+    request
+        .header("Last-Event-ID")
+        .map(|id| id.trim())
+        .filter(|id| !id.is_empty())
+}
+
+/// A single server-sent event.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::http::sse::Event;
+/// let event = Event::new().event("update").id("42").data("{\"count\": 3}");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Event {
+    data: Option<String>,
+    event: Option<String>,
+    id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    /// Creates an empty event.
+    pub fn new() -> Self {
+        Event::default()
+    }
+
+    /// Sets the event's data. Multi-line data is sent as several `data:` lines.
+    pub fn data(mut self, data: impl Into<String>) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// Sets the event name clients listen for with `addEventListener`.
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// Sets the event id, which the client sends back as `Last-Event-ID` when it reconnects.
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Sets how long the client should wait before reconnecting after the stream ends.
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Returns the event id, if one is set.
+    pub fn get_id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Formats the event as specified for `text/event-stream`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = String::new();
+
+        // Line breaks would end the field early, so they're dropped from single-line fields
+        let single_line = |value: &str| value.replace(['\r', '\n'], "");
+        if let Some(event) = &self.event {
+            out.push_str(&format!("event: {}\n", single_line(event)));
+        }
+        if let Some(id) = &self.id {
+            out.push_str(&format!("id: {}\n", single_line(id)));
+        }
+        if let Some(retry) = self.retry {
+            out.push_str(&format!("retry: {}\n", retry.as_millis()));
+        }
+        if let Some(data) = &self.data {
+            for line in data.split('\n') {
+                out.push_str(&format!("data: {}\n", line.trim_end_matches('\r')));
+            }
+        }
+        out.push('\n');
+
+        out.into_bytes()
+    }
+}
+
+/// Numbers events and keeps the most recent ones so reconnecting clients can catch up.
+///
+/// Share one history between the code producing events and the handler serving them, e.g. in
+/// an `Arc`. When a client reconnects with `Last-Event-ID`, send it the events it missed from
+/// [`EventHistory::since`] before switching to live events.
+///
+/// # Examples
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use http_server::{Request, http::sse::{Event, EventHistory, last_event_id}};
+/// # let request = Request::builder().build();
+/// let history = Arc::new(EventHistory::new(100));
+/// let event = history.record(Event::new().data("hello")); // gets id "1"
+///
+/// // In the handler
+/// let missed = history.since(last_event_id(&request));
+/// ```
+pub struct EventHistory {
+    inner: Mutex<HistoryInner>,
+}
+
+struct HistoryInner {
+    next_id: u64,
+    capacity: usize,
+    events: VecDeque<(u64, Event)>,
+}
+
+impl EventHistory {
+    /// Creates a history that keeps up to `capacity` events.
+    pub fn new(capacity: usize) -> Self {
+        EventHistory {
+            inner: Mutex::new(HistoryInner {
+                next_id: 1,
+                capacity,
+                events: VecDeque::with_capacity(capacity),
+            }),
+        }
+    }
+
+    /// Assigns the next id to `event` and remembers it.
+    ///
+    /// # Returns
+    ///
+    /// The event with its id set, ready to be sent to connected clients.
+    pub fn record(&self, event: Event) -> Event {
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id += 1;
+
+        let event = event.id(id.to_string());
+        if inner.capacity > 0 {
+            if inner.events.len() == inner.capacity {
+                inner.events.pop_front();
+            }
+            inner.events.push_back((id, event.clone()));
+        }
+        event
+    }
+
+    /// Returns the remembered events that came after `last_event_id`.
+    ///
+    /// # Arguments
+    ///
+    /// * `last_event_id` - The id the client last saw, or `None` for a new client.
+    ///
+    /// # Returns
+    ///
+    /// The missed events in order, which is empty for new clients and for ids this history
+    /// didn't issue. Events older than the history's capacity are lost.
+    pub fn since(&self, last_event_id: Option<&str>) -> Vec<Event> {
+        let Some(last) = last_event_id.and_then(|id| id.parse::<u64>().ok()) else {
+            return Vec::new();
+        };
+
+        self.inner
+            .lock()
+            .unwrap()
+            .events
+            .iter()
+            .filter(|(id, _)| *id > last)
+            .map(|(_, event)| event.clone())
+            .collect()
+    }
+}
+
+/// A `text/event-stream` response that sends events as a stream produces them.
+///
+/// While the stream is quiet a comment line is sent every `keep_alive` interval, so proxies
+/// don't time the connection out and a client that has gone away is noticed.
+///
+/// # Examples
+///
+/// ```
+/// # use futures::StreamExt;
+/// # use http_server::{Request, Response, http::sse::{Event, Sse}};
+/// async fn handle_events(_request: Request) -> Result<Response, String> {
+///     let ticks = futures::stream::iter(1..=3).map(|n| Event::new().data(n.to_string()));
+///     Ok(Sse::new(ticks).into_response())
+/// }
+/// ```
+pub struct Sse<S> {
+    events: S,
+    keep_alive: Option<Duration>,
+}
+
+impl<S> Sse<S>
+where
+    S: Stream<Item = Event> + Send + 'static,
+{
+    /// Creates an event stream response with a 15 second keep-alive interval.
+    pub fn new(events: S) -> Self {
+        Sse {
+            events,
+            keep_alive: Some(Duration::from_secs(15)),
+        }
+    }
+
+    /// Sets how often a keep-alive comment is sent, or `None` to never send one.
+    pub fn keep_alive(mut self, interval: Option<Duration>) -> Self {
+        self.keep_alive = interval;
+        self
+    }
+
+    /// Converts the event stream into a streaming response.
+    pub fn into_response(self) -> Response {
+        let mut response = Response::new(StatusCode::OK);
+        response.set_content_type("text/event-stream");
+        response
+            .headers
+            .insert("Cache-Control".to_string(), "no-cache".to_string());
+        // Ask reverse proxies such as nginx not to buffer the stream
+        response
+            .headers
+            .insert("X-Accel-Buffering".to_string(), "no".to_string());
+
+        let keep_alive = self.keep_alive;
+        let chunks = futures::stream::unfold(Box::pin(self.events), move |mut events| async move {
+            let next = match keep_alive {
+                Some(interval) => match tokio::time::timeout(interval, events.next()).await {
+                    Ok(next) => next.map(|event| event.to_bytes()),
+                    Err(_) => Some(b":\n\n".to_vec()),
+                },
+                None => events.next().await.map(|event| event.to_bytes()),
+            };
+            next.map(|chunk| (chunk, events))
+        });
+        response.set_stream(chunks);
+        response
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A stage of handling a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Reading and parsing the request, from its first byte to the end of its body.
+    Parse,
+    /// Finding the route that matches the request.
+    Routing,
+    /// Running the route's handler.
+    Handler,
+    /// Writing the response to the client.
+    Write,
+}
+
+impl Phase {
+    /// Returns the phase's name, as used in `Server-Timing` headers.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Parse => "parse",
+            Phase::Routing => "routing",
+            Phase::Handler => "handler",
+            Phase::Write => "write",
+        }
+    }
+}
+
+/// How long each phase of handling a request took, found in the request's extensions.
+///
+/// The server records the parse and write phases and the router the routing and handler
+/// phases. Clones share the same measurements, so a copy taken by middleware sees phases
+/// recorded later on, e.g. the write time once the response has been sent.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Request, http::timing::{Phase, RequestTiming}};
+/// # fn example(request: &Request) {
+/// if let Some(timing) = request.extensions.get::<RequestTiming>() {
+///     println!("parsed in {:?}", timing.get(Phase::Parse));
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RequestTiming {
+    phases: Arc<Mutex<Vec<(Phase, Duration)>>>,
+}
+
+impl RequestTiming {
+    /// Creates an empty set of measurements.
+    pub fn new() -> Self {
+        RequestTiming::default()
+    }
+
+    /// Records how long `phase` took, replacing an earlier measurement of it.
+    pub fn record(&self, phase: Phase, duration: Duration) {
+        let mut phases = self.phases.lock().unwrap();
+        phases.retain(|(recorded, _)| *recorded != phase);
+        phases.push((phase, duration));
+    }
+
+    /// Returns how long `phase` took, if it has been recorded.
+    pub fn get(&self, phase: Phase) -> Option<Duration> {
+        self.phases
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(recorded, _)| *recorded == phase)
+            .map(|(_, duration)| *duration)
+    }
+
+    /// Returns the recorded phases in the order they were recorded.
+    pub fn phases(&self) -> Vec<(Phase, Duration)> {
+        self.phases.lock().unwrap().clone()
+    }
+}
+
+/// One named measurement for the `Server-Timing` header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimingMetric {
+    /// The metric's name; characters that aren't allowed in a header token are replaced.
+    pub name: String,
+    pub duration: Duration,
+    pub description: Option<String>,
+}
+
+/// Custom measurements reported to the client in the `Server-Timing` header, e.g. time spent
+/// in the database or a cache lookup.
+///
+/// The `ServerTimingLayer` puts an empty set into the request's extensions; handlers and
+/// middleware inside it record into that set, and the layer adds everything recorded to the
+/// header alongside the request's phases. Clones share the same measurements.
+///
+/// # Examples
+///
+/// ```
+/// # use std::time::Instant;
+/// # use http_server::{Request, http::timing::ServerTimings};
+/// # struct Db;
+/// # impl Db {
+/// #     async fn load_user(&self, _id: u64) -> Result<String, String> {
+/// #         Ok(String::new())
+/// #     }
+/// # }
+/// # async fn example(request: Request, db: Db, id: u64) -> Result<(), String> {
+/// if let Some(timings) = request.extensions.get::<ServerTimings>() {
+///     let start = Instant::now();
+///     let user = db.load_user(id).await?;
+///     timings.record("db", start.elapsed());
+/// }
+/// // Server-Timing: parse;dur=0.041, routing;dur=0.002, handler;dur=12.304, db;dur=11.870
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ServerTimings {
+    metrics: Arc<Mutex<Vec<TimingMetric>>>,
+}
+
+impl ServerTimings {
+    /// Creates an empty set of measurements.
+    pub fn new() -> Self {
+        ServerTimings::default()
+    }
+
+    /// Records that `name` took `duration`.
+    pub fn record(&self, name: &str, duration: Duration) {
+        self.push(name, duration, None);
+    }
+
+    /// Records that `name` took `duration`, with a description for developer tools to show.
+    pub fn record_with_description(&self, name: &str, duration: Duration, description: &str) {
+        self.push(name, duration, Some(description.to_string()));
+    }
+
+    /// Returns the recorded measurements in the order they were recorded.
+    pub fn metrics(&self) -> Vec<TimingMetric> {
+        self.metrics.lock().unwrap().clone()
+    }
+
+    fn push(&self, name: &str, duration: Duration, description: Option<String>) {
+        // Metric names are header tokens
+        let name = name
+            .chars()
+            .map(|c| if is_token_char(c) { c } else { '_' })
+            .collect();
+        self.metrics.lock().unwrap().push(TimingMetric {
+            name,
+            duration,
+            description,
+        });
+    }
+}
+
+/// Returns whether `c` may appear in an HTTP token (RFC 9110, section 5.6.2).
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)
+}
@@ -0,0 +1,646 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Type alias for the comparison of a number with a bound.
+type Comparison = fn(f64, f64) -> bool;
+
+/// Keywords that only describe a schema, so they're accepted and ignored.
+const ANNOTATIONS: [&str; 14] = [
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "example",
+    "deprecated",
+    "readOnly",
+    "writeOnly",
+    "format",
+    "contentMediaType",
+    "contentEncoding",
+];
+
+/// A JSON type, as named by the `type` keyword.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum JsonType {
+    Null,
+    Boolean,
+    Object,
+    Array,
+    Number,
+    String,
+    Integer,
+}
+
+impl JsonType {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "null" => JsonType::Null,
+            "boolean" => JsonType::Boolean,
+            "object" => JsonType::Object,
+            "array" => JsonType::Array,
+            "number" => JsonType::Number,
+            "string" => JsonType::String,
+            "integer" => JsonType::Integer,
+            _ => return None,
+        })
+    }
+
+    /// Returns the most specific type of `value`.
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => JsonType::Null,
+            Value::Bool(_) => JsonType::Boolean,
+            Value::Object(_) => JsonType::Object,
+            Value::Array(_) => JsonType::Array,
+            Value::Number(number) if is_integer(number) => JsonType::Integer,
+            Value::Number(_) => JsonType::Number,
+            Value::String(_) => JsonType::String,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            JsonType::Null => "null",
+            JsonType::Boolean => "boolean",
+            JsonType::Object => "object",
+            JsonType::Array => "array",
+            JsonType::Number => "number",
+            JsonType::String => "string",
+            JsonType::Integer => "integer",
+        }
+    }
+
+    /// Returns whether `value` is of this type; integers are numbers too.
+    fn accepts(self, value: &Value) -> bool {
+        let actual = JsonType::of(value);
+        actual == self || (self == JsonType::Number && actual == JsonType::Integer)
+    }
+}
+
+/// A compiled schema, or subschema.
+#[derive(Debug, Clone)]
+enum Node {
+    /// `true` accepts everything, `false` nothing.
+    Always(bool),
+    Rules(Box<Rules>),
+}
+
+/// The constraints of a schema object.
+#[derive(Debug, Clone, Default)]
+struct Rules {
+    types: Option<Vec<JsonType>>,
+    allowed: Option<Vec<Value>>,
+    constant: Option<Value>,
+    // Objects
+    properties: Vec<(String, Node)>,
+    required: Vec<String>,
+    additional_properties: Option<Node>,
+    min_properties: Option<usize>,
+    max_properties: Option<usize>,
+    // Arrays
+    items: Option<Node>,
+    min_items: Option<usize>,
+    max_items: Option<usize>,
+    unique_items: bool,
+    // Strings
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    // Numbers
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    exclusive_minimum: Option<f64>,
+    exclusive_maximum: Option<f64>,
+    multiple_of: Option<f64>,
+    // Combinators
+    all_of: Vec<Node>,
+    any_of: Vec<Node>,
+    one_of: Vec<Node>,
+    not: Option<Node>,
+}
+
+/// A place in the request body that doesn't match the schema.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Violation {
+    /// A JSON pointer to the offending value, e.g. `/items/0/name`; empty for the whole body.
+    pub path: String,
+    pub message: String,
+}
+
+/// A JSON Schema, compiled once so requests can be checked against it quickly.
+///
+/// The subset of JSON Schema 2020-12 that describes the shape of request bodies is
+/// supported: `type`, `enum`, `const`, `properties`, `required`, `additionalProperties`,
+/// `minProperties`, `maxProperties`, `items`, `minItems`, `maxItems`, `uniqueItems`,
+/// `minLength`, `maxLength`, `minimum`, `maximum`, `exclusiveMinimum`, `exclusiveMaximum`,
+/// `multipleOf`, `allOf`, `anyOf`, `oneOf` and `not`. Annotations such as `description` and
+/// `format` are ignored. Other keywords, such as `$ref` and `pattern`, make compiling fail
+/// rather than being silently skipped.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::json_schema::Schema;
+/// # use serde_json::json;
+/// # fn main() -> Result<(), String> {
+/// let schema = Schema::compile(&json!({
+///     "type": "object",
+///     "properties": {
+///         "name": { "type": "string", "minLength": 1 },
+///         "age": { "type": "integer", "minimum": 0 }
+///     },
+///     "required": ["name"],
+///     "additionalProperties": false
+/// }))?;
+/// assert!(schema.validate(&json!({"name": "Ada", "age": 36})).is_empty());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Schema {
+    source: Value,
+    root: Node,
+}
+
+impl Schema {
+    /// Compiles `schema`.
+    ///
+    /// # Returns
+    ///
+    /// The compiled schema, or an error naming the first keyword that is unsupported or
+    /// malformed.
+    pub fn compile(schema: &Value) -> Result<Self, String> {
+        Ok(Schema {
+            source: schema.clone(),
+            root: compile(schema, "")?,
+        })
+    }
+
+    /// Returns the schema as it was given, e.g. for documentation.
+    pub fn as_value(&self) -> &Value {
+        &self.source
+    }
+
+    /// Checks `value` against the schema.
+    ///
+    /// # Returns
+    ///
+    /// Every violation found, or an empty list if `value` matches.
+    pub fn validate(&self, value: &Value) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        check(&self.root, value, "", &mut violations);
+        violations
+    }
+}
+
+/// Compiles the schema at the JSON pointer `at`.
+fn compile(schema: &Value, at: &str) -> Result<Node, String> {
+    let object = match schema {
+        Value::Bool(accept) => return Ok(Node::Always(*accept)),
+        Value::Object(object) => object,
+        _ => return Err(format!("Schema at `{}` isn't an object or boolean", at)),
+    };
+
+    let mut rules = Rules::default();
+    for (keyword, value) in object {
+        let here = format!("{}/{}", at, escape(keyword));
+        let malformed = || format!("Malformed `{}` at `{}`", keyword, at);
+        match keyword.as_str() {
+            "type" => {
+                let names = match value {
+                    Value::String(name) => vec![name.as_str()],
+                    Value::Array(names) => names
+                        .iter()
+                        .map(|name| name.as_str().ok_or_else(malformed))
+                        .collect::<Result<_, _>>()?,
+                    _ => return Err(malformed()),
+                };
+                let types = names
+                    .into_iter()
+                    .map(|name| JsonType::parse(name).ok_or_else(malformed))
+                    .collect::<Result<_, _>>()?;
+                rules.types = Some(types);
+            }
+            "enum" => rules.allowed = Some(value.as_array().ok_or_else(malformed)?.clone()),
+            "const" => rules.constant = Some(value.clone()),
+            "properties" => {
+                for (name, property) in value.as_object().ok_or_else(malformed)? {
+                    let node = compile(property, &format!("{}/{}", here, escape(name)))?;
+                    rules.properties.push((name.clone(), node));
+                }
+            }
+            "required" => {
+                rules.required = value
+                    .as_array()
+                    .ok_or_else(malformed)?
+                    .iter()
+                    .map(|name| name.as_str().map(str::to_string).ok_or_else(malformed))
+                    .collect::<Result<_, _>>()?;
+            }
+            "additionalProperties" => rules.additional_properties = Some(compile(value, &here)?),
+            "minProperties" => rules.min_properties = Some(count(value).ok_or_else(malformed)?),
+            "maxProperties" => rules.max_properties = Some(count(value).ok_or_else(malformed)?),
+            "items" => rules.items = Some(compile(value, &here)?),
+            "minItems" => rules.min_items = Some(count(value).ok_or_else(malformed)?),
+            "maxItems" => rules.max_items = Some(count(value).ok_or_else(malformed)?),
+            "uniqueItems" => rules.unique_items = value.as_bool().ok_or_else(malformed)?,
+            "minLength" => rules.min_length = Some(count(value).ok_or_else(malformed)?),
+            "maxLength" => rules.max_length = Some(count(value).ok_or_else(malformed)?),
+            "minimum" => rules.minimum = Some(value.as_f64().ok_or_else(malformed)?),
+            "maximum" => rules.maximum = Some(value.as_f64().ok_or_else(malformed)?),
+            "exclusiveMinimum" => {
+                rules.exclusive_minimum = Some(value.as_f64().ok_or_else(malformed)?)
+            }
+            "exclusiveMaximum" => {
+                rules.exclusive_maximum = Some(value.as_f64().ok_or_else(malformed)?)
+            }
+            "multipleOf" => {
+                let divisor = value.as_f64().filter(|divisor| *divisor > 0.0);
+                rules.multiple_of = Some(divisor.ok_or_else(malformed)?);
+            }
+            "allOf" => rules.all_of = compile_all(value, &here).ok_or_else(malformed)??,
+            "anyOf" => rules.any_of = compile_all(value, &here).ok_or_else(malformed)??,
+            "oneOf" => rules.one_of = compile_all(value, &here).ok_or_else(malformed)??,
+            "not" => rules.not = Some(compile(value, &here)?),
+            keyword if ANNOTATIONS.contains(&keyword) => {}
+            _ => return Err(format!("Unsupported keyword `{}` at `{}`", keyword, at)),
+        }
+    }
+    Ok(Node::Rules(Box::new(rules)))
+}
+
+/// Compiles the array of schemas of a combinator, or returns `None` if it isn't an array.
+fn compile_all(value: &Value, at: &str) -> Option<Result<Vec<Node>, String>> {
+    let schemas = value.as_array()?;
+    Some(
+        schemas
+            .iter()
+            .enumerate()
+            .map(|(i, schema)| compile(schema, &format!("{}/{}", at, i)))
+            .collect(),
+    )
+}
+
+/// Reads a non-negative integer keyword.
+fn count(value: &Value) -> Option<usize> {
+    value.as_u64().and_then(|count| usize::try_from(count).ok())
+}
+
+/// Checks `value`, at the JSON pointer `at`, against `node`.
+fn check(node: &Node, value: &Value, at: &str, violations: &mut Vec<Violation>) {
+    let rules = match node {
+        Node::Always(true) => return,
+        Node::Always(false) => {
+            violations.push(violation(at, "is not allowed".to_string()));
+            return;
+        }
+        Node::Rules(rules) => rules,
+    };
+
+    if let Some(types) = &rules.types
+        && !types.iter().any(|expected| expected.accepts(value))
+    {
+        let expected: Vec<&str> = types.iter().map(|expected| expected.name()).collect();
+        let message = format!(
+            "expected {}, found {}",
+            expected.join(" or "),
+            JsonType::of(value).name()
+        );
+        violations.push(violation(at, message));
+        // The other keywords would only repeat the mismatch
+        return;
+    }
+    if let Some(allowed) = &rules.allowed
+        && !allowed.iter().any(|candidate| equal(candidate, value))
+    {
+        let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+        violations.push(violation(
+            at,
+            format!("must be one of {}", allowed.join(", ")),
+        ));
+    }
+    if let Some(constant) = &rules.constant
+        && !equal(constant, value)
+    {
+        violations.push(violation(at, format!("must be {}", constant)));
+    }
+
+    match value {
+        Value::Object(object) => check_object(rules, object, at, violations),
+        Value::Array(items) => check_array(rules, items, at, violations),
+        Value::String(string) => {
+            let length = string.chars().count();
+            if let Some(min) = rules.min_length
+                && length < min
+            {
+                violations.push(violation(
+                    at,
+                    format!("must be at least {} characters long", min),
+                ));
+            }
+            if let Some(max) = rules.max_length
+                && length > max
+            {
+                violations.push(violation(
+                    at,
+                    format!("must be at most {} characters long", max),
+                ));
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or(f64::NAN);
+            let bounds: [(Option<f64>, Comparison, &str); 4] = [
+                (rules.minimum, |n, bound| n >= bound, "at least"),
+                (rules.maximum, |n, bound| n <= bound, "at most"),
+                (
+                    rules.exclusive_minimum,
+                    |n, bound| n > bound,
+                    "greater than",
+                ),
+                (rules.exclusive_maximum, |n, bound| n < bound, "less than"),
+            ];
+            for (bound, within, relation) in bounds {
+                if let Some(bound) = bound
+                    && !within(number, bound)
+                {
+                    violations.push(violation(at, format!("must be {} {}", relation, bound)));
+                }
+            }
+            if let Some(divisor) = rules.multiple_of {
+                let quotient = number / divisor;
+                if (quotient - quotient.round()).abs() > 1e-9 {
+                    violations.push(violation(at, format!("must be a multiple of {}", divisor)));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    for node in &rules.all_of {
+        check(node, value, at, violations);
+    }
+    if !rules.any_of.is_empty() && !rules.any_of.iter().any(|node| matches(node, value)) {
+        violations.push(violation(
+            at,
+            "must match at least one of the allowed schemas".to_string(),
+        ));
+    }
+    if !rules.one_of.is_empty() {
+        let matched = rules
+            .one_of
+            .iter()
+            .filter(|node| matches(node, value))
+            .count();
+        if matched != 1 {
+            violations.push(violation(
+                at,
+                format!(
+                    "must match exactly one of the allowed schemas, matched {}",
+                    matched
+                ),
+            ));
+        }
+    }
+    if let Some(node) = &rules.not
+        && matches(node, value)
+    {
+        violations.push(violation(at, "matches a disallowed schema".to_string()));
+    }
+}
+
+/// Checks the object keywords.
+fn check_object(
+    rules: &Rules,
+    object: &Map<String, Value>,
+    at: &str,
+    violations: &mut Vec<Violation>,
+) {
+    for name in &rules.required {
+        if !object.contains_key(name) {
+            violations.push(violation(
+                at,
+                format!("missing required property `{}`", name),
+            ));
+        }
+    }
+    if let Some(min) = rules.min_properties
+        && object.len() < min
+    {
+        violations.push(violation(
+            at,
+            format!("must have at least {} properties", min),
+        ));
+    }
+    if let Some(max) = rules.max_properties
+        && object.len() > max
+    {
+        violations.push(violation(
+            at,
+            format!("must have at most {} properties", max),
+        ));
+    }
+    for (name, value) in object {
+        let here = format!("{}/{}", at, escape(name));
+        match rules
+            .properties
+            .iter()
+            .find(|(property, _)| property == name)
+        {
+            Some((_, node)) => check(node, value, &here, violations),
+            None => match &rules.additional_properties {
+                Some(Node::Always(false)) => {
+                    violations.push(violation(&here, "is not an allowed property".to_string()))
+                }
+                Some(node) => check(node, value, &here, violations),
+                None => {}
+            },
+        }
+    }
+}
+
+/// Checks the array keywords.
+fn check_array(rules: &Rules, items: &[Value], at: &str, violations: &mut Vec<Violation>) {
+    if let Some(min) = rules.min_items
+        && items.len() < min
+    {
+        violations.push(violation(at, format!("must have at least {} items", min)));
+    }
+    if let Some(max) = rules.max_items
+        && items.len() > max
+    {
+        violations.push(violation(at, format!("must have at most {} items", max)));
+    }
+    if rules.unique_items {
+        for (i, item) in items.iter().enumerate() {
+            if items[..i].iter().any(|earlier| equal(earlier, item)) {
+                violations.push(violation(
+                    &format!("{}/{}", at, i),
+                    "duplicates an earlier item".to_string(),
+                ));
+            }
+        }
+    }
+    if let Some(node) = &rules.items {
+        for (i, item) in items.iter().enumerate() {
+            check(node, item, &format!("{}/{}", at, i), violations);
+        }
+    }
+}
+
+/// Returns whether `value` matches `node`, for the combinators.
+fn matches(node: &Node, value: &Value) -> bool {
+    let mut violations = Vec::new();
+    check(node, value, "", &mut violations);
+    violations.is_empty()
+}
+
+/// Compares JSON values, treating numbers of equal value as equal, e.g. `1` and `1.0`.
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equal(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(name, value)| b.get(name).is_some_and(|other| equal(value, other)))
+        }
+        _ => a == b,
+    }
+}
+
+/// Returns whether a JSON number has no fractional part, as JSON Schema's `integer` requires.
+fn is_integer(number: &serde_json::Number) -> bool {
+    number.is_i64() || number.is_u64() || number.as_f64().is_some_and(|n| n.fract() == 0.0)
+}
+
+/// Escapes a property name for a JSON pointer (RFC 6901).
+fn escape(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+fn violation(at: &str, message: String) -> Violation {
+    Violation {
+        path: at.to_string(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::http::{Method, Request, Response, StatusCode};
+    use crate::router::Router;
+
+    fn user_schema() -> Schema {
+        Schema::compile(&json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "minLength": 1, "maxLength": 8 },
+                "age": { "type": "integer", "minimum": 0 },
+                "tags": { "type": "array", "items": { "type": "string" }, "uniqueItems": true },
+                "a/b": { "const": 1 }
+            },
+            "required": ["name"],
+            "additionalProperties": false
+        }))
+        .unwrap()
+    }
+
+    fn paths(violations: &[Violation]) -> Vec<&str> {
+        violations
+            .iter()
+            .map(|violation| violation.path.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn accepts_matching_values() {
+        let schema = user_schema();
+        assert!(
+            schema
+                .validate(&json!({"name": "Ada", "age": 36, "tags": ["a", "b"], "a/b": 1.0}))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn reports_every_violation_with_its_path() {
+        let violations = user_schema().validate(&json!({
+            "age": -1.5,
+            "tags": ["a", "a", 3],
+            "a/b": 2,
+            "extra": true
+        }));
+        assert_eq!(
+            paths(&violations),
+            ["", "/a~1b", "/age", "/extra", "/tags/1", "/tags/2"]
+        );
+        assert_eq!(violations[0].message, "missing required property `name`");
+        assert_eq!(violations[2].message, "expected integer, found number");
+        assert_eq!(violations[3].message, "is not an allowed property");
+    }
+
+    #[test]
+    fn type_mismatches_stop_further_checks() {
+        let violations = user_schema().validate(&json!(["name"]));
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].message, "expected object, found array");
+    }
+
+    #[test]
+    fn combinators() {
+        let schema = Schema::compile(&json!({
+            "oneOf": [{ "type": "integer" }, { "type": "number", "multipleOf": 0.5 }],
+            "not": { "const": 0 }
+        }))
+        .unwrap();
+        assert!(schema.validate(&json!(0.5)).is_empty());
+        // 2 is both an integer and a multiple of 0.5
+        assert_eq!(schema.validate(&json!(2)).len(), 1);
+        assert_eq!(schema.validate(&json!(0.3)).len(), 1);
+        assert_eq!(schema.validate(&json!(0)).len(), 2);
+    }
+
+    #[test]
+    fn compile_refuses_unsupported_keywords() {
+        assert!(Schema::compile(&json!({ "$ref": "#/defs/user" })).is_err());
+        assert!(Schema::compile(&json!({ "type": "string", "pattern": "^a" })).is_err());
+        assert!(Schema::compile(&json!({ "type": "strnig" })).is_err());
+        assert!(Schema::compile(&json!({ "minLength": -1 })).is_err());
+        assert!(Schema::compile(&json!(1)).is_err());
+        assert!(Schema::compile(&json!({ "description": "ignored" })).is_ok());
+    }
+
+    #[tokio::test]
+    async fn routes_refuse_invalid_bodies() {
+        async fn create(_request: Request) -> Result<Response, String> {
+            Ok(Response::new(StatusCode::Created))
+        }
+        let router = Router::new().post("/users", create).validate(user_schema());
+        let post = |body: &str| {
+            Request::builder()
+                .method(Method::Post)
+                .path("/users")
+                .body(body.as_bytes().to_vec())
+        };
+
+        let response = router.oneshot(post(r#"{"name":"Ada"}"#)).await;
+        assert_eq!(response.status_code, StatusCode::Created);
+
+        let response = router.oneshot(post(r#"{"name":""}"#)).await;
+        assert_eq!(response.status_code, StatusCode::UnprocessableEntity);
+        let body: Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(body["violations"][0]["path"], "/name");
+
+        let response = router.oneshot(post("{not json")).await;
+        assert_eq!(response.status_code, StatusCode::BadRequest);
+        let body: Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(body["error"], "Request body isn't valid JSON");
+    }
+}
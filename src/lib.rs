@@ -0,0 +1,40 @@
+//! A lightweight HTTP/1.1 server with Tower-inspired services, middleware layers and routing.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use http_server::{Request, Response, Router, StatusCode, new_server};
+//!
+//! async fn hello(_request: Request) -> Result<Response, String> {
+//!     let mut response = Response::new(StatusCode::OK);
+//!     response.set_content_type("text/plain");
+//!     response.set_body(b"Hello, World!".to_vec());
+//!     Ok(response)
+//! }
+//!
+//! let router = Router::new().get("/hello", hello);
+//! new_server("127.0.0.1:8080", router).run().unwrap();
+//! ```
+
+pub mod admin;
+pub mod auth;
+mod crypto;
+pub mod embedded;
+pub mod fs_path;
+pub mod health;
+pub mod http;
+pub mod json_schema;
+pub mod logging;
+pub mod middleware;
+pub mod mime;
+pub mod openapi;
+pub mod router;
+pub mod server;
+pub mod service;
+pub mod static_files;
+pub mod testing;
+
+pub use http::{Method, Request, Response, StatusCode};
+pub use router::Router;
+pub use server::{Server, new_server};
+pub use service::{Layer, Service, ServiceBuilder, service_fn};
@@ -0,0 +1,248 @@
+use std::cell::RefCell;
+use std::fmt::{self, Display};
+use std::sync::OnceLock;
+
+use chrono::{SecondsFormat, Utc};
+
+/// The severity of a log record, from most to least severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Something failed, e.g. a connection or a request.
+    Error,
+    /// Something unexpected that the server recovered from, e.g. a slow request.
+    Warn,
+    /// Lifecycle events, such as starting to listen and shutting down.
+    Info,
+    /// Detail useful while investigating a problem.
+    Debug,
+}
+
+impl Level {
+    /// Returns the level's name in upper case, e.g. `WARN`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A diagnostic emitted by the server or its middleware.
+pub struct Record<'a> {
+    pub level: Level,
+    /// The component that emitted it, e.g. `server` or `access_log`.
+    pub target: &'a str,
+    pub message: &'a str,
+    /// Structured context, such as the peer address or request ID.
+    pub fields: &'a [(&'a str, &'a dyn Display)],
+}
+
+/// Destination for the crate's internal diagnostics.
+///
+/// Install one with [`set_logger`] to send diagnostics to your own logging stack; until then
+/// they go to a [`StderrLogger`] at [`Level::Info`]. Closures taking a [`Record`] implement
+/// this trait.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::logging::{self, Level, Record};
+/// # fn my_app_log(_level: &str, _target: &str, _message: &str) {}
+/// # fn main() -> Result<(), String> {
+/// logging::set_logger(|record: &Record| {
+///     if record.level <= Level::Warn {
+///         my_app_log(record.level.as_str(), record.target, record.message);
+///     }
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait Logger: Send + Sync {
+    /// Returns whether records at `level` are wanted, so callers can skip building them.
+    fn enabled(&self, _level: Level) -> bool {
+        true
+    }
+
+    /// Writes a record.
+    fn log(&self, record: &Record<'_>);
+}
+
+impl<F> Logger for F
+where
+    F: Fn(&Record<'_>) + Send + Sync,
+{
+    fn log(&self, record: &Record<'_>) {
+        self(record)
+    }
+}
+
+/// A [`Logger`] that writes one line per record to standard error, with fields as
+/// `key=value` pairs.
+///
+/// ```text
+/// 2025-01-01T12:00:00.000Z WARN server: Connection limit reached, turning away peer=10.0.0.7:51234
+/// ```
+pub struct StderrLogger {
+    level: Level,
+}
+
+impl StderrLogger {
+    /// Creates a logger that writes records at `level` and more severe.
+    pub fn new(level: Level) -> Self {
+        StderrLogger { level }
+    }
+}
+
+impl Logger for StderrLogger {
+    fn enabled(&self, level: Level) -> bool {
+        level <= self.level
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        eprintln!(
+            "{} {} {}: {}{}",
+            Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            record.level,
+            record.target,
+            record.message,
+            format_fields(record.fields)
+        );
+    }
+}
+
+/// A [`Logger`] that discards everything.
+pub struct NullLogger;
+
+impl Logger for NullLogger {
+    fn enabled(&self, _level: Level) -> bool {
+        false
+    }
+
+    fn log(&self, _record: &Record<'_>) {}
+}
+
+static LOGGER: OnceLock<Box<dyn Logger>> = OnceLock::new();
+
+/// Installs the logger for the crate's internal diagnostics.
+///
+/// Call it before starting the server; a logger can only be installed once.
+///
+/// # Returns
+///
+/// An error if a logger was already installed, or if something was logged before, which
+/// installs the default logger.
+pub fn set_logger(logger: impl Logger + 'static) -> Result<(), String> {
+    LOGGER
+        .set(Box::new(logger))
+        .map_err(|_| "A logger is already installed".to_string())
+}
+
+/// Returns the installed logger, installing the default one if there is none.
+pub fn logger() -> &'static dyn Logger {
+    LOGGER
+        .get_or_init(|| Box::new(StderrLogger::new(Level::Info)))
+        .as_ref()
+}
+
+/// Logs a message with structured fields through the installed logger.
+///
+/// # Arguments
+///
+/// * `level` - The record's severity.
+/// * `target` - The component emitting it.
+/// * `message` - A description of what happened.
+/// * `fields` - Context as name-value pairs.
+///
+/// Inside a request's scope, the request's ID is added as a `request_id` field unless the
+/// fields already include one.
+pub fn log(level: Level, target: &str, message: &str, fields: &[(&str, &dyn Display)]) {
+    let logger = logger();
+    if !logger.enabled(level) {
+        return;
+    }
+    let request_id = request_id().filter(|_| !fields.iter().any(|(name, _)| *name == "request_id"));
+    let mut with_id;
+    let fields = match &request_id {
+        Some(id) => {
+            with_id = fields.to_vec();
+            with_id.push(("request_id", id));
+            &with_id[..]
+        }
+        None => fields,
+    };
+    logger.log(&Record {
+        level,
+        target,
+        message,
+        fields,
+    });
+}
+
+/// Logs at [`Level::Error`]; see [`log`].
+pub fn error(target: &str, message: &str, fields: &[(&str, &dyn Display)]) {
+    log(Level::Error, target, message, fields);
+}
+
+/// Logs at [`Level::Warn`]; see [`log`].
+pub fn warn(target: &str, message: &str, fields: &[(&str, &dyn Display)]) {
+    log(Level::Warn, target, message, fields);
+}
+
+/// Logs at [`Level::Info`]; see [`log`].
+pub fn info(target: &str, message: &str, fields: &[(&str, &dyn Display)]) {
+    log(Level::Info, target, message, fields);
+}
+
+/// Logs at [`Level::Debug`]; see [`log`].
+pub fn debug(target: &str, message: &str, fields: &[(&str, &dyn Display)]) {
+    log(Level::Debug, target, message, fields);
+}
+
+tokio::task_local! {
+    /// The ID of the request being handled, once one has been assigned.
+    static REQUEST_ID: RefCell<Option<String>>;
+}
+
+/// Runs `future` as the handling of one request, giving it a slot for the request's ID.
+///
+/// The server runs each request in a scope, so the ID assigned by the request ID middleware
+/// shows up in every diagnostic logged while the request is handled, including the server's
+/// own once the service returns.
+pub async fn request_scope<F: Future>(future: F) -> F::Output {
+    REQUEST_ID.scope(RefCell::new(None), future).await
+}
+
+/// Sets the ID of the request being handled. Does nothing outside a request's scope.
+pub fn set_request_id(id: &str) {
+    let _ = REQUEST_ID.try_with(|current| *current.borrow_mut() = Some(id.to_string()));
+}
+
+/// Returns the ID of the request being handled, if it has one.
+pub fn request_id() -> Option<String> {
+    REQUEST_ID
+        .try_with(|current| current.borrow().clone())
+        .ok()
+        .flatten()
+}
+
+/// Formats fields as ` key=value` pairs, quoting values that contain spaces, quotes or `=`.
+fn format_fields(fields: &[(&str, &dyn Display)]) -> String {
+    let mut formatted = String::new();
+    for (name, value) in fields {
+        let value = value.to_string();
+        if value.is_empty() || value.contains([' ', '"', '=']) {
+            formatted.push_str(&format!(" {}={:?}", name, value));
+        } else {
+            formatted.push_str(&format!(" {}={}", name, value));
+        }
+    }
+    formatted
+}
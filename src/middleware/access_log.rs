@@ -0,0 +1,389 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use crate::http::{Request, Response, StatusCode};
+use crate::logging;
+use crate::router::MatchedRoute;
+use crate::server::ConnectInfo;
+use crate::service::{Layer, Service};
+
+/// The layout of access log lines.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    /// Common Log Format: `host ident user [time] "request" status bytes`.
+    Common,
+    /// Common Log Format followed by the quoted `Referer` and `User-Agent` headers, as written
+    /// by Apache and nginx by default.
+    Combined,
+    /// One JSON object per line, with every recorded field including latency and request ID.
+    Json,
+}
+
+/// Destination for access log lines.
+///
+/// Writers are called on the request's task once the response is ready, so they should be
+/// quick; [`ChannelWriter`] hands lines to another task for slow destinations.
+pub trait LogWriter: Send + Sync {
+    /// Writes one log line, given without a trailing newline.
+    fn write_line(&self, line: &str);
+}
+
+/// Writes log lines to standard output.
+pub struct StdoutWriter;
+
+impl LogWriter for StdoutWriter {
+    fn write_line(&self, line: &str) {
+        let mut stdout = io::stdout().lock();
+        let _ = writeln!(stdout, "{}", line);
+    }
+}
+
+/// Appends log lines to a file.
+pub struct FileWriter {
+    file: Mutex<File>,
+}
+
+impl FileWriter {
+    /// Opens `path` for appending, creating it if needed.
+    ///
+    /// # Returns
+    ///
+    /// The writer, or an error if the file couldn't be opened.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open log file {}: {}", path.display(), e))?;
+        Ok(FileWriter {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl LogWriter for FileWriter {
+    fn write_line(&self, line: &str) {
+        // The file is unlocked before reporting, in case the logger writes to it too
+        let written = writeln!(self.file.lock().unwrap(), "{}", line);
+        if let Err(e) = written {
+            logging::error("access_log", "Failed to write access log", &[("error", &e)]);
+        }
+    }
+}
+
+/// Sends log lines over a bounded channel, for a task that ships them elsewhere.
+///
+/// Lines are dropped while the channel is full, so a slow consumer never stalls requests.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::middleware::access_log::{AccessLogLayer, ChannelWriter, LogFormat};
+/// # async fn ship(_line: String) {}
+/// # async fn example() {
+/// let (writer, mut lines) = ChannelWriter::new(1024);
+/// tokio::spawn(async move {
+///     while let Some(line) = lines.recv().await {
+///         ship(line).await;
+///     }
+/// });
+/// let layer = AccessLogLayer::new(LogFormat::Json, writer);
+/// # }
+/// ```
+pub struct ChannelWriter {
+    sender: mpsc::Sender<String>,
+}
+
+impl ChannelWriter {
+    /// Creates a writer and the receiving end of its channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The most lines buffered before new ones are dropped.
+    pub fn new(capacity: usize) -> (Self, mpsc::Receiver<String>) {
+        let (sender, receiver) = mpsc::channel(capacity);
+        (ChannelWriter { sender }, receiver)
+    }
+}
+
+impl LogWriter for ChannelWriter {
+    fn write_line(&self, line: &str) {
+        let _ = self.sender.try_send(line.to_string());
+    }
+}
+
+/// Middleware that writes an access log line for every request.
+///
+/// With a slow-request threshold set, requests that take longer are also reported as a
+/// warning through the crate logger, with their timing and matched route, whatever the access log format.
+///
+/// # Examples
+///
+/// ```
+/// # use std::time::Duration;
+/// # use http_server::{Router, ServiceBuilder};
+/// # use http_server::middleware::access_log::{AccessLogLayer, FileWriter, LogFormat};
+/// # fn example(router: Router) -> Result<(), String> {
+/// let service = ServiceBuilder::new(router)
+///     .layer(
+///         AccessLogLayer::new(LogFormat::Combined, FileWriter::open("access.log")?)
+///             .slow_threshold(Duration::from_millis(500)),
+///     )
+///     .service();
+/// # Ok(())
+/// # }
+/// ```
+pub struct AccessLogLayer {
+    format: LogFormat,
+    writer: Arc<dyn LogWriter>,
+    slow_threshold: Option<Duration>,
+}
+
+impl AccessLogLayer {
+    /// Creates a layer that writes lines in `format` to `writer`.
+    pub fn new(format: LogFormat, writer: impl LogWriter + 'static) -> Self {
+        AccessLogLayer {
+            format,
+            writer: Arc::new(writer),
+            slow_threshold: None,
+        }
+    }
+
+    /// Warns about requests whose response takes longer than `threshold` to produce.
+    pub fn slow_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_threshold = Some(threshold);
+        self
+    }
+}
+
+impl<S> Layer<S> for AccessLogLayer {
+    type Service = AccessLogMiddleware<S>;
+
+    /// Wraps the given service with the access logging middleware.
+    fn layer(&self, service: S) -> Self::Service {
+        AccessLogMiddleware {
+            inner: service,
+            format: self.format,
+            writer: self.writer.clone(),
+            slow_threshold: self.slow_threshold,
+        }
+    }
+}
+
+/// Middleware service that logs each request once its response is ready.
+#[derive(Clone)]
+pub struct AccessLogMiddleware<S> {
+    inner: S,
+    format: LogFormat,
+    writer: Arc<dyn LogWriter>,
+    slow_threshold: Option<Duration>,
+}
+
+impl<S> Service for AccessLogMiddleware<S>
+where
+    S: Service<Response = Response, Error = String> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Checks if the service is ready to accept a request.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Calls the inner service and logs the request with its outcome.
+    fn call(&mut self, request: Request) -> Self::Future {
+        let mut entry = AccessLogEntry::new(&request);
+        let format = self.format;
+        let writer = self.writer.clone();
+        let slow_threshold = self.slow_threshold;
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let result = future.await;
+            entry.finish(&result);
+            writer.write_line(&entry.format(format));
+            if let Some(threshold) = slow_threshold
+                && entry.latency > threshold
+            {
+                entry.warn_slow(threshold);
+            }
+            result
+        })
+    }
+}
+
+/// The fields of one access log line.
+struct AccessLogEntry {
+    client: Option<String>,
+    time: DateTime<Utc>,
+    start: Instant,
+    method: String,
+    target: String,
+    version: String,
+    referer: Option<String>,
+    user_agent: Option<String>,
+    request_id: Option<String>,
+    /// The pattern of the route that handled the request.
+    route: Option<String>,
+    status: u16,
+    /// Body bytes sent, unknown for streamed bodies.
+    bytes: Option<u64>,
+    latency: Duration,
+    error: Option<String>,
+}
+
+impl AccessLogEntry {
+    /// Records the request's fields before it is handled.
+    fn new(request: &Request) -> Self {
+        let target = match &request.raw_query {
+            Some(query) => format!("{}?{}", request.path, query),
+            None => request.path.clone(),
+        };
+        AccessLogEntry {
+            client: request
+                .extensions
+                .get::<ConnectInfo>()
+                .map(|info| info.peer.ip().to_string()),
+            time: Utc::now(),
+            start: Instant::now(),
+            method: request.method.to_string(),
+            target,
+            version: request.version.to_string(),
+            referer: request.header("Referer").cloned(),
+            user_agent: request.header("User-Agent").cloned(),
+            request_id: request.header("X-Request-Id").cloned(),
+            route: None,
+            status: 0,
+            bytes: None,
+            latency: Duration::ZERO,
+            error: None,
+        }
+    }
+
+    /// Records the outcome; a handler error is logged as the 500 the client receives.
+    fn finish(&mut self, result: &Result<Response, String>) {
+        self.latency = self.start.elapsed();
+        match result {
+            Ok(response) => {
+                self.status = response.status_code as u16;
+                self.bytes = response
+                    .headers
+                    .get("Content-Length")
+                    .and_then(|len| len.parse().ok());
+                self.route = response
+                    .extensions
+                    .get::<MatchedRoute>()
+                    .map(|route| route.0.clone());
+                if self.request_id.is_none() {
+                    self.request_id = response.headers.get("X-Request-Id").cloned();
+                }
+            }
+            Err(e) => {
+                self.status = StatusCode::InternalServerError as u16;
+                self.error = Some(e.clone());
+            }
+        }
+        // The ID assigned by the request ID middleware wins over the one the client sent
+        if let Some(id) = logging::request_id() {
+            self.request_id = Some(id);
+        }
+    }
+
+    fn format(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::Common => self.common(),
+            LogFormat::Combined => format!(
+                "{} \"{}\" \"{}\"",
+                self.common(),
+                quoted(self.referer.as_deref()),
+                quoted(self.user_agent.as_deref())
+            ),
+            LogFormat::Json => json!({
+                "time": self.time.to_rfc3339(),
+                "client": self.client,
+                "method": self.method,
+                "target": self.target,
+                "version": self.version,
+                "status": self.status,
+                "bytes": self.bytes,
+                "latency_ms": millis(self.latency),
+                "route": self.route,
+                "referer": self.referer,
+                "user_agent": self.user_agent,
+                "request_id": self.request_id,
+                "error": self.error,
+            })
+            .to_string(),
+        }
+    }
+
+    /// Logs a warning for a request that exceeded the slow-request threshold.
+    fn warn_slow(&self, threshold: Duration) {
+        logging::warn(
+            "access_log",
+            "Slow request",
+            &[
+                ("method", &self.method),
+                ("target", &self.target),
+                ("route", &self.route.as_deref().unwrap_or("-")),
+                ("status", &self.status),
+                ("latency_ms", &millis(self.latency)),
+                ("threshold_ms", &millis(threshold)),
+                ("started", &self.time.to_rfc3339()),
+                ("client", &self.client.as_deref().unwrap_or("-")),
+                ("request_id", &self.request_id.as_deref().unwrap_or("-")),
+            ],
+        );
+    }
+
+    fn common(&self) -> String {
+        format!(
+            "{} - - [{}] \"{} {} {}\" {} {}",
+            self.client.as_deref().unwrap_or("-"),
+            self.time.format("%d/%b/%Y:%H:%M:%S %z"),
+            self.method,
+            quoted(Some(&self.target)),
+            self.version,
+            self.status,
+            self.bytes
+                .map_or_else(|| "-".to_string(), |bytes| bytes.to_string())
+        )
+    }
+}
+
+/// Converts a duration to milliseconds, rounded to microseconds.
+fn millis(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 1_000_000.0).round() / 1000.0
+}
+
+/// Escapes a value for a quoted log field, using `-` for a missing value.
+///
+/// Quotes, backslashes and control characters are escaped so a client can't forge log lines.
+fn quoted(value: Option<&str>) -> String {
+    let Some(value) = value else {
+        return "-".to_string();
+    };
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,262 @@
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use chrono::{DateTime, Utc};
+use serde_json::json;
+
+use super::access_log::LogWriter;
+use crate::auth::Principal;
+use crate::http::{Method, Request, Response, StatusCode};
+use crate::logging;
+use crate::router::MatchedRoute;
+use crate::server::ConnectInfo;
+use crate::service::{Layer, Service};
+
+/// Type alias for predicates choosing which requests are audited.
+type AuditFilter = dyn Fn(&Request) -> bool + Send + Sync;
+
+/// How an audited request ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuditOutcome {
+    /// The request succeeded (a 1xx, 2xx or 3xx response).
+    Success,
+    /// Access was refused with `401 Unauthorized` or `403 Forbidden`.
+    Denied,
+    /// The request failed for another reason, including handler errors.
+    Failure,
+}
+
+impl AuditOutcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AuditOutcome::Success => "success",
+            AuditOutcome::Denied => "denied",
+            AuditOutcome::Failure => "failure",
+        }
+    }
+}
+
+/// A security-relevant event: who did what to which resource, and whether it was allowed.
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub time: DateTime<Utc>,
+    /// The authenticated principal's ID, or `None` for anonymous requests.
+    pub principal: Option<String>,
+    pub method: Method,
+    /// The resource path, without the query string.
+    pub path: String,
+    /// The pattern of the route that handled the request.
+    pub route: Option<String>,
+    pub outcome: AuditOutcome,
+    /// The status sent to the client; handler errors are sent as 500.
+    pub status: u16,
+    pub client_ip: Option<IpAddr>,
+    pub request_id: Option<String>,
+}
+
+impl AuditEvent {
+    /// Formats the event as a single-line JSON object.
+    pub fn to_json(&self) -> String {
+        json!({
+            "time": self.time.to_rfc3339(),
+            "principal": self.principal,
+            "method": self.method.to_string(),
+            "path": self.path,
+            "route": self.route,
+            "outcome": self.outcome.as_str(),
+            "status": self.status,
+            "client_ip": self.client_ip.map(|ip| ip.to_string()),
+            "request_id": self.request_id,
+        })
+        .to_string()
+    }
+}
+
+/// Destination for audit events, e.g. a SIEM forwarder.
+///
+/// Kept apart from access logging so audit trails can have their own storage, retention and
+/// access controls. Closures taking an [`AuditEvent`] implement this trait.
+pub trait AuditSink: Send + Sync {
+    /// Records an event. Called on the request's task, so it must not block for long.
+    fn record(&self, event: AuditEvent);
+}
+
+impl<F> AuditSink for F
+where
+    F: Fn(AuditEvent) + Send + Sync,
+{
+    fn record(&self, event: AuditEvent) {
+        self(event)
+    }
+}
+
+/// An [`AuditSink`] that writes events as JSON lines to a [`LogWriter`], e.g. a dedicated,
+/// rotated file.
+pub struct JsonAuditSink {
+    writer: Box<dyn LogWriter>,
+}
+
+impl JsonAuditSink {
+    /// Creates a sink that writes to `writer`.
+    pub fn new(writer: impl LogWriter + 'static) -> Self {
+        JsonAuditSink {
+            writer: Box::new(writer),
+        }
+    }
+}
+
+impl AuditSink for JsonAuditSink {
+    fn record(&self, event: AuditEvent) {
+        self.writer.write_line(&event.to_json());
+    }
+}
+
+/// Middleware that records audit events for security-relevant requests.
+///
+/// By default requests that may change state (anything but `GET`, `HEAD` and `OPTIONS`) are
+/// audited, as is every request refused with 401 or 403. Use [`AuditLayer::filter`] to audit a
+/// different set, e.g. everything under `/admin`.
+///
+/// The principal is taken from the request's extensions, so apply authentication outside this
+/// layer, or from the response's extensions when a handler authenticates the caller itself.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Router, ServiceBuilder};
+/// # use http_server::middleware::{self, audit::{AuditLayer, JsonAuditSink}};
+/// # use http_server::middleware::rotation::LogRotation;
+/// # fn example(router: Router) -> Result<(), String> {
+/// # let auth_layer = middleware::from_fn(|req, next| next.run(req));
+/// let sink = JsonAuditSink::new(LogRotation::new("logs/audit.log").max_files(90).open()?);
+/// let service = ServiceBuilder::new(router)
+///     .layer(AuditLayer::new(sink).filter(|req| req.path.starts_with("/admin")))
+///     .layer(auth_layer)
+///     .service();
+/// # Ok(())
+/// # }
+/// ```
+pub struct AuditLayer {
+    sink: Arc<dyn AuditSink>,
+    filter: Option<Arc<AuditFilter>>,
+}
+
+impl AuditLayer {
+    /// Creates a layer that records events to `sink`.
+    pub fn new(sink: impl AuditSink + 'static) -> Self {
+        AuditLayer {
+            sink: Arc::new(sink),
+            filter: None,
+        }
+    }
+
+    /// Audits the requests `filter` returns `true` for, in addition to denied requests,
+    /// instead of the state-changing ones.
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&Request) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+}
+
+impl<S> Layer<S> for AuditLayer {
+    type Service = AuditMiddleware<S>;
+
+    /// Wraps the given service with the audit middleware.
+    fn layer(&self, service: S) -> Self::Service {
+        AuditMiddleware {
+            inner: service,
+            sink: self.sink.clone(),
+            filter: self.filter.clone(),
+        }
+    }
+}
+
+/// Middleware service that records audit events.
+#[derive(Clone)]
+pub struct AuditMiddleware<S> {
+    inner: S,
+    sink: Arc<dyn AuditSink>,
+    filter: Option<Arc<AuditFilter>>,
+}
+
+impl<S> Service for AuditMiddleware<S>
+where
+    S: Service<Response = Response, Error = String> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Checks if the service is ready to accept a request.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Calls the inner service and records an event if the request is security-relevant.
+    fn call(&mut self, request: Request) -> Self::Future {
+        let selected = match &self.filter {
+            Some(filter) => filter(&request),
+            None => !matches!(request.method, Method::Get | Method::Head | Method::Options),
+        };
+        let mut event = AuditEvent {
+            time: Utc::now(),
+            principal: request
+                .extensions
+                .get::<Principal>()
+                .map(|principal| principal.id.clone()),
+            method: request.method.clone(),
+            path: request.path.clone(),
+            route: None,
+            outcome: AuditOutcome::Success,
+            status: 0,
+            client_ip: request
+                .extensions
+                .get::<ConnectInfo>()
+                .map(|info| info.peer.ip()),
+            request_id: request.header("X-Request-Id").cloned(),
+        };
+        let sink = self.sink.clone();
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let result = future.await;
+            match &result {
+                Ok(response) => {
+                    event.status = response.status_code as u16;
+                    event.outcome = match response.status_code {
+                        StatusCode::Unauthorized | StatusCode::Forbidden => AuditOutcome::Denied,
+                        _ if event.status >= 400 => AuditOutcome::Failure,
+                        _ => AuditOutcome::Success,
+                    };
+                    event.route = response
+                        .extensions
+                        .get::<MatchedRoute>()
+                        .map(|route| route.0.clone());
+                    if event.principal.is_none() {
+                        event.principal = response
+                            .extensions
+                            .get::<Principal>()
+                            .map(|principal| principal.id.clone());
+                    }
+                }
+                Err(_) => {
+                    event.status = StatusCode::InternalServerError as u16;
+                    event.outcome = AuditOutcome::Failure;
+                }
+            }
+            if let Some(id) = logging::request_id() {
+                event.request_id = Some(id);
+            }
+            if selected || event.outcome == AuditOutcome::Denied {
+                sink.record(event);
+            }
+            result
+        })
+    }
+}
@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use serde_json::{Map, Value, json};
+
+use super::access_log::LogWriter;
+use crate::fs_path::percent_decode;
+use crate::http::{Request, Response};
+use crate::service::{Layer, Service};
+
+/// Replaces redacted values in logged headers and bodies.
+const REDACTED: &str = "[REDACTED]";
+
+/// Bodies are logged up to this many bytes unless configured otherwise.
+const DEFAULT_MAX_BODY_SIZE: usize = 4096;
+
+/// Headers redacted out of the box, since they carry credentials.
+const DEFAULT_REDACTED_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+];
+
+/// Copies headers, replacing the values of those redacted out of the box, e.g. for error
+/// reports handed to user code.
+pub(crate) fn redact_default_headers(headers: &HashMap<String, String>) -> HashMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if DEFAULT_REDACTED_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+                REDACTED
+            } else {
+                value
+            };
+            (name.clone(), value.to_string())
+        })
+        .collect()
+}
+
+/// JSON and form fields redacted out of the box.
+const DEFAULT_REDACTED_FIELDS: &[&str] = &["password", "token", "secret"];
+
+/// Debug middleware that logs request and response headers and bodies, with secrets redacted.
+///
+/// Each exchange is written as one JSON line. Bodies are cut off after a size cap. JSON and
+/// form bodies have matching fields replaced with `[REDACTED]` before they're cut, and
+/// credentials headers such as `Authorization` and `Cookie` are redacted by default.
+///
+/// Field rules without a dot, like `password`, match the key at any depth; dotted rules, like
+/// `user.profile.ssn`, match that path from the top of the document, looking through arrays.
+///
+/// Meant for debugging: even redacted, payloads may hold personal data.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::middleware::{access_log::FileWriter, body_log::BodyLogLayer};
+/// # fn example() -> Result<(), String> {
+/// let layer = BodyLogLayer::new(FileWriter::open("bodies.log")?)
+///     .max_body_size(16 * 1024)
+///     .redact_header("X-Session")
+///     .redact_field("card.number");
+/// # Ok(())
+/// # }
+/// ```
+pub struct BodyLogLayer {
+    writer: Arc<dyn LogWriter>,
+    rules: Arc<Redaction>,
+}
+
+/// What to leave out of logged exchanges.
+pub(super) struct Redaction {
+    max_body_size: usize,
+    /// Lowercase header names.
+    headers: Vec<String>,
+    /// Field rules, each split into path segments.
+    fields: Vec<Vec<String>>,
+}
+
+impl BodyLogLayer {
+    /// Creates a layer that writes exchanges to `writer`, with the default size cap and
+    /// redaction rules.
+    pub fn new(writer: impl LogWriter + 'static) -> Self {
+        BodyLogLayer {
+            writer: Arc::new(writer),
+            rules: Arc::new(Redaction::default()),
+        }
+    }
+
+    /// Logs at most `bytes` of each body, 4 KiB by default.
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        self.rules_mut().max_body_size = bytes;
+        self
+    }
+
+    /// Redacts the header `name` in requests and responses, ignoring case.
+    pub fn redact_header(mut self, name: &str) -> Self {
+        self.rules_mut().headers.push(name.to_ascii_lowercase());
+        self
+    }
+
+    /// Redacts a JSON or form field, either a bare name or a dotted path.
+    pub fn redact_field(mut self, path: &str) -> Self {
+        let segments = path.split('.').map(str::to_string).collect();
+        self.rules_mut().fields.push(segments);
+        self
+    }
+
+    fn rules_mut(&mut self) -> &mut Redaction {
+        // Only called while building, before any middleware shares the rules
+        Arc::get_mut(&mut self.rules).expect("redaction rules are shared")
+    }
+}
+
+impl<S> Layer<S> for BodyLogLayer {
+    type Service = BodyLogMiddleware<S>;
+
+    /// Wraps the given service with the body logging middleware.
+    fn layer(&self, service: S) -> Self::Service {
+        BodyLogMiddleware {
+            inner: service,
+            writer: self.writer.clone(),
+            rules: self.rules.clone(),
+        }
+    }
+}
+
+/// Middleware service that logs each exchange once its response is ready.
+#[derive(Clone)]
+pub struct BodyLogMiddleware<S> {
+    inner: S,
+    writer: Arc<dyn LogWriter>,
+    rules: Arc<Redaction>,
+}
+
+impl<S> Service for BodyLogMiddleware<S>
+where
+    S: Service<Response = Response, Error = String> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Checks if the service is ready to accept a request.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Records the request, calls the inner service and logs the exchange.
+    fn call(&mut self, request: Request) -> Self::Future {
+        let mut target = request.path.clone();
+        if let Some(query) = &request.raw_query {
+            target = format!("{}?{}", target, query);
+        }
+        let content_type = request.header("Content-Type").map(String::as_str);
+        let mut entry = json!({
+            "method": request.method.to_string(),
+            "target": target,
+            "request": {
+                "headers": self.rules.headers(&request.headers),
+                "body": self.rules.body(&request.body, content_type),
+            },
+        });
+        let writer = self.writer.clone();
+        let rules = self.rules.clone();
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let result = future.await;
+            entry["response"] = match &result {
+                Ok(response) => {
+                    let content_type = response.headers.get("Content-Type").map(String::as_str);
+                    let body = if response.file.is_some() {
+                        Value::String("<file>".to_string())
+                    } else if response.stream.is_some() {
+                        Value::String("<stream>".to_string())
+                    } else {
+                        rules.body(&response.body, content_type)
+                    };
+                    json!({
+                        "status": response.status_code as u16,
+                        "headers": rules.headers(&response.headers),
+                        "body": body,
+                    })
+                }
+                Err(e) => json!({ "error": e }),
+            };
+            writer.write_line(&entry.to_string());
+            result
+        })
+    }
+}
+
+impl Default for Redaction {
+    /// The default size cap and redaction rules.
+    fn default() -> Self {
+        Redaction {
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            headers: DEFAULT_REDACTED_HEADERS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            fields: DEFAULT_REDACTED_FIELDS
+                .iter()
+                .map(|field| vec![field.to_string()])
+                .collect(),
+        }
+    }
+}
+
+impl Redaction {
+    /// Sets the size cap, in bytes.
+    pub(super) fn set_max_body_size(&mut self, bytes: usize) {
+        self.max_body_size = bytes;
+    }
+
+    /// Copies headers, replacing the values of redacted ones.
+    pub(super) fn headers(&self, headers: &HashMap<String, String>) -> Value {
+        let headers: Map<String, Value> = headers
+            .iter()
+            .map(|(name, value)| {
+                let value = if self.headers.contains(&name.to_ascii_lowercase()) {
+                    REDACTED
+                } else {
+                    value
+                };
+                (name.clone(), Value::String(value.to_string()))
+            })
+            .collect();
+        Value::Object(headers)
+    }
+
+    /// Renders a body for the log: redacted, cut to the size cap, and as text if possible.
+    pub(super) fn body(&self, body: &[u8], content_type: Option<&str>) -> Value {
+        if body.is_empty() {
+            return Value::Null;
+        }
+        let media_type = content_type
+            .and_then(|content_type| content_type.split(';').next())
+            .map(|media_type| media_type.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        let text = if media_type == "application/json" || media_type.ends_with("+json") {
+            match serde_json::from_slice::<Value>(body) {
+                Ok(mut document) => {
+                    self.redact_json(&mut document, &mut Vec::new());
+                    document.to_string()
+                }
+                Err(_) => String::from_utf8_lossy(body).into_owned(),
+            }
+        } else if media_type == "application/x-www-form-urlencoded" {
+            self.redact_form(&String::from_utf8_lossy(body))
+        } else {
+            match std::str::from_utf8(body) {
+                Ok(text) => text.to_string(),
+                Err(_) => return Value::String(format!("<{} bytes of binary data>", body.len())),
+            }
+        };
+        Value::String(self.truncate(text))
+    }
+
+    /// Cuts text to the size cap on a character boundary, noting how much was left out.
+    fn truncate(&self, mut text: String) -> String {
+        if text.len() <= self.max_body_size {
+            return text;
+        }
+        let omitted = text.len();
+        let mut end = self.max_body_size;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        format!("{}... ({} bytes total)", text, omitted)
+    }
+
+    /// Replaces the values of fields matching a rule, tracking the path from the root.
+    fn redact_json(&self, value: &mut Value, path: &mut Vec<String>) {
+        match value {
+            Value::Object(fields) => {
+                for (key, field) in fields.iter_mut() {
+                    path.push(key.clone());
+                    if self.matches(path) {
+                        *field = Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact_json(field, path);
+                    }
+                    path.pop();
+                }
+            }
+            // Array elements share their parent's path
+            Value::Array(items) => {
+                for item in items {
+                    self.redact_json(item, path);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Replaces the values of form fields named by a bare rule.
+    fn redact_form(&self, form: &str) -> String {
+        form.split('&')
+            .map(|pair| match pair.split_once('=') {
+                // Match the name as the application decodes it, e.g. `pass%77ord`
+                Some((key, _))
+                    if self
+                        .matches(&[percent_decode(&key.replace('+', " "))
+                            .unwrap_or_else(|| key.to_string())]) =>
+                {
+                    format!("{}={}", key, REDACTED)
+                }
+                _ => pair.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    /// Returns whether a field at `path` is redacted.
+    fn matches(&self, path: &[String]) -> bool {
+        let Some(key) = path.last() else {
+            return false;
+        };
+        self.fields.iter().any(|rule| match rule.as_slice() {
+            [name] => name.eq_ignore_ascii_case(key),
+            rule => rule == path,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::http::StatusCode;
+    use crate::service::{self, ServiceBuilder, service_fn};
+
+    /// Collects log lines for inspection.
+    #[derive(Clone, Default)]
+    struct Lines(Arc<Mutex<Vec<String>>>);
+
+    impl LogWriter for Lines {
+        fn write_line(&self, line: &str) {
+            self.0.lock().unwrap().push(line.to_string());
+        }
+    }
+
+    fn redact(rules: &Redaction, body: &str, content_type: &str) -> String {
+        match rules.body(body.as_bytes(), Some(content_type)) {
+            Value::String(text) => text,
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn redacts_json_fields_at_any_depth_and_by_path() {
+        let mut rules = Redaction::default();
+        rules
+            .fields
+            .push(vec!["card".to_string(), "number".to_string()]);
+        let body = r#"{"user":{"Password":"hunter2","name":"ada"},"card":{"number":"4242"},"items":[{"token":"t"}],"number":1}"#;
+        let logged: Value =
+            serde_json::from_str(&redact(&rules, body, "application/json; charset=utf-8")).unwrap();
+        assert_eq!(logged["user"]["Password"], REDACTED);
+        assert_eq!(logged["user"]["name"], "ada");
+        assert_eq!(logged["card"]["number"], REDACTED);
+        assert_eq!(logged["items"][0]["token"], REDACTED);
+        assert_eq!(logged["number"], 1);
+    }
+
+    #[test]
+    fn redacts_form_fields_even_when_encoded() {
+        let rules = Redaction::default();
+        assert_eq!(
+            redact(
+                &rules,
+                "user=ada&password=hunter2&pass%77ord=x&to+ken=y",
+                "application/x-www-form-urlencoded"
+            ),
+            "user=ada&password=[REDACTED]&pass%77ord=[REDACTED]&to+ken=y"
+        );
+    }
+
+    #[test]
+    fn truncates_on_character_boundaries() {
+        let mut rules = Redaction::default();
+        rules.set_max_body_size(5);
+        assert_eq!(
+            redact(&rules, "abcdéfg", "text/plain"),
+            "abcd... (8 bytes total)"
+        );
+        assert_eq!(
+            rules.body(&[0xff, 0xfe], None),
+            Value::String("<2 bytes of binary data>".to_string())
+        );
+        assert_eq!(rules.body(b"", None), Value::Null);
+    }
+
+    #[tokio::test]
+    async fn logs_exchanges_with_secrets_redacted() {
+        let lines = Lines::default();
+        let layer = BodyLogLayer::new(lines.clone()).redact_header("X-Session");
+        let mut service = ServiceBuilder::new(service_fn(|_request: Request| async {
+            let mut response = Response::new(StatusCode::OK);
+            response.set_content_type("application/json");
+            response.set_body(br#"{"token":"issued","ok":true}"#.to_vec());
+            response
+                .headers
+                .insert("Set-Cookie".to_string(), "session=abc".to_string());
+            Ok(response)
+        }))
+        .layer(layer)
+        .service();
+
+        let request = Request::builder()
+            .path("/login?next=/")
+            .header("Authorization", "Bearer secret")
+            .header("x-session", "s")
+            .header("Content-Type", "application/json")
+            .body(br#"{"user":"ada","password":"hunter2"}"#.to_vec());
+        service::oneshot(&mut service, request).await;
+
+        let lines = lines.0.lock().unwrap();
+        assert_eq!(lines.len(), 1);
+        assert!(!lines[0].contains("hunter2"));
+        assert!(!lines[0].contains("Bearer secret"));
+        assert!(!lines[0].contains("issued"));
+        assert!(!lines[0].contains("session=abc"));
+        let entry: Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(entry["target"], "/login?next=/");
+        assert_eq!(entry["request"]["headers"]["x-session"], REDACTED);
+        assert_eq!(entry["response"]["status"], 200);
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde_json::{Value, json};
+
+use super::body_log::Redaction;
+use crate::http::{Method, Request, Response, StatusCode};
+use crate::service::{Layer, Service};
+
+/// Boxed future returned by [`RecentRequests::handler`].
+type ResponseFuture = Pin<Box<dyn Future<Output = Result<Response, String>> + Send>>;
+
+/// Bodies are kept up to this many bytes unless configured otherwise.
+const DEFAULT_MAX_BODY_SIZE: usize = 1024;
+
+/// A request and the response or error it got, as kept by [`RecentRequests`].
+#[derive(Debug, Clone)]
+pub struct CapturedExchange {
+    pub time: DateTime<Utc>,
+    pub latency: Duration,
+    pub method: Method,
+    /// The path and query string.
+    pub target: String,
+    /// The response status, or `None` if the service failed.
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    /// The redacted request headers and truncated body, as `{"headers": .., "body": ..}`.
+    pub request: Value,
+    /// The redacted response headers and truncated body, or `Null` if the service failed.
+    pub response: Value,
+}
+
+impl CapturedExchange {
+    /// Formats the exchange as a JSON object.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "time": self.time.to_rfc3339(),
+            "latency_ms": self.latency.as_secs_f64() * 1000.0,
+            "method": self.method.to_string(),
+            "target": self.target,
+            "status": self.status,
+            "error": self.error,
+            "request": self.request,
+            "response": self.response,
+        })
+    }
+}
+
+/// The last few requests and their responses, kept in memory for debugging.
+///
+/// Helps with "it failed five minutes ago" reports without logging every body: capture
+/// exchanges with a [`CaptureLayer`], then look at them through [`RecentRequests::entries`] or
+/// the JSON endpoint from [`RecentRequests::handler`]. Once full, the oldest exchange is
+/// dropped for each new one.
+///
+/// Headers and bodies are redacted like the body logging layer's defaults, and bodies are cut
+/// off after 1 KiB. Captures can still hold personal data, so mount the endpoint behind
+/// authentication.
+///
+/// Clones share the same buffer.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Router, ServiceBuilder};
+/// # use http_server::middleware::capture::{CaptureLayer, RecentRequests};
+/// let recent = RecentRequests::new(100);
+/// let router = Router::new().get("/admin/requests", recent.handler());
+/// let service = ServiceBuilder::new(router)
+///     .layer(CaptureLayer::new(recent.clone()))
+///     .service();
+/// ```
+#[derive(Clone)]
+pub struct RecentRequests {
+    inner: Arc<Recent>,
+}
+
+struct Recent {
+    capacity: usize,
+    rules: Redaction,
+    entries: Mutex<VecDeque<CapturedExchange>>,
+}
+
+impl RecentRequests {
+    /// Creates a buffer that keeps the last `capacity` exchanges.
+    pub fn new(capacity: usize) -> Self {
+        let mut rules = Redaction::default();
+        rules.set_max_body_size(DEFAULT_MAX_BODY_SIZE);
+        RecentRequests {
+            inner: Arc::new(Recent {
+                capacity: capacity.max(1),
+                rules,
+                entries: Mutex::new(VecDeque::with_capacity(capacity.max(1))),
+            }),
+        }
+    }
+
+    /// Keeps at most `bytes` of each body, 1 KiB by default.
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        // Only called while building, before any layer shares the buffer
+        Arc::get_mut(&mut self.inner)
+            .expect("capture buffer is shared")
+            .rules
+            .set_max_body_size(bytes);
+        self
+    }
+
+    /// Returns the captured exchanges, newest first.
+    pub fn entries(&self) -> Vec<CapturedExchange> {
+        self.inner
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+
+    /// Drops all captured exchanges.
+    pub fn clear(&self) {
+        self.inner.entries.lock().unwrap().clear();
+    }
+
+    /// Returns a handler that serves the captured exchanges as a JSON array, newest first.
+    pub fn handler(&self) -> impl Fn(Request) -> ResponseFuture + Send + Sync + 'static {
+        let recent = self.clone();
+        move |_request| {
+            let entries: Vec<Value> = recent
+                .entries()
+                .iter()
+                .map(CapturedExchange::to_json)
+                .collect();
+            let mut response = Response::new(StatusCode::OK);
+            response.set_content_type("application/json");
+            response
+                .headers
+                .insert("Cache-Control".to_string(), "no-store".to_string());
+            response.set_body(Value::Array(entries).to_string().into_bytes());
+            Box::pin(async move { Ok(response) })
+        }
+    }
+
+    /// Adds an exchange, dropping the oldest one if the buffer is full.
+    fn push(&self, exchange: CapturedExchange) {
+        let mut entries = self.inner.entries.lock().unwrap();
+        if entries.len() == self.inner.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(exchange);
+    }
+}
+
+/// Middleware that records each exchange into a [`RecentRequests`] buffer.
+pub struct CaptureLayer {
+    recent: RecentRequests,
+}
+
+impl CaptureLayer {
+    /// Creates a layer that captures exchanges into `recent`.
+    pub fn new(recent: RecentRequests) -> Self {
+        CaptureLayer { recent }
+    }
+}
+
+impl<S> Layer<S> for CaptureLayer {
+    type Service = CaptureMiddleware<S>;
+
+    /// Wraps the given service with the capture middleware.
+    fn layer(&self, service: S) -> Self::Service {
+        CaptureMiddleware {
+            inner: service,
+            recent: self.recent.clone(),
+        }
+    }
+}
+
+/// Middleware service that captures each exchange once its response is ready.
+#[derive(Clone)]
+pub struct CaptureMiddleware<S> {
+    inner: S,
+    recent: RecentRequests,
+}
+
+impl<S> Service for CaptureMiddleware<S>
+where
+    S: Service<Response = Response, Error = String> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Checks if the service is ready to accept a request.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Records the request, calls the inner service and captures the exchange.
+    fn call(&mut self, request: Request) -> Self::Future {
+        let rules = &self.recent.inner.rules;
+        let mut target = request.path.clone();
+        if let Some(query) = &request.raw_query {
+            target = format!("{}?{}", target, query);
+        }
+        let content_type = request.header("Content-Type").map(String::as_str);
+        let mut exchange = CapturedExchange {
+            time: Utc::now(),
+            latency: Duration::ZERO,
+            method: request.method.clone(),
+            target,
+            status: None,
+            error: None,
+            request: json!({
+                "headers": rules.headers(&request.headers),
+                "body": rules.body(&request.body, content_type),
+            }),
+            response: Value::Null,
+        };
+        let start = Instant::now();
+        let recent = self.recent.clone();
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let result = future.await;
+            exchange.latency = start.elapsed();
+            match &result {
+                Ok(response) => {
+                    let rules = &recent.inner.rules;
+                    let content_type = response.headers.get("Content-Type").map(String::as_str);
+                    let body = if response.file.is_some() {
+                        Value::String("<file>".to_string())
+                    } else if response.stream.is_some() {
+                        Value::String("<stream>".to_string())
+                    } else {
+                        rules.body(&response.body, content_type)
+                    };
+                    exchange.status = Some(response.status_code as u16);
+                    exchange.response = json!({
+                        "headers": rules.headers(&response.headers),
+                        "body": body,
+                    });
+                }
+                Err(e) => exchange.error = Some(e.clone()),
+            }
+            recent.push(exchange);
+            result
+        })
+    }
+}
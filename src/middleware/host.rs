@@ -0,0 +1,270 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use crate::http::{Request, Response, StatusCode};
+use crate::logging;
+use crate::service::{Layer, Service};
+
+/// A host name the server answers to.
+#[derive(Debug, Clone, PartialEq)]
+enum HostPattern {
+    /// Exactly this name.
+    Exact(String),
+    /// Any subdomain of this name, at any depth, but not the name itself.
+    Subdomains(String),
+}
+
+impl HostPattern {
+    /// Parses `example.com` or `*.example.com`.
+    fn parse(pattern: &str) -> Self {
+        let pattern = normalize(pattern);
+        match pattern.strip_prefix("*.") {
+            Some(domain) => HostPattern::Subdomains(domain.to_string()),
+            None => HostPattern::Exact(pattern),
+        }
+    }
+
+    /// Returns whether the normalized `host` matches.
+    fn matches(&self, host: &str) -> bool {
+        match self {
+            HostPattern::Exact(name) => host == name,
+            HostPattern::Subdomains(domain) => host
+                .strip_suffix(domain.as_str())
+                .and_then(|sub| sub.strip_suffix('.'))
+                .is_some_and(|sub| !sub.is_empty()),
+        }
+    }
+}
+
+/// Middleware that refuses requests whose `Host` header names a host the server doesn't
+/// serve.
+///
+/// Without it, a request for any name reaches the handlers, so an attacker can point a domain
+/// of theirs at the server (DNS rebinding) or get links and cache entries generated for a
+/// host of their choosing. Requests for other hosts are answered with `421 Misdirected
+/// Request`, and requests without a valid `Host` header with `400 Bad Request`.
+///
+/// Patterns are host names, compared without case, port or trailing dot; `*.example.com`
+/// matches every subdomain of `example.com` but not `example.com` itself. An empty allowlist
+/// refuses every request.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Router, ServiceBuilder, middleware::host::HostLayer};
+/// # fn example(router: Router) {
+/// let service = ServiceBuilder::new(router)
+///     .layer(HostLayer::new().allow("example.com").allow("*.example.com"))
+///     .service();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct HostLayer {
+    allowed: Arc<Vec<HostPattern>>,
+}
+
+impl HostLayer {
+    /// Creates a layer with an empty allowlist.
+    pub fn new() -> Self {
+        HostLayer {
+            allowed: Arc::new(Vec::new()),
+        }
+    }
+
+    /// Allows requests for hosts matching `pattern`, e.g. `example.com` or `*.example.com`.
+    pub fn allow(mut self, pattern: &str) -> Self {
+        Arc::make_mut(&mut self.allowed).push(HostPattern::parse(pattern));
+        self
+    }
+
+    /// Checks the request's `Host` header against the allowlist.
+    ///
+    /// # Returns
+    ///
+    /// The status to refuse the request with, if it's refused.
+    pub(crate) fn check(&self, request: &Request) -> Result<(), StatusCode> {
+        let host = request
+            .header("Host")
+            .and_then(|header| host_name(header))
+            .ok_or(StatusCode::BadRequest)?;
+        if self.allowed.iter().any(|pattern| pattern.matches(&host)) {
+            Ok(())
+        } else {
+            Err(StatusCode::MisdirectedRequest)
+        }
+    }
+}
+
+impl Default for HostLayer {
+    fn default() -> Self {
+        HostLayer::new()
+    }
+}
+
+impl<S> Layer<S> for HostLayer {
+    type Service = HostMiddleware<S>;
+
+    /// Wraps the given service with the host validation middleware.
+    fn layer(&self, service: S) -> Self::Service {
+        HostMiddleware {
+            inner: service,
+            config: self.clone(),
+        }
+    }
+}
+
+/// Middleware service that refuses requests for unknown hosts.
+#[derive(Clone)]
+pub struct HostMiddleware<S> {
+    inner: S,
+    config: HostLayer,
+}
+
+impl<S> Service for HostMiddleware<S>
+where
+    S: Service<Response = Response, Error = String> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Checks if the service is ready to accept a request.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Calls the inner service only if the request is for an allowed host.
+    fn call(&mut self, request: Request) -> Self::Future {
+        if let Err(status) = self.config.check(&request) {
+            let host = request.header("Host").cloned().unwrap_or_default();
+            logging::warn(
+                "host",
+                "Refused a request for an unknown host",
+                &[("host", &host), ("path", &request.path)],
+            );
+            let mut response = Response::new(status);
+            response.set_content_type("text/plain");
+            response.set_body(status.reason_phrase().as_bytes().to_vec());
+            return Box::pin(async move { Ok(response) });
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+/// Extracts the normalized host name from a `Host` header, dropping the port.
+///
+/// # Returns
+///
+/// The host name, or `None` if the header is malformed.
+fn host_name(header: &str) -> Option<String> {
+    let (host, port) = if header.starts_with('[') {
+        let end = header.find(']')?;
+        let port = header[end + 1..].strip_prefix(':');
+        if port.is_none() && end + 1 != header.len() {
+            return None;
+        }
+        (&header[..=end], port)
+    } else {
+        match header.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (header, None),
+        }
+    };
+
+    if port.is_some_and(|port| port.is_empty() || !port.bytes().all(|b| b.is_ascii_digit())) {
+        return None;
+    }
+    let valid = |b: u8| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_');
+    let name_ok = match host.strip_prefix('[') {
+        Some(literal) => literal.strip_suffix(']').is_some_and(|ip| {
+            ip.bytes()
+                .all(|b| b.is_ascii_hexdigit() || b == b':' || b == b'.')
+        }),
+        None => host.bytes().all(valid),
+    };
+    if host.is_empty() || !name_ok {
+        return None;
+    }
+    Some(normalize(host))
+}
+
+/// Lowercases a host name and drops a trailing dot.
+fn normalize(host: &str) -> String {
+    host.strip_suffix('.').unwrap_or(host).to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_name_drops_port_case_and_trailing_dot() {
+        assert_eq!(
+            host_name("Example.COM:8080").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(host_name("example.com.").as_deref(), Some("example.com"));
+        assert_eq!(host_name("[::1]:443").as_deref(), Some("[::1]"));
+        assert_eq!(host_name("[::1]").as_deref(), Some("[::1]"));
+    }
+
+    #[test]
+    fn host_name_rejects_malformed_headers() {
+        for header in [
+            "",
+            ":80",
+            "example.com:",
+            "example.com:80x",
+            "example.com:80:80",
+            "exa mple.com",
+            "example.com/evil",
+            "user@example.com",
+            "[::1",
+            "[::1]x",
+            "[evil.com]",
+        ] {
+            assert_eq!(host_name(header), None, "{:?}", header);
+        }
+    }
+
+    #[test]
+    fn patterns_match_exact_names_and_subdomains() {
+        let exact = HostPattern::parse("Example.com.");
+        assert!(exact.matches("example.com"));
+        assert!(!exact.matches("www.example.com"));
+
+        let wildcard = HostPattern::parse("*.example.com");
+        assert!(wildcard.matches("www.example.com"));
+        assert!(wildcard.matches("a.b.example.com"));
+        assert!(!wildcard.matches("example.com"));
+        assert!(!wildcard.matches("evilexample.com"));
+        assert!(!wildcard.matches(".example.com"));
+    }
+
+    #[test]
+    fn check_refuses_unknown_and_missing_hosts() {
+        let layer = HostLayer::new().allow("example.com").allow("*.example.com");
+        let request = |host: &str| Request::builder().header("Host", host).build();
+
+        assert_eq!(layer.check(&request("example.com:8080")), Ok(()));
+        assert_eq!(layer.check(&request("api.example.com")), Ok(()));
+        assert_eq!(
+            layer.check(&request("attacker.net")),
+            Err(StatusCode::MisdirectedRequest)
+        );
+        assert_eq!(
+            layer.check(&request("example.com:bad")),
+            Err(StatusCode::BadRequest)
+        );
+        assert_eq!(
+            layer.check(&Request::builder().build()),
+            Err(StatusCode::BadRequest)
+        );
+        assert_eq!(
+            HostLayer::new().check(&request("example.com")),
+            Err(StatusCode::MisdirectedRequest)
+        );
+    }
+}
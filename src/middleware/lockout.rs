@@ -0,0 +1,558 @@
+use std::collections::{BTreeSet, HashMap};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use crate::http::{Request, Response, StatusCode};
+use crate::logging;
+use crate::server::ConnectInfo;
+use crate::service::{Layer, Service};
+
+/// Type alias for the function picking the client a request's failures are counted against.
+type KeyFn = dyn Fn(&Request) -> Option<String> + Send + Sync;
+
+/// Failures recorded for one client.
+struct Offender {
+    /// Failures, less those that have decayed by `updated`.
+    score: u32,
+    updated: Instant,
+    /// Requests still being handled, counted against the threshold so that concurrent guesses
+    /// can't all get through before it trips.
+    in_flight: u32,
+    /// When the client was last seen, to find the least recently seen client.
+    last_seen: Instant,
+    blocked_until: Option<Instant>,
+}
+
+impl Offender {
+    /// Brings the score up to date, forgetting one failure per whole `decay` period.
+    fn decay(&mut self, now: Instant, decay: Duration) {
+        let periods = now.duration_since(self.updated).as_nanos() / decay.as_nanos();
+        self.score = self
+            .score
+            .saturating_sub(u32::try_from(periods).unwrap_or(u32::MAX));
+        if self.score == 0 {
+            self.updated = now;
+        } else {
+            // Keep the partial period, so steady failures still decay
+            self.updated += decay * periods as u32;
+        }
+    }
+}
+
+/// Why a request was refused without reaching the inner service.
+enum Refusal {
+    /// The client is locked out for the given time.
+    Blocked(Duration),
+    /// The client's failures and requests in flight already add up to the threshold.
+    Busy,
+    /// Every tracked client is locked out, so a new one can't be tracked until the given time.
+    Full(Duration),
+}
+
+/// The tracked clients, indexed so that expired lockouts and the client to evict are found
+/// without scanning them all.
+#[derive(Default)]
+struct Offenders {
+    clients: HashMap<String, Offender>,
+    /// Clients that aren't locked out, by when they were last seen.
+    by_last_seen: BTreeSet<(Instant, String)>,
+    /// Locked-out clients, by when their lockout ends.
+    by_unblock: BTreeSet<(Instant, String)>,
+}
+
+impl Offenders {
+    /// Forgets clients whose lockout has ended; their score was cleared when they were locked
+    /// out.
+    fn expire(&mut self, now: Instant) {
+        while let Some((until, _)) = self.by_unblock.first()
+            && *until <= now
+        {
+            if let Some((_, key)) = self.by_unblock.pop_first() {
+                self.clients.remove(&key);
+            }
+        }
+    }
+
+    /// Makes room for a new client by forgetting the least recently seen one that isn't locked
+    /// out.
+    ///
+    /// # Returns
+    ///
+    /// Whether there's room; never at the expense of a locked-out client.
+    fn make_room(&mut self, max_clients: usize) -> bool {
+        while self.clients.len() >= max_clients {
+            match self.by_last_seen.pop_first() {
+                Some((_, key)) => {
+                    self.clients.remove(&key);
+                }
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Marks a client that isn't locked out as seen at `now`.
+    fn touch(&mut self, key: &str, now: Instant) {
+        let Some(offender) = self.clients.get_mut(key) else {
+            return;
+        };
+        self.by_last_seen
+            .remove(&(offender.last_seen, key.to_string()));
+        offender.last_seen = now;
+        self.by_last_seen.insert((now, key.to_string()));
+    }
+
+    /// Forgets a client that isn't locked out.
+    fn forget(&mut self, key: &str) {
+        if let Some(offender) = self.clients.remove(key) {
+            self.by_last_seen
+                .remove(&(offender.last_seen, key.to_string()));
+        }
+    }
+}
+
+/// Middleware that locks out clients after repeated `401 Unauthorized` and `403 Forbidden`
+/// responses, slowing down password guessing and token brute-forcing.
+///
+/// Each failure adds one to the client's score, and the score decays by one every `decay`
+/// period, 1 minute by default. A client whose score reaches the threshold, 5 by default, is
+/// answered with `429 Too Many Requests` and a `Retry-After` header for the lockout period,
+/// 15 minutes by default, without reaching the inner service. Only time lowers the score;
+/// successful responses leave it alone, so successes interleaved with guesses don't keep an
+/// attacker under the threshold. Locked-out requests can also be delayed before being
+/// answered, to tie up attackers' connections.
+///
+/// Clients are told apart by IP address; use [`key_by`](LockoutLayer::key_by) to count by
+/// account instead, so a distributed attack on one account is caught too. At most 10 000
+/// clients are tracked. When the limit is reached the least recently seen client that isn't
+/// locked out is forgotten; if every tracked client is locked out, new clients are refused
+/// until a lockout ends, so flooding the table with new keys can't free a locked-out client.
+///
+/// Requests still being handled count towards the threshold along with the score, so a client
+/// can't get more guesses through by sending them concurrently.
+///
+/// # Examples
+///
+/// ```
+/// # use std::time::Duration;
+/// # use http_server::{Router, ServiceBuilder};
+/// # use http_server::middleware::{ConditionLayer, lockout::LockoutLayer};
+/// # fn example(router: Router) {
+/// let service = ServiceBuilder::new(router)
+///     .layer(ConditionLayer::path_prefix(
+///         "/login",
+///         LockoutLayer::new()
+///             .threshold(10)
+///             .lockout(Duration::from_secs(3600))
+///             .tarpit(Duration::from_secs(2)),
+///     ))
+///     .service();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct LockoutLayer {
+    key: Arc<KeyFn>,
+    threshold: u32,
+    decay: Duration,
+    lockout: Duration,
+    tarpit: Option<Duration>,
+    max_clients: usize,
+    offenders: Arc<Mutex<Offenders>>,
+}
+
+impl LockoutLayer {
+    /// Creates a layer with the default thresholds, counting failures per IP address.
+    pub fn new() -> Self {
+        LockoutLayer {
+            key: Arc::new(client_ip),
+            threshold: 5,
+            decay: Duration::from_secs(60),
+            lockout: Duration::from_secs(15 * 60),
+            tarpit: None,
+            max_clients: 10_000,
+            offenders: Arc::new(Mutex::new(Offenders::default())),
+        }
+    }
+
+    /// Picks the client a request's failures are counted against with `key`; `None` leaves
+    /// the request untracked.
+    pub fn key_by<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&Request) -> Option<String> + Send + Sync + 'static,
+    {
+        self.key = Arc::new(key);
+        self
+    }
+
+    /// Locks clients out once their score reaches `failures`.
+    pub fn threshold(mut self, failures: u32) -> Self {
+        self.threshold = failures.max(1);
+        self
+    }
+
+    /// Lets one failure decay every `decay`.
+    pub fn decay(mut self, decay: Duration) -> Self {
+        self.decay = decay.max(Duration::from_millis(1));
+        self
+    }
+
+    /// Locks clients out for `lockout`.
+    pub fn lockout(mut self, lockout: Duration) -> Self {
+        self.lockout = lockout;
+        self
+    }
+
+    /// Waits `delay` before answering locked-out requests.
+    pub fn tarpit(mut self, delay: Duration) -> Self {
+        self.tarpit = Some(delay);
+        self
+    }
+
+    /// Tracks at most `clients` clients.
+    pub fn max_clients(mut self, clients: usize) -> Self {
+        self.max_clients = clients.max(1);
+        self
+    }
+
+    /// Admits a request from `key`, counting it as in flight until it's finished.
+    fn admit(&self, key: &str, now: Instant) -> Result<(), Refusal> {
+        let mut offenders = self.offenders.lock().unwrap();
+        offenders.expire(now);
+
+        if let Some(offender) = offenders.clients.get_mut(key) {
+            if let Some(blocked_until) = offender.blocked_until {
+                return Err(Refusal::Blocked(blocked_until - now));
+            }
+            offender.decay(now, self.decay);
+            if offender.score + offender.in_flight >= self.threshold {
+                return Err(Refusal::Busy);
+            }
+            offender.in_flight += 1;
+            offenders.touch(key, now);
+            return Ok(());
+        }
+
+        if !offenders.make_room(self.max_clients) {
+            let (until, _) = offenders.by_unblock.first().expect("table is full");
+            let remaining = *until - now;
+            logging::warn(
+                "lockout",
+                "Refused a new client, every tracked client is locked out",
+                &[("client", &key)],
+            );
+            return Err(Refusal::Full(remaining));
+        }
+        offenders.clients.insert(
+            key.to_string(),
+            Offender {
+                score: 0,
+                updated: now,
+                in_flight: 1,
+                last_seen: now,
+                blocked_until: None,
+            },
+        );
+        offenders.by_last_seen.insert((now, key.to_string()));
+        Ok(())
+    }
+
+    /// Finishes a request admitted from `key`, counting it against the client if it `failed`.
+    fn finish(&self, key: &str, failed: bool, now: Instant) {
+        let mut offenders = self.offenders.lock().unwrap();
+        let Some(offender) = offenders.clients.get_mut(key) else {
+            return;
+        };
+        offender.in_flight = offender.in_flight.saturating_sub(1);
+        if offender.blocked_until.is_some() {
+            return;
+        }
+
+        // Successes don't clear the score: with per-IP keys, an attacker could interleave
+        // requests that succeed, e.g. logins to their own account, between guesses
+        offender.decay(now, self.decay);
+        if failed {
+            offender.score += 1;
+        }
+        if offender.score >= self.threshold {
+            offender.score = 0;
+            let blocked_until = now + self.lockout;
+            offender.blocked_until = Some(blocked_until);
+            let last_seen = offender.last_seen;
+            offenders.by_last_seen.remove(&(last_seen, key.to_string()));
+            offenders
+                .by_unblock
+                .insert((blocked_until, key.to_string()));
+            logging::warn(
+                "lockout",
+                "Locked out a client after repeated failures",
+                &[("client", &key), ("seconds", &self.lockout.as_secs())],
+            );
+        } else if offender.score == 0 && offender.in_flight == 0 {
+            offenders.forget(key);
+        }
+    }
+}
+
+/// A request admitted from a client, finished when dropped so that cancelled requests don't
+/// stay counted as in flight.
+struct Attempt {
+    config: LockoutLayer,
+    key: String,
+    failed: bool,
+}
+
+impl Attempt {
+    /// Finishes the attempt, counting it against the client if it was refused.
+    fn finish(mut self, response: &Response) {
+        let status = response.status_code as u16;
+        self.failed = status == 401 || status == 403;
+    }
+}
+
+impl Drop for Attempt {
+    fn drop(&mut self) {
+        self.config.finish(&self.key, self.failed, Instant::now());
+    }
+}
+
+impl Default for LockoutLayer {
+    fn default() -> Self {
+        LockoutLayer::new()
+    }
+}
+
+impl<S> Layer<S> for LockoutLayer {
+    type Service = LockoutMiddleware<S>;
+
+    /// Wraps the given service with the lockout middleware.
+    fn layer(&self, service: S) -> Self::Service {
+        LockoutMiddleware {
+            inner: service,
+            config: self.clone(),
+        }
+    }
+}
+
+/// Middleware service that locks out clients with repeated failures.
+#[derive(Clone)]
+pub struct LockoutMiddleware<S> {
+    inner: S,
+    config: LockoutLayer,
+}
+
+impl<S> Service for LockoutMiddleware<S>
+where
+    S: Service<Response = Response, Error = String> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Checks if the service is ready to accept a request.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Refuses locked-out clients, and records the outcome of other requests.
+    fn call(&mut self, request: Request) -> Self::Future {
+        let Some(key) = (self.config.key)(&request) else {
+            return Box::pin(self.inner.call(request));
+        };
+
+        let remaining = match self.config.admit(&key, Instant::now()) {
+            Ok(()) => None,
+            Err(Refusal::Blocked(remaining) | Refusal::Full(remaining)) => Some(remaining),
+            // Another attempt is already under way, so this one could trip the threshold
+            Err(Refusal::Busy) => Some(Duration::from_secs(1)),
+        };
+        if let Some(remaining) = remaining {
+            let tarpit = self.config.tarpit;
+            return Box::pin(async move {
+                if let Some(delay) = tarpit {
+                    tokio::time::sleep(delay).await;
+                }
+                let mut response = Response::new(StatusCode::TooManyRequests);
+                response.set_content_type("text/plain");
+                response.set_body(b"Too many failed attempts".to_vec());
+                // Round up, so clients retrying on time aren't refused again
+                let retry_after = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+                response
+                    .headers
+                    .insert("Retry-After".to_string(), retry_after.to_string());
+                Ok(response)
+            });
+        }
+
+        let attempt = Attempt {
+            config: self.config.clone(),
+            key,
+            failed: false,
+        };
+        let future = self.inner.call(request);
+        Box::pin(async move {
+            let response = future.await?;
+            attempt.finish(&response);
+            Ok(response)
+        })
+    }
+}
+
+/// Counts failures against the client's IP address.
+fn client_ip(request: &Request) -> Option<String> {
+    request
+        .extensions
+        .get::<ConnectInfo>()
+        .map(|info| info.peer.ip().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::service::{self, ServiceBuilder, service_fn};
+
+    /// Answers `/login` with 401 and everything else with 200, keyed by the `X-Client` header.
+    fn service(layer: LockoutLayer) -> impl Service<Response = Response, Error = String> {
+        let layer = layer.key_by(|request| request.header("X-Client").cloned());
+        ServiceBuilder::new(service_fn(|request: Request| async move {
+            let status = match request.path.as_str() {
+                "/login" => StatusCode::Unauthorized,
+                _ => StatusCode::OK,
+            };
+            Ok(Response::new(status))
+        }))
+        .layer(layer)
+        .service()
+    }
+
+    #[tokio::test]
+    async fn locks_out_after_threshold() {
+        let mut service = service(LockoutLayer::new().threshold(3));
+        for _ in 0..3 {
+            assert_eq!(status_for(&mut service, "attacker", "/login").await, 401);
+        }
+        let request = Request::builder()
+            .path("/")
+            .header("X-Client", "attacker")
+            .build();
+        let response = service::oneshot(&mut service, request).await;
+        assert_eq!(response.status_code, StatusCode::TooManyRequests);
+        assert_eq!(response.headers.get("Retry-After").unwrap(), "900");
+    }
+
+    #[tokio::test]
+    async fn successes_do_not_reset_the_score() {
+        let mut service = service(LockoutLayer::new().threshold(3));
+        for _ in 0..3 {
+            assert_eq!(status_for(&mut service, "attacker", "/").await, 200);
+            assert_eq!(status_for(&mut service, "attacker", "/login").await, 401);
+        }
+        assert_eq!(status_for(&mut service, "attacker", "/").await, 429);
+    }
+
+    #[tokio::test]
+    async fn tracks_clients_separately() {
+        let mut service = service(LockoutLayer::new().threshold(1));
+        assert_eq!(status_for(&mut service, "attacker", "/login").await, 401);
+        let request = Request::builder()
+            .path("/")
+            .header("X-Client", "someone else")
+            .build();
+        let response = service::oneshot(&mut service, request).await;
+        assert_eq!(response.status_code, StatusCode::OK);
+    }
+
+    async fn status_for(
+        service: &mut impl Service<Response = Response, Error = String>,
+        client: &str,
+        path: &str,
+    ) -> u16 {
+        let request = Request::builder()
+            .path(path)
+            .header("X-Client", client)
+            .build();
+        service::oneshot(service, request).await.status_code as u16
+    }
+
+    #[tokio::test]
+    async fn eviction_keeps_locked_out_clients() {
+        let mut service = service(LockoutLayer::new().threshold(2).max_clients(2));
+        assert_eq!(status_for(&mut service, "attacker", "/login").await, 401);
+        assert_eq!(status_for(&mut service, "attacker", "/login").await, 401);
+        for i in 0..10 {
+            let client = format!("filler {i}");
+            assert_eq!(status_for(&mut service, &client, "/login").await, 401);
+        }
+        assert_eq!(status_for(&mut service, "attacker", "/").await, 429);
+    }
+
+    #[tokio::test]
+    async fn refuses_new_clients_when_all_are_locked_out() {
+        let mut service = service(LockoutLayer::new().threshold(1).max_clients(1));
+        assert_eq!(status_for(&mut service, "attacker", "/login").await, 401);
+        assert_eq!(status_for(&mut service, "newcomer", "/").await, 429);
+        assert_eq!(status_for(&mut service, "attacker", "/").await, 429);
+    }
+
+    #[tokio::test]
+    async fn forgets_clients_without_failures() {
+        let layer = LockoutLayer::new();
+        let mut service = service(layer.clone());
+        assert_eq!(status_for(&mut service, "visitor", "/").await, 200);
+        let offenders = layer.offenders.lock().unwrap();
+        assert!(offenders.clients.is_empty());
+        assert!(offenders.by_last_seen.is_empty());
+    }
+
+    #[tokio::test]
+    async fn counts_concurrent_attempts_against_the_threshold() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let handler_calls = calls.clone();
+        let service = LockoutLayer::new()
+            .threshold(3)
+            .key_by(|request| request.header("X-Client").cloned())
+            .layer(service_fn(move |_request: Request| {
+                let calls = handler_calls.clone();
+                async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    Ok(Response::new(StatusCode::Unauthorized))
+                }
+            }));
+
+        let statuses = futures::future::join_all((0..10).map(|_| {
+            let mut service = service.clone();
+            async move { status_for(&mut service, "attacker", "/login").await }
+        }))
+        .await;
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(statuses.iter().filter(|status| **status == 401).count(), 3);
+        assert!(
+            statuses
+                .iter()
+                .all(|status| *status == 401 || *status == 429)
+        );
+    }
+
+    #[test]
+    fn score_decays_one_failure_per_period() {
+        let now = Instant::now();
+        let decay = Duration::from_secs(60);
+        let mut offender = Offender {
+            score: 5,
+            updated: now,
+            in_flight: 0,
+            last_seen: now,
+            blocked_until: None,
+        };
+        offender.decay(now + decay * 3 + decay / 2, decay);
+        assert_eq!(offender.score, 2);
+        assert_eq!(offender.updated, now + decay * 3);
+        offender.decay(now + decay * 10, decay);
+        assert_eq!(offender.score, 0);
+    }
+}
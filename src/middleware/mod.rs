@@ -0,0 +1,325 @@
+pub mod access_log;
+pub mod audit;
+pub mod body_log;
+pub mod capture;
+pub mod host;
+pub mod lockout;
+pub mod otlp;
+pub mod quota;
+pub mod request_id;
+pub mod rotation;
+pub mod secure_transport;
+pub mod security_headers;
+pub mod server_timing;
+pub mod trace;
+pub mod webhook;
+
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    http::{Request, Response},
+    service::{Layer, Service},
+};
+
+/// Middleware to handle Cross-Origin Resource Sharing (CORS)
+pub struct CorsLayer;
+
+impl<S> Layer<S> for CorsLayer {
+    type Service = CorsMiddleware<S>;
+
+    /// Wraps the given service with the CORS middleware.
+    fn layer(&self, service: S) -> Self::Service {
+        CorsMiddleware { inner: service }
+    }
+}
+
+/// Middleware service that adds CORS headers to responses.
+#[derive(Clone)]
+pub struct CorsMiddleware<S> {
+    inner: S,
+}
+
+impl<S> Service for CorsMiddleware<S>
+where
+    S: Service<Response = Response, Error = String> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+
+    type Error = S::Error;
+
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Checks if the service is ready to accept a request.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Handles the incoming request and adds CORS headers to the response.
+    fn call(&mut self, request: Request) -> Self::Future {
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let mut response = future.await?;
+
+            response
+                .headers
+                .insert("Access-Control-Allow-Origin".to_owned(), "*".to_string());
+            response.headers.insert(
+                "Access-Control-Allow-Methods".to_owned(),
+                "GET, POST, PUT, DELETE, OPTIONS".to_string(),
+            );
+
+            response.headers.insert(
+                "Access-Control-Allow-Headers".to_owned(),
+                "Content-Type, Authorization".to_string(),
+            );
+
+            Ok(response)
+        })
+    }
+}
+
+/// Type alias for request predicates used by conditional middleware.
+type Predicate = dyn Fn(&Request) -> bool + Send + Sync;
+
+/// Middleware that applies an inner layer only to requests matching a predicate
+pub struct ConditionLayer<L> {
+    predicate: Arc<Predicate>,
+    layer: L,
+}
+
+impl<L> ConditionLayer<L> {
+    /// Creates a new `ConditionLayer` that applies `layer` when `predicate` returns `true`.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - A function deciding whether a request goes through the layer.
+    /// * `layer` - The layer to apply to matching requests.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::middleware::{ConditionLayer, host::HostLayer};
+    /// let admin_host = HostLayer::new().allow("admin.example.com");
+    /// let layer = ConditionLayer::new(|req| req.path.starts_with("/admin/"), admin_host);
+    /// ```
+    pub fn new<P>(predicate: P, layer: L) -> Self
+    where
+        P: Fn(&Request) -> bool + Send + Sync + 'static,
+    {
+        ConditionLayer {
+            predicate: Arc::new(predicate),
+            layer,
+        }
+    }
+
+    /// Creates a new `ConditionLayer` that applies `layer` to paths starting with `prefix`.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The path prefix requests must start with, e.g. `/api/`.
+    /// * `layer` - The layer to apply to matching requests.
+    pub fn path_prefix(prefix: &str, layer: L) -> Self {
+        let prefix = prefix.to_string();
+        Self::new(move |req| req.path.starts_with(&prefix), layer)
+    }
+}
+
+impl<S, L> Layer<S> for ConditionLayer<L>
+where
+    S: Clone,
+    L: Layer<S>,
+{
+    type Service = ConditionMiddleware<L::Service, S>;
+
+    /// Wraps the given service, keeping an unwrapped copy for non-matching requests.
+    fn layer(&self, service: S) -> Self::Service {
+        ConditionMiddleware {
+            predicate: self.predicate.clone(),
+            layered: self.layer.layer(service.clone()),
+            inner: service,
+        }
+    }
+}
+
+/// Middleware service that routes requests through the layered or the plain service.
+pub struct ConditionMiddleware<L, S> {
+    predicate: Arc<Predicate>,
+    layered: L,
+    inner: S,
+}
+
+impl<L, S> Service for ConditionMiddleware<L, S>
+where
+    L: Service<Response = Response, Error = String> + Send,
+    L::Future: Send + 'static,
+    S: Service<Response = Response, Error = String> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = String;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Checks that both the layered and the plain service are ready.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match (self.layered.poll_ready(cx)?, self.inner.poll_ready(cx)?) {
+            (Poll::Ready(()), Poll::Ready(())) => Poll::Ready(Ok(())),
+            _ => Poll::Pending,
+        }
+    }
+
+    /// Dispatches the request to the layered service if the predicate matches.
+    fn call(&mut self, request: Request) -> Self::Future {
+        if (self.predicate)(&request) {
+            Box::pin(self.layered.call(request))
+        } else {
+            Box::pin(self.inner.call(request))
+        }
+    }
+}
+
+impl<L: Clone, S: Clone> Clone for ConditionMiddleware<L, S> {
+    fn clone(&self) -> Self {
+        ConditionMiddleware {
+            predicate: self.predicate.clone(),
+            layered: self.layered.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// Type alias for the boxed remainder of a middleware stack.
+type NextFn =
+    dyn FnOnce(Request) -> Pin<Box<dyn Future<Output = Result<Response, String>> + Send>> + Send;
+
+/// The rest of the middleware stack, handed to middleware created with [`from_fn`].
+pub struct Next {
+    inner: Box<NextFn>,
+}
+
+impl Next {
+    /// Passes the request on to the inner service.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The (possibly modified) request.
+    ///
+    /// # Returns
+    ///
+    /// The response or error produced by the rest of the stack.
+    pub async fn run(self, request: Request) -> Result<Response, String> {
+        (self.inner)(request).await
+    }
+}
+
+/// Creates a middleware layer from an async function.
+///
+/// The function receives the request and a [`Next`] handle to the inner service, so it can
+/// inspect or modify the request, short-circuit with its own response, or post-process the
+/// response returned by `next.run(request)`.
+///
+/// # Arguments
+///
+/// * `f` - The middleware function.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::middleware;
+/// let layer = middleware::from_fn(|req, next| async move {
+///     let mut response = next.run(req).await?;
+///     response.headers.insert("X-Powered-By".to_string(), "RustHTTP".to_string());
+///     Ok(response)
+/// });
+/// ```
+pub fn from_fn<F, Fut>(f: F) -> FromFnLayer<F>
+where
+    F: Fn(Request, Next) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Response, String>> + Send + 'static,
+{
+    FromFnLayer { f: Arc::new(f) }
+}
+
+/// Layer produced by [`from_fn`].
+pub struct FromFnLayer<F> {
+    f: Arc<F>,
+}
+
+impl<S, F> Layer<S> for FromFnLayer<F> {
+    type Service = FromFnMiddleware<F, S>;
+
+    /// Wraps the given service with the function middleware.
+    fn layer(&self, service: S) -> Self::Service {
+        FromFnMiddleware {
+            f: self.f.clone(),
+            inner: service,
+        }
+    }
+}
+
+/// Middleware service that runs an async function around the inner service.
+pub struct FromFnMiddleware<F, S> {
+    f: Arc<F>,
+    inner: S,
+}
+
+impl<F, Fut, S> Service for FromFnMiddleware<F, S>
+where
+    F: Fn(Request, Next) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Response, String>> + Send + 'static,
+    S: Service<Response = Response, Error = String> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = String;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Checks if the inner service is ready to accept a request.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Calls the middleware function with the request and the rest of the stack.
+    fn call(&mut self, request: Request) -> Self::Future {
+        // Hand the service that was polled ready to `Next`, keeping a fresh clone for later calls
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let next = Next {
+            inner: Box::new(move |req| Box::pin(inner.call(req))),
+        };
+
+        Box::pin((self.f)(request, next))
+    }
+}
+
+impl<F, S: Clone> Clone for FromFnMiddleware<F, S> {
+    fn clone(&self) -> Self {
+        FromFnMiddleware {
+            f: self.f.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// Helper function to extract request body as JSON
+///
+/// # Arguments
+///
+/// * `request` - A reference to the incoming request.
+///
+/// # Returns
+///
+/// * `Result<T, String>` - The deserialized JSON body or an error message.
+pub async fn json_extractor<T: DeserializeOwned>(request: &Request) -> Result<T, String> {
+    let body = &request.body;
+    let result: T =
+        serde_json::from_slice(body).map_err(|e| format!("Failed to parse JSON: {}", e))?;
+    Ok(result)
+}
@@ -0,0 +1,254 @@
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use serde_json::{Value, json};
+
+use super::trace::{AttributeValue, Span, SpanExporter};
+use crate::logging;
+
+/// Most spans sent in one export request.
+const MAX_BATCH_SIZE: usize = 512;
+
+/// Longest a finished span waits before its batch is sent.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Longest an export request may take to connect, send or receive.
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends spans to an OpenTelemetry collector over OTLP/HTTP, using the JSON encoding.
+///
+/// Spans are queued and sent in batches from a background thread, so exporting never blocks
+/// a request. Export failures are logged and the batch is dropped. Queued spans
+/// are sent when the exporter is dropped.
+///
+/// Only plain `http://` endpoints are supported; put a collector or sidecar on the same host
+/// to forward spans over TLS.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Router, ServiceBuilder};
+/// # use http_server::middleware::{otlp::OtlpExporter, trace::TraceLayer};
+/// # fn example(router: Router) -> Result<(), String> {
+/// let exporter = OtlpExporter::new("http://localhost:4318", "my-service")?;
+/// let service = ServiceBuilder::new(router)
+///     .layer(TraceLayer::new(exporter))
+///     .service();
+/// # Ok(())
+/// # }
+/// ```
+pub struct OtlpExporter {
+    sender: Option<Sender<Span>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+/// Where export requests are sent.
+struct Endpoint {
+    /// `host:port` to connect to.
+    address: String,
+    /// The `Host` header value.
+    host: String,
+    path: String,
+}
+
+impl OtlpExporter {
+    /// Creates an exporter and starts its background thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - The collector's base URL, e.g. `http://localhost:4318`. Spans are posted
+    ///   to `/v1/traces` unless the URL has a path of its own.
+    /// * `service_name` - Reported as the `service.name` resource attribute.
+    ///
+    /// # Returns
+    ///
+    /// The exporter, or an error if the endpoint is not a valid `http://` URL.
+    pub fn new(endpoint: &str, service_name: &str) -> Result<Self, String> {
+        let endpoint = Endpoint::parse(endpoint)?;
+        let service_name = service_name.to_string();
+        let (sender, receiver) = mpsc::channel::<Span>();
+
+        let worker = thread::Builder::new()
+            .name("otlp-exporter".to_string())
+            .spawn(move || {
+                let mut batch = Vec::new();
+                let mut deadline = Instant::now() + FLUSH_INTERVAL;
+                loop {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    let disconnected = match receiver.recv_timeout(timeout) {
+                        Ok(span) => {
+                            batch.push(span);
+                            false
+                        }
+                        Err(RecvTimeoutError::Timeout) => false,
+                        Err(RecvTimeoutError::Disconnected) => true,
+                    };
+                    if batch.len() >= MAX_BATCH_SIZE || Instant::now() >= deadline || disconnected {
+                        if !batch.is_empty() {
+                            let body = encode(&service_name, &batch).to_string();
+                            if let Err(e) = endpoint.post(&body) {
+                                logging::error(
+                                    "otlp",
+                                    "Failed to export spans",
+                                    &[("spans", &batch.len()), ("error", &e)],
+                                );
+                            }
+                            batch.clear();
+                        }
+                        deadline = Instant::now() + FLUSH_INTERVAL;
+                    }
+                    if disconnected {
+                        return;
+                    }
+                }
+            })
+            .map_err(|e| format!("Failed to start the exporter thread: {}", e))?;
+
+        Ok(OtlpExporter {
+            sender: Some(sender),
+            worker: Some(worker),
+        })
+    }
+}
+
+impl SpanExporter for OtlpExporter {
+    /// Queues a span for the next batch.
+    fn export(&self, span: Span) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(span);
+        }
+    }
+}
+
+impl Drop for OtlpExporter {
+    /// Sends the queued spans and stops the background thread.
+    fn drop(&mut self) {
+        // Closing the channel makes the worker flush and exit
+        self.sender.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Endpoint {
+    /// Parses an `http://host[:port][/path]` URL.
+    fn parse(url: &str) -> Result<Self, String> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("Unsupported OTLP endpoint, expected http://: {}", url))?;
+        let (host, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, ""),
+        };
+        if host.is_empty() {
+            return Err(format!("Missing host in OTLP endpoint: {}", url));
+        }
+        let address = if host
+            .rsplit_once(':')
+            .is_some_and(|(_, port)| !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()))
+        {
+            host.to_string()
+        } else {
+            format!("{}:80", host)
+        };
+        let path = match path.trim_end_matches('/') {
+            "" => "/v1/traces".to_string(),
+            path => path.to_string(),
+        };
+        Ok(Endpoint {
+            address,
+            host: host.to_string(),
+            path,
+        })
+    }
+
+    /// Posts a JSON body and checks that the collector accepted it.
+    fn post(&self, body: &str) -> Result<(), String> {
+        let mut stream = TcpStream::connect(&self.address)
+            .map_err(|e| format!("Failed to connect to {}: {}", self.address, e))?;
+        let _ = stream.set_read_timeout(Some(EXPORT_TIMEOUT));
+        let _ = stream.set_write_timeout(Some(EXPORT_TIMEOUT));
+
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.host,
+            body.len(),
+            body
+        );
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|e| format!("Failed to send spans: {}", e))?;
+
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response);
+        let status_line = String::from_utf8_lossy(&response);
+        let status_line = status_line.lines().next().unwrap_or_default();
+        match status_line.split_whitespace().nth(1) {
+            Some(status) if status.starts_with('2') => Ok(()),
+            _ => Err(format!("Collector responded with {:?}", status_line)),
+        }
+    }
+}
+
+/// Encodes spans as an OTLP `ExportTraceServiceRequest` in JSON.
+fn encode(service_name: &str, spans: &[Span]) -> Value {
+    let spans: Vec<Value> = spans.iter().map(encode_span).collect();
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [attribute("service.name", &AttributeValue::String(service_name.to_string()))]
+            },
+            "scopeSpans": [{
+                "scope": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans
+            }]
+        }]
+    })
+}
+
+fn encode_span(span: &Span) -> Value {
+    let mut value = json!({
+        "traceId": format!("{:032x}", span.context.trace_id),
+        "spanId": format!("{:016x}", span.context.span_id),
+        "name": span.name,
+        // SPAN_KIND_SERVER
+        "kind": 2,
+        "startTimeUnixNano": unix_nanos(span.start),
+        "endTimeUnixNano": unix_nanos(span.end),
+        "attributes": span
+            .attributes
+            .iter()
+            .map(|(key, value)| attribute(key, value))
+            .collect::<Vec<_>>(),
+        // STATUS_CODE_ERROR or STATUS_CODE_UNSET
+        "status": { "code": if span.error { 2 } else { 0 } },
+    });
+    if let Some(parent) = span.parent_span_id {
+        value["parentSpanId"] = json!(format!("{:016x}", parent));
+    }
+    if let Some(trace_state) = &span.context.trace_state {
+        value["traceState"] = json!(trace_state);
+    }
+    value
+}
+
+fn attribute(key: &str, value: &AttributeValue) -> Value {
+    let value = match value {
+        AttributeValue::String(value) => json!({ "stringValue": value }),
+        // 64-bit integers are strings in OTLP JSON
+        AttributeValue::Int(value) => json!({ "intValue": value.to_string() }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+/// Formats a time as nanoseconds since the Unix epoch, a string in OTLP JSON.
+fn unix_nanos(time: std::time::SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_nanos())
+        .to_string()
+}
@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+
+use crate::auth::Principal;
+use crate::http::{Request, Response, StatusCode};
+use crate::logging;
+use crate::server::ConnectInfo;
+use crate::service::{Layer, Service};
+
+/// Type alias for the function picking the key a request is counted against.
+type KeyFn = dyn Fn(&Request) -> Option<String> + Send + Sync;
+
+/// Type alias for the function giving a key's quota.
+type LimitFn = dyn Fn(&str) -> Option<u64> + Send + Sync;
+
+/// The calendar period a quota covers, in UTC. Windows start at the beginning of each period
+/// rather than sliding, so every client's budget resets at the same, predictable time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuotaPeriod {
+    Minute,
+    Hour,
+    Day,
+    Month,
+}
+
+impl QuotaPeriod {
+    /// Returns the start of the window containing `time` and the start of the next one.
+    fn window(self, time: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let date = time.date_naive();
+        let start = match self {
+            QuotaPeriod::Minute => time.timestamp() - time.timestamp() % 60,
+            QuotaPeriod::Hour => time.timestamp() - time.timestamp() % 3600,
+            QuotaPeriod::Day => time.timestamp() - time.timestamp() % 86_400,
+            QuotaPeriod::Month => date
+                .with_day(1)
+                .and_then(|first| first.and_hms_opt(0, 0, 0))
+                .map_or(0, |first| first.and_utc().timestamp()),
+        };
+        let start = Utc.timestamp_opt(start, 0).single().unwrap_or(time);
+        let end = match self {
+            QuotaPeriod::Minute => start + Duration::minutes(1),
+            QuotaPeriod::Hour => start + Duration::hours(1),
+            QuotaPeriod::Day => start + Duration::days(1),
+            QuotaPeriod::Month => start
+                .checked_add_months(chrono::Months::new(1))
+                .unwrap_or(start + Duration::days(31)),
+        };
+        (start, end)
+    }
+
+    /// Returns the nominal length of the period in seconds, for the `RateLimit-Policy` header.
+    fn seconds(self) -> i64 {
+        match self {
+            QuotaPeriod::Minute => 60,
+            QuotaPeriod::Hour => 3600,
+            QuotaPeriod::Day => 86_400,
+            QuotaPeriod::Month => 30 * 86_400,
+        }
+    }
+}
+
+/// Where quota usage is counted.
+///
+/// Implement it over a database or a shared cache to keep counts across restarts and share
+/// them between server instances; [`MemoryQuotaStore`] keeps them in this process only.
+pub trait QuotaStore: Send + Sync {
+    /// Adds one request to the count of `key` in the window starting at `window_start`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The API key or client the request is counted against.
+    /// * `window_start` - The start of the current window; counts of earlier windows are no
+    ///   longer needed.
+    /// * `window_end` - When the window ends, e.g. to set an expiry on the stored count.
+    ///
+    /// # Returns
+    ///
+    /// The count including this request, or an error if the store is unavailable.
+    fn increment(
+        &self,
+        key: &str,
+        window_start: DateTime<Utc>,
+        window_end: DateTime<Utc>,
+    ) -> Result<u64, String>;
+}
+
+/// A [`QuotaStore`] keeping counts in memory, for a single layer.
+#[derive(Clone, Default)]
+pub struct MemoryQuotaStore {
+    inner: Arc<Mutex<MemoryCounts>>,
+}
+
+#[derive(Default)]
+struct MemoryCounts {
+    /// The latest window seen; counts of earlier windows are dropped when a later one starts.
+    window_start: Option<DateTime<Utc>>,
+    counts: HashMap<String, u64>,
+}
+
+impl MemoryQuotaStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        MemoryQuotaStore::default()
+    }
+}
+
+impl QuotaStore for MemoryQuotaStore {
+    fn increment(
+        &self,
+        key: &str,
+        window_start: DateTime<Utc>,
+        _window_end: DateTime<Utc>,
+    ) -> Result<u64, String> {
+        let mut inner = self.inner.lock().unwrap();
+        // A request racing the start of a new window mustn't reset it
+        if inner.window_start < Some(window_start) {
+            inner.window_start = Some(window_start);
+            inner.counts.clear();
+        }
+        let count = inner.counts.entry(key.to_string()).or_insert(0);
+        *count += 1;
+        Ok(*count)
+    }
+}
+
+/// Middleware enforcing request quotas per API key or client, such as 10 000 requests a day.
+///
+/// Requests over the quota are answered with `429 Too Many Requests` and a `Retry-After`
+/// header. Every counted response tells the client where it stands, in both the widespread
+/// `X-RateLimit-Limit`, `X-RateLimit-Remaining` and `X-RateLimit-Reset` (a Unix time)
+/// headers and the IETF draft's `RateLimit` and `RateLimit-Policy`.
+///
+/// Requests are counted against the authenticated [`Principal`], such as the owner of an API
+/// key, so apply the layer inside authentication; requests without one are counted against
+/// the client's IP address. Change that with [`key_by`](QuotaLayer::key_by). Requests without
+/// a key aren't limited. If the store fails, requests are let through rather than refused.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Router, ServiceBuilder};
+/// # use http_server::middleware::quota::{MemoryQuotaStore, QuotaLayer, QuotaPeriod};
+/// # struct Plans;
+/// # impl Plans { fn limit_for(&self, _key: &str) -> Option<u64> { Some(100_000) } }
+/// # fn example(router: Router, plans: &'static Plans) {
+/// let store = MemoryQuotaStore::new();
+/// let service = ServiceBuilder::new(router)
+///     .layer(
+///         QuotaLayer::new(store, QuotaPeriod::Month, 100_000)
+///             .limits(|key| plans.limit_for(key)),
+///     )
+///     .service();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct QuotaLayer {
+    store: Arc<dyn QuotaStore>,
+    period: QuotaPeriod,
+    key: Arc<KeyFn>,
+    limit: Arc<LimitFn>,
+}
+
+impl QuotaLayer {
+    /// Creates a layer allowing each key `limit` requests per `period`.
+    pub fn new<Q>(store: Q, period: QuotaPeriod, limit: u64) -> Self
+    where
+        Q: QuotaStore + 'static,
+    {
+        QuotaLayer {
+            store: Arc::new(store),
+            period,
+            key: Arc::new(default_key),
+            limit: Arc::new(move |_: &str| Some(limit)),
+        }
+    }
+
+    /// Picks the key each request is counted against with `key`; `None` leaves the request
+    /// unlimited.
+    pub fn key_by<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&Request) -> Option<String> + Send + Sync + 'static,
+    {
+        self.key = Arc::new(key);
+        self
+    }
+
+    /// Gives each key its own quota with `limit`, e.g. by the customer's plan; `None` leaves
+    /// the key unlimited.
+    pub fn limits<F>(mut self, limit: F) -> Self
+    where
+        F: Fn(&str) -> Option<u64> + Send + Sync + 'static,
+    {
+        self.limit = Arc::new(limit);
+        self
+    }
+}
+
+impl<S> Layer<S> for QuotaLayer {
+    type Service = QuotaMiddleware<S>;
+
+    /// Wraps the given service with the quota middleware.
+    fn layer(&self, service: S) -> Self::Service {
+        QuotaMiddleware {
+            inner: service,
+            config: self.clone(),
+        }
+    }
+}
+
+/// Middleware service that counts requests against quotas.
+#[derive(Clone)]
+pub struct QuotaMiddleware<S> {
+    inner: S,
+    config: QuotaLayer,
+}
+
+impl<S> Service for QuotaMiddleware<S>
+where
+    S: Service<Response = Response, Error = String> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Checks if the service is ready to accept a request.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Counts the request, refusing it if it's over quota, and reports the remaining quota.
+    fn call(&mut self, request: Request) -> Self::Future {
+        let Some(key) = (self.config.key)(&request) else {
+            return Box::pin(self.inner.call(request));
+        };
+        let Some(limit) = (self.config.limit)(&key) else {
+            return Box::pin(self.inner.call(request));
+        };
+
+        let now = Utc::now();
+        let (start, end) = self.config.period.window(now);
+        let used = match self.config.store.increment(&key, start, end) {
+            Ok(used) => used,
+            Err(e) => {
+                logging::error("quota", "Quota store failed", &[("error", &e)]);
+                return Box::pin(self.inner.call(request));
+            }
+        };
+
+        let status = QuotaStatus {
+            limit,
+            remaining: limit.saturating_sub(used),
+            reset: end,
+            reset_after: (end - now).num_seconds().max(0),
+            window: self.config.period.seconds(),
+        };
+        if used > limit {
+            let mut response = Response::new(StatusCode::TooManyRequests);
+            response.set_content_type("text/plain");
+            response.set_body(b"Quota exceeded".to_vec());
+            response
+                .headers
+                .insert("Retry-After".to_string(), status.reset_after.to_string());
+            status.apply(&mut response);
+            return Box::pin(async move { Ok(response) });
+        }
+
+        let future = self.inner.call(request);
+        Box::pin(async move {
+            let mut response = future.await?;
+            status.apply(&mut response);
+            Ok(response)
+        })
+    }
+}
+
+/// Where a key stands against its quota, as reported to the client.
+struct QuotaStatus {
+    limit: u64,
+    remaining: u64,
+    reset: DateTime<Utc>,
+    /// Seconds until the window resets.
+    reset_after: i64,
+    /// Nominal length of the window in seconds.
+    window: i64,
+}
+
+impl QuotaStatus {
+    /// Adds the rate limit headers to a response.
+    fn apply(&self, response: &mut Response) {
+        for (name, value) in [
+            ("X-RateLimit-Limit", self.limit.to_string()),
+            ("X-RateLimit-Remaining", self.remaining.to_string()),
+            ("X-RateLimit-Reset", self.reset.timestamp().to_string()),
+            (
+                "RateLimit",
+                format!(
+                    "limit={}, remaining={}, reset={}",
+                    self.limit, self.remaining, self.reset_after
+                ),
+            ),
+            (
+                "RateLimit-Policy",
+                format!("{};w={}", self.limit, self.window),
+            ),
+        ] {
+            response.headers.insert(name.to_string(), value);
+        }
+    }
+}
+
+/// Counts requests against their principal, or their client's IP address.
+fn default_key(request: &Request) -> Option<String> {
+    if let Some(principal) = Principal::from_request(request) {
+        return Some(format!("principal:{}", principal.id));
+    }
+    request
+        .extensions
+        .get::<ConnectInfo>()
+        .map(|info| format!("ip:{}", info.peer.ip()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::{self, ServiceBuilder, service_fn};
+
+    fn service(layer: QuotaLayer) -> impl Service<Response = Response, Error = String> {
+        ServiceBuilder::new(service_fn(|_request: Request| async {
+            Ok(Response::new(StatusCode::OK))
+        }))
+        .layer(layer)
+        .service()
+    }
+
+    fn from(ip: &str) -> Request {
+        Request::builder()
+            .extension(ConnectInfo {
+                peer: format!("{}:40000", ip).parse().unwrap(),
+                local: "127.0.0.1:8080".parse().unwrap(),
+            })
+            .build()
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn windows_follow_the_calendar() {
+        let (start, end) = QuotaPeriod::Day.window(at("2024-02-29T13:45:10Z"));
+        assert_eq!(
+            (start, end),
+            (at("2024-02-29T00:00:00Z"), at("2024-03-01T00:00:00Z"))
+        );
+        let (start, end) = QuotaPeriod::Month.window(at("2024-01-31T23:59:59Z"));
+        assert_eq!(
+            (start, end),
+            (at("2024-01-01T00:00:00Z"), at("2024-02-01T00:00:00Z"))
+        );
+        let (start, end) = QuotaPeriod::Month.window(at("2024-12-15T00:00:00Z"));
+        assert_eq!(
+            (start, end),
+            (at("2024-12-01T00:00:00Z"), at("2025-01-01T00:00:00Z"))
+        );
+        let (start, end) = QuotaPeriod::Minute.window(at("2024-12-15T10:20:30Z"));
+        assert_eq!(
+            (start, end),
+            (at("2024-12-15T10:20:00Z"), at("2024-12-15T10:21:00Z"))
+        );
+    }
+
+    #[test]
+    fn memory_store_resets_only_for_later_windows() {
+        let store = MemoryQuotaStore::new();
+        let (first, first_end) = QuotaPeriod::Day.window(at("2024-01-01T12:00:00Z"));
+        let (second, second_end) = QuotaPeriod::Day.window(at("2024-01-02T12:00:00Z"));
+        assert_eq!(store.increment("a", first, first_end), Ok(1));
+        assert_eq!(store.increment("a", first, first_end), Ok(2));
+        assert_eq!(store.increment("b", first, first_end), Ok(1));
+        assert_eq!(store.increment("a", second, second_end), Ok(1));
+        // A request that read the clock before the window changed doesn't reset it again
+        assert_eq!(store.increment("a", first, first_end), Ok(2));
+    }
+
+    #[tokio::test]
+    async fn refuses_requests_over_the_quota_with_headers() {
+        let mut service = service(QuotaLayer::new(
+            MemoryQuotaStore::new(),
+            QuotaPeriod::Day,
+            2,
+        ));
+        let response = service::oneshot(&mut service, from("10.0.0.1")).await;
+        assert_eq!(response.status_code, StatusCode::OK);
+        assert_eq!(response.headers["X-RateLimit-Limit"], "2");
+        assert_eq!(response.headers["X-RateLimit-Remaining"], "1");
+        assert_eq!(response.headers["RateLimit-Policy"], "2;w=86400");
+        assert!(response.headers["RateLimit"].starts_with("limit=2, remaining=1, reset="));
+
+        service::oneshot(&mut service, from("10.0.0.1")).await;
+        let response = service::oneshot(&mut service, from("10.0.0.1")).await;
+        assert_eq!(response.status_code, StatusCode::TooManyRequests);
+        assert_eq!(response.headers["X-RateLimit-Remaining"], "0");
+        let retry_after: i64 = response.headers["Retry-After"].parse().unwrap();
+        assert!((0..=86_400).contains(&retry_after));
+
+        // Other clients have their own budget
+        let response = service::oneshot(&mut service, from("10.0.0.2")).await;
+        assert_eq!(response.status_code, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn counts_against_the_principal_and_per_key_limits() {
+        let layer =
+            QuotaLayer::new(MemoryQuotaStore::new(), QuotaPeriod::Day, 1).limits(|key| match key {
+                "principal:partner" => None,
+                _ => Some(1),
+            });
+        let mut service = service(layer);
+        let as_principal = |id: &str, ip: &str| {
+            let mut request = from(ip);
+            request.extensions.insert(Principal::new(id));
+            request
+        };
+
+        // The same principal from two addresses shares one budget
+        let response = service::oneshot(&mut service, as_principal("user", "10.0.0.1")).await;
+        assert_eq!(response.status_code, StatusCode::OK);
+        let response = service::oneshot(&mut service, as_principal("user", "10.0.0.2")).await;
+        assert_eq!(response.status_code, StatusCode::TooManyRequests);
+
+        for _ in 0..3 {
+            let response =
+                service::oneshot(&mut service, as_principal("partner", "10.0.0.1")).await;
+            assert_eq!(response.status_code, StatusCode::OK);
+            assert!(!response.headers.contains_key("X-RateLimit-Limit"));
+        }
+    }
+
+    #[tokio::test]
+    async fn lets_requests_through_when_the_store_fails() {
+        struct Failing;
+        impl QuotaStore for Failing {
+            fn increment(
+                &self,
+                _key: &str,
+                _window_start: DateTime<Utc>,
+                _window_end: DateTime<Utc>,
+            ) -> Result<u64, String> {
+                Err("unavailable".to_string())
+            }
+        }
+        let mut service = service(QuotaLayer::new(Failing, QuotaPeriod::Day, 0));
+        let response = service::oneshot(&mut service, from("10.0.0.1")).await;
+        assert_eq!(response.status_code, StatusCode::OK);
+    }
+}
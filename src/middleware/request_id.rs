@@ -0,0 +1,154 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use super::trace::random_id;
+use crate::http::{Request, Response};
+use crate::logging;
+use crate::service::{Layer, Service};
+
+/// Incoming IDs longer than this are replaced, so clients can't bloat every log line.
+const MAX_INCOMING_LEN: usize = 128;
+
+/// The ID correlating everything logged about a request, found in the request's extensions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// Generates a new random ID of 32 hex digits.
+    pub fn generate() -> Self {
+        RequestId(format!("{:016x}{:016x}", random_id(), random_id()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Middleware that gives each request an ID and reports it everywhere the request shows up.
+///
+/// The ID is taken from the request's `X-Request-Id` header when it holds a short, printable
+/// value, and generated otherwise. It is then:
+///
+/// * put into the request's extensions as a [`RequestId`] and into its `X-Request-Id` header,
+///   where handlers and the access log, audit and trace layers find it;
+/// * added as a `request_id` field to every diagnostic logged while the request is handled,
+///   including the server's own;
+/// * echoed in the response's `X-Request-Id` header, including on error responses the
+///   server generates when the service fails.
+///
+/// Apply it outermost so every other layer sees the ID.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Router, ServiceBuilder};
+/// # use http_server::middleware::access_log::{AccessLogLayer, LogFormat, StdoutWriter};
+/// # use http_server::middleware::request_id::RequestIdLayer;
+/// # fn example(router: Router) {
+/// let service = ServiceBuilder::new(router)
+///     .layer(RequestIdLayer::new())
+///     .layer(AccessLogLayer::new(LogFormat::Json, StdoutWriter))
+///     .service();
+/// # }
+/// ```
+pub struct RequestIdLayer {
+    header: String,
+    trust_incoming: bool,
+}
+
+impl RequestIdLayer {
+    /// Creates a layer that uses the `X-Request-Id` header and trusts incoming IDs.
+    pub fn new() -> Self {
+        RequestIdLayer {
+            header: "X-Request-Id".to_string(),
+            trust_incoming: true,
+        }
+    }
+
+    /// Reads and writes the ID in the header `name` instead of `X-Request-Id`.
+    pub fn header(mut self, name: &str) -> Self {
+        self.header = name.to_string();
+        self
+    }
+
+    /// Whether to keep IDs sent by clients, `true` by default. Turn it off on servers facing
+    /// untrusted clients without a proxy that assigns IDs.
+    pub fn trust_incoming(mut self, trust: bool) -> Self {
+        self.trust_incoming = trust;
+        self
+    }
+}
+
+impl Default for RequestIdLayer {
+    fn default() -> Self {
+        RequestIdLayer::new()
+    }
+}
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestIdMiddleware<S>;
+
+    /// Wraps the given service with the request ID middleware.
+    fn layer(&self, service: S) -> Self::Service {
+        RequestIdMiddleware {
+            inner: service,
+            header: self.header.clone(),
+            trust_incoming: self.trust_incoming,
+        }
+    }
+}
+
+/// Middleware service that assigns request IDs.
+#[derive(Clone)]
+pub struct RequestIdMiddleware<S> {
+    inner: S,
+    header: String,
+    trust_incoming: bool,
+}
+
+impl<S> Service for RequestIdMiddleware<S>
+where
+    S: Service<Response = Response, Error = String> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Checks if the service is ready to accept a request.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Assigns the request an ID, calls the inner service and echoes the ID in its response.
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        let id = request
+            .header(&self.header)
+            .filter(|_| self.trust_incoming)
+            .filter(|id| is_valid(id))
+            .map(|id| RequestId(id.clone()))
+            .unwrap_or_else(RequestId::generate);
+
+        // Replace any header spelling, so there's a single value however the client sent it
+        request
+            .headers
+            .retain(|name, _| !name.eq_ignore_ascii_case(&self.header));
+        request.headers.insert(self.header.clone(), id.0.clone());
+        request.extensions.insert(id.clone());
+        logging::set_request_id(id.as_str());
+
+        let header = self.header.clone();
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let mut response = future.await?;
+            response.headers.insert(header, id.0);
+            Ok(response)
+        })
+    }
+}
+
+/// Returns whether an incoming ID is short and made of visible ASCII characters.
+fn is_valid(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_INCOMING_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
@@ -0,0 +1,271 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Utc};
+
+use super::access_log::LogWriter;
+use crate::logging;
+
+/// Settings for a log file that is rotated as it grows or ages.
+///
+/// On rotation the current file is renamed with a timestamp suffix, e.g.
+/// `access.log.20261016-000000`, and a fresh file is started. Only the newest rotated files
+/// are kept when a limit is set.
+///
+/// # Examples
+///
+/// ```
+/// # use std::time::Duration;
+/// # use http_server::middleware::{access_log::{AccessLogLayer, LogFormat}, rotation::LogRotation};
+/// # fn example() -> Result<(), String> {
+/// let writer = LogRotation::new("logs/access.log")
+///     .max_size(100 * 1024 * 1024)
+///     .interval(Duration::from_secs(24 * 60 * 60))
+///     .max_files(14)
+///     .compress(true)
+///     .open()?;
+/// let layer = AccessLogLayer::new(LogFormat::Combined, writer);
+/// # Ok(())
+/// # }
+/// ```
+pub struct LogRotation {
+    path: PathBuf,
+    max_size: Option<u64>,
+    interval: Option<Duration>,
+    max_files: Option<usize>,
+    compress: bool,
+}
+
+impl LogRotation {
+    /// Creates settings for the log at `path`, which is never rotated until limits are set.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        LogRotation {
+            path: path.into(),
+            max_size: None,
+            interval: None,
+            max_files: None,
+            compress: false,
+        }
+    }
+
+    /// Rotates the file before a line would grow it past `bytes`.
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Rotates the file every `interval`, aligned to multiples of it since the Unix epoch, so
+    /// a day-long interval rotates at midnight UTC.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval).filter(|interval| !interval.is_zero());
+        self
+    }
+
+    /// Keeps only the `count` newest rotated files, deleting older ones.
+    pub fn max_files(mut self, count: usize) -> Self {
+        self.max_files = Some(count);
+        self
+    }
+
+    /// Compresses rotated files with the system's `gzip` program, in the background.
+    pub fn compress(mut self, enabled: bool) -> Self {
+        self.compress = enabled;
+        self
+    }
+
+    /// Opens the log for appending, creating it and its directory if needed.
+    ///
+    /// # Returns
+    ///
+    /// The writer, or an error if the file couldn't be opened.
+    pub fn open(self) -> Result<RotatingFileWriter, String> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create log directory {}: {}", dir.display(), e))?;
+        }
+        let file = open_append(&self.path)?;
+        let size = file.metadata().map_or(0, |metadata| metadata.len());
+        let next_rotation = self.next_rotation(SystemTime::now());
+        Ok(RotatingFileWriter {
+            state: Mutex::new(LogFile {
+                file,
+                size,
+                next_rotation,
+            }),
+            settings: self,
+        })
+    }
+
+    /// Returns when a file started at `now` is due for time-based rotation.
+    fn next_rotation(&self, now: SystemTime) -> Option<SystemTime> {
+        let interval = self.interval?.as_secs().max(1);
+        let since_epoch = now.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        let boundary = (since_epoch / interval + 1) * interval;
+        Some(UNIX_EPOCH + Duration::from_secs(boundary))
+    }
+}
+
+/// A [`LogWriter`] that appends to a file and rotates it according to [`LogRotation`].
+pub struct RotatingFileWriter {
+    settings: LogRotation,
+    state: Mutex<LogFile>,
+}
+
+/// The file currently written to.
+struct LogFile {
+    file: File,
+    size: u64,
+    next_rotation: Option<SystemTime>,
+}
+
+impl RotatingFileWriter {
+    /// Renames the current file aside, starts a new one and enforces the retention limit.
+    fn rotate(&self, state: &mut LogFile, now: SystemTime) -> Result<(), String> {
+        let rotated = self.rotated_path(now);
+        fs::rename(&self.settings.path, &rotated)
+            .map_err(|e| format!("Failed to rotate {}: {}", self.settings.path.display(), e))?;
+        state.file = open_append(&self.settings.path)?;
+        state.size = 0;
+        state.next_rotation = self.settings.next_rotation(now);
+
+        if self.settings.compress {
+            let max_files = self.settings.max_files;
+            let settings_path = self.settings.path.clone();
+            // Prune once gzip has replaced the file, so its two copies aren't both counted
+            thread::spawn(move || {
+                gzip(&rotated);
+                if let Some(max_files) = max_files {
+                    prune(&settings_path, max_files);
+                }
+            });
+        } else if let Some(max_files) = self.settings.max_files {
+            prune(&self.settings.path, max_files);
+        }
+        Ok(())
+    }
+
+    /// Returns an unused name for the file rotated at `now`.
+    fn rotated_path(&self, now: SystemTime) -> PathBuf {
+        let stamp = DateTime::<Utc>::from(now).format("%Y%m%d-%H%M%S");
+        let base = format!("{}.{}", self.settings.path.display(), stamp);
+        // Size-based rotation can happen more than once a second
+        (0..)
+            .map(|n| match n {
+                0 => PathBuf::from(&base),
+                n => PathBuf::from(format!("{}.{}", base, n)),
+            })
+            .find(|path| !path.exists() && !gz_path(path).exists())
+            .unwrap_or_else(|| PathBuf::from(base))
+    }
+}
+
+impl LogWriter for RotatingFileWriter {
+    fn write_line(&self, line: &str) {
+        let mut state = self.state.lock().unwrap();
+        let now = SystemTime::now();
+        let line_len = line.len() as u64 + 1;
+
+        let too_big = self
+            .settings
+            .max_size
+            .is_some_and(|max_size| state.size > 0 && state.size + line_len > max_size);
+        let too_old = state.next_rotation.is_some_and(|due| now >= due);
+        let mut rotation_error = None;
+        if (too_big || too_old)
+            && let Err(e) = self.rotate(&mut state, now)
+        {
+            // Keep appending to the current file rather than lose lines
+            rotation_error = Some(e);
+            state.next_rotation = self.settings.next_rotation(now);
+        }
+
+        let written = writeln!(state.file, "{}", line);
+        if written.is_ok() {
+            state.size += line_len;
+        }
+        drop(state);
+
+        // Reported once the file is unlocked, in case the logger writes to it too
+        if let Some(e) = rotation_error {
+            logging::error("rotation", "Failed to rotate log", &[("error", &e)]);
+        }
+        if let Err(e) = written {
+            logging::error("rotation", "Failed to write log", &[("error", &e)]);
+        }
+    }
+}
+
+fn open_append(path: &Path) -> Result<File, String> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open log file {}: {}", path.display(), e))
+}
+
+fn gz_path(path: &Path) -> PathBuf {
+    let mut gz = path.as_os_str().to_os_string();
+    gz.push(".gz");
+    PathBuf::from(gz)
+}
+
+/// Compresses a rotated file in place, replacing it with a `.gz` file.
+fn gzip(path: &Path) {
+    match Command::new("gzip").arg("-f").arg(path).status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => logging::error(
+            "rotation",
+            "gzip failed",
+            &[("path", &path.display()), ("status", &status)],
+        ),
+        Err(e) => logging::error(
+            "rotation",
+            "Failed to run gzip",
+            &[("path", &path.display()), ("error", &e)],
+        ),
+    }
+}
+
+/// Deletes the oldest rotated files of the log at `path`, keeping `max_files`.
+fn prune(path: &Path, max_files: usize) {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return;
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let prefix = format!("{}.", name.to_string_lossy());
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut rotated: Vec<(String, PathBuf)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let suffix = name.strip_prefix(&prefix)?;
+            // Timestamp suffixes sort chronologically once `.gz` is set aside
+            let suffix = suffix.strip_suffix(".gz").unwrap_or(suffix);
+            suffix
+                .starts_with(|c: char| c.is_ascii_digit())
+                .then(|| (suffix.to_string(), entry.path()))
+        })
+        .collect();
+    rotated.sort_by(|a, b| b.0.cmp(&a.0));
+    for (_, old) in rotated.into_iter().skip(max_files) {
+        if let Err(e) = fs::remove_file(&old) {
+            logging::error(
+                "rotation",
+                "Failed to remove old log",
+                &[("path", &old.display()), ("error", &e)],
+            );
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use crate::http::{Request, Response, StatusCode};
+use crate::server::https_redirect;
+use crate::service::{Layer, Service};
+
+/// Type alias for the predicate deciding whether a request arrived over a secure transport.
+type SecurePredicate = dyn Fn(&Request) -> bool + Send + Sync;
+
+/// What the secure transport middleware does with plain-HTTP requests.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlaintextPolicy {
+    /// Redirect to the same URL over HTTPS on the given port, like
+    /// [`https_redirect_server`](crate::server::https_redirect_server).
+    Redirect { https_port: u16 },
+    /// Answer `403 Forbidden`.
+    Forbid,
+}
+
+/// Middleware that makes the application usable over HTTPS only.
+///
+/// When enabled it:
+///
+/// * marks every cookie the application sets `Secure`, so browsers never send them over plain
+///   HTTP;
+/// * sends `Strict-Transport-Security` on secure responses, so browsers stop trying plain
+///   HTTP at all;
+/// * redirects or refuses plain-HTTP requests, except for exempt path prefixes such as health
+///   checks or ACME challenges;
+/// * drops configured secure-only headers from responses to exempt plain-HTTP requests.
+///
+/// The server doesn't terminate TLS itself, so a request counts as secure when the proxy in
+/// front of it says so with `X-Forwarded-Proto: https` or `Forwarded: proto=https`. Only rely
+/// on that behind a proxy that overwrites those headers, or pass a predicate of your own to
+/// [`secure_when`](SecureTransportLayer::secure_when).
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Router, ServiceBuilder, middleware::secure_transport::SecureTransportLayer};
+/// # fn example(router: Router) {
+/// let service = ServiceBuilder::new(router)
+///     .layer(
+///         SecureTransportLayer::new()
+///             .exempt("/healthz")
+///             .exempt("/.well-known/acme-challenge/")
+///             .secure_only_header("X-Api-Token"),
+///     )
+///     .service();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SecureTransportLayer {
+    enabled: bool,
+    is_secure: Arc<SecurePredicate>,
+    plaintext: PlaintextPolicy,
+    exempt: Vec<String>,
+    secure_only_headers: Vec<String>,
+    hsts_max_age: Duration,
+    hsts_include_subdomains: bool,
+    hsts_preload: bool,
+}
+
+impl SecureTransportLayer {
+    /// Creates an enabled layer that trusts the forwarded protocol, redirects plain-HTTP
+    /// requests to port 443 and asks browsers to use HTTPS for a year.
+    pub fn new() -> Self {
+        SecureTransportLayer {
+            enabled: true,
+            is_secure: Arc::new(forwarded_https),
+            plaintext: PlaintextPolicy::Redirect { https_port: 443 },
+            exempt: Vec::new(),
+            secure_only_headers: Vec::new(),
+            hsts_max_age: Duration::from_secs(365 * 24 * 60 * 60),
+            hsts_include_subdomains: false,
+            hsts_preload: false,
+        }
+    }
+
+    /// Turns enforcement on or off, e.g. from configuration so it stays off in development.
+    /// A disabled layer passes requests and responses through unchanged.
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Decides whether a request arrived over a secure transport with `predicate` instead of
+    /// the forwarded protocol headers.
+    pub fn secure_when<P>(mut self, predicate: P) -> Self
+    where
+        P: Fn(&Request) -> bool + Send + Sync + 'static,
+    {
+        self.is_secure = Arc::new(predicate);
+        self
+    }
+
+    /// Sets what happens to plain-HTTP requests for paths that aren't exempt.
+    pub fn plaintext(mut self, policy: PlaintextPolicy) -> Self {
+        self.plaintext = policy;
+        self
+    }
+
+    /// Lets plain-HTTP requests for paths starting with `prefix` through.
+    pub fn exempt(mut self, prefix: &str) -> Self {
+        self.exempt.push(prefix.to_string());
+        self
+    }
+
+    /// Never sends the header `name` in responses to plain-HTTP requests, e.g. one carrying a
+    /// token.
+    pub fn secure_only_header(mut self, name: &str) -> Self {
+        self.secure_only_headers.push(name.to_string());
+        self
+    }
+
+    /// Configures `Strict-Transport-Security`.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_age` - How long browsers remember to use HTTPS; zero makes them forget.
+    /// * `include_subdomains` - Whether the policy covers subdomains too.
+    /// * `preload` - Whether to allow inclusion in browsers' preload lists, which is hard to
+    ///   undo.
+    pub fn hsts(mut self, max_age: Duration, include_subdomains: bool, preload: bool) -> Self {
+        self.hsts_max_age = max_age;
+        self.hsts_include_subdomains = include_subdomains;
+        self.hsts_preload = preload;
+        self
+    }
+
+    /// Returns the `Strict-Transport-Security` header value.
+    fn hsts_value(&self) -> String {
+        let mut value = format!("max-age={}", self.hsts_max_age.as_secs());
+        if self.hsts_include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+        if self.hsts_preload {
+            value.push_str("; preload");
+        }
+        value
+    }
+}
+
+impl Default for SecureTransportLayer {
+    fn default() -> Self {
+        SecureTransportLayer::new()
+    }
+}
+
+impl<S> Layer<S> for SecureTransportLayer {
+    type Service = SecureTransportMiddleware<S>;
+
+    /// Wraps the given service with the secure transport middleware.
+    fn layer(&self, service: S) -> Self::Service {
+        SecureTransportMiddleware {
+            inner: service,
+            hsts: self.hsts_value(),
+            config: Arc::new(self.clone()),
+        }
+    }
+}
+
+/// Middleware service that enforces secure transport.
+#[derive(Clone)]
+pub struct SecureTransportMiddleware<S> {
+    inner: S,
+    config: Arc<SecureTransportLayer>,
+    hsts: String,
+}
+
+impl<S> Service for SecureTransportMiddleware<S>
+where
+    S: Service<Response = Response, Error = String> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Checks if the service is ready to accept a request.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Redirects or refuses plain-HTTP requests, and secures the inner service's responses.
+    fn call(&mut self, request: Request) -> Self::Future {
+        if !self.config.enabled {
+            return Box::pin(self.inner.call(request));
+        }
+
+        let secure = (self.config.is_secure)(&request);
+        let exempt = self
+            .config
+            .exempt
+            .iter()
+            .any(|prefix| request.path.starts_with(prefix.as_str()));
+        if !secure && !exempt {
+            let response = match self.config.plaintext {
+                PlaintextPolicy::Redirect { https_port } => https_redirect(&request, https_port),
+                PlaintextPolicy::Forbid => {
+                    let mut response = Response::new(StatusCode::Forbidden);
+                    response.set_content_type("text/plain");
+                    response.set_body(b"HTTPS is required".to_vec());
+                    response
+                }
+            };
+            return Box::pin(async move { Ok(response) });
+        }
+
+        let config = self.config.clone();
+        let hsts = self.hsts.clone();
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let mut response = future.await?;
+            for (name, value) in response.headers.iter_mut() {
+                if name.eq_ignore_ascii_case("Set-Cookie") {
+                    mark_secure(value);
+                }
+            }
+            response.cookies.iter_mut().for_each(mark_secure);
+            if secure {
+                response
+                    .headers
+                    .insert("Strict-Transport-Security".to_string(), hsts);
+            } else {
+                response.headers.retain(|name, _| {
+                    !config
+                        .secure_only_headers
+                        .iter()
+                        .any(|secure_only| name.eq_ignore_ascii_case(secure_only))
+                });
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Returns whether the proxy in front of the server received `request` over HTTPS, going by
+/// the first hop in `Forwarded` or `X-Forwarded-Proto`.
+fn forwarded_https(request: &Request) -> bool {
+    if let Some(forwarded) = request.header("Forwarded") {
+        let first_hop = forwarded.split(',').next().unwrap_or("");
+        return first_hop.split(';').any(|pair| {
+            pair.split_once('=').is_some_and(|(name, value)| {
+                name.trim().eq_ignore_ascii_case("proto")
+                    && value.trim().trim_matches('"').eq_ignore_ascii_case("https")
+            })
+        });
+    }
+    request.header("X-Forwarded-Proto").is_some_and(|proto| {
+        proto
+            .split(',')
+            .next()
+            .is_some_and(|proto| proto.trim().eq_ignore_ascii_case("https"))
+    })
+}
+
+/// Adds the `Secure` attribute to a `Set-Cookie` value that lacks it.
+fn mark_secure(cookie: &mut String) {
+    let secure = cookie
+        .split(';')
+        .skip(1)
+        .any(|attribute| attribute.trim().eq_ignore_ascii_case("secure"));
+    if !secure {
+        cookie.push_str("; Secure");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Method;
+    use crate::service::{self, ServiceBuilder, service_fn};
+
+    /// Sets a session cookie and a secure-only token header.
+    fn service(layer: SecureTransportLayer) -> impl Service<Response = Response, Error = String> {
+        ServiceBuilder::new(service_fn(|_request: Request| async {
+            let mut response = Response::new(StatusCode::OK);
+            response.cookies.push("session=abc; HttpOnly".to_string());
+            response
+                .cookies
+                .push("theme=dark; Path=/; secure".to_string());
+            response
+                .headers
+                .insert("X-Api-Token".to_string(), "secret".to_string());
+            Ok(response)
+        }))
+        .layer(layer)
+        .service()
+    }
+
+    fn request(path: &str, proto: Option<&str>) -> Request {
+        let mut builder = Request::builder()
+            .path(path)
+            .header("Host", "example.com:8080");
+        if let Some(proto) = proto {
+            builder = builder.header("X-Forwarded-Proto", proto);
+        }
+        builder.build()
+    }
+
+    #[tokio::test]
+    async fn secures_responses_to_https_requests() {
+        let mut service = service(SecureTransportLayer::new().secure_only_header("X-Api-Token"));
+        let response = service::oneshot(&mut service, request("/", Some("https"))).await;
+        assert_eq!(response.status_code, StatusCode::OK);
+        assert_eq!(
+            response.cookies,
+            [
+                "session=abc; HttpOnly; Secure",
+                "theme=dark; Path=/; secure"
+            ]
+        );
+        assert_eq!(
+            response.headers["Strict-Transport-Security"],
+            "max-age=31536000"
+        );
+        assert_eq!(response.headers["X-Api-Token"], "secret");
+    }
+
+    #[tokio::test]
+    async fn redirects_or_refuses_plain_http() {
+        let mut redirecting = service(SecureTransportLayer::new());
+        let response = service::oneshot(&mut redirecting, request("/account?tab=1", None)).await;
+        assert_eq!(response.status_code, StatusCode::MovedPermanently);
+        assert_eq!(
+            response.headers["Location"],
+            "https://example.com/account?tab=1"
+        );
+        // Only the first hop counts, as later ones were added by the client
+        let response = service::oneshot(&mut redirecting, request("/", Some("http, https"))).await;
+        assert_eq!(response.status_code, StatusCode::MovedPermanently);
+
+        let mut forbidding =
+            service(SecureTransportLayer::new().plaintext(PlaintextPolicy::Forbid));
+        let response = service::oneshot(&mut forbidding, request("/", None)).await;
+        assert_eq!(response.status_code, StatusCode::Forbidden);
+    }
+
+    #[tokio::test]
+    async fn exempt_paths_drop_secure_only_headers() {
+        let layer = SecureTransportLayer::new()
+            .exempt("/healthz")
+            .secure_only_header("x-api-token");
+        let mut service = service(layer);
+        let response = service::oneshot(&mut service, request("/healthz", None)).await;
+        assert_eq!(response.status_code, StatusCode::OK);
+        assert!(!response.headers.contains_key("X-Api-Token"));
+        assert!(!response.headers.contains_key("Strict-Transport-Security"));
+        assert_eq!(response.cookies[0], "session=abc; HttpOnly; Secure");
+    }
+
+    #[tokio::test]
+    async fn disabled_layer_passes_through() {
+        let mut service = service(SecureTransportLayer::new().enabled(false));
+        let response = service::oneshot(&mut service, request("/", None)).await;
+        assert_eq!(response.status_code, StatusCode::OK);
+        assert_eq!(response.cookies[0], "session=abc; HttpOnly");
+    }
+
+    #[test]
+    fn reads_the_forwarded_protocol() {
+        let with = |name: &str, value: &str| Request::builder().header(name, value).build();
+        assert!(forwarded_https(&with(
+            "Forwarded",
+            "for=1.2.3.4;proto=\"HTTPS\""
+        )));
+        assert!(!forwarded_https(&with(
+            "Forwarded",
+            "proto=http, proto=https"
+        )));
+        assert!(forwarded_https(&with("X-Forwarded-Proto", "https")));
+        assert!(!forwarded_https(&with("X-Forwarded-Proto", "http")));
+        assert!(!forwarded_https(
+            &Request::builder().method(Method::Get).build()
+        ));
+    }
+
+    #[test]
+    fn hsts_value_lists_the_directives() {
+        let layer = SecureTransportLayer::new().hsts(Duration::from_secs(60), true, true);
+        assert_eq!(layer.hsts_value(), "max-age=60; includeSubDomains; preload");
+    }
+}
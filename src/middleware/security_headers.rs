@@ -0,0 +1,486 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use crate::crypto::{random_bytes, to_hex};
+use crate::http::{Request, Response};
+use crate::logging;
+use crate::service::{Layer, Service};
+
+/// The page's own origin.
+pub const SELF: &str = "'self'";
+/// Nothing at all.
+pub const NONE: &str = "'none'";
+/// Inline scripts or styles; ignored by browsers when the directive also has a nonce.
+pub const UNSAFE_INLINE: &str = "'unsafe-inline'";
+/// `eval()` and similar.
+pub const UNSAFE_EVAL: &str = "'unsafe-eval'";
+/// Scripts loaded by already trusted scripts.
+pub const STRICT_DYNAMIC: &str = "'strict-dynamic'";
+/// `data:` URLs.
+pub const DATA: &str = "data:";
+/// `blob:` URLs.
+pub const BLOB: &str = "blob:";
+/// Any URL over HTTPS.
+pub const HTTPS: &str = "https:";
+
+/// The directives a per-request nonce is added to.
+const NONCED: [&str; 3] = ["default-src", "script-src", "style-src"];
+
+/// One or several CSP sources, so directives take `SELF` as well as `[SELF, "https://cdn"]`.
+pub trait Sources {
+    /// Returns the sources as strings.
+    fn into_sources(self) -> Vec<String>;
+}
+
+impl Sources for &str {
+    fn into_sources(self) -> Vec<String> {
+        vec![self.to_string()]
+    }
+}
+
+impl Sources for String {
+    fn into_sources(self) -> Vec<String> {
+        vec![self]
+    }
+}
+
+impl<const N: usize> Sources for [&str; N] {
+    fn into_sources(self) -> Vec<String> {
+        self.iter().map(|source| source.to_string()).collect()
+    }
+}
+
+impl Sources for &[&str] {
+    fn into_sources(self) -> Vec<String> {
+        self.iter().map(|source| source.to_string()).collect()
+    }
+}
+
+impl Sources for Vec<String> {
+    fn into_sources(self) -> Vec<String> {
+        self
+    }
+}
+
+/// A `Content-Security-Policy`, telling browsers where the page may load scripts, styles and
+/// other resources from.
+///
+/// With [`nonce_per_request`](Csp::nonce_per_request), [`SecurityHeadersLayer`] generates a
+/// fresh nonce for every request, adds it to the policy and puts it into the request's
+/// extensions as a [`CspNonce`], for templates to put on the inline scripts and styles they
+/// trust.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::middleware::security_headers::{Csp, DATA, NONE, SELF};
+/// let csp = Csp::new()
+///     .default_src(SELF)
+///     .script_src([SELF, "https://cdn.example.com"])
+///     .img_src([SELF, DATA])
+///     .frame_ancestors(NONE)
+///     .nonce_per_request();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Csp {
+    directives: Vec<(String, Vec<String>)>,
+    nonce: bool,
+}
+
+impl Csp {
+    /// Creates an empty policy, which allows everything.
+    pub fn new() -> Self {
+        Csp::default()
+    }
+
+    /// Sets the sources of directive `name`, replacing any set before. Use it for directives
+    /// without a method of their own.
+    ///
+    /// Sources are written into the header as given, so they must not contain `;` or `,`.
+    pub fn directive<S: Sources>(mut self, name: &str, sources: S) -> Self {
+        let sources = sources.into_sources();
+        match self
+            .directives
+            .iter_mut()
+            .find(|(existing, _)| existing == name)
+        {
+            Some((_, existing)) => *existing = sources,
+            None => self.directives.push((name.to_string(), sources)),
+        }
+        self
+    }
+
+    /// Sets the fallback for the fetch directives that aren't set.
+    pub fn default_src<S: Sources>(self, sources: S) -> Self {
+        self.directive("default-src", sources)
+    }
+
+    /// Sets where scripts may be loaded from.
+    pub fn script_src<S: Sources>(self, sources: S) -> Self {
+        self.directive("script-src", sources)
+    }
+
+    /// Sets where stylesheets may be loaded from.
+    pub fn style_src<S: Sources>(self, sources: S) -> Self {
+        self.directive("style-src", sources)
+    }
+
+    /// Sets where images may be loaded from.
+    pub fn img_src<S: Sources>(self, sources: S) -> Self {
+        self.directive("img-src", sources)
+    }
+
+    /// Sets where fonts may be loaded from.
+    pub fn font_src<S: Sources>(self, sources: S) -> Self {
+        self.directive("font-src", sources)
+    }
+
+    /// Sets what scripts may connect to, with `fetch`, WebSockets or `EventSource`.
+    pub fn connect_src<S: Sources>(self, sources: S) -> Self {
+        self.directive("connect-src", sources)
+    }
+
+    /// Sets where audio and video may be loaded from.
+    pub fn media_src<S: Sources>(self, sources: S) -> Self {
+        self.directive("media-src", sources)
+    }
+
+    /// Sets where plugins may be loaded from; usually [`NONE`].
+    pub fn object_src<S: Sources>(self, sources: S) -> Self {
+        self.directive("object-src", sources)
+    }
+
+    /// Sets where frames in the page may be loaded from.
+    pub fn frame_src<S: Sources>(self, sources: S) -> Self {
+        self.directive("frame-src", sources)
+    }
+
+    /// Sets which pages may embed this one in a frame.
+    pub fn frame_ancestors<S: Sources>(self, sources: S) -> Self {
+        self.directive("frame-ancestors", sources)
+    }
+
+    /// Sets the URLs the page's `<base>` element may point to.
+    pub fn base_uri<S: Sources>(self, sources: S) -> Self {
+        self.directive("base-uri", sources)
+    }
+
+    /// Sets where forms may be submitted to.
+    pub fn form_action<S: Sources>(self, sources: S) -> Self {
+        self.directive("form-action", sources)
+    }
+
+    /// Makes browsers load `http:` resources over HTTPS instead.
+    pub fn upgrade_insecure_requests(self) -> Self {
+        self.directive("upgrade-insecure-requests", Vec::new())
+    }
+
+    /// Has browsers report violations to `uri`.
+    pub fn report_uri(self, uri: &str) -> Self {
+        self.directive("report-uri", uri)
+    }
+
+    /// Adds a fresh nonce to `default-src`, `script-src` and `style-src`, whichever are set,
+    /// on every request. A policy without any of them doesn't restrict inline scripts, so it
+    /// gets no nonce.
+    pub fn nonce_per_request(mut self) -> Self {
+        self.nonce = true;
+        self
+    }
+
+    /// Returns the header value, with `nonce` added where the policy asks for it.
+    pub fn to_header(&self, nonce: Option<&CspNonce>) -> String {
+        let nonce = nonce
+            .filter(|_| self.nonce)
+            .map(|nonce| format!("'nonce-{}'", nonce.as_str()));
+        let mut directives: Vec<String> = Vec::with_capacity(self.directives.len());
+        for (name, sources) in &self.directives {
+            let mut directive = name.clone();
+            for source in sources {
+                directive.push(' ');
+                directive.push_str(source);
+            }
+            if let Some(nonce) = &nonce
+                && NONCED.contains(&name.as_str())
+            {
+                directive.push(' ');
+                directive.push_str(nonce);
+            }
+            directives.push(directive);
+        }
+        directives.join("; ")
+    }
+}
+
+/// The nonce generated for a request's `Content-Security-Policy`, found in the request's
+/// extensions.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Request, middleware::security_headers::CspNonce};
+/// # fn example(request: &Request) {
+/// let nonce = CspNonce::from_request(request).map_or("", |nonce| nonce.as_str());
+/// let html = format!(r#"<script nonce="{}">start()</script>"#, nonce);
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CspNonce(String);
+
+impl CspNonce {
+    /// Generates a nonce of 32 hex digits.
+    pub fn generate() -> Result<Self, String> {
+        Ok(CspNonce(to_hex(&random_bytes(16)?)))
+    }
+
+    /// Returns the nonce generated for the request, if its policy asks for one.
+    pub fn from_request(request: &Request) -> Option<&CspNonce> {
+        request.extensions.get::<CspNonce>()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Middleware that adds the usual security headers to responses:
+///
+/// * `X-Content-Type-Options: nosniff`, so browsers trust the `Content-Type`;
+/// * `X-Frame-Options: DENY`, so other sites can't frame the pages;
+/// * `Referrer-Policy: strict-origin-when-cross-origin`, so full URLs don't leak to other
+///   sites;
+/// * `Content-Security-Policy`, when a [`Csp`] is configured.
+///
+/// Headers the handler already set are left alone, so single routes can relax them.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Router, ServiceBuilder};
+/// # use http_server::middleware::security_headers::{Csp, SELF, SecurityHeadersLayer};
+/// # fn example(router: Router) {
+/// let service = ServiceBuilder::new(router)
+///     .layer(
+///         SecurityHeadersLayer::new()
+///             .csp(Csp::new().default_src(SELF).script_src(SELF).nonce_per_request()),
+///     )
+///     .service();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SecurityHeadersLayer {
+    headers: Vec<(String, String)>,
+    csp: Option<Arc<Csp>>,
+    csp_report_only: bool,
+}
+
+impl SecurityHeadersLayer {
+    /// Creates a layer sending the default headers, without a content security policy.
+    pub fn new() -> Self {
+        SecurityHeadersLayer {
+            headers: vec![
+                ("X-Content-Type-Options".to_string(), "nosniff".to_string()),
+                ("X-Frame-Options".to_string(), "DENY".to_string()),
+                (
+                    "Referrer-Policy".to_string(),
+                    "strict-origin-when-cross-origin".to_string(),
+                ),
+            ],
+            csp: None,
+            csp_report_only: false,
+        }
+    }
+
+    /// Sends `value` in the header `name`, replacing the default for it; an empty value stops
+    /// the header from being sent.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers
+            .retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+        if !value.is_empty() {
+            self.headers.push((name.to_string(), value.to_string()));
+        }
+        self
+    }
+
+    /// Sends `csp` as the content security policy.
+    pub fn csp(mut self, csp: Csp) -> Self {
+        self.csp = Some(Arc::new(csp));
+        self
+    }
+
+    /// Sends the policy as `Content-Security-Policy-Report-Only`, so violations are reported
+    /// but not blocked, e.g. while trying out a new policy.
+    pub fn csp_report_only(mut self, report_only: bool) -> Self {
+        self.csp_report_only = report_only;
+        self
+    }
+}
+
+impl Default for SecurityHeadersLayer {
+    fn default() -> Self {
+        SecurityHeadersLayer::new()
+    }
+}
+
+impl<S> Layer<S> for SecurityHeadersLayer {
+    type Service = SecurityHeadersMiddleware<S>;
+
+    /// Wraps the given service with the security headers middleware.
+    fn layer(&self, service: S) -> Self::Service {
+        SecurityHeadersMiddleware {
+            inner: service,
+            config: self.clone(),
+        }
+    }
+}
+
+/// Middleware service that adds security headers to responses.
+#[derive(Clone)]
+pub struct SecurityHeadersMiddleware<S> {
+    inner: S,
+    config: SecurityHeadersLayer,
+}
+
+impl<S> Service for SecurityHeadersMiddleware<S>
+where
+    S: Service<Response = Response, Error = String> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Checks if the service is ready to accept a request.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Hands the request its nonce, and adds the headers to the response.
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        let mut nonce = None;
+        if self.config.csp.as_ref().is_some_and(|csp| csp.nonce) {
+            match CspNonce::generate() {
+                Ok(generated) => {
+                    request.extensions.insert(generated.clone());
+                    nonce = Some(generated);
+                }
+                // Without a nonce, inline scripts are blocked rather than allowed
+                Err(e) => logging::error("csp", "Failed to generate a nonce", &[("error", &e)]),
+            }
+        }
+
+        let config = self.config.clone();
+        let future = self.inner.call(request);
+        Box::pin(async move {
+            let mut response = future.await?;
+            let csp_header = if config.csp_report_only {
+                "Content-Security-Policy-Report-Only"
+            } else {
+                "Content-Security-Policy"
+            };
+            let csp = config
+                .csp
+                .as_ref()
+                .map(|csp| (csp_header.to_string(), csp.to_header(nonce.as_ref())));
+            for (name, value) in config.headers.iter().cloned().chain(csp) {
+                let set = response
+                    .headers
+                    .keys()
+                    .any(|existing| existing.eq_ignore_ascii_case(&name));
+                if !set {
+                    response.headers.insert(name, value);
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::StatusCode;
+    use crate::service::{self, ServiceBuilder, service_fn};
+
+    /// Answers with the request's nonce as the body, as a template would use it.
+    fn service(layer: SecurityHeadersLayer) -> impl Service<Response = Response, Error = String> {
+        ServiceBuilder::new(service_fn(|request: Request| async move {
+            let nonce = CspNonce::from_request(&request).map_or("", |nonce| nonce.as_str());
+            let mut response = Response::new(StatusCode::OK);
+            response.set_body(nonce.as_bytes().to_vec());
+            Ok(response)
+        }))
+        .layer(layer)
+        .service()
+    }
+
+    fn nonced_csp() -> Csp {
+        Csp::new()
+            .default_src(SELF)
+            .script_src([SELF, "https://cdn.example.com"])
+            .img_src([SELF, DATA])
+            .nonce_per_request()
+    }
+
+    #[tokio::test]
+    async fn hands_each_request_a_fresh_nonce() {
+        let mut service = service(SecurityHeadersLayer::new().csp(nonced_csp()));
+        let first = service::oneshot(&mut service, Request::builder().build()).await;
+        let nonce = String::from_utf8(first.body.clone()).unwrap();
+        assert_eq!(nonce.len(), 32);
+        assert!(nonce.bytes().all(|b| b.is_ascii_hexdigit()));
+        assert_eq!(
+            first.headers["Content-Security-Policy"],
+            format!(
+                "default-src 'self' 'nonce-{0}'; script-src 'self' https://cdn.example.com \
+                 'nonce-{0}'; img-src 'self' data:",
+                nonce
+            )
+        );
+
+        let second = service::oneshot(&mut service, Request::builder().build()).await;
+        assert_ne!(second.body, first.body);
+    }
+
+    #[tokio::test]
+    async fn policies_without_nonces_leave_requests_alone() {
+        let csp = Csp::new().default_src(NONE).frame_ancestors(NONE);
+        let mut service = service(SecurityHeadersLayer::new().csp(csp).csp_report_only(true));
+        let response = service::oneshot(&mut service, Request::builder().build()).await;
+        assert!(response.body.is_empty());
+        assert_eq!(
+            response.headers["Content-Security-Policy-Report-Only"],
+            "default-src 'none'; frame-ancestors 'none'"
+        );
+        assert!(!response.headers.contains_key("Content-Security-Policy"));
+    }
+
+    #[tokio::test]
+    async fn adds_default_headers_without_overriding_the_handler() {
+        let layer = SecurityHeadersLayer::new()
+            .header("x-frame-options", "")
+            .header("Referrer-Policy", "no-referrer");
+        let mut service = ServiceBuilder::new(service_fn(|_request: Request| async {
+            let mut response = Response::new(StatusCode::OK);
+            response
+                .headers
+                .insert("x-content-type-options".to_string(), "custom".to_string());
+            Ok(response)
+        }))
+        .layer(layer)
+        .service();
+        let response = service::oneshot(&mut service, Request::builder().build()).await;
+        assert_eq!(response.headers["x-content-type-options"], "custom");
+        assert!(!response.headers.contains_key("X-Content-Type-Options"));
+        assert!(!response.headers.contains_key("X-Frame-Options"));
+        assert_eq!(response.headers["Referrer-Policy"], "no-referrer");
+    }
+
+    #[test]
+    fn to_header_ignores_nonces_the_policy_did_not_ask_for() {
+        let nonce = CspNonce::generate().unwrap();
+        let csp = Csp::new().script_src(SELF);
+        assert_eq!(csp.to_header(Some(&nonce)), "script-src 'self'");
+    }
+}
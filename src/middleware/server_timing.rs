@@ -0,0 +1,107 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use crate::http::timing::{RequestTiming, ServerTimings};
+use crate::http::{Request, Response};
+use crate::service::{Layer, Service};
+
+/// Middleware that reports the request's phase timings to the client in a `Server-Timing`
+/// header, so browser developer tools can show where the time went.
+///
+/// Phases recorded by the time the response is ready are included: parsing, routing and
+/// the handler. Handlers and inner middleware can add their own measurements through the
+/// [`ServerTimings`] the layer puts into the request's extensions. The header lets anyone see how long the backend spends, so consider
+/// applying the layer only to trusted clients with a `ConditionLayer`.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Router, ServiceBuilder, middleware::server_timing::ServerTimingLayer};
+/// # fn example(router: Router) {
+/// let service = ServiceBuilder::new(router)
+///     .layer(ServerTimingLayer)
+///     .service();
+/// // Server-Timing: parse;dur=0.041, routing;dur=0.002, handler;dur=12.304
+/// # }
+/// ```
+pub struct ServerTimingLayer;
+
+impl<S> Layer<S> for ServerTimingLayer {
+    type Service = ServerTimingMiddleware<S>;
+
+    /// Wraps the given service with the Server-Timing middleware.
+    fn layer(&self, service: S) -> Self::Service {
+        ServerTimingMiddleware { inner: service }
+    }
+}
+
+/// Middleware service that adds a `Server-Timing` header to responses.
+#[derive(Clone)]
+pub struct ServerTimingMiddleware<S> {
+    inner: S,
+}
+
+impl<S> Service for ServerTimingMiddleware<S>
+where
+    S: Service<Response = Response, Error = String> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Checks if the service is ready to accept a request.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Calls the inner service and adds the recorded timings to its response.
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        let timing = request.extensions.get::<RequestTiming>().cloned();
+        let timings = ServerTimings::new();
+        request.extensions.insert(timings.clone());
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let mut response = future.await?;
+            let mut metrics: Vec<String> = timing
+                .map(|timing| timing.phases())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(phase, duration)| metric(phase.name(), duration, None))
+                .collect();
+            metrics.extend(timings.metrics().into_iter().map(|recorded| {
+                metric(
+                    &recorded.name,
+                    recorded.duration,
+                    recorded.description.as_deref(),
+                )
+            }));
+            if !metrics.is_empty() {
+                response
+                    .headers
+                    .insert("Server-Timing".to_string(), metrics.join(", "));
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Formats one `Server-Timing` metric with its duration in milliseconds.
+fn metric(name: &str, duration: Duration, description: Option<&str>) -> String {
+    let mut metric = format!("{};dur={:.3}", name, duration.as_secs_f64() * 1000.0);
+    if let Some(description) = description {
+        // A quoted-string, with quotes and backslashes escaped and control characters dropped
+        let escaped: String = description
+            .chars()
+            .filter(|c| !c.is_control())
+            .flat_map(|c| match c {
+                '"' | '\\' => vec!['\\', c],
+                c => vec![c],
+            })
+            .collect();
+        metric.push_str(&format!(";desc=\"{}\"", escaped));
+    }
+    metric
+}
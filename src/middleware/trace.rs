@@ -0,0 +1,334 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+
+use crate::http::{Request, Response};
+use crate::logging;
+use crate::router::MatchedRoute;
+use crate::server::ConnectInfo;
+use crate::service::{Layer, Service};
+
+/// The W3C trace context of a request: which trace it belongs to and which span it is.
+///
+/// [`TraceLayer`] puts the context of each request's server span into the request's
+/// extensions. Handlers that call other services pass it on with [`TraceContext::traceparent`]
+/// so those calls join the same trace.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Request, middleware::trace::TraceContext};
+/// # fn example(request: &Request, outgoing: &mut Request) {
+/// if let Some(context) = request.extensions.get::<TraceContext>() {
+///     outgoing.headers.insert("traceparent".to_string(), context.traceparent());
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceContext {
+    /// The id shared by every span in the trace.
+    pub trace_id: u128,
+    /// The id of this span.
+    pub span_id: u64,
+    /// Whether the trace is being recorded.
+    pub sampled: bool,
+    /// Vendor-specific trace state, passed along unchanged.
+    pub trace_state: Option<String>,
+}
+
+impl TraceContext {
+    /// Starts a new, sampled trace.
+    pub fn new_root() -> Self {
+        TraceContext {
+            trace_id: (u128::from(random_id()) << 64) | u128::from(random_id()),
+            span_id: random_id(),
+            sampled: true,
+            trace_state: None,
+        }
+    }
+
+    /// Creates the context of a span whose parent is this one.
+    pub fn child(&self) -> Self {
+        TraceContext {
+            span_id: random_id(),
+            ..self.clone()
+        }
+    }
+
+    /// Parses a `traceparent` header.
+    ///
+    /// # Returns
+    ///
+    /// The context, or `None` if the header is malformed or holds all-zero ids.
+    pub fn from_traceparent(header: &str) -> Option<Self> {
+        let mut fields = header.trim().split('-');
+        let version = fields.next()?;
+        let trace_id = fields.next()?;
+        let span_id = fields.next()?;
+        let flags = fields.next()?;
+        // Version 00 has exactly four fields; later versions may append more
+        if version.len() != 2 || version == "ff" || (version == "00" && fields.next().is_some()) {
+            return None;
+        }
+        if trace_id.len() != 32 || span_id.len() != 16 || flags.len() != 2 {
+            return None;
+        }
+        if ![version, trace_id, span_id, flags].iter().all(|field| {
+            field
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+        }) {
+            return None;
+        }
+
+        let trace_id = u128::from_str_radix(trace_id, 16).ok()?;
+        let span_id = u64::from_str_radix(span_id, 16).ok()?;
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+        if trace_id == 0 || span_id == 0 {
+            return None;
+        }
+        Some(TraceContext {
+            trace_id,
+            span_id,
+            sampled: flags & 1 == 1,
+            trace_state: None,
+        })
+    }
+
+    /// Extracts the context a request's caller sent in `traceparent` and `tracestate`.
+    pub fn from_request(request: &Request) -> Option<Self> {
+        let mut context = Self::from_traceparent(request.header("traceparent")?)?;
+        context.trace_state = request
+            .header("tracestate")
+            .map(|state| state.trim().to_string())
+            .filter(|state| !state.is_empty());
+        Some(context)
+    }
+
+    /// Formats the context as a `traceparent` header value.
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id,
+            self.span_id,
+            u8::from(self.sampled)
+        )
+    }
+}
+
+/// The value of a span attribute.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    String(String),
+    Int(i64),
+}
+
+/// A finished server span, describing how one request was handled.
+///
+/// Attributes follow the OpenTelemetry semantic conventions for HTTP servers, e.g.
+/// `http.request.method`, `url.path` and `http.response.status_code`.
+#[derive(Debug, Clone)]
+pub struct Span {
+    /// The span name, the request method as the conventions suggest.
+    pub name: String,
+    /// The span's own context.
+    pub context: TraceContext,
+    /// The id of the caller's span, if the request continued a trace.
+    pub parent_span_id: Option<u64>,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub attributes: Vec<(&'static str, AttributeValue)>,
+    /// Whether the request failed: a handler error or a 5xx response.
+    pub error: bool,
+}
+
+/// Receives finished spans, e.g. to send them to a tracing backend.
+///
+/// Closures taking a [`Span`] implement this trait.
+pub trait SpanExporter: Send + Sync {
+    /// Hands over a finished span. Called on the request's task, so it must not block.
+    fn export(&self, span: Span);
+}
+
+impl<F> SpanExporter for F
+where
+    F: Fn(Span) + Send + Sync,
+{
+    fn export(&self, span: Span) {
+        self(span)
+    }
+}
+
+/// Middleware that records a server span for every request and exports it.
+///
+/// A request carrying a valid `traceparent` header continues the caller's trace, keeping its
+/// sampling decision; otherwise a new trace is started. Only sampled spans are exported.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Router, ServiceBuilder};
+/// # use http_server::middleware::{otlp::OtlpExporter, trace::TraceLayer};
+/// # fn example(router: Router) -> Result<(), String> {
+/// let exporter = OtlpExporter::new("http://localhost:4318", "my-service")?;
+/// let service = ServiceBuilder::new(router)
+///     .layer(TraceLayer::new(exporter))
+///     .service();
+/// # Ok(())
+/// # }
+/// ```
+pub struct TraceLayer {
+    exporter: Arc<dyn SpanExporter>,
+}
+
+impl TraceLayer {
+    /// Creates a layer that hands finished spans to `exporter`.
+    pub fn new(exporter: impl SpanExporter + 'static) -> Self {
+        TraceLayer {
+            exporter: Arc::new(exporter),
+        }
+    }
+}
+
+impl<S> Layer<S> for TraceLayer {
+    type Service = TraceMiddleware<S>;
+
+    /// Wraps the given service with the tracing middleware.
+    fn layer(&self, service: S) -> Self::Service {
+        TraceMiddleware {
+            inner: service,
+            exporter: self.exporter.clone(),
+        }
+    }
+}
+
+/// Middleware service that records a span around the inner service.
+#[derive(Clone)]
+pub struct TraceMiddleware<S> {
+    inner: S,
+    exporter: Arc<dyn SpanExporter>,
+}
+
+impl<S> Service for TraceMiddleware<S>
+where
+    S: Service<Response = Response, Error = String> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Checks if the service is ready to accept a request.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Starts the request's span, calls the inner service and exports the finished span.
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        let parent = TraceContext::from_request(&request);
+        let context = match &parent {
+            Some(parent) => parent.child(),
+            None => TraceContext::new_root(),
+        };
+        request.extensions.insert(context.clone());
+
+        let mut span = Span {
+            name: request.method.to_string(),
+            context,
+            parent_span_id: parent.map(|parent| parent.span_id),
+            start: SystemTime::now(),
+            end: SystemTime::now(),
+            attributes: request_attributes(&request),
+            error: false,
+        };
+        let exporter = self.exporter.clone();
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let result = future.await;
+            span.end = SystemTime::now();
+            match &result {
+                Ok(response) => {
+                    if let Some(route) = response.extensions.get::<MatchedRoute>() {
+                        span.attributes
+                            .push(("http.route", AttributeValue::String(route.0.clone())));
+                    }
+                    let status = response.status_code as u16;
+                    span.attributes.push((
+                        "http.response.status_code",
+                        AttributeValue::Int(i64::from(status)),
+                    ));
+                    if status >= 500 {
+                        span.error = true;
+                        span.attributes
+                            .push(("error.type", AttributeValue::String(status.to_string())));
+                    }
+                }
+                Err(e) => {
+                    span.error = true;
+                    span.attributes
+                        .push(("error.type", AttributeValue::String(e.clone())));
+                }
+            }
+            if let Some(id) = logging::request_id() {
+                span.attributes.push((
+                    "http.request.header.x-request-id",
+                    AttributeValue::String(id),
+                ));
+            }
+            if span.context.sampled {
+                exporter.export(span);
+            }
+            result
+        })
+    }
+}
+
+/// Collects the semantic-convention attributes known before the request is handled.
+fn request_attributes(request: &Request) -> Vec<(&'static str, AttributeValue)> {
+    let string = |value: &str| AttributeValue::String(value.to_string());
+    let mut attributes = vec![
+        ("http.request.method", string(&request.method.to_string())),
+        ("url.path", string(&request.path)),
+        ("url.scheme", string("http")),
+        (
+            "network.protocol.version",
+            string(request.version.to_string().trim_start_matches("HTTP/")),
+        ),
+    ];
+    if let Some(query) = &request.raw_query {
+        attributes.push(("url.query", string(query)));
+    }
+    if let Some(host) = request.header("Host") {
+        attributes.push(("server.address", string(host)));
+    }
+    if let Some(user_agent) = request.header("User-Agent") {
+        attributes.push(("user_agent.original", string(user_agent)));
+    }
+    if let Some(info) = request.extensions.get::<ConnectInfo>() {
+        attributes.push(("client.address", string(&info.peer.ip().to_string())));
+        attributes.push((
+            "client.port",
+            AttributeValue::Int(i64::from(info.peer.port())),
+        ));
+    }
+    attributes
+}
+
+/// Generates a random, non-zero id.
+pub(super) fn random_id() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    loop {
+        // `RandomState` is seeded from the OS, so its hashes are unpredictable
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        let id = hasher.finish();
+        if id != 0 {
+            return id;
+        }
+    }
+}
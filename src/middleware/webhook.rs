@@ -0,0 +1,389 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::crypto::{constant_time_eq, from_hex, hmac_sha256};
+use crate::http::{Request, Response, StatusCode};
+use crate::logging;
+use crate::service::{Layer, Service};
+
+/// How a webhook sender signs its requests. All schemes use HMAC-SHA256 in hex.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignatureScheme {
+    /// GitHub's `X-Hub-Signature-256: sha256=<hmac>`, over the body.
+    GitHub,
+    /// Stripe's `Stripe-Signature: t=<unix time>,v1=<hmac>`, over `<unix time>.<body>`. The
+    /// header may hold several `v1` signatures while the sender rotates secrets.
+    Stripe,
+    /// A signature of the body in `header`, after `prefix`. With a `timestamp_header`, the
+    /// signature covers `<timestamp>.<body>` and the timestamp, in Unix seconds, must be
+    /// recent.
+    Custom {
+        header: String,
+        prefix: String,
+        timestamp_header: Option<String>,
+    },
+}
+
+/// The signatures and signed timestamp found in a request.
+struct Signed {
+    signatures: Vec<Vec<u8>>,
+    timestamp: Option<String>,
+}
+
+impl SignatureScheme {
+    /// Extracts the signatures and timestamp from the request's headers.
+    ///
+    /// # Returns
+    ///
+    /// The signatures, or why the request can't be verified.
+    fn extract(&self, request: &Request) -> Result<Signed, String> {
+        match self {
+            SignatureScheme::GitHub => {
+                let header = request
+                    .header("X-Hub-Signature-256")
+                    .ok_or("Missing X-Hub-Signature-256 header")?;
+                let signature = header
+                    .strip_prefix("sha256=")
+                    .and_then(from_hex)
+                    .ok_or("Malformed X-Hub-Signature-256 header")?;
+                Ok(Signed {
+                    signatures: vec![signature],
+                    timestamp: None,
+                })
+            }
+            SignatureScheme::Stripe => {
+                let header = request
+                    .header("Stripe-Signature")
+                    .ok_or("Missing Stripe-Signature header")?;
+                let mut signed = Signed {
+                    signatures: Vec::new(),
+                    timestamp: None,
+                };
+                for (name, value) in header.split(',').filter_map(|pair| pair.split_once('=')) {
+                    match name.trim() {
+                        "t" => signed.timestamp = Some(value.trim().to_string()),
+                        "v1" => signed.signatures.extend(from_hex(value.trim())),
+                        _ => {}
+                    }
+                }
+                if signed.timestamp.is_none() || signed.signatures.is_empty() {
+                    return Err("Malformed Stripe-Signature header".to_string());
+                }
+                Ok(signed)
+            }
+            SignatureScheme::Custom {
+                header,
+                prefix,
+                timestamp_header,
+            } => {
+                let value = request
+                    .header(header)
+                    .ok_or_else(|| format!("Missing {} header", header))?;
+                let signature = value
+                    .strip_prefix(prefix.as_str())
+                    .and_then(from_hex)
+                    .ok_or_else(|| format!("Malformed {} header", header))?;
+                let timestamp = match timestamp_header {
+                    Some(name) => Some(
+                        request
+                            .header(name)
+                            .ok_or_else(|| format!("Missing {} header", name))?
+                            .clone(),
+                    ),
+                    None => None,
+                };
+                Ok(Signed {
+                    signatures: vec![signature],
+                    timestamp,
+                })
+            }
+        }
+    }
+}
+
+/// Middleware that verifies the HMAC signatures webhook senders put on their requests, and
+/// answers `401 Unauthorized` before the handler runs when a signature is missing or wrong.
+///
+/// For schemes with a signed timestamp, requests older or further in the future than the
+/// tolerance, 5 minutes by default, are refused too, so captured requests can't be replayed
+/// later.
+///
+/// Apply it only to the webhook routes, e.g. with a `ConditionLayer`.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Router, ServiceBuilder};
+/// # use http_server::middleware::{ConditionLayer, webhook::{SignatureScheme, WebhookLayer}};
+/// # fn example(router: Router, github_secret: &str) {
+/// let webhooks = WebhookLayer::new(github_secret.as_bytes(), SignatureScheme::GitHub);
+/// let service = ServiceBuilder::new(router)
+///     .layer(ConditionLayer::path_prefix("/webhooks/github", webhooks))
+///     .service();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct WebhookLayer {
+    secrets: Arc<Vec<Vec<u8>>>,
+    scheme: Arc<SignatureScheme>,
+    tolerance: Duration,
+}
+
+impl WebhookLayer {
+    /// Creates a layer verifying signatures made with `secret` in `scheme`.
+    pub fn new(secret: &[u8], scheme: SignatureScheme) -> Self {
+        WebhookLayer {
+            secrets: Arc::new(vec![secret.to_vec()]),
+            scheme: Arc::new(scheme),
+            tolerance: Duration::from_secs(5 * 60),
+        }
+    }
+
+    /// Also accepts signatures made with `secret`, e.g. while rotating secrets.
+    pub fn secret(mut self, secret: &[u8]) -> Self {
+        Arc::make_mut(&mut self.secrets).push(secret.to_vec());
+        self
+    }
+
+    /// Sets how far a signed timestamp may be from the current time.
+    pub fn tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Checks the request's signature.
+    ///
+    /// # Returns
+    ///
+    /// Why the request is refused, if it is.
+    fn verify(&self, request: &Request) -> Result<(), String> {
+        let signed = self.scheme.extract(request)?;
+
+        let mut message = Vec::with_capacity(request.body.len() + 16);
+        if let Some(timestamp) = &signed.timestamp {
+            let sent = timestamp
+                .parse::<u64>()
+                .map_err(|_| "Malformed signature timestamp")?;
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_secs());
+            if now.abs_diff(sent) > self.tolerance.as_secs() {
+                return Err("Signature timestamp outside the tolerance".to_string());
+            }
+            message.extend_from_slice(timestamp.as_bytes());
+            message.push(b'.');
+        }
+        message.extend_from_slice(&request.body);
+
+        let valid = self.secrets.iter().any(|secret| {
+            let expected = hmac_sha256(secret, &message);
+            signed
+                .signatures
+                .iter()
+                .any(|signature| constant_time_eq(&expected, signature))
+        });
+        if !valid {
+            return Err("Signature mismatch".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl<S> Layer<S> for WebhookLayer {
+    type Service = WebhookMiddleware<S>;
+
+    /// Wraps the given service with the webhook signature middleware.
+    fn layer(&self, service: S) -> Self::Service {
+        WebhookMiddleware {
+            inner: service,
+            config: self.clone(),
+        }
+    }
+}
+
+/// Middleware service that verifies webhook signatures.
+#[derive(Clone)]
+pub struct WebhookMiddleware<S> {
+    inner: S,
+    config: WebhookLayer,
+}
+
+impl<S> Service for WebhookMiddleware<S>
+where
+    S: Service<Response = Response, Error = String> + Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Checks if the service is ready to accept a request.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Verifies the request's signature, calling the inner service only if it's valid.
+    fn call(&mut self, request: Request) -> Self::Future {
+        if let Err(e) = self.config.verify(&request) {
+            logging::warn(
+                "webhook",
+                "Refused a webhook request",
+                &[("path", &request.path), ("error", &e)],
+            );
+            let mut response = Response::new(StatusCode::Unauthorized);
+            response.set_content_type("text/plain");
+            response.set_body(b"Invalid signature".to_vec());
+            return Box::pin(async move { Ok(response) });
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::to_hex;
+    use crate::service::{self, ServiceBuilder, service_fn};
+
+    const SECRET: &[u8] = b"webhook secret";
+
+    fn service(layer: WebhookLayer) -> impl Service<Response = Response, Error = String> {
+        ServiceBuilder::new(service_fn(|_request: Request| async {
+            Ok(Response::new(StatusCode::OK))
+        }))
+        .layer(layer)
+        .service()
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    /// Builds a Stripe-style request for `body`, signed with `secret` at `timestamp`.
+    fn stripe_request(secret: &[u8], timestamp: u64, body: &[u8]) -> Request {
+        let mut message = format!("{}.", timestamp).into_bytes();
+        message.extend_from_slice(body);
+        let header = format!(
+            "t={},v1={}",
+            timestamp,
+            to_hex(&hmac_sha256(secret, &message))
+        );
+        Request::builder()
+            .path("/webhooks/stripe")
+            .header("Stripe-Signature", &header)
+            .body(body.to_vec())
+    }
+
+    async fn status(layer: WebhookLayer, request: Request) -> u16 {
+        service::oneshot(&mut service(layer), request)
+            .await
+            .status_code as u16
+    }
+
+    #[tokio::test]
+    async fn accepts_a_valid_github_signature() {
+        let body = b"{\"action\":\"opened\"}";
+        let header = format!("sha256={}", to_hex(&hmac_sha256(SECRET, body)));
+        let request = Request::builder()
+            .path("/webhooks/github")
+            .header("X-Hub-Signature-256", &header)
+            .body(body.to_vec());
+        let layer = WebhookLayer::new(SECRET, SignatureScheme::GitHub);
+        assert_eq!(status(layer, request).await, 200);
+    }
+
+    #[tokio::test]
+    async fn refuses_a_bad_signature() {
+        let layer = WebhookLayer::new(SECRET, SignatureScheme::GitHub);
+        let header = format!("sha256={}", to_hex(&hmac_sha256(b"other secret", b"{}")));
+        let request = Request::builder()
+            .header("X-Hub-Signature-256", &header)
+            .body(b"{}".to_vec());
+        assert_eq!(status(layer.clone(), request).await, 401);
+
+        let request = Request::builder()
+            .header("X-Hub-Signature-256", "sha256=not hex")
+            .body(b"{}".to_vec());
+        assert_eq!(status(layer.clone(), request).await, 401);
+
+        let request = Request::builder().body(b"{}".to_vec());
+        assert_eq!(status(layer, request).await, 401);
+    }
+
+    #[tokio::test]
+    async fn accepts_rotated_secrets() {
+        let layer = WebhookLayer::new(b"new secret", SignatureScheme::Stripe).secret(SECRET);
+        assert_eq!(
+            status(layer, stripe_request(SECRET, now(), b"{}")).await,
+            200
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_a_stale_timestamp() {
+        let layer = WebhookLayer::new(SECRET, SignatureScheme::Stripe);
+        let stale = now() - 10 * 60;
+        assert_eq!(
+            status(layer.clone(), stripe_request(SECRET, stale, b"{}")).await,
+            401
+        );
+        let future = now() + 10 * 60;
+        assert_eq!(
+            status(layer, stripe_request(SECRET, future, b"{}")).await,
+            401
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_replays_with_a_new_timestamp_or_body() {
+        let layer = WebhookLayer::new(SECRET, SignatureScheme::Stripe);
+        let original = stripe_request(SECRET, now() - 10 * 60, b"{\"amount\":1}");
+        let signature = original.header("Stripe-Signature").unwrap().clone();
+        let v1 = signature.split_once(",v1=").unwrap().1;
+
+        // Moving the captured signature to a fresh timestamp breaks it
+        let replayed = Request::builder()
+            .header("Stripe-Signature", &format!("t={},v1={}", now(), v1))
+            .body(b"{\"amount\":1}".to_vec());
+        assert_eq!(status(layer.clone(), replayed).await, 401);
+
+        // As does reusing it for another body
+        let fresh = stripe_request(SECRET, now(), b"{\"amount\":1}");
+        let signature = fresh.header("Stripe-Signature").unwrap().clone();
+        let tampered = Request::builder()
+            .header("Stripe-Signature", &signature)
+            .body(b"{\"amount\":1000}".to_vec());
+        assert_eq!(status(layer, tampered).await, 401);
+    }
+
+    #[tokio::test]
+    async fn custom_scheme_requires_its_timestamp_header() {
+        let scheme = SignatureScheme::Custom {
+            header: "X-Signature".to_string(),
+            prefix: String::new(),
+            timestamp_header: Some("X-Timestamp".to_string()),
+        };
+        let layer = WebhookLayer::new(SECRET, scheme);
+        let timestamp = now().to_string();
+        let signature = to_hex(&hmac_sha256(
+            SECRET,
+            format!("{}.{{}}", timestamp).as_bytes(),
+        ));
+
+        let request = Request::builder()
+            .header("X-Signature", &signature)
+            .header("X-Timestamp", &timestamp)
+            .body(b"{}".to_vec());
+        assert_eq!(status(layer.clone(), request).await, 200);
+
+        let request = Request::builder()
+            .header("X-Signature", &signature)
+            .body(b"{}".to_vec());
+        assert_eq!(status(layer, request).await, 401);
+    }
+}
@@ -0,0 +1,467 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{OnceLock, RwLock};
+
+/// The type used when an extension isn't known.
+pub const DEFAULT: &str = "application/octet-stream";
+
+/// Built-in mappings from lowercase file extensions to MIME types.
+const TYPES: &[(&str, &str)] = &[
+    // Text
+    ("appcache", "text/cache-manifest"),
+    ("css", "text/css"),
+    ("csv", "text/csv"),
+    ("htm", "text/html"),
+    ("html", "text/html"),
+    ("ics", "text/calendar"),
+    ("ini", "text/plain"),
+    ("log", "text/plain"),
+    ("markdown", "text/markdown"),
+    ("md", "text/markdown"),
+    ("mjs", "text/javascript"),
+    ("js", "text/javascript"),
+    ("rtx", "text/richtext"),
+    ("sgml", "text/sgml"),
+    ("shtml", "text/html"),
+    ("srt", "text/plain"),
+    ("text", "text/plain"),
+    ("tsv", "text/tab-separated-values"),
+    ("txt", "text/plain"),
+    ("vcard", "text/vcard"),
+    ("vcf", "text/x-vcard"),
+    ("vtt", "text/vtt"),
+    ("xhtml", "application/xhtml+xml"),
+    ("xml", "text/xml"),
+    ("xsl", "text/xml"),
+    ("yaml", "text/yaml"),
+    ("yml", "text/yaml"),
+    ("conf", "text/plain"),
+    ("def", "text/plain"),
+    ("list", "text/plain"),
+    ("in", "text/plain"),
+    // Source code
+    ("c", "text/x-c"),
+    ("cc", "text/x-c"),
+    ("cpp", "text/x-c"),
+    ("cxx", "text/x-c"),
+    ("h", "text/x-c"),
+    ("hh", "text/x-c"),
+    ("java", "text/x-java-source"),
+    ("py", "text/x-python"),
+    ("rs", "text/x-rust"),
+    ("s", "text/x-asm"),
+    ("asm", "text/x-asm"),
+    ("sh", "application/x-sh"),
+    ("toml", "application/toml"),
+    ("ts", "text/typescript"),
+    ("tsx", "text/tsx"),
+    ("jsx", "text/jsx"),
+    ("go", "text/x-go"),
+    ("rb", "text/x-ruby"),
+    ("pl", "text/x-perl"),
+    ("php", "application/x-httpd-php"),
+    ("lua", "text/x-lua"),
+    ("sql", "application/sql"),
+    ("diff", "text/x-diff"),
+    ("patch", "text/x-diff"),
+    // Application data
+    ("json", "application/json"),
+    ("jsonld", "application/ld+json"),
+    ("map", "application/json"),
+    ("webmanifest", "application/manifest+json"),
+    ("geojson", "application/geo+json"),
+    ("atom", "application/atom+xml"),
+    ("rss", "application/rss+xml"),
+    ("rdf", "application/rdf+xml"),
+    ("xslt", "application/xslt+xml"),
+    ("wasm", "application/wasm"),
+    ("pdf", "application/pdf"),
+    ("ps", "application/postscript"),
+    ("eps", "application/postscript"),
+    ("ai", "application/postscript"),
+    ("rtf", "application/rtf"),
+    ("wsdl", "application/wsdl+xml"),
+    ("xsd", "application/xml"),
+    ("dtd", "application/xml-dtd"),
+    ("bin", "application/octet-stream"),
+    ("exe", "application/octet-stream"),
+    ("dll", "application/octet-stream"),
+    ("so", "application/octet-stream"),
+    ("iso", "application/octet-stream"),
+    ("img", "application/octet-stream"),
+    ("dmg", "application/octet-stream"),
+    ("msi", "application/octet-stream"),
+    ("deb", "application/vnd.debian.binary-package"),
+    ("rpm", "application/x-rpm"),
+    ("apk", "application/vnd.android.package-archive"),
+    ("jar", "application/java-archive"),
+    ("war", "application/java-archive"),
+    ("ear", "application/java-archive"),
+    ("class", "application/java-vm"),
+    ("ser", "application/java-serialized-object"),
+    ("swf", "application/x-shockwave-flash"),
+    ("der", "application/x-x509-ca-cert"),
+    ("pem", "application/x-pem-file"),
+    ("crt", "application/x-x509-ca-cert"),
+    ("cer", "application/pkix-cert"),
+    ("crl", "application/pkix-crl"),
+    ("p7b", "application/x-pkcs7-certificates"),
+    ("p7c", "application/pkcs7-mime"),
+    ("p7s", "application/pkcs7-signature"),
+    ("p8", "application/pkcs8"),
+    ("p10", "application/pkcs10"),
+    ("p12", "application/x-pkcs12"),
+    ("pfx", "application/x-pkcs12"),
+    ("asc", "application/pgp-signature"),
+    ("sig", "application/pgp-signature"),
+    ("gpg", "application/pgp-encrypted"),
+    ("torrent", "application/x-bittorrent"),
+    ("ogx", "application/ogg"),
+    ("mathml", "application/mathml+xml"),
+    ("mml", "application/mathml+xml"),
+    ("epub", "application/epub+zip"),
+    ("mobi", "application/x-mobipocket-ebook"),
+    ("azw", "application/vnd.amazon.ebook"),
+    ("ipynb", "application/x-ipynb+json"),
+    ("sqlite", "application/vnd.sqlite3"),
+    ("db", "application/octet-stream"),
+    ("wgt", "application/widget"),
+    ("xpi", "application/x-xpinstall"),
+    ("crx", "application/x-chrome-extension"),
+    ("bz", "application/x-bzip"),
+    ("bz2", "application/x-bzip2"),
+    ("gz", "application/gzip"),
+    ("tgz", "application/gzip"),
+    ("br", "application/x-brotli"),
+    ("xz", "application/x-xz"),
+    ("lz", "application/x-lzip"),
+    ("lzma", "application/x-lzma"),
+    ("zst", "application/zstd"),
+    ("z", "application/x-compress"),
+    ("zip", "application/zip"),
+    ("7z", "application/x-7z-compressed"),
+    ("rar", "application/vnd.rar"),
+    ("tar", "application/x-tar"),
+    ("cpio", "application/x-cpio"),
+    ("shar", "application/x-shar"),
+    ("cab", "application/vnd.ms-cab-compressed"),
+    ("latex", "application/x-latex"),
+    ("tex", "application/x-tex"),
+    ("dvi", "application/x-dvi"),
+    ("texinfo", "application/x-texinfo"),
+    ("bat", "application/x-msdownload"),
+    ("com", "application/x-msdownload"),
+    ("csh", "application/x-csh"),
+    ("tcl", "application/x-tcl"),
+    ("pac", "application/x-ns-proxy-autoconfig"),
+    ("ttl", "text/turtle"),
+    ("n3", "text/n3"),
+    ("sparql", "application/sparql-query"),
+    ("srx", "application/sparql-results+xml"),
+    ("graphql", "application/graphql"),
+    ("proto", "text/plain"),
+    ("avro", "application/avro"),
+    ("parquet", "application/vnd.apache.parquet"),
+    ("msgpack", "application/msgpack"),
+    ("cbor", "application/cbor"),
+    ("bson", "application/bson"),
+    // Office documents
+    ("doc", "application/msword"),
+    ("dot", "application/msword"),
+    (
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    ),
+    (
+        "dotx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.template",
+    ),
+    ("xls", "application/vnd.ms-excel"),
+    ("xlt", "application/vnd.ms-excel"),
+    (
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    ),
+    (
+        "xltx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.template",
+    ),
+    ("ppt", "application/vnd.ms-powerpoint"),
+    ("pps", "application/vnd.ms-powerpoint"),
+    (
+        "pptx",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    ),
+    (
+        "ppsx",
+        "application/vnd.openxmlformats-officedocument.presentationml.slideshow",
+    ),
+    ("mdb", "application/x-msaccess"),
+    ("pub", "application/x-mspublisher"),
+    ("vsd", "application/vnd.visio"),
+    ("xps", "application/vnd.ms-xpsdocument"),
+    ("odt", "application/vnd.oasis.opendocument.text"),
+    ("ott", "application/vnd.oasis.opendocument.text-template"),
+    ("ods", "application/vnd.oasis.opendocument.spreadsheet"),
+    (
+        "ots",
+        "application/vnd.oasis.opendocument.spreadsheet-template",
+    ),
+    ("odp", "application/vnd.oasis.opendocument.presentation"),
+    (
+        "otp",
+        "application/vnd.oasis.opendocument.presentation-template",
+    ),
+    ("odg", "application/vnd.oasis.opendocument.graphics"),
+    ("odc", "application/vnd.oasis.opendocument.chart"),
+    ("odf", "application/vnd.oasis.opendocument.formula"),
+    ("odb", "application/vnd.oasis.opendocument.database"),
+    ("key", "application/vnd.apple.keynote"),
+    ("numbers", "application/vnd.apple.numbers"),
+    ("pages", "application/vnd.apple.pages"),
+    ("kml", "application/vnd.google-earth.kml+xml"),
+    ("kmz", "application/vnd.google-earth.kmz"),
+    ("gpx", "application/gpx+xml"),
+    ("xul", "application/vnd.mozilla.xul+xml"),
+    ("m3u8", "application/vnd.apple.mpegurl"),
+    ("mpd", "application/dash+xml"),
+    ("pkpass", "application/vnd.apple.pkpass"),
+    // Images
+    ("apng", "image/apng"),
+    ("avif", "image/avif"),
+    ("bmp", "image/bmp"),
+    ("cur", "image/x-icon"),
+    ("gif", "image/gif"),
+    ("heic", "image/heic"),
+    ("heif", "image/heif"),
+    ("ico", "image/x-icon"),
+    ("jfif", "image/jpeg"),
+    ("jp2", "image/jp2"),
+    ("jpe", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("jpg", "image/jpeg"),
+    ("jxl", "image/jxl"),
+    ("pjpeg", "image/jpeg"),
+    ("pjp", "image/jpeg"),
+    ("png", "image/png"),
+    ("psd", "image/vnd.adobe.photoshop"),
+    ("svg", "image/svg+xml"),
+    ("svgz", "image/svg+xml"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("webp", "image/webp"),
+    ("wbmp", "image/vnd.wap.wbmp"),
+    ("xbm", "image/x-xbitmap"),
+    ("xpm", "image/x-xpixmap"),
+    ("pbm", "image/x-portable-bitmap"),
+    ("pgm", "image/x-portable-graymap"),
+    ("ppm", "image/x-portable-pixmap"),
+    ("pnm", "image/x-portable-anymap"),
+    ("ras", "image/x-cmu-raster"),
+    ("rgb", "image/x-rgb"),
+    ("tga", "image/x-tga"),
+    ("dds", "image/vnd.ms-dds"),
+    ("djvu", "image/vnd.djvu"),
+    ("djv", "image/vnd.djvu"),
+    ("dng", "image/x-adobe-dng"),
+    ("cr2", "image/x-canon-cr2"),
+    ("nef", "image/x-nikon-nef"),
+    ("exr", "image/x-exr"),
+    ("hdr", "image/vnd.radiance"),
+    ("emf", "image/emf"),
+    ("wmf", "image/wmf"),
+    // Audio
+    ("aac", "audio/aac"),
+    ("aif", "audio/x-aiff"),
+    ("aifc", "audio/x-aiff"),
+    ("aiff", "audio/x-aiff"),
+    ("amr", "audio/amr"),
+    ("au", "audio/basic"),
+    ("caf", "audio/x-caf"),
+    ("flac", "audio/flac"),
+    ("kar", "audio/midi"),
+    ("m3u", "audio/x-mpegurl"),
+    ("m4a", "audio/mp4"),
+    ("mid", "audio/midi"),
+    ("midi", "audio/midi"),
+    ("mka", "audio/x-matroska"),
+    ("mp2", "audio/mpeg"),
+    ("mp3", "audio/mpeg"),
+    ("mpga", "audio/mpeg"),
+    ("oga", "audio/ogg"),
+    ("ogg", "audio/ogg"),
+    ("opus", "audio/opus"),
+    ("pls", "audio/x-scpls"),
+    ("ra", "audio/x-realaudio"),
+    ("ram", "audio/x-pn-realaudio"),
+    ("snd", "audio/basic"),
+    ("spx", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("weba", "audio/webm"),
+    ("wma", "audio/x-ms-wma"),
+    ("3ga", "audio/3gpp"),
+    // Video
+    ("3g2", "video/3gpp2"),
+    ("3gp", "video/3gpp"),
+    ("asf", "video/x-ms-asf"),
+    ("asx", "video/x-ms-asf"),
+    ("avi", "video/x-msvideo"),
+    ("flv", "video/x-flv"),
+    ("h264", "video/h264"),
+    ("m1v", "video/mpeg"),
+    ("m2v", "video/mpeg"),
+    ("m4v", "video/x-m4v"),
+    ("mkv", "video/x-matroska"),
+    ("mng", "video/x-mng"),
+    ("mov", "video/quicktime"),
+    ("mp4", "video/mp4"),
+    ("mpe", "video/mpeg"),
+    ("mpeg", "video/mpeg"),
+    ("mpg", "video/mpeg"),
+    ("mpg4", "video/mp4"),
+    ("ogv", "video/ogg"),
+    ("qt", "video/quicktime"),
+    ("m2ts", "video/mp2t"),
+    ("mts", "video/mp2t"),
+    ("webm", "video/webm"),
+    ("wmv", "video/x-ms-wmv"),
+    ("wmx", "video/x-ms-wmx"),
+    ("wvx", "video/x-ms-wvx"),
+    // Fonts
+    ("eot", "application/vnd.ms-fontobject"),
+    ("otf", "font/otf"),
+    ("ttc", "font/collection"),
+    ("ttf", "font/ttf"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("pfb", "application/x-font-type1"),
+    ("pfm", "application/x-font-type1"),
+    ("afm", "application/x-font-type1"),
+    ("bdf", "application/x-font-bdf"),
+    ("pcf", "application/x-font-pcf"),
+    ("snf", "application/x-font-snf"),
+    // 3D models
+    ("glb", "model/gltf-binary"),
+    ("gltf", "model/gltf+json"),
+    ("obj", "model/obj"),
+    ("stl", "model/stl"),
+    ("usdz", "model/vnd.usdz+zip"),
+    ("wrl", "model/vrml"),
+    ("vrml", "model/vrml"),
+    ("x3d", "model/x3d+xml"),
+    ("3mf", "model/3mf"),
+    ("dae", "model/vnd.collada+xml"),
+    ("igs", "model/iges"),
+    ("iges", "model/iges"),
+    ("msh", "model/mesh"),
+    ("mesh", "model/mesh"),
+    ("silo", "model/mesh"),
+    // Messages
+    ("eml", "message/rfc822"),
+    ("mht", "message/rfc822"),
+    ("mhtml", "message/rfc822"),
+    ("mime", "message/rfc822"),
+];
+
+/// Types registered at runtime, which take precedence over the built-in table.
+fn overrides() -> &'static RwLock<HashMap<String, String>> {
+    static OVERRIDES: OnceLock<RwLock<HashMap<String, String>>> = OnceLock::new();
+    OVERRIDES.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Registers the MIME type for an extension, replacing the built-in one if there is one.
+///
+/// Registrations are process-wide and apply to [`from_path`] and [`from_extension`]
+/// everywhere, including the static file service.
+///
+/// # Arguments
+///
+/// * `extension` - The file extension, with or without the leading `.`; case-insensitive.
+/// * `mime_type` - The type to use, e.g. `"application/x-custom"`.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::mime;
+/// mime::register("tpl", "text/x-template");
+/// assert_eq!(mime::from_path("page.tpl"), "text/x-template; charset=utf-8");
+/// ```
+pub fn register(extension: &str, mime_type: &str) {
+    let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+    overrides()
+        .write()
+        .unwrap()
+        .insert(extension, mime_type.to_string());
+}
+
+/// Looks up the MIME type for a file extension, without any charset.
+///
+/// # Arguments
+///
+/// * `extension` - The file extension, without the leading `.`; case-insensitive.
+///
+/// # Returns
+///
+/// The registered or built-in type, or `None` if the extension isn't known.
+pub fn from_extension(extension: &str) -> Option<String> {
+    let extension = extension.to_ascii_lowercase();
+    if let Some(mime_type) = overrides().read().unwrap().get(&extension) {
+        return Some(mime_type.clone());
+    }
+    TYPES
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map(|(_, mime_type)| mime_type.to_string())
+}
+
+/// Picks the `Content-Type` for a file from its extension.
+///
+/// Textual types get `; charset=utf-8` appended unless they already carry parameters, so
+/// browsers don't have to guess the encoding.
+///
+/// # Arguments
+///
+/// * `path` - The file's path or name; only the extension is used.
+///
+/// # Returns
+///
+/// The content type, or [`DEFAULT`] for unknown extensions and files without one.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::mime;
+/// assert_eq!(mime::from_path("index.html"), "text/html; charset=utf-8");
+/// assert_eq!(mime::from_path("logo.PNG"), "image/png");
+/// ```
+pub fn from_path(path: impl AsRef<Path>) -> String {
+    let mime_type = path
+        .as_ref()
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(from_extension);
+    match mime_type {
+        Some(mime_type) if is_text(&mime_type) && !mime_type.contains(';') => {
+            format!("{}; charset=utf-8", mime_type)
+        }
+        Some(mime_type) => mime_type,
+        None => DEFAULT.to_string(),
+    }
+}
+
+/// Returns whether a type holds text, and so should declare its charset.
+fn is_text(mime_type: &str) -> bool {
+    mime_type.starts_with("text/")
+        || mime_type.ends_with("+json")
+        || mime_type.ends_with("+xml")
+        || matches!(
+            mime_type,
+            "application/json"
+                | "application/javascript"
+                | "application/xml"
+                | "application/toml"
+                | "application/sql"
+                | "application/graphql"
+                | "application/x-sh"
+        )
+}
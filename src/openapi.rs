@@ -0,0 +1,334 @@
+use std::pin::Pin;
+
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+
+use crate::http::{Request, Response, StatusCode};
+use crate::static_files::html_escape;
+
+/// Boxed future returned by [`swagger_ui`].
+type ResponseFuture = Pin<Box<dyn Future<Output = Result<Response, String>> + Send>>;
+
+/// A documented query parameter.
+#[derive(Debug, Clone)]
+struct QueryParam {
+    name: String,
+    description: String,
+    required: bool,
+}
+
+/// A documented request or response body.
+#[derive(Debug, Clone)]
+struct Body {
+    description: String,
+    schema: Option<Value>,
+    example: Option<Value>,
+}
+
+/// Documentation for a route, attached with [`Router::doc`](crate::router::Router::doc) and
+/// published by [`Router::openapi`](crate::router::Router::openapi).
+///
+/// Bodies are described by a JSON Schema, or by an example value of a serde type from which
+/// the schema is inferred: every field present in the example is listed, and all but those
+/// serialized as `null` are required.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Request, Response, Router, StatusCode, openapi::RouteDoc};
+/// # use serde::Serialize;
+/// # #[derive(Serialize)]
+/// # struct User { id: u64, name: String }
+/// # impl User { fn example() -> Self { User { id: 1, name: "Ada".to_string() } } }
+/// # async fn handle_user(_req: Request) -> Result<Response, String> {
+/// #     Ok(Response::new(StatusCode::OK))
+/// # }
+/// let router = Router::new()
+///     .get("/users/:id", handle_user)
+///     .doc(
+///         RouteDoc::new()
+///             .summary("Get a user")
+///             .tag("users")
+///             .response_example(StatusCode::OK, "The user", &User::example())
+///             .response(StatusCode::NotFound, "No such user"),
+///     );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RouteDoc {
+    summary: Option<String>,
+    description: Option<String>,
+    operation_id: Option<String>,
+    tags: Vec<String>,
+    deprecated: bool,
+    query: Vec<QueryParam>,
+    request_body: Option<Body>,
+    responses: Vec<(u16, Body)>,
+}
+
+impl RouteDoc {
+    /// Creates empty documentation.
+    pub fn new() -> Self {
+        RouteDoc::default()
+    }
+
+    /// Sets a one-line summary of what the route does.
+    pub fn summary(mut self, summary: &str) -> Self {
+        self.summary = Some(summary.to_string());
+        self
+    }
+
+    /// Sets a longer description, which may use CommonMark.
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// Sets the operation's unique ID, used by client generators as the method name.
+    pub fn operation_id(mut self, id: &str) -> Self {
+        self.operation_id = Some(id.to_string());
+        self
+    }
+
+    /// Adds a tag grouping the route with related ones.
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    /// Marks the route as deprecated.
+    pub fn deprecated(mut self) -> Self {
+        self.deprecated = true;
+        self
+    }
+
+    /// Documents a string query parameter.
+    pub fn query_param(mut self, name: &str, description: &str, required: bool) -> Self {
+        self.query.push(QueryParam {
+            name: name.to_string(),
+            description: description.to_string(),
+            required,
+        });
+        self
+    }
+
+    /// Documents a JSON request body by its schema.
+    pub fn request_schema(mut self, description: &str, schema: Value) -> Self {
+        self.request_body = Some(Body {
+            description: description.to_string(),
+            schema: Some(schema),
+            example: None,
+        });
+        self
+    }
+
+    /// Documents a JSON request body by an example, inferring its schema.
+    pub fn request_example<T: Serialize>(mut self, description: &str, example: &T) -> Self {
+        self.request_body = Some(Body::from_example(description, example));
+        self
+    }
+
+    /// Documents a response without a body.
+    pub fn response(mut self, status: StatusCode, description: &str) -> Self {
+        self.responses.push((
+            status as u16,
+            Body {
+                description: description.to_string(),
+                schema: None,
+                example: None,
+            },
+        ));
+        self
+    }
+
+    /// Documents a JSON response by its schema.
+    pub fn response_schema(mut self, status: StatusCode, description: &str, schema: Value) -> Self {
+        self.responses.push((
+            status as u16,
+            Body {
+                description: description.to_string(),
+                schema: Some(schema),
+                example: None,
+            },
+        ));
+        self
+    }
+
+    /// Documents a JSON response by an example, inferring its schema.
+    pub fn response_example<T: Serialize>(
+        mut self,
+        status: StatusCode,
+        description: &str,
+        example: &T,
+    ) -> Self {
+        self.responses
+            .push((status as u16, Body::from_example(description, example)));
+        self
+    }
+}
+
+impl Body {
+    fn from_example<T: Serialize>(description: &str, example: &T) -> Self {
+        let example = serde_json::to_value(example).unwrap_or(Value::Null);
+        Body {
+            description: description.to_string(),
+            schema: Some(schema_of(&example)),
+            example: Some(example),
+        }
+    }
+
+    /// Builds the body's `content` object, or `None` if it has no schema.
+    fn content(&self) -> Option<Value> {
+        let schema = self.schema.as_ref()?;
+        let mut media = json!({ "schema": schema });
+        if let Some(example) = &self.example {
+            media["example"] = example.clone();
+        }
+        Some(json!({ "application/json": media }))
+    }
+}
+
+/// Infers a JSON Schema from an example value.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::openapi::schema_of;
+/// # use serde_json::json;
+/// let schema = schema_of(&json!({"id": 1, "name": "Ada", "email": null}));
+/// // {"type": "object", "properties": {...}, "required": ["id", "name"]}
+/// ```
+pub fn schema_of(example: &Value) -> Value {
+    match example {
+        Value::Null => json!({}),
+        Value::Bool(_) => json!({ "type": "boolean" }),
+        Value::Number(number) if number.is_f64() => json!({ "type": "number" }),
+        Value::Number(_) => json!({ "type": "integer" }),
+        Value::String(_) => json!({ "type": "string" }),
+        Value::Array(items) => json!({
+            "type": "array",
+            "items": items.first().map_or_else(|| json!({}), schema_of),
+        }),
+        Value::Object(fields) => {
+            let properties: Map<String, Value> = fields
+                .iter()
+                .map(|(name, value)| (name.clone(), schema_of(value)))
+                .collect();
+            // Fields serialized as null are usually `Option`s
+            let required: Vec<&String> = fields
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(name, _)| name)
+                .collect();
+            json!({
+                "type": "object",
+                "properties": properties,
+                "required": required,
+            })
+        }
+    }
+}
+
+/// Builds the operation object for a route.
+///
+/// # Arguments
+///
+/// * `doc` - The route's documentation, if it has any.
+/// * `path_params` - The names of the route's path parameters.
+pub(crate) fn operation(doc: Option<&RouteDoc>, path_params: &[String]) -> Value {
+    let default = RouteDoc::default();
+    let doc = doc.unwrap_or(&default);
+
+    let mut parameters: Vec<Value> = path_params
+        .iter()
+        .map(|name| {
+            json!({
+                "name": name,
+                "in": "path",
+                "required": true,
+                "schema": { "type": "string" },
+            })
+        })
+        .collect();
+    parameters.extend(doc.query.iter().map(|param| {
+        json!({
+            "name": param.name,
+            "in": "query",
+            "description": param.description,
+            "required": param.required,
+            "schema": { "type": "string" },
+        })
+    }));
+
+    let mut responses = Map::new();
+    for (status, body) in &doc.responses {
+        let mut response = json!({ "description": body.description });
+        if let Some(content) = body.content() {
+            response["content"] = content;
+        }
+        responses.insert(status.to_string(), response);
+    }
+    if responses.is_empty() {
+        responses.insert("200".to_string(), json!({ "description": "OK" }));
+    }
+
+    let mut operation = Map::new();
+    if let Some(summary) = &doc.summary {
+        operation.insert("summary".to_string(), json!(summary));
+    }
+    if let Some(description) = &doc.description {
+        operation.insert("description".to_string(), json!(description));
+    }
+    if let Some(id) = &doc.operation_id {
+        operation.insert("operationId".to_string(), json!(id));
+    }
+    if !doc.tags.is_empty() {
+        operation.insert("tags".to_string(), json!(doc.tags));
+    }
+    if doc.deprecated {
+        operation.insert("deprecated".to_string(), json!(true));
+    }
+    if !parameters.is_empty() {
+        operation.insert("parameters".to_string(), json!(parameters));
+    }
+    if let Some(body) = &doc.request_body {
+        let mut request_body = json!({ "description": body.description, "required": true });
+        if let Some(content) = body.content() {
+            request_body["content"] = content;
+        }
+        operation.insert("requestBody".to_string(), request_body);
+    }
+    operation.insert("responses".to_string(), Value::Object(responses));
+    Value::Object(operation)
+}
+
+/// Wraps the operations of each path in an OpenAPI 3.1 document.
+pub(crate) fn document(title: &str, version: &str, paths: Map<String, Value>) -> Value {
+    json!({
+        "openapi": "3.1.0",
+        "info": { "title": title, "version": version },
+        "paths": paths,
+    })
+}
+
+/// Returns a handler serving Swagger UI for the document at `spec_url`.
+///
+/// The page loads Swagger UI's scripts and styles from the unpkg CDN, so browsers viewing it
+/// need internet access.
+pub fn swagger_ui(spec_url: &str) -> impl Fn(Request) -> ResponseFuture + Send + Sync + 'static {
+    // The URL is passed through an attribute so it needs no escaping for JavaScript
+    let page = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>API documentation</title>\
+         <link rel=\"stylesheet\" href=\"https://unpkg.com/swagger-ui-dist@5/swagger-ui.css\">\
+         </head><body><div id=\"swagger-ui\" data-url=\"{}\"></div>\
+         <script src=\"https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js\"></script>\
+         <script>SwaggerUIBundle({{url: document.getElementById('swagger-ui').dataset.url, \
+         dom_id: '#swagger-ui'}});</script></body></html>\n",
+        html_escape(spec_url)
+    );
+    move |_request| {
+        let mut response = Response::new(StatusCode::OK);
+        response.set_content_type("text/html; charset=utf-8");
+        response.set_body(page.clone().into_bytes());
+        Box::pin(async move { Ok(response) })
+    }
+}
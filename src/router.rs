@@ -1,18 +1,44 @@
-use std::{collections::HashMap, pin::Pin, sync::Arc, task::Poll};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::Poll,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value, json};
 
 /// The router module provides routing functionality for HTTP requests.
 /// It includes definitions for route patterns, path segments, and the router itself.
 use crate::{
-    http::{Method, Request, Response, StatusCode},
-    service::Service,
+    auth::{
+        Principal,
+        rbac::{GrantsPolicy, Policy, Requirement},
+    },
+    http::{
+        Method, Request, Response, StatusCode,
+        timing::{Phase, RequestTiming},
+    },
+    json_schema::{Schema, Violation},
+    logging,
+    middleware::body_log,
+    openapi::{self, RouteDoc},
+    server::ConnectInfo,
+    service::{self, Service},
 };
 
-/// Type alias for middleware functions.
-pub(super) type Middleware = fn(&mut Request) -> Result<(), Response>;
-
 /// Represents a route pattern with segments.
+///
+/// The source and segments are shared between clones, so cloning the router for each request
+/// doesn't copy its patterns.
 pub struct RoutePattern {
-    segments: Vec<PathSegment>,
+    source: Arc<str>,
+    segments: Arc<[PathSegment]>,
 }
 
 /// Enum representing different types of path segments.
@@ -32,7 +58,9 @@ impl RoutePattern {
     /// # Examples
     ///
     /// ```
+    /// # use http_server::router::RoutePattern;
     /// let pattern = RoutePattern::new("/users/:id");
+    /// assert_eq!(pattern.matches("/users/42").unwrap()["id"], "42");
     /// ```
     pub fn new(pattern: &str) -> Self {
         let segments = pattern
@@ -49,7 +77,37 @@ impl RoutePattern {
             })
             .collect();
 
-        RoutePattern { segments }
+        RoutePattern {
+            source: pattern.into(),
+            segments,
+        }
+    }
+
+    /// Returns the pattern as it was written, e.g. `/users/:id`.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns the pattern as an OpenAPI path template, e.g. `/users/{id}`, and the names of
+    /// its parameters. A wildcard becomes a `path` parameter.
+    fn openapi_path(&self) -> (String, Vec<String>) {
+        let mut params = Vec::new();
+        let segments: Vec<String> = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                PathSegment::Exact(text) => text.clone(),
+                PathSegment::Param(name) => {
+                    params.push(name.clone());
+                    format!("{{{}}}", name)
+                }
+                PathSegment::Wildcard => {
+                    params.push("path".to_string());
+                    "{path}".to_string()
+                }
+            })
+            .collect();
+        (format!("/{}", segments.join("/")), params)
     }
 
     /// Checks if the given path matches the route pattern.
@@ -96,7 +154,8 @@ impl RoutePattern {
                     path_index += 1;
                 }
                 PathSegment::Wildcard => {
-                    // Wildcard matches all remaining segments
+                    // Wildcard matches all remaining segments, which are available as `*`
+                    params.insert("*".to_string(), path_segments[path_index..].join("/"));
                     return Some(params);
                 }
             }
@@ -111,21 +170,200 @@ impl RoutePattern {
     }
 }
 
+/// The pattern of the route that handled a request, e.g. `/users/:id`.
+///
+/// The router puts it into the extensions of the request passed to the handler and of the
+/// response, so outer layers can group requests by route rather than by raw path. Requests
+/// handled by the not-found handler have none.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Response, router::MatchedRoute};
+/// # fn example(response: &Response) {
+/// let route = response.extensions.get::<MatchedRoute>().map(|route| route.0.as_str());
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchedRoute(pub String);
+
 /// Type alias for handler functions.
 type HandlerFn =
     dyn Fn(Request) -> Pin<Box<dyn Future<Output = Result<Response, String>> + Send>> + Send + Sync;
 
+/// Type alias for before hooks, which may modify the request or short-circuit with a response.
+type BeforeHookFn = dyn Fn(Request) -> Pin<Box<dyn Future<Output = Result<Request, Response>> + Send>>
+    + Send
+    + Sync;
+
+/// Type alias for after hooks, which post-process the response.
+type AfterHookFn = dyn Fn(Response) -> Pin<Box<dyn Future<Output = Response> + Send>> + Send + Sync;
+
+/// Type alias for error hooks, which observe failed requests.
+type ErrorHookFn = dyn Fn(&ErrorReport) + Send + Sync;
+
+/// A request that failed, as passed to [`Router::on_error`] hooks.
+#[derive(Debug, Clone)]
+pub struct ErrorReport {
+    pub method: Method,
+    pub path: String,
+    pub query: Option<String>,
+    /// The request headers, with credentials such as `Authorization` and `Cookie` redacted.
+    pub headers: HashMap<String, String>,
+    /// The client's address, if the server recorded it.
+    pub client: Option<SocketAddr>,
+    /// The pattern of the route that handled the request, if one matched.
+    pub route: Option<String>,
+    /// The status sent to the client; handler errors are sent as 500.
+    pub status: u16,
+    /// The handler's error, or `None` if it returned a 5xx response.
+    pub error: Option<String>,
+    /// The ID the request ID middleware assigned, if it's applied.
+    pub request_id: Option<String>,
+}
+
 /// Represents a route with a pattern, method, and handler.
 pub struct Route {
     pattern: RoutePattern,
     method: Option<Method>,
     handler: Arc<HandlerFn>,
+    stats: Arc<MatchStats>,
+    doc: Option<Arc<RouteDoc>>,
+    requirement: Option<Arc<Requirement>>,
+    schema: Option<Arc<Schema>>,
+}
+
+/// Counts how often a route matched and how long it took; shared between clones of the router.
+#[derive(Default)]
+struct MatchStats {
+    hits: AtomicU64,
+    /// Milliseconds since the Unix epoch of the last match, or 0 if there was none.
+    last_matched: AtomicU64,
+    /// Handler calls that have completed, and the microseconds they took in total.
+    completed: AtomicU64,
+    total_micros: AtomicU64,
 }
 
-/// Represents the router with a collection of routes and a not-found handler.
+impl MatchStats {
+    fn record(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_millis() as u64);
+        self.last_matched.store(now, Ordering::Relaxed);
+    }
+
+    fn record_latency(&self, latency: Duration) {
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        self.total_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn mean_latency(&self) -> Option<Duration> {
+        match self.completed.load(Ordering::Relaxed) {
+            0 => None,
+            completed => Some(Duration::from_micros(
+                self.total_micros.load(Ordering::Relaxed) / completed,
+            )),
+        }
+    }
+
+    fn last_matched(&self) -> Option<SystemTime> {
+        match self.last_matched.load(Ordering::Relaxed) {
+            0 => None,
+            millis => Some(UNIX_EPOCH + Duration::from_millis(millis)),
+        }
+    }
+}
+
+/// A description of a registered route, as returned by [`Router::route_info`].
+#[derive(Debug, Clone)]
+pub struct RouteInfo {
+    /// The route pattern, e.g. `/users/:id`.
+    pub pattern: String,
+    /// The method the route answers, or `None` for any method.
+    pub method: Option<Method>,
+    /// How many requests the route has handled.
+    pub hits: u64,
+    /// When the route last handled a request.
+    pub last_matched: Option<SystemTime>,
+    /// How long the route's handler takes on average, once a call has completed.
+    pub mean_latency: Option<Duration>,
+}
+
+/// The routes of a router, with live statistics, as returned by [`Router::route_stats`] and
+/// served by [`Router::route_table`].
+///
+/// Clones share the statistics, which keep updating as the router handles requests.
+#[derive(Clone)]
+pub struct RouteTable {
+    routes: Vec<(String, Option<Method>, Arc<MatchStats>)>,
+    not_found: Arc<MatchStats>,
+    before_hooks: usize,
+    after_hooks: usize,
+}
+
+impl RouteTable {
+    /// Describes the routes in matching order, with their current statistics.
+    pub fn info(&self) -> Vec<RouteInfo> {
+        self.routes
+            .iter()
+            .map(|(pattern, method, stats)| RouteInfo {
+                pattern: pattern.clone(),
+                method: method.clone(),
+                hits: stats.hits.load(Ordering::Relaxed),
+                last_matched: stats.last_matched(),
+                mean_latency: stats.mean_latency(),
+            })
+            .collect()
+    }
+
+    /// Returns how many requests matched no route.
+    pub fn not_found_hits(&self) -> u64 {
+        self.not_found.hits.load(Ordering::Relaxed)
+    }
+
+    /// Builds the JSON response listing the routes.
+    fn response(&self) -> Response {
+        let routes: Vec<_> = self
+            .info()
+            .into_iter()
+            .map(|info| {
+                json!({
+                    "pattern": info.pattern,
+                    "method": info.method.map_or_else(|| "*".to_string(), |method| method.to_string()),
+                    "hits": info.hits,
+                    "last_matched": info.last_matched.map(|time| DateTime::<Utc>::from(time).to_rfc3339()),
+                    "mean_latency_ms": info.mean_latency.map(|latency| latency.as_secs_f64() * 1000.0),
+                })
+            })
+            .collect();
+        let body = json!({
+            "routes": routes,
+            "not_found_hits": self.not_found_hits(),
+            "before_hooks": self.before_hooks,
+            "after_hooks": self.after_hooks,
+        });
+
+        let mut response = Response::new(StatusCode::OK);
+        response.set_content_type("application/json");
+        response
+            .headers
+            .insert("Cache-Control".to_string(), "no-store".to_string());
+        response.set_body(body.to_string().into_bytes());
+        response
+    }
+}
+
+/// Represents the router with a collection of routes, hooks, and a not-found handler.
 pub struct Router {
     pub routes: Vec<Route>,
     pub not_found_handler: Arc<HandlerFn>,
+    pub before_hooks: Vec<Arc<BeforeHookFn>>,
+    pub after_hooks: Vec<Arc<AfterHookFn>>,
+    pub error_hooks: Vec<Arc<ErrorHookFn>>,
+    not_found_stats: Arc<MatchStats>,
+    policy: Arc<dyn Policy>,
 }
 
 impl Router {
@@ -147,6 +385,11 @@ impl Router {
         Router {
             routes: Vec::new(),
             not_found_handler,
+            before_hooks: Vec::new(),
+            after_hooks: Vec::new(),
+            error_hooks: Vec::new(),
+            not_found_stats: Arc::new(MatchStats::default()),
+            policy: Arc::new(GrantsPolicy),
         }
     }
 
@@ -161,7 +404,11 @@ impl Router {
     /// # Examples
     ///
     /// ```
-    /// router.route("/users/:id", Some(Method::GET), handler);
+    /// # use http_server::{Method, Request, Response, Router, StatusCode};
+    /// # async fn handler(_req: Request) -> Result<Response, String> {
+    /// #     Ok(Response::new(StatusCode::OK))
+    /// # }
+    /// let router = Router::new().route("/users/:id", Some(Method::Get), handler);
     /// ```
     pub fn route<F, Fut>(mut self, pattern: &str, method: Option<Method>, handler: F) -> Self
     where
@@ -177,6 +424,10 @@ impl Router {
             pattern: RoutePattern::new(pattern),
             method,
             handler,
+            stats: Arc::new(MatchStats::default()),
+            doc: None,
+            requirement: None,
+            schema: None,
         });
 
         self
@@ -192,7 +443,11 @@ impl Router {
     /// # Examples
     ///
     /// ```
-    /// router.get("/users/:id", handler);
+    /// # use http_server::{Request, Response, Router, StatusCode};
+    /// # async fn handler(_req: Request) -> Result<Response, String> {
+    /// #     Ok(Response::new(StatusCode::OK))
+    /// # }
+    /// let router = Router::new().get("/users/:id", handler);
     /// ```
     pub fn get<F, Fut>(self, pattern: &str, handler: F) -> Self
     where
@@ -212,7 +467,11 @@ impl Router {
     /// # Examples
     ///
     /// ```
-    /// router.post("/users", handler);
+    /// # use http_server::{Request, Response, Router, StatusCode};
+    /// # async fn handler(_req: Request) -> Result<Response, String> {
+    /// #     Ok(Response::new(StatusCode::OK))
+    /// # }
+    /// let router = Router::new().post("/users", handler);
     /// ```
     pub fn post<F, Fut>(self, pattern: &str, handler: F) -> Self
     where
@@ -222,6 +481,188 @@ impl Router {
         self.route(pattern, Some(Method::Post), handler)
     }
 
+    /// Documents the route added last, for the OpenAPI document.
+    ///
+    /// # Arguments
+    ///
+    /// * `doc` - The route's summary, parameters and bodies.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::{Request, Response, Router, StatusCode, openapi::RouteDoc};
+    /// # use serde_json::json;
+    /// # async fn handle_create_user(_req: Request) -> Result<Response, String> {
+    /// #     Ok(Response::new(StatusCode::Created))
+    /// # }
+    /// let new_user = json!({"name": "Ada"});
+    /// let router = Router::new()
+    ///     .post("/users", handle_create_user)
+    ///     .doc(RouteDoc::new().summary("Create a user").request_example("The user", &new_user));
+    /// ```
+    pub fn doc(mut self, doc: RouteDoc) -> Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.doc = Some(Arc::new(doc));
+        }
+        self
+    }
+
+    /// Guards the route added last: requests reach its handler only if the router's policy
+    /// allows them, and are answered with `401 Unauthorized` or `403 Forbidden` otherwise.
+    ///
+    /// The default policy checks the grants of the [`Principal`] that authentication
+    /// middleware or a before hook put into the request's extensions.
+    ///
+    /// # Arguments
+    ///
+    /// * `requirement` - The roles and permissions the route requires.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::{Request, Response, Router, StatusCode, auth::rbac::Requirement};
+    /// # async fn handle_create_user(_req: Request) -> Result<Response, String> {
+    /// #     Ok(Response::new(StatusCode::Created))
+    /// # }
+    /// # async fn handle_admin(_req: Request) -> Result<Response, String> {
+    /// #     Ok(Response::new(StatusCode::OK))
+    /// # }
+    /// let router = Router::new()
+    ///     .post("/users", handle_create_user)
+    ///     .require(Requirement::permission("users:write"))
+    ///     .get("/admin/*", handle_admin)
+    ///     .require(Requirement::role("admin"));
+    /// ```
+    pub fn require(mut self, requirement: Requirement) -> Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.requirement = Some(Arc::new(requirement));
+        }
+        self
+    }
+
+    /// Validates the JSON bodies of requests to the route added last against `schema`, so its
+    /// handler can assume well-formed input.
+    ///
+    /// Bodies that aren't JSON are answered with `400 Bad Request`, and bodies that don't
+    /// match the schema with `422 Unprocessable Entity`; both list the problems found as
+    /// `{"error": ..., "violations": [{"path": ..., "message": ...}]}`. The schema also
+    /// documents the request body in [`Router::openapi`] unless [`Router::doc`] does.
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - The schema bodies must match, compiled at startup.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::{Request, Response, Router, StatusCode, json_schema::Schema};
+    /// # use serde_json::json;
+    /// # async fn handle_create_user(_req: Request) -> Result<Response, String> {
+    /// #     Ok(Response::new(StatusCode::Created))
+    /// # }
+    /// # fn main() -> Result<(), String> {
+    /// let new_user = Schema::compile(&json!({
+    ///     "type": "object",
+    ///     "properties": { "name": { "type": "string", "minLength": 1 } },
+    ///     "required": ["name"]
+    /// }))?;
+    /// let router = Router::new().post("/users", handle_create_user).validate(new_user);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn validate(mut self, schema: Schema) -> Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.schema = Some(Arc::new(schema));
+        }
+        self
+    }
+
+    /// Decides on requests to guarded routes with `policy` instead of [`GrantsPolicy`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::{Request, Router, auth::{Principal, rbac::{Decision, Requirement}}};
+    /// // Users may also edit their own profile
+    /// let router = Router::new().policy(
+    ///     |principal: Option<&Principal>, req: &Request, requirement: &Requirement| {
+    ///         match principal {
+    ///             None => Decision::Unauthenticated,
+    ///             Some(p) if requirement.is_met_by(p) || req.param("id") == Some(&p.id) => {
+    ///                 Decision::Allow
+    ///             }
+    ///             Some(_) => Decision::Forbidden,
+    ///         }
+    ///     },
+    /// );
+    /// ```
+    pub fn policy<P>(mut self, policy: P) -> Self
+    where
+        P: Policy + 'static,
+    {
+        self.policy = Arc::new(policy);
+        self
+    }
+
+    /// Generates an OpenAPI 3.1 document describing the routes.
+    ///
+    /// Every route with a method is listed, with its path parameters; routes documented with
+    /// [`Router::doc`] also get their summaries, query parameters and bodies. Routes
+    /// answering any method are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `title` - The API's name.
+    /// * `version` - The API's version.
+    pub fn openapi(&self, title: &str, version: &str) -> Value {
+        let mut paths = Map::new();
+        for route in &self.routes {
+            let Some(method) = &route.method else {
+                continue;
+            };
+            let (path, params) = route.pattern.openapi_path();
+            let item = paths.entry(path).or_insert_with(|| json!({}));
+            let mut operation = openapi::operation(route.doc.as_deref(), &params);
+            if let Some(schema) = &route.schema
+                && operation.get("requestBody").is_none()
+            {
+                operation["requestBody"] = json!({
+                    "required": true,
+                    "content": { "application/json": { "schema": schema.as_value() } },
+                });
+            }
+            item[method.to_string().to_ascii_lowercase()] = operation;
+        }
+        openapi::document(title, version, paths)
+    }
+
+    /// Adds a `GET` route at `path` serving the OpenAPI document as JSON.
+    ///
+    /// Add it after the other routes; the document describes the routes registered before it.
+    /// Pair it with [`swagger_ui`](crate::openapi::swagger_ui) to browse the API.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::{Request, Response, Router, StatusCode, openapi::swagger_ui};
+    /// # async fn handle_user(_req: Request) -> Result<Response, String> {
+    /// #     Ok(Response::new(StatusCode::OK))
+    /// # }
+    /// let router = Router::new()
+    ///     .get("/users/:id", handle_user)
+    ///     .openapi_json("/openapi.json", "Users API", "1.0.0")
+    ///     .get("/docs", swagger_ui("/openapi.json"));
+    /// ```
+    pub fn openapi_json(self, path: &str, title: &str, version: &str) -> Self {
+        let body = self.openapi(title, version).to_string().into_bytes();
+        self.get(path, move |_req| {
+            let mut response = Response::new(StatusCode::OK);
+            response.set_content_type("application/json");
+            response.set_body(body.clone());
+            async move { Ok(response) }
+        })
+    }
+
     /// Sets the not-found handler for the router.
     ///
     /// # Arguments
@@ -231,7 +672,11 @@ impl Router {
     /// # Examples
     ///
     /// ```
-    /// router.set_not_found_handler(handler);
+    /// # use http_server::{Request, Response, Router, StatusCode};
+    /// # async fn handler(_req: Request) -> Result<Response, String> {
+    /// #     Ok(Response::new(StatusCode::OK))
+    /// # }
+    /// let router = Router::new().set_not_found_handler(handler);
     /// ```
     pub fn set_not_found_handler<F, Fut>(mut self, handler: F) -> Self
     where
@@ -245,6 +690,177 @@ impl Router {
         self
     }
 
+    /// Adds a hook that runs before routing.
+    ///
+    /// Hooks run in the order they were added. A hook returns the (possibly modified) request
+    /// to continue, or a response to short-circuit; short-circuited responses still go through
+    /// the after hooks.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - A function that receives the request.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::{Response, Router, StatusCode};
+    /// let router = Router::new().before(|req| async move {
+    ///     if req.headers.contains_key("Authorization") {
+    ///         Ok(req)
+    ///     } else {
+    ///         Err(Response::new(StatusCode::Unauthorized))
+    ///     }
+    /// });
+    /// ```
+    pub fn before<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Request, Response>> + Send + 'static,
+    {
+        self.before_hooks.push(Arc::new(move |req| {
+            let fut = hook(req);
+            Box::pin(fut) as Pin<Box<dyn Future<Output = Result<Request, Response>> + Send>>
+        }));
+        self
+    }
+
+    /// Adds a hook that runs on every response produced by the router.
+    ///
+    /// Hooks run in the order they were added. Handler errors bypass the after hooks.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - A function that receives the response and returns the response to send.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::Router;
+    /// let router = Router::new().after(|mut response| async move {
+    ///     response.headers.insert("X-Frame-Options".to_string(), "DENY".to_string());
+    ///     response
+    /// });
+    /// ```
+    pub fn after<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: Fn(Response) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Response> + Send + 'static,
+    {
+        self.after_hooks.push(Arc::new(move |response| {
+            let fut = hook(response);
+            Box::pin(fut) as Pin<Box<dyn Future<Output = Response> + Send>>
+        }));
+        self
+    }
+
+    /// Adds a hook that observes every request that fails, e.g. to report it to an error
+    /// tracker.
+    ///
+    /// Hooks run for handler errors and for 5xx responses, after the after hooks, in the order
+    /// they were added. They run on the request's task, so hand slow work off to a channel or
+    /// spawned task.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - A function that receives a summary of the request and the error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::Router;
+    /// let router = Router::new().on_error(|report| {
+    ///     eprintln!("{} {} failed with {}: {:?}", report.method, report.path, report.status, report.error);
+    /// });
+    /// ```
+    pub fn on_error<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&ErrorReport) + Send + Sync + 'static,
+    {
+        self.error_hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Describes the registered routes in matching order, with their match statistics.
+    pub fn route_info(&self) -> Vec<RouteInfo> {
+        self.route_table_snapshot().info()
+    }
+
+    /// Returns a live view of the registered routes and their match statistics, e.g. for a
+    /// status page served elsewhere. Routes added afterwards aren't included.
+    pub fn route_stats(&self) -> RouteTable {
+        self.route_table_snapshot()
+    }
+
+    /// Adds a `GET` route at `path` that lists the routes and their match statistics as JSON.
+    ///
+    /// Add it after the other routes, since it lists the routes registered up to and
+    /// including itself. The table reveals the application's surface, so requests are only
+    /// answered when `allow` returns `true`; others get the not-found response.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Where to serve the table, e.g. `/debug/routes`.
+    /// * `allow` - Decides whether a request may see the table, e.g. by checking a token.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::{Request, Response, Router, StatusCode};
+    /// # async fn handle_user(_req: Request) -> Result<Response, String> {
+    /// #     Ok(Response::new(StatusCode::OK))
+    /// # }
+    /// let admin_token = std::env::var("ADMIN_TOKEN").unwrap_or_default();
+    /// let router = Router::new()
+    ///     .get("/users/:id", handle_user)
+    ///     .route_table("/debug/routes", move |req| {
+    ///         req.header("X-Admin-Token").is_some_and(|token| *token == admin_token)
+    ///     });
+    /// ```
+    pub fn route_table<F>(self, path: &str, allow: F) -> Self
+    where
+        F: Fn(&Request) -> bool + Send + Sync + 'static,
+    {
+        // Filled in once the route is registered, so the table includes itself
+        let table: Arc<OnceLock<RouteTable>> = Arc::new(OnceLock::new());
+        let handler_table = table.clone();
+        let not_found_handler = self.not_found_handler.clone();
+        let router = self.get(path, move |req: Request| {
+            let response = handler_table
+                .get()
+                .filter(|_| allow(&req))
+                .map(RouteTable::response);
+            let not_found_handler = not_found_handler.clone();
+            async move {
+                match response {
+                    Some(response) => Ok(response),
+                    None => not_found_handler(req).await,
+                }
+            }
+        });
+        let _ = table.set(router.route_table_snapshot());
+        router
+    }
+
+    /// Captures the routes and their shared statistics.
+    fn route_table_snapshot(&self) -> RouteTable {
+        RouteTable {
+            routes: self
+                .routes
+                .iter()
+                .map(|route| {
+                    (
+                        route.pattern.as_str().to_string(),
+                        route.method.clone(),
+                        route.stats.clone(),
+                    )
+                })
+                .collect(),
+            not_found: self.not_found_stats.clone(),
+            before_hooks: self.before_hooks.len(),
+            after_hooks: self.after_hooks.len(),
+        }
+    }
+
     /// Handles an incoming request and returns a response.
     ///
     /// # Arguments
@@ -255,29 +871,224 @@ impl Router {
     ///
     /// A `Future` that resolves to a `Result` containing the response or an error message.
     pub async fn handle(&self, req: Request) -> Result<Response, String> {
+        if self.error_hooks.is_empty() {
+            return self.run(req).await;
+        }
+
+        let mut report = ErrorReport {
+            method: req.method.clone(),
+            path: req.path.clone(),
+            query: req.raw_query.clone(),
+            headers: body_log::redact_default_headers(&req.headers),
+            client: req.extensions.get::<ConnectInfo>().map(|info| info.peer),
+            route: None,
+            status: 0,
+            error: None,
+            request_id: logging::request_id(),
+        };
+        let result = self.run(req).await;
+        match &result {
+            Ok(response) if response.status_code as u16 >= 500 => {
+                report.status = response.status_code as u16;
+                report.route = response
+                    .extensions
+                    .get::<MatchedRoute>()
+                    .map(|route| route.0.clone());
+            }
+            Ok(_) => return result,
+            Err(e) => {
+                report.status = StatusCode::InternalServerError as u16;
+                report.error = Some(e.clone());
+            }
+        }
+        for hook in &self.error_hooks {
+            hook(&report);
+        }
+        result
+    }
+
+    /// Runs a request through the router's hooks and routes, without a server or sockets, e.g.
+    /// to test handlers and their path parameters quickly.
+    ///
+    /// A handler error is turned into `500 Internal Server Error` as the server would. To run
+    /// the request through middleware as well, use [`service::oneshot`] on the layered
+    /// service.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - The request to handle.
+    ///
+    /// # Returns
+    ///
+    /// The response the client would receive from the router.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::{Request, Response, Router, StatusCode};
+    /// # async fn handle_user(_req: Request) -> Result<Response, String> {
+    /// #     Ok(Response::new(StatusCode::OK))
+    /// # }
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let router = Router::new().get("/users/:id", handle_user);
+    /// let response = router.oneshot(Request::builder().path("/users/42").build()).await;
+    /// assert_eq!(response.status_code, StatusCode::OK);
+    /// # }
+    /// ```
+    pub async fn oneshot(&self, req: Request) -> Response {
+        service::oneshot(&mut self.clone(), req).await
+    }
+
+    /// Runs the hooks and the matching handler.
+    async fn run(&self, req: Request) -> Result<Response, String> {
+        let mut req = req;
+
+        // Run before hooks, stopping at the first one that short-circuits
+        for hook in &self.before_hooks {
+            match hook(req).await {
+                Ok(next) => req = next,
+                Err(response) => return Ok(self.run_after_hooks(response).await),
+            }
+        }
+
+        let response = self.dispatch(req).await?;
+        Ok(self.run_after_hooks(response).await)
+    }
+
+    /// Runs the after hooks over a response.
+    async fn run_after_hooks(&self, response: Response) -> Response {
+        let mut response = response;
+        for hook in &self.after_hooks {
+            response = hook(response).await;
+        }
+        response
+    }
+
+    /// Finds the route matching the request and calls its handler.
+    async fn dispatch(&self, req: Request) -> Result<Response, String> {
         // Extract path from request
         let path = &req.path;
+        let routing_start = Instant::now();
 
         // Find matching route
         for route in &self.routes {
-            if let Some(method) = &route.method {
-                if &req.method != method {
-                    continue;
-                }
+            if let Some(method) = &route.method
+                && &req.method != method
+            {
+                continue;
             }
 
             if let Some(params) = route.pattern.matches(path) {
-                let mut req = req.clone();
+                route.stats.record();
+                let matched = MatchedRoute(route.pattern.as_str().to_string());
+                let mut req = req;
                 req.params = params;
-                return (route.handler)(req).await;
+                req.extensions.insert(matched.clone());
+                if let Some(requirement) = &route.requirement {
+                    let principal = Principal::from_request(&req);
+                    if let Some(status_code) = self
+                        .policy
+                        .authorize(principal, &req, requirement)
+                        .status_code()
+                    {
+                        let mut response = denied(status_code);
+                        response.extensions.insert(matched);
+                        return Ok(response);
+                    }
+                }
+                if let Some(schema) = &route.schema
+                    && let Some(mut response) = check_body(schema, &req)
+                {
+                    response.extensions.insert(matched);
+                    return Ok(response);
+                }
+                let mut response =
+                    call_timed(&*route.handler, &route.stats, req, routing_start).await?;
+                response.extensions.insert(matched);
+                return Ok(response);
             }
         }
 
         // No route found, use the 404 handler
-        (self.not_found_handler)(req).await
+        self.not_found_stats.record();
+        call_timed(
+            &*self.not_found_handler,
+            &self.not_found_stats,
+            req,
+            routing_start,
+        )
+        .await
     }
 }
 
+/// Builds the response for a request the policy denied.
+fn denied(status_code: StatusCode) -> Response {
+    let mut response = Response::new(status_code);
+    response.set_content_type("text/plain");
+    response.set_body(status_code.reason_phrase().as_bytes().to_vec());
+    response
+}
+
+/// Checks the request's body against a route's schema.
+///
+/// # Returns
+///
+/// The response refusing the request, if the body isn't JSON or doesn't match.
+fn check_body(schema: &Schema, req: &Request) -> Option<Response> {
+    let (status_code, error, violations) = match serde_json::from_slice::<Value>(&req.body) {
+        Ok(body) => {
+            let violations = schema.validate(&body);
+            if violations.is_empty() {
+                return None;
+            }
+            (
+                StatusCode::UnprocessableEntity,
+                "Request body doesn't match the schema",
+                violations,
+            )
+        }
+        Err(e) => (
+            StatusCode::BadRequest,
+            "Request body isn't valid JSON",
+            vec![Violation {
+                path: String::new(),
+                message: e.to_string(),
+            }],
+        ),
+    };
+    let mut response = Response::new(status_code);
+    response.set_content_type("application/json");
+    response.set_body(
+        json!({ "error": error, "violations": violations })
+            .to_string()
+            .into_bytes(),
+    );
+    Some(response)
+}
+
+/// Calls a handler, recording its latency in the route's statistics and the routing and
+/// handler phases in the request's timing.
+async fn call_timed(
+    handler: &HandlerFn,
+    stats: &MatchStats,
+    req: Request,
+    routing_start: Instant,
+) -> Result<Response, String> {
+    let timing = req.extensions.get::<RequestTiming>().cloned();
+    let handler_start = Instant::now();
+    if let Some(timing) = &timing {
+        timing.record(Phase::Routing, handler_start - routing_start);
+    }
+    let result = handler(req).await;
+    let latency = handler_start.elapsed();
+    stats.record_latency(latency);
+    if let Some(timing) = timing {
+        timing.record(Phase::Handler, latency);
+    }
+    result
+}
+
 impl Default for Router {
     fn default() -> Self {
         Self::new()
@@ -308,6 +1119,11 @@ impl Clone for Router {
         Router {
             routes: self.routes.clone(),
             not_found_handler: self.not_found_handler.clone(),
+            before_hooks: self.before_hooks.clone(),
+            after_hooks: self.after_hooks.clone(),
+            error_hooks: self.error_hooks.clone(),
+            not_found_stats: self.not_found_stats.clone(),
+            policy: self.policy.clone(),
         }
     }
 }
@@ -318,6 +1134,10 @@ impl Clone for Route {
             pattern: self.pattern.clone(),
             method: self.method.clone(),
             handler: self.handler.clone(),
+            stats: self.stats.clone(),
+            doc: self.doc.clone(),
+            requirement: self.requirement.clone(),
+            schema: self.schema.clone(),
         }
     }
 }
@@ -325,17 +1145,43 @@ impl Clone for Route {
 impl Clone for RoutePattern {
     fn clone(&self) -> Self {
         RoutePattern {
+            source: self.source.clone(),
             segments: self.segments.clone(),
         }
     }
 }
 
-impl Clone for PathSegment {
-    fn clone(&self) -> Self {
-        match self {
-            PathSegment::Exact(s) => PathSegment::Exact(s.clone()),
-            PathSegment::Param(s) => PathSegment::Param(s.clone()),
-            PathSegment::Wildcard => PathSegment::Wildcard,
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn error_reports_redact_credentials() {
+        async fn fails(_request: Request) -> Result<Response, String> {
+            Err("database unavailable".to_string())
         }
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let seen = reports.clone();
+        let router = Router::new()
+            .get("/fails", fails)
+            .on_error(move |report| seen.lock().unwrap().push(report.clone()));
+
+        let request = Request::builder()
+            .path("/fails")
+            .header("Authorization", "Bearer secret-token")
+            .header("Cookie", "session=abc")
+            .header("Accept", "text/plain")
+            .build();
+        let response = router.oneshot(request).await;
+        assert_eq!(response.status_code, StatusCode::InternalServerError);
+
+        let reports = reports.lock().unwrap();
+        let report = &reports[0];
+        assert_eq!(report.error.as_deref(), Some("database unavailable"));
+        assert_eq!(report.headers["Authorization"], "[REDACTED]");
+        assert_eq!(report.headers["Cookie"], "[REDACTED]");
+        assert_eq!(report.headers["Accept"], "text/plain");
     }
 }
@@ -0,0 +1,251 @@
+use std::collections::{BTreeSet, HashMap};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use super::AbusePolicy;
+use crate::logging;
+
+/// A protocol violation counted against a client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Violation {
+    /// The request couldn't be parsed.
+    Malformed,
+    /// The request exceeded `max_request_size`.
+    TooLarge,
+    /// The request wasn't sent within the header timeout or the minimum data rate.
+    Slow,
+}
+
+impl Violation {
+    fn name(self) -> &'static str {
+        match self {
+            Violation::Malformed => "malformed",
+            Violation::TooLarge => "too_large",
+            Violation::Slow => "slow",
+        }
+    }
+}
+
+/// The violations of one client.
+struct Offender {
+    /// Violations since `window_start`.
+    count: u32,
+    window_start: Instant,
+    last_seen: Instant,
+    banned_until: Option<Instant>,
+}
+
+/// The tracked clients, indexed so that expired bans and the client to forget are found
+/// without scanning them all.
+#[derive(Default)]
+struct Offenders {
+    clients: HashMap<IpAddr, Offender>,
+    /// Clients that aren't banned, by when they were last seen.
+    by_last_seen: BTreeSet<(Instant, IpAddr)>,
+    /// Banned clients, by when their ban ends.
+    by_unban: BTreeSet<(Instant, IpAddr)>,
+}
+
+impl Offenders {
+    /// Forgets clients whose ban has ended; their count was cleared when they were banned.
+    fn expire(&mut self, now: Instant) {
+        while let Some((until, _)) = self.by_unban.first()
+            && *until <= now
+        {
+            if let Some((_, ip)) = self.by_unban.pop_first() {
+                self.clients.remove(&ip);
+            }
+        }
+    }
+
+    /// Makes room for a new client by forgetting the least recently seen one that isn't
+    /// banned.
+    ///
+    /// # Returns
+    ///
+    /// Whether there's room; never at the expense of a banned client.
+    fn make_room(&mut self, max_clients: usize) -> bool {
+        while self.clients.len() >= max_clients {
+            match self.by_last_seen.pop_first() {
+                Some((_, ip)) => {
+                    self.clients.remove(&ip);
+                }
+                None => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Counts protocol violations per client IP address and bans clients past the policy's
+/// threshold.
+///
+/// At most `max_clients` clients are tracked. The least recently seen client that isn't banned
+/// is forgotten to make room for a new one, so flooding the table from many addresses can't
+/// lift a ban; while every tracked client is banned, new clients' violations aren't counted.
+pub(super) struct AbuseTracker {
+    policy: Option<AbusePolicy>,
+    offenders: Mutex<Offenders>,
+}
+
+impl AbuseTracker {
+    /// Creates a tracker enforcing `policy`; without one, nobody is ever banned.
+    pub(super) fn new(policy: Option<AbusePolicy>) -> Self {
+        AbuseTracker {
+            policy,
+            offenders: Mutex::new(Offenders::default()),
+        }
+    }
+
+    /// Returns the policy, if a banned `ip` is to be dealt with by it.
+    pub(super) fn banned(&self, ip: IpAddr) -> Option<&AbusePolicy> {
+        let policy = self.policy.as_ref()?;
+        let offenders = self.offenders.lock().unwrap();
+        let banned_until = offenders.clients.get(&ip)?.banned_until?;
+        (banned_until > Instant::now()).then_some(policy)
+    }
+
+    /// Counts a violation against `ip`, banning it once it reaches the threshold.
+    pub(super) fn record(&self, ip: IpAddr, violation: Violation) {
+        let Some(policy) = &self.policy else {
+            return;
+        };
+        let now = Instant::now();
+        let mut offenders = self.offenders.lock().unwrap();
+        offenders.expire(now);
+
+        if !offenders.clients.contains_key(&ip) {
+            if !offenders.make_room(policy.max_clients.max(1)) {
+                return;
+            }
+            offenders.clients.insert(
+                ip,
+                Offender {
+                    count: 0,
+                    window_start: now,
+                    last_seen: now,
+                    banned_until: None,
+                },
+            );
+            offenders.by_last_seen.insert((now, ip));
+        }
+        let Offenders {
+            clients,
+            by_last_seen,
+            by_unban,
+        } = &mut *offenders;
+        let offender = clients.get_mut(&ip).expect("client is tracked");
+        if now.duration_since(offender.window_start) > policy.window {
+            offender.count = 0;
+            offender.window_start = now;
+        }
+        offender.count += 1;
+        if offender.banned_until.is_none() {
+            by_last_seen.remove(&(offender.last_seen, ip));
+            by_last_seen.insert((now, ip));
+        }
+        offender.last_seen = now;
+
+        if offender.count >= policy.threshold.max(1) {
+            offender.count = 0;
+            offender.window_start = now;
+            match offender.banned_until {
+                Some(banned_until) => {
+                    by_unban.remove(&(banned_until, ip));
+                }
+                None => {
+                    by_last_seen.remove(&(now, ip));
+                }
+            }
+            let banned_until = now + policy.ban_duration;
+            offender.banned_until = Some(banned_until);
+            by_unban.insert((banned_until, ip));
+            logging::warn(
+                "server",
+                "Banned a client for protocol violations",
+                &[
+                    ("peer", &ip),
+                    ("violation", &violation.name()),
+                    ("seconds", &policy.ban_duration.as_secs()),
+                ],
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::server::AbuseAction;
+
+    fn tracker(threshold: u32, max_clients: usize) -> AbuseTracker {
+        AbuseTracker::new(Some(AbusePolicy {
+            threshold,
+            window: Duration::from_secs(60),
+            ban_duration: Duration::from_secs(600),
+            action: AbuseAction::Drop,
+            max_clients,
+        }))
+    }
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([10, 0, 0, last])
+    }
+
+    #[test]
+    fn bans_after_threshold() {
+        let tracker = tracker(3, 100);
+        tracker.record(ip(1), Violation::Malformed);
+        tracker.record(ip(1), Violation::Slow);
+        assert!(tracker.banned(ip(1)).is_none());
+        tracker.record(ip(1), Violation::TooLarge);
+        assert!(tracker.banned(ip(1)).is_some());
+        assert!(tracker.banned(ip(2)).is_none());
+    }
+
+    #[test]
+    fn without_a_policy_nobody_is_banned() {
+        let tracker = AbuseTracker::new(None);
+        for _ in 0..100 {
+            tracker.record(ip(1), Violation::Malformed);
+        }
+        assert!(tracker.banned(ip(1)).is_none());
+    }
+
+    #[test]
+    fn eviction_keeps_banned_clients() {
+        let tracker = tracker(2, 2);
+        tracker.record(ip(1), Violation::Malformed);
+        tracker.record(ip(1), Violation::Malformed);
+        for last in 2..20 {
+            tracker.record(ip(last), Violation::Malformed);
+        }
+        assert!(tracker.banned(ip(1)).is_some());
+
+        // With every slot banned, new clients aren't tracked
+        let tracker = self::tracker(1, 1);
+        tracker.record(ip(1), Violation::Malformed);
+        tracker.record(ip(2), Violation::Malformed);
+        assert!(tracker.banned(ip(1)).is_some());
+        assert!(tracker.banned(ip(2)).is_none());
+    }
+
+    #[test]
+    fn evicts_the_least_recently_seen_client() {
+        let tracker = tracker(2, 2);
+        tracker.record(ip(1), Violation::Malformed);
+        tracker.record(ip(2), Violation::Malformed);
+        tracker.record(ip(1), Violation::Slow);
+        // Client 1 is banned, so the newcomers only ever replace each other
+        tracker.record(ip(3), Violation::Malformed);
+        tracker.record(ip(2), Violation::Malformed);
+        assert!(tracker.banned(ip(2)).is_none());
+        let offenders = tracker.offenders.lock().unwrap();
+        assert_eq!(offenders.clients.len(), 2);
+        assert_eq!(offenders.by_last_seen.len(), 1);
+        assert_eq!(offenders.by_unban.len(), 1);
+    }
+}
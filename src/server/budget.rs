@@ -0,0 +1,83 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Tracks the request and response bytes buffered by all connections of a server.
+///
+/// Each connection charges what it buffers through a [`Reservation`], so a burst of large
+/// requests is shed once the limit is reached instead of exhausting memory.
+pub(super) struct MemoryBudget {
+    limit: Option<usize>,
+    used: AtomicUsize,
+}
+
+impl MemoryBudget {
+    /// Creates an empty budget.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Maximum number of bytes buffered at once, or `None` to only keep count.
+    pub(super) fn new(limit: Option<usize>) -> Self {
+        MemoryBudget {
+            limit,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of bytes currently charged to the budget.
+    pub(super) fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Charges `bytes` to the budget if it fits within the limit.
+    fn try_acquire(&self, bytes: usize) -> bool {
+        self.used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                let used = used.checked_add(bytes)?;
+                match self.limit {
+                    Some(limit) if used > limit => None,
+                    _ => Some(used),
+                }
+            })
+            .is_ok()
+    }
+
+    /// Returns `bytes` to the budget.
+    fn release(&self, bytes: usize) {
+        self.used.fetch_sub(bytes, Ordering::AcqRel);
+    }
+}
+
+/// The share of a [`MemoryBudget`] held by one connection, released when dropped.
+pub(super) struct Reservation<'a> {
+    budget: &'a MemoryBudget,
+    bytes: usize,
+}
+
+impl<'a> Reservation<'a> {
+    /// Creates a reservation that holds nothing yet.
+    pub(super) fn new(budget: &'a MemoryBudget) -> Self {
+        Reservation { budget, bytes: 0 }
+    }
+
+    /// Grows or shrinks the reservation to exactly `bytes`.
+    ///
+    /// # Returns
+    ///
+    /// `false`, leaving the reservation unchanged, if growing it would exceed the budget.
+    pub(super) fn resize(&mut self, bytes: usize) -> bool {
+        if bytes > self.bytes {
+            if !self.budget.try_acquire(bytes - self.bytes) {
+                return false;
+            }
+        } else {
+            self.budget.release(self.bytes - bytes);
+        }
+        self.bytes = bytes;
+        true
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        self.budget.release(self.bytes);
+    }
+}
@@ -0,0 +1,127 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Buffers that grew past this multiple of the configured size aren't pooled again, so one
+/// large request doesn't pin its memory for the life of the server.
+const MAX_GROWTH_FACTOR: usize = 16;
+
+/// A pool of read buffers shared by all connections of a server.
+///
+/// Each connection checks a buffer out when it starts and returns it when it closes, so
+/// steady-state traffic reuses buffers instead of allocating new ones.
+pub struct BufferPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    max_pooled: usize,
+    buffer_size: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// A snapshot of buffer pool usage.
+#[derive(Debug, Clone, Copy)]
+pub struct BufferPoolStats {
+    /// Checkouts served from the pool.
+    pub hits: u64,
+    /// Checkouts that had to allocate a new buffer.
+    pub misses: u64,
+    /// Buffers currently idle in the pool.
+    pub pooled: usize,
+}
+
+impl BufferPoolStats {
+    /// Returns the fraction of checkouts served from the pool, between 0 and 1.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+impl BufferPool {
+    /// Creates an empty pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_pooled` - Maximum number of idle buffers kept for reuse.
+    /// * `buffer_size` - Initial capacity of newly allocated buffers.
+    pub(super) fn new(max_pooled: usize, buffer_size: usize) -> Self {
+        BufferPool {
+            buffers: Mutex::new(Vec::new()),
+            max_pooled,
+            buffer_size,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Takes an empty buffer from the pool, allocating one if none are idle.
+    ///
+    /// The buffer goes back to the pool when the returned guard is dropped.
+    pub(super) fn get(&self) -> PooledBuffer<'_> {
+        let pooled = self.buffers.lock().unwrap().pop();
+        let buffer = match pooled {
+            Some(buffer) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                buffer
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                Vec::with_capacity(self.buffer_size)
+            }
+        };
+
+        PooledBuffer { pool: self, buffer }
+    }
+
+    /// Returns a buffer to the pool for reuse.
+    fn put(&self, mut buffer: Vec<u8>) {
+        if buffer.capacity() > self.buffer_size * MAX_GROWTH_FACTOR {
+            return;
+        }
+
+        buffer.clear();
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < self.max_pooled {
+            buffers.push(buffer);
+        }
+    }
+
+    /// Returns the current pool usage.
+    pub fn stats(&self) -> BufferPoolStats {
+        BufferPoolStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            pooled: self.buffers.lock().unwrap().len(),
+        }
+    }
+}
+
+/// A buffer checked out of a [`BufferPool`], returned to it when dropped.
+pub(super) struct PooledBuffer<'a> {
+    pool: &'a BufferPool,
+    buffer: Vec<u8>,
+}
+
+impl Deref for PooledBuffer<'_> {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer<'_> {
+    fn drop(&mut self) {
+        self.pool.put(std::mem::take(&mut self.buffer));
+    }
+}
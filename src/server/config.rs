@@ -0,0 +1,215 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use tokio::runtime::{Builder, Runtime};
+
+use super::SocketOptions;
+
+/// Configuration for connection handling in a [`Server`](super::Server).
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// How long a client may take to send the request line and headers once it starts a request.
+    pub header_read_timeout: Duration,
+    /// How long a client may take to send the request body after the headers.
+    pub body_read_timeout: Duration,
+    /// How long writing a response may block before the connection is dropped.
+    pub write_timeout: Duration,
+    /// How long an idle keep-alive connection is kept open waiting for the next request.
+    pub keep_alive_timeout: Duration,
+    /// Slowest a client may send a request before it's closed with `408 Request Timeout`, or
+    /// `None` to rely on the read timeouts alone.
+    pub min_data_rate: Option<MinDataRate>,
+    /// Upper bound on the time from the first byte of a request until its response is ready.
+    pub request_timeout: Duration,
+    /// How long in-flight requests may run after a graceful shutdown starts.
+    pub drain_timeout: Duration,
+    /// Maximum number of connections handled at once, or `None` for as many as
+    /// `runtime.max_blocking_threads`, since each open connection holds a blocking-pool thread.
+    pub max_connections: Option<usize>,
+    /// What to do with new connections while `max_connections` are open.
+    pub connection_limit_policy: ConnectionLimitPolicy,
+    /// Number of accept loops, e.g. `std::thread::available_parallelism()` for one per core.
+    pub acceptors: usize,
+    /// Give each accept loop its own `SO_REUSEPORT` socket instead of sharing one (Linux only).
+    pub reuse_port: bool,
+    /// Options applied to the listening socket and accepted connections.
+    pub socket: SocketOptions,
+    /// Use listening sockets passed in by systemd (`LISTEN_FDS`) instead of binding, if present.
+    /// Only the first server in the process to bind gets them.
+    pub socket_activation: bool,
+    /// Runtime used by [`Server::run`](super::Server::run).
+    pub runtime: RuntimeConfig,
+    /// Largest request, headers and body together, a connection may buffer before it's answered
+    /// with `413 Payload Too Large`.
+    pub max_request_size: usize,
+    /// Maximum number of request and response bytes buffered across all connections, or `None`
+    /// for no limit. Requests that don't fit are answered with `503 Service Unavailable`.
+    pub memory_limit: Option<usize>,
+    /// Initial capacity of per-connection read buffers.
+    pub read_buffer_size: usize,
+    /// Maximum number of idle read buffers kept for reuse by new connections.
+    pub buffer_pool_size: usize,
+    /// Bans clients that keep sending malformed, oversized or slow requests, or `None` to
+    /// serve them like any other.
+    pub abuse: Option<AbusePolicy>,
+}
+
+/// Configuration for the tokio runtime built by [`Server::run`](super::Server::run).
+///
+/// Each open connection occupies a thread from the blocking pool, so `max_blocking_threads`
+/// also bounds the number of connections served at once. It is also the default for
+/// `max_connections`, including for servers running on a runtime they didn't build.
+#[derive(Debug, Clone)]
+pub struct RuntimeConfig {
+    /// Number of worker threads, or `None` for one per CPU core.
+    pub worker_threads: Option<usize>,
+    /// Name given to the runtime's threads.
+    pub thread_name: String,
+    /// Maximum number of threads in the blocking pool.
+    pub max_blocking_threads: usize,
+    /// Run all async work on the calling thread instead of a worker pool.
+    pub single_threaded: bool,
+}
+
+impl RuntimeConfig {
+    /// Builds a tokio runtime from this configuration.
+    pub fn build(&self) -> io::Result<Runtime> {
+        let mut builder = if self.single_threaded {
+            Builder::new_current_thread()
+        } else {
+            let mut builder = Builder::new_multi_thread();
+            if let Some(worker_threads) = self.worker_threads {
+                builder.worker_threads(worker_threads);
+            }
+            builder
+        };
+
+        builder
+            .thread_name(self.thread_name.clone())
+            .max_blocking_threads(self.max_blocking_threads)
+            .enable_all()
+            .build()
+    }
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        RuntimeConfig {
+            worker_threads: None,
+            thread_name: "http-server-worker".to_string(),
+            max_blocking_threads: 512,
+            single_threaded: false,
+        }
+    }
+}
+
+/// A minimum transfer rate for request headers and bodies, guarding against clients that
+/// trickle bytes to keep a connection open (slowloris).
+#[derive(Debug, Clone)]
+pub struct MinDataRate {
+    /// Bytes per second a client must sustain, averaged over the whole request.
+    pub bytes_per_second: u64,
+    /// How long a request may take before the rate is enforced.
+    pub grace_period: Duration,
+}
+
+impl MinDataRate {
+    /// Returns when a request that started at `started` and has sent `received` bytes so far
+    /// falls behind this rate if nothing more arrives.
+    pub(super) fn deadline(&self, started: Instant, received: usize) -> Instant {
+        // A zero rate never falls behind; the read timeouts still apply
+        let earned = (received as u64)
+            .checked_div(self.bytes_per_second)
+            .map_or(Duration::MAX, Duration::from_secs);
+        started
+            .checked_add(self.grace_period.max(earned))
+            .unwrap_or(started + Duration::from_secs(86_400))
+    }
+}
+
+impl Default for MinDataRate {
+    fn default() -> Self {
+        MinDataRate {
+            bytes_per_second: 240,
+            grace_period: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Limits on the protocol violations a client IP address may commit before it's banned.
+///
+/// Malformed requests, requests over `max_request_size` and requests sent too slowly, past
+/// the header timeout or the minimum data rate, all count as violations. Clients that commit
+/// `threshold` of them within `window` are banned for `ban_duration`.
+#[derive(Debug, Clone)]
+pub struct AbusePolicy {
+    /// Violations within `window` that get a client banned.
+    pub threshold: u32,
+    /// How long violations are remembered.
+    pub window: Duration,
+    /// How long a ban lasts.
+    pub ban_duration: Duration,
+    /// What happens to new connections from banned clients.
+    pub action: AbuseAction,
+    /// Maximum number of clients tracked; the least recently seen are forgotten beyond it.
+    pub max_clients: usize,
+}
+
+impl Default for AbusePolicy {
+    fn default() -> Self {
+        AbusePolicy {
+            threshold: 10,
+            window: Duration::from_secs(60),
+            ban_duration: Duration::from_secs(10 * 60),
+            action: AbuseAction::Drop,
+            max_clients: 10_000,
+        }
+    }
+}
+
+/// What the server does with a new connection from a banned client.
+#[derive(Debug, Clone)]
+pub enum AbuseAction {
+    /// Wait the given duration before serving the connection, without holding a connection
+    /// slot meanwhile, so the client is slowed down at little cost.
+    Tarpit(Duration),
+    /// Close the connection without reading from it.
+    Drop,
+}
+
+/// What the server does with a new connection when the connection limit is reached.
+#[derive(Debug, Clone)]
+pub enum ConnectionLimitPolicy {
+    /// Wait up to the given duration for a slot to free up, then close the connection.
+    Queue(Duration),
+    /// Close the connection immediately.
+    Reject,
+    /// Answer with `503 Service Unavailable` and close the connection.
+    ServiceUnavailable,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            header_read_timeout: Duration::from_secs(30),
+            body_read_timeout: Duration::from_secs(30),
+            write_timeout: Duration::from_secs(30),
+            keep_alive_timeout: Duration::from_secs(5),
+            min_data_rate: Some(MinDataRate::default()),
+            request_timeout: Duration::from_secs(60),
+            drain_timeout: Duration::from_secs(30),
+            max_connections: None,
+            connection_limit_policy: ConnectionLimitPolicy::ServiceUnavailable,
+            acceptors: 1,
+            reuse_port: false,
+            socket: SocketOptions::default(),
+            socket_activation: false,
+            runtime: RuntimeConfig::default(),
+            max_request_size: 1024 * 1024,
+            memory_limit: None,
+            read_buffer_size: 4096,
+            buffer_pool_size: 1024,
+            abuse: None,
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use super::ConnectionTracker;
+
+/// Switches a server into draining mode ahead of maintenance or a deploy.
+///
+/// While draining, keep-alive connections are closed after their current response, idle ones
+/// are closed right away, and new connections are answered with `503 Service Unavailable` and
+/// a `Retry-After` header. The server keeps running until it's stopped.
+///
+/// Clones control the same server. Attach one with
+/// [`Server::with_drain_control`](super::Server::with_drain_control), or get one from
+/// [`ServerHandle::drain_control`](super::ServerHandle::drain_control).
+#[derive(Clone, Default)]
+pub struct DrainControl {
+    pub(super) tracker: ConnectionTracker,
+}
+
+impl DrainControl {
+    /// Creates a control that isn't attached to a server yet.
+    pub fn new() -> Self {
+        DrainControl::default()
+    }
+
+    /// Starts draining. Calling it again has no further effect.
+    pub fn drain(&self) {
+        self.tracker.start_draining();
+    }
+
+    /// Returns whether the server is draining.
+    pub fn is_draining(&self) -> bool {
+        self.tracker.is_draining()
+    }
+
+    /// Waits until draining has started and the last in-flight request has completed.
+    pub async fn drained(&self) {
+        self.tracker.drained().await;
+    }
+}
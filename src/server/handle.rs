@@ -0,0 +1,135 @@
+use std::net::{SocketAddr, TcpListener};
+use std::pin::Pin;
+use std::process::{Child, Command};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use super::{BufferPool, BufferPoolStats, DrainControl, MemoryBudget, ServerMetrics, socket};
+
+/// A handle to a server running on a background task, returned by
+/// [`Server::spawn`](super::Server::spawn).
+///
+/// Awaiting the handle waits for the server to finish and yields its result.
+pub struct ServerHandle {
+    pub(super) local_addr: SocketAddr,
+    pub(super) shutdown: watch::Sender<Option<Duration>>,
+    pub(super) task: JoinHandle<Result<(), String>>,
+    pub(super) buffers: Arc<BufferPool>,
+    pub(super) memory: Arc<MemoryBudget>,
+    pub(super) drain: DrainControl,
+    pub(super) metrics: ServerMetrics,
+    /// Copies of the listening sockets for handing over to a new process, released on stop.
+    pub(super) listeners: Mutex<Vec<TcpListener>>,
+    pub(super) drain_timeout: Duration,
+}
+
+impl ServerHandle {
+    /// Returns the address the server is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns usage statistics for the server's read buffer pool.
+    pub fn buffer_pool_stats(&self) -> BufferPoolStats {
+        self.buffers.stats()
+    }
+
+    /// Returns the number of request and response bytes currently buffered by the server.
+    pub fn memory_in_use(&self) -> usize {
+        self.memory.used()
+    }
+
+    /// Returns a control for switching the server into draining mode.
+    pub fn drain_control(&self) -> DrainControl {
+        self.drain.clone()
+    }
+
+    /// Returns the server's connection and request gauges.
+    pub fn metrics(&self) -> ServerMetrics {
+        self.metrics.clone()
+    }
+
+    /// Stops accepting connections and closes open connections right away.
+    pub fn stop(&self) {
+        self.graceful_stop(Duration::ZERO);
+    }
+
+    /// Stops accepting connections and gives in-flight requests up to `timeout` to finish.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - How long to wait before closing connections that are still open.
+    pub fn graceful_stop(&self, timeout: Duration) {
+        // Don't keep the port open once nothing accepts on it
+        self.listeners.lock().unwrap().clear();
+        self.shutdown.send_if_modified(|requested| {
+            // The first stop request wins
+            if requested.is_none() {
+                *requested = Some(timeout);
+                true
+            } else {
+                false
+            }
+        });
+    }
+
+    /// Starts a new server process on this server's listening sockets, then drains this server.
+    ///
+    /// The new process inherits the sockets and picks them up when it binds, so it keeps
+    /// accepting on the same address and no connection is refused during a binary upgrade.
+    /// Connections that arrive before it starts accepting wait in the listen queue. This
+    /// server stops accepting and gives in-flight requests the configured drain timeout.
+    ///
+    /// Only supported on Linux on x86, ARM and RISC-V.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command that starts the new server, typically the upgraded binary.
+    ///
+    /// # Returns
+    ///
+    /// The new server process.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// # use http_server::{Router, Server};
+    /// # async fn example(server: Server<Router>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let handle = server.spawn()?;
+    /// // e.g. from an admin endpoint or a signal handler
+    /// let child = handle.upgrade(&mut Command::new(std::env::current_exe()?))?;
+    /// handle.await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn upgrade(&self, command: &mut Command) -> Result<Child, String> {
+        let listeners = self.listeners.lock().unwrap();
+        if listeners.is_empty() {
+            return Err("Server is already shutting down".to_string());
+        }
+        let child = socket::spawn_with_listeners(command, &listeners)
+            .map_err(|e| format!("Failed to start new server process: {}", e))?;
+        drop(listeners);
+
+        self.graceful_stop(self.drain_timeout);
+        Ok(child)
+    }
+}
+
+impl Future for ServerHandle {
+    type Output = Result<(), String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.task)
+            .poll(cx)
+            .map(|result| match result {
+                Ok(result) => result,
+                Err(e) => Err(format!("Server task failed: {}", e)),
+            })
+    }
+}
@@ -0,0 +1,94 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::ConnectInfo;
+use crate::http::timing::RequestTiming;
+use crate::http::{Method, Request};
+
+type ConnectHook = dyn Fn(&ConnectInfo) + Send + Sync;
+type RequestHook = dyn Fn(&RequestEvent<'_>) + Send + Sync;
+type ResponseHook = dyn Fn(&ResponseEvent) + Send + Sync;
+type DisconnectHook = dyn Fn(&DisconnectEvent) + Send + Sync;
+
+/// A request that has been read and is about to be handled, as passed to
+/// [`Server::on_request`](super::Server::on_request) hooks.
+pub struct RequestEvent<'a> {
+    pub peer: SocketAddr,
+    pub request: &'a Request,
+}
+
+/// How the server finished with a request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResponseOutcome {
+    /// The response was written to the client.
+    Sent,
+    /// The client disconnected before the response was ready.
+    ClientGone,
+    /// Writing the response failed.
+    WriteFailed(String),
+}
+
+/// A request the server has finished with, as passed to
+/// [`Server::on_response`](super::Server::on_response) hooks.
+#[derive(Debug, Clone)]
+pub struct ResponseEvent {
+    pub peer: SocketAddr,
+    pub method: Method,
+    pub path: String,
+    /// The response's status, or `None` if the client went away first.
+    pub status: Option<u16>,
+    /// The time from the start of reading the request to the end of writing the response.
+    pub duration: Duration,
+    /// The time each phase took.
+    pub timing: RequestTiming,
+    pub outcome: ResponseOutcome,
+}
+
+/// A connection that has closed, as passed to
+/// [`Server::on_disconnect`](super::Server::on_disconnect) hooks.
+#[derive(Debug, Clone)]
+pub struct DisconnectEvent {
+    pub peer: SocketAddr,
+    /// How long the connection was open.
+    pub duration: Duration,
+    /// How many requests were read on the connection.
+    pub requests: u64,
+    /// Why the connection was closed, if it failed.
+    pub error: Option<String>,
+}
+
+/// Callbacks run at points in a connection's lifecycle.
+#[derive(Clone, Default)]
+pub(super) struct ServerHooks {
+    pub(super) connect: Vec<Arc<ConnectHook>>,
+    pub(super) request: Vec<Arc<RequestHook>>,
+    pub(super) response: Vec<Arc<ResponseHook>>,
+    pub(super) disconnect: Vec<Arc<DisconnectHook>>,
+}
+
+impl ServerHooks {
+    pub(super) fn connected(&self, info: &ConnectInfo) {
+        for hook in &self.connect {
+            hook(info);
+        }
+    }
+
+    pub(super) fn requested(&self, event: &RequestEvent<'_>) {
+        for hook in &self.request {
+            hook(event);
+        }
+    }
+
+    pub(super) fn responded(&self, event: &ResponseEvent) {
+        for hook in &self.response {
+            hook(event);
+        }
+    }
+
+    pub(super) fn disconnected(&self, event: &DisconnectEvent) {
+        for hook in &self.disconnect {
+            hook(event);
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use std::fmt::Write;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::http::{Request, Response, StatusCode};
+
+/// Boxed future returned by [`ServerMetrics::handler`].
+type ResponseFuture = Pin<Box<dyn Future<Output = Result<Response, String>> + Send>>;
+
+/// Live gauges and counters for a server's connections and requests.
+///
+/// Watch these to alert on saturation before clients see errors: open connections close to
+/// `max_connections`, a growing number of in-flight requests, or rising rejections.
+///
+/// Clones observe the same server. Attach one with
+/// [`Server::with_metrics`](super::Server::with_metrics), or get one from
+/// [`ServerHandle::metrics`](super::ServerHandle::metrics).
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Router, Server, server::ServerMetrics};
+/// let metrics = ServerMetrics::new();
+/// let router = Router::new().get("/metrics", metrics.handler());
+/// let server = Server::new("127.0.0.1:8080", router).with_metrics(metrics);
+/// ```
+#[derive(Clone, Default)]
+pub struct ServerMetrics {
+    inner: Arc<Gauges>,
+}
+
+#[derive(Default)]
+struct Gauges {
+    open_connections: AtomicUsize,
+    idle_connections: AtomicUsize,
+    in_flight_requests: AtomicUsize,
+    accepted_connections: AtomicU64,
+    rejected_connections: AtomicU64,
+    requests: AtomicU64,
+    started: OnceLock<Instant>,
+}
+
+/// Destination for metric samples pushed by a [`MetricsExporter`], e.g. a StatsD agent.
+///
+/// Implement it to ship the server's gauges and counters to a monitoring system that doesn't
+/// scrape. Tags are name-value pairs for backends that support them.
+pub trait MetricsSink: Send + Sync {
+    /// Records the current value of a gauge.
+    fn gauge(&self, name: &str, value: u64, tags: &[(&str, &str)]);
+
+    /// Records that a counter grew by `delta` since the last sample.
+    fn count(&self, name: &str, delta: u64, tags: &[(&str, &str)]);
+
+    /// Records one measured duration, e.g. of a request.
+    fn timing(&self, name: &str, duration: Duration, tags: &[(&str, &str)]);
+
+    /// Sends buffered samples. Called after each round of samples.
+    fn flush(&self) {}
+}
+
+/// Pushes a server's metrics to a [`MetricsSink`] at a fixed interval from a background
+/// thread, as returned by [`ServerMetrics::export_to`].
+///
+/// Gauges are sent as their current values and counters as their growth since the previous
+/// push. A last round is pushed when the exporter is dropped.
+pub struct MetricsExporter {
+    stop: Option<Sender<()>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Drop for MetricsExporter {
+    /// Pushes a last round of samples and stops the background thread.
+    fn drop(&mut self) {
+        // Closing the channel makes the worker push and exit
+        self.stop.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A snapshot of a server's [`ServerMetrics`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServerStats {
+    /// Connections currently open.
+    pub open_connections: usize,
+    /// Open keep-alive connections waiting for their next request.
+    pub idle_connections: usize,
+    /// Requests currently being handled by the service.
+    pub in_flight_requests: usize,
+    /// Connections accepted since the server started.
+    pub accepted_connections: u64,
+    /// Connections turned away because the connection limit was reached.
+    pub rejected_connections: u64,
+    /// Requests handed to the service since the server started.
+    pub requests: u64,
+    /// How long the server has been listening, or `None` if it hasn't started.
+    pub uptime: Option<Duration>,
+}
+
+impl ServerMetrics {
+    /// Creates metrics that aren't attached to a server yet.
+    pub fn new() -> Self {
+        ServerMetrics::default()
+    }
+
+    /// Returns the current values.
+    pub fn snapshot(&self) -> ServerStats {
+        let gauges = &self.inner;
+        ServerStats {
+            open_connections: gauges.open_connections.load(Ordering::Relaxed),
+            idle_connections: gauges.idle_connections.load(Ordering::Relaxed),
+            in_flight_requests: gauges.in_flight_requests.load(Ordering::Relaxed),
+            accepted_connections: gauges.accepted_connections.load(Ordering::Relaxed),
+            rejected_connections: gauges.rejected_connections.load(Ordering::Relaxed),
+            requests: gauges.requests.load(Ordering::Relaxed),
+            uptime: gauges.started.get().map(Instant::elapsed),
+        }
+    }
+
+    /// Formats the current values in the Prometheus text exposition format.
+    pub fn render_prometheus(&self) -> String {
+        let stats = self.snapshot();
+        let metrics: [(&str, &str, &str, u64); 6] = [
+            (
+                "http_server_open_connections",
+                "gauge",
+                "Connections currently open.",
+                stats.open_connections as u64,
+            ),
+            (
+                "http_server_idle_connections",
+                "gauge",
+                "Keep-alive connections waiting for their next request.",
+                stats.idle_connections as u64,
+            ),
+            (
+                "http_server_in_flight_requests",
+                "gauge",
+                "Requests currently being handled.",
+                stats.in_flight_requests as u64,
+            ),
+            (
+                "http_server_accepted_connections_total",
+                "counter",
+                "Connections accepted.",
+                stats.accepted_connections,
+            ),
+            (
+                "http_server_rejected_connections_total",
+                "counter",
+                "Connections turned away at the connection limit.",
+                stats.rejected_connections,
+            ),
+            (
+                "http_server_requests_total",
+                "counter",
+                "Requests handed to the service.",
+                stats.requests,
+            ),
+        ];
+
+        let mut text = String::new();
+        for (name, kind, help, value) in metrics {
+            let _ = writeln!(text, "# HELP {} {}", name, help);
+            let _ = writeln!(text, "# TYPE {} {}", name, kind);
+            let _ = writeln!(text, "{} {}", name, value);
+        }
+        text
+    }
+
+    /// Returns a handler that serves the metrics for Prometheus to scrape.
+    pub fn handler(&self) -> impl Fn(Request) -> ResponseFuture + Send + Sync + 'static {
+        let metrics = self.clone();
+        move |_request| {
+            let mut response = Response::new(StatusCode::OK);
+            response.set_content_type("text/plain; version=0.0.4; charset=utf-8");
+            response
+                .headers
+                .insert("Cache-Control".to_string(), "no-store".to_string());
+            response.set_body(metrics.render_prometheus().into_bytes());
+            Box::pin(async move { Ok(response) })
+        }
+    }
+
+    /// Starts pushing the metrics to `sink` every `interval`.
+    ///
+    /// # Returns
+    ///
+    /// The exporter, which keeps pushing until it's dropped, or an error if its thread
+    /// couldn't be started.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::{sync::Arc, time::Duration};
+    /// # use http_server::server::{ServerMetrics, statsd::StatsdSink};
+    /// # async fn example(metrics: ServerMetrics) -> Result<(), String> {
+    /// let sink = Arc::new(StatsdSink::new("127.0.0.1:8125", "myapp")?.dogstatsd());
+    /// let _exporter = metrics.export_to(sink.clone(), Duration::from_secs(10))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn export_to<K>(&self, sink: K, interval: Duration) -> Result<MetricsExporter, String>
+    where
+        K: MetricsSink + 'static,
+    {
+        let metrics = self.clone();
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        let worker = thread::Builder::new()
+            .name("metrics-exporter".to_string())
+            .spawn(move || {
+                let mut previous = metrics.snapshot();
+                loop {
+                    let done = matches!(
+                        stopped.recv_timeout(interval),
+                        Err(RecvTimeoutError::Disconnected)
+                    );
+                    let stats = metrics.snapshot();
+                    push(&sink, &stats, &previous);
+                    previous = stats;
+                    if done {
+                        return;
+                    }
+                }
+            })
+            .map_err(|e| format!("Failed to start the exporter thread: {}", e))?;
+
+        Ok(MetricsExporter {
+            stop: Some(stop),
+            worker: Some(worker),
+        })
+    }
+
+    /// Notes that the server started listening, unless it was noted already.
+    pub(super) fn started(&self) {
+        let _ = self.inner.started.set(Instant::now());
+    }
+
+    /// Counts an accepted connection as open until the returned guard is dropped.
+    pub(super) fn connection(&self) -> GaugeGuard<'_> {
+        self.inner
+            .accepted_connections
+            .fetch_add(1, Ordering::Relaxed);
+        GaugeGuard::new(&self.inner.open_connections)
+    }
+
+    /// Counts a connection as idle until the returned guard is dropped.
+    pub(super) fn idle(&self) -> GaugeGuard<'_> {
+        GaugeGuard::new(&self.inner.idle_connections)
+    }
+
+    /// Counts a request as in flight until the returned guard is dropped.
+    pub(super) fn request(&self) -> GaugeGuard<'_> {
+        self.inner.requests.fetch_add(1, Ordering::Relaxed);
+        GaugeGuard::new(&self.inner.in_flight_requests)
+    }
+
+    /// Counts a connection turned away at the connection limit.
+    pub(super) fn rejected(&self) {
+        self.inner
+            .rejected_connections
+            .fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Keeps a gauge incremented while alive, so early returns can't leave it off.
+pub(super) struct GaugeGuard<'a> {
+    gauge: &'a AtomicUsize,
+}
+
+impl<'a> GaugeGuard<'a> {
+    fn new(gauge: &'a AtomicUsize) -> Self {
+        gauge.fetch_add(1, Ordering::Relaxed);
+        GaugeGuard { gauge }
+    }
+}
+
+impl Drop for GaugeGuard<'_> {
+    fn drop(&mut self) {
+        self.gauge.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl<K: MetricsSink + ?Sized> MetricsSink for Arc<K> {
+    fn gauge(&self, name: &str, value: u64, tags: &[(&str, &str)]) {
+        (**self).gauge(name, value, tags);
+    }
+
+    fn count(&self, name: &str, delta: u64, tags: &[(&str, &str)]) {
+        (**self).count(name, delta, tags);
+    }
+
+    fn timing(&self, name: &str, duration: Duration, tags: &[(&str, &str)]) {
+        (**self).timing(name, duration, tags);
+    }
+
+    fn flush(&self) {
+        (**self).flush();
+    }
+}
+
+/// Sends one round of samples: gauges as they are, counters as their growth since `previous`.
+fn push(sink: &dyn MetricsSink, stats: &ServerStats, previous: &ServerStats) {
+    sink.gauge(
+        "http.server.open_connections",
+        stats.open_connections as u64,
+        &[],
+    );
+    sink.gauge(
+        "http.server.idle_connections",
+        stats.idle_connections as u64,
+        &[],
+    );
+    sink.gauge(
+        "http.server.in_flight_requests",
+        stats.in_flight_requests as u64,
+        &[],
+    );
+    sink.count(
+        "http.server.accepted_connections",
+        stats.accepted_connections - previous.accepted_connections,
+        &[],
+    );
+    sink.count(
+        "http.server.rejected_connections",
+        stats.rejected_connections - previous.rejected_connections,
+        &[],
+    );
+    sink.count(
+        "http.server.requests",
+        stats.requests - previous.requests,
+        &[],
+    );
+    sink.flush();
+}
@@ -0,0 +1,1935 @@
+mod abuse;
+mod budget;
+mod buffer;
+mod config;
+mod drain;
+mod handle;
+mod hooks;
+pub mod metrics;
+mod socket;
+pub mod statsd;
+mod upgrade;
+
+use abuse::{AbuseTracker, Violation};
+use budget::{MemoryBudget, Reservation};
+pub use buffer::{BufferPool, BufferPoolStats};
+pub use config::{
+    AbuseAction, AbusePolicy, ConnectionLimitPolicy, MinDataRate, RuntimeConfig, ServerConfig,
+};
+pub use drain::DrainControl;
+pub use handle::ServerHandle;
+use hooks::ServerHooks;
+pub use hooks::{DisconnectEvent, RequestEvent, ResponseEvent, ResponseOutcome};
+pub use metrics::ServerMetrics;
+pub use socket::{SocketOptions, TcpKeepalive};
+pub use upgrade::{OnUpgrade, Upgraded};
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use tokio::runtime::Handle;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, watch};
+use tokio::task::JoinSet;
+
+use crate::http::parser::parse;
+use crate::http::response::{StreamBody, write_chunk};
+use crate::http::timing::{Phase, RequestTiming};
+use crate::http::{Method, Request, Response, StatusCode, Version};
+use crate::logging;
+use crate::middleware::CorsLayer;
+use crate::middleware::access_log::{AccessLogLayer, LogFormat, StdoutWriter};
+use crate::router::Router;
+use crate::service::{Service, ServiceBuilder, service_fn};
+
+/// How long the accept loop sleeps when no connection is pending.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// How often a connection whose request is still being handled is checked for a disconnect.
+const DISCONNECT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How many bytes are read from a connection at a time.
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// An HTTP/1.1 server handing every request to a [`Service`].
+///
+/// The accept loops and services run on the tokio runtime, but socket I/O is blocking: each
+/// open connection, idle keep-alive ones included, occupies a thread of the runtime's
+/// blocking pool until it closes. Without a `max_connections` limit, connections are capped
+/// at `runtime.max_blocking_threads` so that those past the pool's size are turned away by the
+/// connection limit policy rather than queued unanswered.
+pub struct Server<S> {
+    address: String,
+    service: S,
+    config: ServerConfig,
+    listeners: Vec<TcpListener>,
+    buffers: Arc<BufferPool>,
+    memory: Arc<MemoryBudget>,
+    tracker: ConnectionTracker,
+    metrics: ServerMetrics,
+    hooks: ServerHooks,
+}
+
+impl<S> Server<S>
+where
+    S: Service<Response = Response, Error = String> + Send + Clone + 'static,
+    S::Future: Send + 'static,
+{
+    pub fn new(address: &str, service: S) -> Self {
+        let config = ServerConfig::default();
+        Server {
+            address: address.to_string(),
+            service,
+            buffers: Arc::new(BufferPool::new(
+                config.buffer_pool_size,
+                config.read_buffer_size,
+            )),
+            memory: Arc::new(MemoryBudget::new(config.memory_limit)),
+            config,
+            listeners: Vec::new(),
+            tracker: ConnectionTracker::default(),
+            metrics: ServerMetrics::new(),
+            hooks: ServerHooks::default(),
+        }
+    }
+
+    /// Replaces the server's connection handling configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration to use.
+    pub fn with_config(mut self, config: ServerConfig) -> Self {
+        self.buffers = Arc::new(BufferPool::new(
+            config.buffer_pool_size,
+            config.read_buffer_size,
+        ));
+        self.memory = Arc::new(MemoryBudget::new(config.memory_limit));
+        self.config = config;
+        self
+    }
+
+    /// Uses `control` to switch the server into draining mode, e.g. from an admin endpoint
+    /// registered on the router before the server exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `control` - The drain control to attach.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::{Response, Router, Server, StatusCode, server::DrainControl};
+    /// let control = DrainControl::new();
+    /// let admin = control.clone();
+    /// let router = Router::new().post("/admin/drain", move |_| {
+    ///     admin.drain();
+    ///     async { Ok(Response::new(StatusCode::Accepted)) }
+    /// });
+    /// let server = Server::new("127.0.0.1:8080", router).with_drain_control(control);
+    /// ```
+    pub fn with_drain_control(mut self, control: DrainControl) -> Self {
+        self.tracker = control.tracker;
+        self
+    }
+
+    /// Returns a control for switching this server into draining mode.
+    pub fn drain_control(&self) -> DrainControl {
+        DrainControl {
+            tracker: self.tracker.clone(),
+        }
+    }
+
+    /// Records the server's connection and request gauges in `metrics`, e.g. ones served by
+    /// a handler registered on the router before the server exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `metrics` - The metrics to update.
+    pub fn with_metrics(mut self, metrics: ServerMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Returns the metrics this server records its connection and request gauges in.
+    pub fn metrics(&self) -> ServerMetrics {
+        self.metrics.clone()
+    }
+
+    /// Calls `hook` when a connection is accepted, before its first request is read.
+    ///
+    /// Hooks run on the connection's thread, so they should return quickly. Connections turned
+    /// away while draining or over the connection limit don't reach them.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - The callback, receiving the client and server addresses.
+    pub fn on_connect<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&ConnectInfo) + Send + Sync + 'static,
+    {
+        self.hooks.connect.push(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` when a request has been read, before it's passed to the service.
+    ///
+    /// Requests the server rejects while reading, e.g. as malformed or too large, aren't
+    /// reported.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - The callback, receiving the client's address and the request.
+    pub fn on_request<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&RequestEvent<'_>) + Send + Sync + 'static,
+    {
+        self.hooks.request.push(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` when the server is done with a request reported to
+    /// [`on_request`](Self::on_request) hooks: once its response is written, writing it
+    /// failed, or the client went away first.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - The callback, receiving the request line, status, timings and outcome.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::net::IpAddr;
+    /// # use http_server::{Router, Server};
+    /// # struct FailedLogins;
+    /// # impl FailedLogins { fn record(&self, _ip: IpAddr) {} }
+    /// # fn example(router: Router, failed_logins: &'static FailedLogins) {
+    /// let server = Server::new("127.0.0.1:8080", router).on_response(|event| {
+    ///     if event.status == Some(401) {
+    ///         failed_logins.record(event.peer.ip());
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub fn on_response<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&ResponseEvent) + Send + Sync + 'static,
+    {
+        self.hooks.response.push(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` when a connection reported to [`on_connect`](Self::on_connect) hooks
+    /// closes, or is handed over to a handler that switched protocols.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - The callback, receiving how long the connection was open, how many
+    ///   requests it carried and the error that closed it, if any.
+    pub fn on_disconnect<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&DisconnectEvent) + Send + Sync + 'static,
+    {
+        self.hooks.disconnect.push(Arc::new(hook));
+        self
+    }
+
+    /// Sets how long in-flight requests may run after a graceful shutdown starts.
+    ///
+    /// Connections still open when the timeout elapses are closed.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The drain timeout.
+    pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+        self.config.drain_timeout = timeout;
+        self
+    }
+
+    /// Sets the slowest rate a client may send a request at before it's closed with
+    /// `408 Request Timeout`.
+    ///
+    /// # Arguments
+    ///
+    /// * `rate` - The minimum data rate, or `None` to rely on the read timeouts alone.
+    pub fn min_data_rate(mut self, rate: Option<MinDataRate>) -> Self {
+        self.config.min_data_rate = rate;
+        self
+    }
+
+    /// Sets how many protocol violations a client may commit before it's banned, and what
+    /// happens to its connections while it is.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The limits and ban, or `None` to never ban clients.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use http_server::{Router, Server, server::{AbuseAction, AbusePolicy}};
+    /// # fn example(router: Router) {
+    /// let server = Server::new("0.0.0.0:8080", router).abuse_policy(Some(AbusePolicy {
+    ///     action: AbuseAction::Tarpit(Duration::from_secs(10)),
+    ///     ..AbusePolicy::default()
+    /// }));
+    /// # }
+    /// ```
+    pub fn abuse_policy(mut self, policy: Option<AbusePolicy>) -> Self {
+        self.config.abuse = policy;
+        self
+    }
+
+    /// Sets `TCP_NODELAY` on accepted connections.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to disable Nagle's algorithm.
+    pub fn tcp_nodelay(mut self, enabled: bool) -> Self {
+        self.config.socket.nodelay = enabled;
+        self
+    }
+
+    /// Enables TCP keepalive probes on accepted connections.
+    ///
+    /// # Arguments
+    ///
+    /// * `keepalive` - The probe timing.
+    pub fn tcp_keepalive(mut self, keepalive: TcpKeepalive) -> Self {
+        self.config.socket.keepalive = Some(keepalive);
+        self
+    }
+
+    /// Sets the kernel send buffer size of accepted connections.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The buffer size in bytes.
+    pub fn send_buffer_size(mut self, size: usize) -> Self {
+        self.config.socket.send_buffer_size = Some(size);
+        self
+    }
+
+    /// Sets the kernel receive buffer size of accepted connections.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The buffer size in bytes.
+    pub fn recv_buffer_size(mut self, size: usize) -> Self {
+        self.config.socket.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// Sets whether IPv6 listeners accept only IPv6 connections.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - `false` lets a `[::]` listener accept IPv4 connections as well.
+    pub fn ipv6_only(mut self, enabled: bool) -> Self {
+        self.config.socket.ipv6_only = Some(enabled);
+        self
+    }
+
+    /// Sets the maximum length of the queue of pending connections.
+    ///
+    /// # Arguments
+    ///
+    /// * `backlog` - The listen backlog.
+    pub fn backlog(mut self, backlog: u32) -> Self {
+        self.config.socket.backlog = Some(backlog);
+        self
+    }
+
+    /// Binds the listening sockets without accepting connections yet.
+    ///
+    /// Binding ahead of time lets callers find out the actual address, e.g. the port assigned
+    /// when binding to port 0. Servers that aren't bound explicitly bind when they start
+    /// listening.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::{Router, Server};
+    /// # async fn example(router: Router) -> Result<(), String> {
+    /// let server = Server::new("127.0.0.1:0", router).bind()?;
+    /// println!("Listening on port {}", server.local_addr()?.port());
+    /// server.listen().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn bind(mut self) -> Result<Self, String> {
+        if self.listeners.is_empty() {
+            self.listeners = self.bind_listeners()?;
+        }
+        Ok(self)
+    }
+
+    /// Returns the address the server is bound to.
+    ///
+    /// # Returns
+    ///
+    /// The local address, or an error if the server hasn't been bound yet.
+    pub fn local_addr(&self) -> Result<SocketAddr, String> {
+        let listener = self
+            .listeners
+            .first()
+            .ok_or_else(|| "Server is not bound".to_string())?;
+        listener
+            .local_addr()
+            .map_err(|e| format!("Failed to get local address: {}", e))
+    }
+
+    /// Accepts connections on the current tokio runtime until the process exits.
+    ///
+    /// The returned future can be awaited directly, spawned, or raced against other futures
+    /// with `tokio::select!`; dropping it stops accepting connections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::{Router, Server};
+    /// # async fn other_work() {}
+    /// # async fn example(server: Server<Router>) -> Result<(), String> {
+    /// tokio::select! {
+    ///     result = server.serve() => result?,
+    ///     _ = other_work() => {}
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn serve(self) -> Result<(), String> {
+        self.with_graceful_shutdown(std::future::pending()).await
+    }
+
+    /// Sets the runtime configuration used by [`Server::run`].
+    ///
+    /// # Arguments
+    ///
+    /// * `runtime` - The runtime configuration.
+    pub fn runtime(mut self, runtime: RuntimeConfig) -> Self {
+        self.config.runtime = runtime;
+        self
+    }
+
+    /// Builds a runtime from the configured [`RuntimeConfig`] and serves on it, blocking the
+    /// calling thread.
+    ///
+    /// Use this from a plain `fn main` instead of `#[tokio::main]` to control the number of
+    /// worker threads, thread names and blocking pool size. Must not be called from within
+    /// another tokio runtime.
+    pub fn run(self) -> Result<(), String> {
+        let runtime = self
+            .config
+            .runtime
+            .build()
+            .map_err(|e| format!("Failed to build runtime: {}", e))?;
+        runtime.block_on(self.serve())
+    }
+
+    /// Accepts connections until the process exits. Same as [`Server::serve`].
+    pub async fn listen(self) -> Result<(), String> {
+        self.serve().await
+    }
+
+    /// Accepts connections until `signal` completes, then shuts down gracefully.
+    ///
+    /// Once the signal fires the listener is closed, in-flight requests are given up to the
+    /// drain timeout to finish, and any connections still open after that are closed.
+    ///
+    /// # Arguments
+    ///
+    /// * `signal` - A future that completes when the server should stop, e.g. a SIGINT/SIGTERM
+    ///   listener such as `tokio::signal::ctrl_c()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::{Router, Server};
+    /// # async fn example(server: Server<Router>) -> Result<(), String> {
+    /// let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    /// server.with_graceful_shutdown(async { rx.await.ok(); }).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn with_graceful_shutdown<F>(self, signal: F) -> Result<(), String>
+    where
+        F: Future<Output = ()>,
+    {
+        let drain_timeout = self.config.drain_timeout;
+        self.serve_until(async move {
+            signal.await;
+            drain_timeout
+        })
+        .await
+    }
+
+    /// Starts the server on a background task.
+    ///
+    /// The server is bound before this returns, so bind errors are reported here. Must be
+    /// called from within a tokio runtime.
+    ///
+    /// # Returns
+    ///
+    /// A [`ServerHandle`] used to stop the server and wait for it to finish.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use http_server::{Router, Server};
+    /// # async fn example(router: Router) -> Result<(), String> {
+    /// let handle = Server::new("127.0.0.1:0", router).spawn()?;
+    /// // ...
+    /// handle.graceful_stop(Duration::from_secs(5));
+    /// handle.await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn(self) -> Result<ServerHandle, String> {
+        let server = self.bind()?;
+        let local_addr = server.local_addr()?;
+        let buffers = server.buffers.clone();
+        let memory = server.memory.clone();
+        let drain = server.drain_control();
+        let metrics = server.metrics();
+        let drain_timeout = server.config.drain_timeout;
+        let listeners = server
+            .listeners
+            .iter()
+            .map(TcpListener::try_clone)
+            .collect::<io::Result<Vec<_>>>()
+            .map_err(|e| format!("Failed to clone listener: {}", e))?;
+
+        let (shutdown, mut requested) = watch::channel(None);
+        let task = tokio::spawn(server.serve_until(async move {
+            loop {
+                if let Some(drain_timeout) = *requested.borrow_and_update() {
+                    return drain_timeout;
+                }
+                // A dropped handle leaves the server running until the process exits
+                if requested.changed().await.is_err() {
+                    std::future::pending::<()>().await;
+                }
+            }
+        }));
+
+        Ok(ServerHandle {
+            local_addr,
+            shutdown,
+            task,
+            buffers,
+            memory,
+            drain,
+            metrics,
+            listeners: Mutex::new(listeners),
+            drain_timeout,
+        })
+    }
+
+    /// Serves connections until `signal` completes, then drains for the duration it returns.
+    async fn serve_until<F>(mut self, signal: F) -> Result<(), String>
+    where
+        F: Future<Output = Duration>,
+    {
+        let listeners = match std::mem::take(&mut self.listeners) {
+            listeners if listeners.is_empty() => self.bind_listeners()?,
+            listeners => listeners,
+        };
+
+        let mut addresses = listeners
+            .iter()
+            .filter_map(|listener| listener.local_addr().ok())
+            .map(|addr| addr.to_string())
+            .collect::<Vec<_>>();
+        addresses.dedup();
+        logging::info(
+            "server",
+            "Listening",
+            &[("addresses", &addresses.join(","))],
+        );
+
+        // A connection past the blocking pool's size would wait for a thread without a word
+        let max_connections = self
+            .config
+            .max_connections
+            .unwrap_or(self.config.runtime.max_blocking_threads)
+            .min(Semaphore::MAX_PERMITS);
+        let shared = Arc::new(Shared {
+            handle: Handle::current(),
+            config: self.config.clone(),
+            tracker: self.tracker.clone(),
+            slots: Arc::new(Semaphore::new(max_connections)),
+            buffers: self.buffers.clone(),
+            memory: self.memory.clone(),
+            metrics: self.metrics.clone(),
+            hooks: self.hooks.clone(),
+            abuse: AbuseTracker::new(self.config.abuse.clone()),
+        });
+        shared.metrics.started();
+
+        // Run one accept loop per listener, each on its own task
+        let (stop, stopped) = watch::channel(false);
+        // Acceptors are aborted if this future is dropped before shutdown
+        let mut acceptors = JoinSet::new();
+        for listener in listeners {
+            acceptors.spawn(Self::accept_loop(
+                listener,
+                self.service.clone(),
+                shared.clone(),
+                stopped.clone(),
+            ));
+        }
+
+        let drain_timeout = signal.await;
+
+        // Stop accepting new connections and let in-flight requests finish
+        let _ = stop.send(true);
+        let mut connections = Vec::new();
+        while let Some(result) = acceptors.join_next().await {
+            if let Ok(set) = result {
+                connections.push(set);
+            }
+        }
+        shared.tracker.start_draining();
+
+        let open = connections.iter().map(JoinSet::len).sum::<usize>();
+        logging::info(
+            "server",
+            "Shutting down, draining",
+            &[("connections", &open)],
+        );
+
+        let drain = async {
+            for set in &mut connections {
+                while set.join_next().await.is_some() {}
+            }
+        };
+        if tokio::time::timeout(drain_timeout, drain).await.is_err() {
+            let open = connections.iter().map(JoinSet::len).sum::<usize>();
+            logging::warn(
+                "server",
+                "Drain timeout elapsed, closing",
+                &[("connections", &open)],
+            );
+            shared.tracker.close_all();
+        }
+
+        Ok(())
+    }
+
+    /// Binds one listener per acceptor for every address the server address resolves to.
+    ///
+    /// With `reuse_port` every acceptor gets its own `SO_REUSEPORT` socket and the kernel
+    /// spreads connections between them; otherwise the acceptors share a single socket.
+    /// With `socket_activation`, sockets passed in by systemd are used instead, one acceptor each.
+    /// Sockets handed over by [`ServerHandle::upgrade`] are used for the addresses they're bound
+    /// to, and each goes to only one server.
+    fn bind_listeners(&self) -> Result<Vec<TcpListener>, String> {
+        let acceptors = self.config.acceptors.max(1);
+
+        let inherited = if self.config.socket_activation {
+            socket::inherited_listeners()
+                .map_err(|e| format!("Failed to take over systemd sockets: {}", e))?
+        } else {
+            None
+        };
+        let listeners = match inherited {
+            Some(listeners) => listeners,
+            None => self.bind_addresses(acceptors)?,
+        };
+
+        for listener in &listeners {
+            socket::configure_listener(listener, &self.config.socket)
+                .and_then(|()| listener.set_nonblocking(true))
+                .map_err(|e| format!("Failed to configure listener: {}", e))?;
+        }
+        Ok(listeners)
+    }
+
+    /// Binds every address the server address resolves to, so e.g. `localhost` is served over
+    /// both IPv4 and IPv6.
+    ///
+    /// Addresses that fail to bind are skipped with a warning as long as at least one succeeds.
+    /// When binding to port 0, all addresses share the port assigned to the first.
+    fn bind_addresses(&self, acceptors: usize) -> Result<Vec<TcpListener>, String> {
+        let bind_error = |e: io::Error| format!("Failed to bind to {}: {}", self.address, e);
+
+        let mut listeners = Vec::new();
+        let mut last_error = None;
+        let mut assigned_port = None;
+        for mut addr in socket::resolve(&self.address).map_err(bind_error)? {
+            if let (0, Some(port)) = (addr.port(), assigned_port) {
+                addr.set_port(port);
+            }
+
+            // Sockets handed over by a previous server process for this address take precedence
+            let handed_over = socket::handed_over_listeners(addr)
+                .map_err(|e| format!("Failed to take over listening sockets: {}", e))?;
+            let bound = if handed_over.is_empty() {
+                self.bind_address(addr, acceptors)
+            } else {
+                Ok(handed_over)
+            };
+            match bound {
+                Ok(bound) => {
+                    if let Some(listener) = bound.first() {
+                        assigned_port = listener.local_addr().ok().map(|addr| addr.port());
+                    }
+                    listeners.extend(bound);
+                }
+                Err(e) => {
+                    logging::error(
+                        "server",
+                        "Failed to bind",
+                        &[("address", &addr), ("error", &e)],
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        if listeners.is_empty() {
+            return Err(match last_error {
+                Some(e) => bind_error(e),
+                None => format!("No address found for {}", self.address),
+            });
+        }
+        Ok(listeners)
+    }
+
+    /// Binds `acceptors` listeners to a single address.
+    fn bind_address(&self, addr: SocketAddr, acceptors: usize) -> io::Result<Vec<TcpListener>> {
+        if self.config.reuse_port {
+            return (0..acceptors)
+                .map(|_| socket::bind_reuse_port(addr, &self.config.socket))
+                .collect();
+        }
+
+        let listener = socket::bind(addr, &self.config.socket)?;
+        let mut listeners = (1..acceptors)
+            .map(|_| listener.try_clone())
+            .collect::<io::Result<Vec<_>>>()?;
+        listeners.push(listener);
+        Ok(listeners)
+    }
+
+    /// Accepts connections from `listener` until `stopped` is signalled.
+    ///
+    /// # Returns
+    ///
+    /// The connections accepted by this loop that are still being handled.
+    async fn accept_loop(
+        listener: TcpListener,
+        service: S,
+        shared: Arc<Shared>,
+        mut stopped: watch::Receiver<bool>,
+    ) -> JoinSet<()> {
+        let mut connections = JoinSet::new();
+
+        // Accept connections and process them
+        loop {
+            let accepted = tokio::select! {
+                _ = stopped.changed() => break,
+                accepted = accept(&listener) => accepted,
+            };
+
+            // Reap connections that have already finished
+            while connections.try_join_next().is_some() {}
+
+            match accepted {
+                Ok((stream, peer)) => {
+                    // Clone the service for each connection
+                    let mut service = service.clone();
+                    let shared = shared.clone();
+
+                    connections.spawn(async move {
+                        if shared.tracker.is_draining() {
+                            logging::info("server", "Draining, turning away", &[("peer", &peer)]);
+                            turn_away(&stream, unavailable_response());
+                            return;
+                        }
+
+                        let banned = shared.abuse.banned(peer.ip());
+                        match banned.map(|policy| policy.action.clone()) {
+                            Some(AbuseAction::Drop) => {
+                                shared.metrics.rejected();
+                                return;
+                            }
+                            Some(AbuseAction::Tarpit(delay)) => tokio::time::sleep(delay).await,
+                            None => {}
+                        }
+
+                        let Some(_slot) = shared.acquire_slot(&stream).await else {
+                            shared.metrics.rejected();
+                            logging::warn(
+                                "server",
+                                "Connection limit reached, turning away",
+                                &[("peer", &peer)],
+                            );
+                            return;
+                        };
+
+                        // Socket I/O is blocking, so each connection runs on the runtime's
+                        // blocking pool while the service futures are driven by the runtime
+                        let _ = tokio::task::spawn_blocking(move || {
+                            let _open = shared.metrics.connection();
+                            // Unregistered on drop, so a panicking handler can't stall draining
+                            let tracked = shared.tracker.register(&stream);
+                            let connected = Instant::now();
+                            if let Ok(local) = stream.local_addr() {
+                                shared.hooks.connected(&ConnectInfo { peer, local });
+                            }
+                            let mut requests = 0;
+                            let result = Self::handle_client(
+                                stream,
+                                &mut service,
+                                &shared,
+                                tracked.id,
+                                &mut requests,
+                            );
+                            if let Err(e) = &result {
+                                logging::error(
+                                    "server",
+                                    "Error handling client",
+                                    &[("peer", &peer), ("error", e)],
+                                );
+                            }
+                            drop(tracked);
+                            shared.hooks.disconnected(&DisconnectEvent {
+                                peer,
+                                duration: connected.elapsed(),
+                                requests,
+                                error: result.err(),
+                            });
+                        })
+                        .await;
+                    });
+                }
+                Err(e) => {
+                    logging::error("server", "Connection failed", &[("error", &e)]);
+                }
+            }
+        }
+
+        connections
+    }
+
+    fn handle_client(
+        mut stream: TcpStream,
+        service: &mut S,
+        shared: &Shared,
+        id: u64,
+        requests: &mut u64,
+    ) -> Result<(), String> {
+        let config = &shared.config;
+
+        // Accepted sockets may inherit the listener's non-blocking mode on some platforms
+        stream
+            .set_nonblocking(false)
+            .and_then(|()| socket::configure_stream(&stream, &config.socket))
+            .map_err(|e| format!("Failed to configure stream: {}", e))?;
+        stream
+            .set_write_timeout(Some(config.write_timeout))
+            .map_err(|e| format!("Failed to set write timeout: {}", e))?;
+        let connect_info = stream
+            .peer_addr()
+            .and_then(|peer| {
+                Ok(ConnectInfo {
+                    peer,
+                    local: stream.local_addr()?,
+                })
+            })
+            .ok();
+
+        // Bytes received but not yet consumed, e.g. the start of a pipelined request
+        let mut pending = shared.buffers.get();
+        // Bytes this connection has buffered, charged against the server's memory budget
+        let mut reservation = Reservation::new(&shared.memory);
+        let mut idle_timeout = config.header_read_timeout;
+        let violated = |violation| {
+            if let Some(info) = connect_info {
+                shared.abuse.record(info.peer.ip(), violation);
+            }
+        };
+
+        loop {
+            // Wait for the first byte of the next request
+            if pending.is_empty() {
+                if !reservation.resize(READ_CHUNK_SIZE) {
+                    return write_response(&mut stream, unavailable_response());
+                }
+                shared.tracker.set_idle(id, true);
+                let idle = shared.metrics.idle();
+                let first = read_chunk(&mut stream, &mut pending, Instant::now() + idle_timeout);
+                drop(idle);
+                shared.tracker.set_idle(id, false);
+
+                match first {
+                    Ok(0) | Err(ReadError::TimedOut) | Err(ReadError::Closed) => return Ok(()),
+                    Ok(_) => {}
+                    Err(e) => return Err(e.to_string()),
+                }
+            }
+            idle_timeout = config.keep_alive_timeout;
+
+            // Parse the request
+            let parse_start = Instant::now();
+            let read = read_request(&mut stream, &mut pending, &mut reservation, config);
+            let (mut request, deadline) = match read {
+                Ok(read) => read,
+                Err(ReadError::Closed) => return Ok(()),
+                Err(ReadError::Invalid(e)) => {
+                    logging::warn("server", "Failed to parse request", &[("error", &e)]);
+                    violated(Violation::Malformed);
+                    return write_response(&mut stream, error_response(StatusCode::BadRequest));
+                }
+                Err(ReadError::Unsupported(e)) => {
+                    logging::warn("server", "Refused an unsupported request", &[("error", &e)]);
+                    return write_response(&mut stream, error_response(StatusCode::NotImplemented));
+                }
+                Err(ReadError::TimedOut) => {
+                    violated(Violation::Slow);
+                    return write_response(&mut stream, error_response(StatusCode::RequestTimeout));
+                }
+                Err(ReadError::TooLarge) => {
+                    violated(Violation::TooLarge);
+                    return write_response(
+                        &mut stream,
+                        error_response(StatusCode::PayloadTooLarge),
+                    );
+                }
+                Err(ReadError::Overloaded) => {
+                    return write_response(&mut stream, unavailable_response());
+                }
+                Err(e) => return Err(e.to_string()),
+            };
+
+            let timing = RequestTiming::new();
+            timing.record(Phase::Parse, parse_start.elapsed());
+            request.extensions.insert(timing.clone());
+            if let Some(connect_info) = connect_info {
+                request.extensions.insert(connect_info);
+            }
+
+            *requests += 1;
+            if let Some(info) = connect_info {
+                shared.hooks.requested(&RequestEvent {
+                    peer: info.peer,
+                    request: &request,
+                });
+            }
+            // Only copied when someone is listening, since the request moves into the service
+            let summary = connect_info
+                .filter(|_| !shared.hooks.response.is_empty())
+                .map(|info| (info.peer, request.method.clone(), request.path.clone()));
+            let report = |status: Option<StatusCode>, outcome: ResponseOutcome| {
+                if let Some((peer, method, path)) = &summary {
+                    shared.hooks.responded(&ResponseEvent {
+                        peer: *peer,
+                        method: method.clone(),
+                        path: path.clone(),
+                        status: status.map(|status| status as u16),
+                        duration: parse_start.elapsed(),
+                        timing: timing.clone(),
+                        outcome,
+                    });
+                }
+            };
+            let keep_alive = keep_alive(&request);
+            // HTTP/1.0 clients don't understand chunked framing
+            let chunked = request.version != Version::HTTP1_0;
+            let upgrade = wants_upgrade(&request).then(|| {
+                let (sender, on_upgrade) = OnUpgrade::new();
+                request.extensions.insert(on_upgrade);
+                sender
+            });
+            let in_flight = shared.metrics.request();
+            let response = Self::respond(service, request, &stream, &shared.handle, deadline);
+            drop(in_flight);
+            let Some(mut response) = response else {
+                // The client went away, so there's nobody to respond to
+                report(None, ResponseOutcome::ClientGone);
+                return Ok(());
+            };
+            // Checked after responding so a request that starts draining closes its connection
+            let keep_alive = keep_alive && !shared.tracker.is_draining();
+
+            // Without chunked framing, closing the connection is what ends a stream body
+            let close_delimited = response.stream.is_some() && !chunked;
+            if close_delimited {
+                response.headers.remove("Transfer-Encoding");
+            }
+            let keep_alive = keep_alive && !close_delimited;
+
+            // The request body is gone; charge the response until it's written instead
+            if !reservation.resize(pending.len() + response.body.len()) {
+                return write_response(&mut stream, unavailable_response());
+            }
+
+            // Hand the connection over to the handler that switched protocols
+            if let (Some(upgrade), StatusCode::SwitchingProtocols) = (upgrade, response.status_code)
+            {
+                let written = response
+                    .write_to(&mut stream)
+                    .and_then(|()| stream.set_read_timeout(None))
+                    .and_then(|()| stream.set_write_timeout(None))
+                    .map_err(|e| format!("Failed to send response: {}", e));
+                report(Some(response.status_code), outcome(&written));
+                written?;
+                let buffered = pending.to_vec();
+                let _ = upgrade.send(Upgraded { stream, buffered });
+                return Ok(());
+            }
+
+            // Send the response back to the client
+            let connection = if keep_alive { "keep-alive" } else { "close" };
+            response
+                .headers
+                .insert("Connection".to_string(), connection.to_string());
+            let write_start = Instant::now();
+            let written = response
+                .write_to(&mut stream)
+                .map_err(|e| format!("Failed to send response: {}", e))
+                .and_then(|()| write_stream(&mut stream, &response, chunked, &shared.handle));
+            if written.is_ok() {
+                timing.record(Phase::Write, write_start.elapsed());
+            }
+            report(Some(response.status_code), outcome(&written));
+            written?;
+            if !keep_alive {
+                return Ok(());
+            }
+            reservation.resize(pending.len());
+        }
+    }
+
+    /// Runs a request through the service, giving up once `deadline` passes.
+    ///
+    /// # Returns
+    ///
+    /// The response, or `None` if the client disconnected first. The service future is dropped
+    /// in that case, so handlers waiting on something (e.g. a long poll) are cleaned up.
+    fn respond(
+        service: &mut S,
+        request: Request,
+        stream: &TcpStream,
+        handle: &Handle,
+        deadline: Instant,
+    ) -> Option<Response> {
+        let deadline = tokio::time::Instant::from_std(deadline);
+
+        let served = async {
+            // Make sure service is ready
+            let ready = std::future::poll_fn(|cx| service.poll_ready(cx));
+            match tokio::time::timeout_at(deadline, ready).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    logging::error("server", "Service not ready", &[("error", &e)]);
+                    return Err(error_response(StatusCode::ServiceUnavailable));
+                }
+                Err(_) => {
+                    logging::error("server", "Timed out waiting for service", &[]);
+                    return Err(error_response(StatusCode::ServiceUnavailable));
+                }
+            }
+
+            // Process the request through the service
+            match tokio::time::timeout_at(deadline, service.call(request)).await {
+                Ok(Ok(response)) => Ok(response),
+                Ok(Err(e)) => {
+                    logging::error("server", "Error processing request", &[("error", &e)]);
+                    Err(error_response(StatusCode::InternalServerError))
+                }
+                Err(_) => {
+                    logging::warn("server", "Request timed out", &[]);
+                    Err(error_response(StatusCode::ServiceUnavailable))
+                }
+            }
+        };
+        // Diagnostics logged while handling the request carry its ID, once one is assigned
+        let response = logging::request_scope(async {
+            served.await.unwrap_or_else(|mut response| {
+                if let Some(id) = logging::request_id() {
+                    response.headers.insert("X-Request-Id".to_string(), id);
+                }
+                response
+            })
+        });
+
+        handle.block_on(async {
+            tokio::select! {
+                response = response => Some(response),
+                () = disconnected(stream) => None,
+            }
+        })
+    }
+}
+
+impl<S> IntoFuture for Server<S>
+where
+    S: Service<Response = Response, Error = String> + Send + Clone + 'static,
+    S::Future: Send + 'static,
+{
+    type Output = Result<(), String>;
+    type IntoFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+
+    /// Serves connections when the server itself is awaited.
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.serve())
+    }
+}
+
+/// The addresses of the connection a request arrived on, found in the request's extensions.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Request, server::ConnectInfo};
+/// # fn example(request: &Request) {
+/// let peer = request.extensions.get::<ConnectInfo>().map(|info| info.peer);
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectInfo {
+    /// The client's address.
+    pub peer: SocketAddr,
+    /// The server address the client connected to.
+    pub local: SocketAddr,
+}
+
+/// State shared between the accept loop and connection handlers.
+struct Shared {
+    handle: Handle,
+    config: ServerConfig,
+    tracker: ConnectionTracker,
+    slots: Arc<Semaphore>,
+    buffers: Arc<BufferPool>,
+    memory: Arc<MemoryBudget>,
+    metrics: ServerMetrics,
+    hooks: ServerHooks,
+    abuse: AbuseTracker,
+}
+
+impl Shared {
+    /// Takes a connection slot, applying the connection limit policy if none is free.
+    ///
+    /// # Returns
+    ///
+    /// The slot, released when dropped, or `None` if the connection was turned away.
+    async fn acquire_slot(&self, stream: &TcpStream) -> Option<OwnedSemaphorePermit> {
+        if let Ok(slot) = self.slots.clone().try_acquire_owned() {
+            return Some(slot);
+        }
+
+        match self.config.connection_limit_policy {
+            ConnectionLimitPolicy::Queue(wait) => {
+                tokio::time::timeout(wait, self.slots.clone().acquire_owned())
+                    .await
+                    .ok()?
+                    .ok()
+            }
+            ConnectionLimitPolicy::Reject => None,
+            ConnectionLimitPolicy::ServiceUnavailable => {
+                turn_away(stream, error_response(StatusCode::ServiceUnavailable));
+                None
+            }
+        }
+    }
+}
+
+/// Reasons reading a request from a connection can fail.
+enum ReadError {
+    /// The client closed the connection.
+    Closed,
+    /// The client didn't send the request within the configured timeouts.
+    TimedOut,
+    /// The request exceeds the configured `max_request_size`.
+    TooLarge,
+    /// Buffering the request would exceed the server's memory budget.
+    Overloaded,
+    /// The request could not be parsed.
+    Invalid(String),
+    /// The request uses a feature the server doesn't implement, e.g. chunked request bodies.
+    Unsupported(String),
+    /// The socket failed.
+    Io(String),
+}
+
+impl std::fmt::Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadError::Closed => write!(f, "Connection closed"),
+            ReadError::TimedOut => write!(f, "Timed out reading request"),
+            ReadError::TooLarge => write!(f, "Request too large"),
+            ReadError::Overloaded => write!(f, "Memory budget exceeded"),
+            ReadError::Invalid(e) => write!(f, "Invalid request: {}", e),
+            ReadError::Unsupported(e) => write!(f, "Unsupported request: {}", e),
+            ReadError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Reads the next request from the connection.
+///
+/// `pending` holds bytes already received from the client and must contain at least the first
+/// byte of the request. Bytes past the end of the request are left in `pending`.
+///
+/// # Returns
+///
+/// The request and the deadline by which its response must be ready.
+fn read_request(
+    stream: &mut TcpStream,
+    pending: &mut Vec<u8>,
+    reservation: &mut Reservation,
+    config: &ServerConfig,
+) -> Result<(Request, Instant), ReadError> {
+    let started = Instant::now();
+    let request_deadline = started + config.request_timeout;
+
+    // Bytes of this request read so far, for the minimum data rate check
+    let mut received = 0;
+    let rate_deadline = |deadline: Instant, received: usize| match &config.min_data_rate {
+        Some(rate) => deadline.min(rate.deadline(started, received)),
+        None => deadline,
+    };
+
+    // Read until the end of the headers
+    let header_deadline = (started + config.header_read_timeout).min(request_deadline);
+    let head_len = loop {
+        if let Some(end) = pending.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end + 4;
+        }
+        if pending.len() > config.max_request_size {
+            return Err(ReadError::TooLarge);
+        }
+        if !reservation.resize(pending.len() + READ_CHUNK_SIZE) {
+            return Err(ReadError::Overloaded);
+        }
+        match read_chunk(stream, pending, rate_deadline(header_deadline, received))? {
+            0 => return Err(ReadError::Closed),
+            n => received += n,
+        }
+    };
+
+    let mut request = parse(&pending[..head_len]).map_err(ReadError::Invalid)?;
+
+    // Bodies are only framed by Content-Length. Reading a chunked body as anything else would
+    // let the rest of it be taken for the next request on the connection.
+    if request.header("Transfer-Encoding").is_some() {
+        return Err(match request.header("Content-Length") {
+            Some(_) => ReadError::Invalid("Transfer-Encoding with Content-Length".to_string()),
+            None => ReadError::Unsupported("Transfer-Encoding".to_string()),
+        });
+    }
+
+    // Read the body, if any
+    let content_length = match request.header("Content-Length") {
+        Some(value) if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+            value.parse::<usize>().map_err(|_| ReadError::TooLarge)?
+        }
+        Some(_) => return Err(ReadError::Invalid("Invalid Content-Length".to_string())),
+        None => 0,
+    };
+    if head_len + content_length > config.max_request_size {
+        return Err(ReadError::TooLarge);
+    }
+    // Room for the whole request plus the copy of its body handed to the service
+    if !reservation.resize(pending.len().max(head_len + content_length) + content_length) {
+        return Err(ReadError::Overloaded);
+    }
+
+    let body_deadline = (Instant::now() + config.body_read_timeout).min(request_deadline);
+    let request_len = head_len + content_length;
+    while pending.len() < request_len {
+        match read_chunk(stream, pending, rate_deadline(body_deadline, received))? {
+            0 => return Err(ReadError::Closed),
+            n => received += n,
+        }
+    }
+
+    request.body = pending[head_len..request_len].to_vec();
+    pending.drain(..request_len);
+
+    Ok((request, request_deadline))
+}
+
+/// Reads the next chunk of data into `buffer`, giving up once `deadline` passes.
+///
+/// # Returns
+///
+/// The number of bytes read, or zero if the client closed the connection.
+fn read_chunk(
+    stream: &mut TcpStream,
+    buffer: &mut Vec<u8>,
+    deadline: Instant,
+) -> Result<usize, ReadError> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err(ReadError::TimedOut);
+    }
+    stream
+        .set_read_timeout(Some(remaining))
+        .map_err(|e| ReadError::Io(format!("Failed to set read timeout: {}", e)))?;
+
+    // Read straight into the buffer's spare room rather than a temporary chunk
+    let filled = buffer.len();
+    buffer.resize(filled + READ_CHUNK_SIZE, 0);
+    let result = stream.read(&mut buffer[filled..]);
+    buffer.truncate(filled + *result.as_ref().unwrap_or(&0));
+
+    match result {
+        Ok(bytes_read) => Ok(bytes_read),
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            Err(ReadError::TimedOut)
+        }
+        Err(e) => Err(ReadError::Io(format!("Error reading from stream: {}", e))),
+    }
+}
+
+/// Decides whether the connection should stay open after responding to `request`.
+fn keep_alive(request: &Request) -> bool {
+    if connection_has(request, "close") {
+        false
+    } else if connection_has(request, "keep-alive") {
+        true
+    } else {
+        request.version == Version::HTTP1_1
+    }
+}
+
+/// Decides whether `request` asks to switch protocols on this connection.
+fn wants_upgrade(request: &Request) -> bool {
+    connection_has(request, "upgrade")
+}
+
+/// Checks whether the request's `Connection` header contains `option`, ignoring case, without
+/// copying the header for each request.
+fn connection_has(request: &Request, option: &str) -> bool {
+    request.header("Connection").is_some_and(|value| {
+        value
+            .as_bytes()
+            .windows(option.len())
+            .any(|window| window.eq_ignore_ascii_case(option.as_bytes()))
+    })
+}
+
+/// Sends a final response on a connection that is about to be closed.
+fn write_response(stream: &mut TcpStream, mut response: Response) -> Result<(), String> {
+    response
+        .headers
+        .insert("Connection".to_string(), "close".to_string());
+    response
+        .write_to(stream)
+        .map_err(|e| format!("Failed to send response: {}", e))
+}
+
+/// Sends `response` to a connection that won't be served, from async code.
+fn turn_away(stream: &TcpStream, mut response: Response) {
+    // Best effort: with a non-blocking socket a full send buffer just drops the response
+    // instead of stalling the runtime
+    let _ = stream.set_nonblocking(true);
+    response
+        .headers
+        .insert("Connection".to_string(), "close".to_string());
+    let _ = response.write_to(&mut &*stream);
+}
+
+/// Describes how writing a response went, for response hooks.
+fn outcome(written: &Result<(), String>) -> ResponseOutcome {
+    match written {
+        Ok(()) => ResponseOutcome::Sent,
+        Err(e) => ResponseOutcome::WriteFailed(e.clone()),
+    }
+}
+
+/// Sends the stream body of `response`, if any, one chunk at a time as the stream yields them.
+///
+/// The stream is driven on the runtime while the calling thread blocks, and dropped as soon as
+/// the client goes away.
+///
+/// # Arguments
+///
+/// * `chunked` - Whether to frame the chunks with chunked transfer encoding; otherwise they're
+///   written as is and the caller ends the body by closing the connection.
+fn write_stream(
+    stream: &mut TcpStream,
+    response: &Response,
+    chunked: bool,
+    handle: &Handle,
+) -> Result<(), String> {
+    let Some(mut chunks) = response.stream.as_ref().and_then(StreamBody::take) else {
+        return Ok(());
+    };
+
+    loop {
+        let chunk = handle.block_on(chunks.next());
+        // An empty chunk would end the body early
+        if chunk.as_ref().is_some_and(|chunk| chunk.is_empty()) {
+            continue;
+        }
+        let written = match &chunk {
+            Some(chunk) if !chunked => stream.write_all(chunk),
+            None if !chunked => stream.flush(),
+            _ => write_chunk(stream, chunk.as_deref().unwrap_or_default()),
+        };
+        written.map_err(|e| format!("Failed to send response: {}", e))?;
+        if chunk.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+/// Builds the `503 Service Unavailable` response sent when the server can't take on more work,
+/// asking the client to retry shortly.
+fn unavailable_response() -> Response {
+    let mut response = error_response(StatusCode::ServiceUnavailable);
+    response
+        .headers
+        .insert("Retry-After".to_string(), "1".to_string());
+    response
+}
+
+/// Builds a plain-text response whose body is the status code's reason phrase.
+pub(crate) fn error_response(status_code: StatusCode) -> Response {
+    let mut response = Response::new(status_code);
+    response.set_content_type("text/plain");
+    response.set_body(status_code.reason_phrase().as_bytes().to_vec());
+    response
+}
+
+/// Keeps handles to open connections so they can be closed during shutdown.
+#[derive(Clone)]
+struct ConnectionTracker {
+    inner: Arc<Mutex<TrackedConnections>>,
+    /// Number of open connections, watched to find out when draining is complete.
+    open: Arc<watch::Sender<usize>>,
+}
+
+impl Default for ConnectionTracker {
+    fn default() -> Self {
+        ConnectionTracker {
+            inner: Arc::default(),
+            open: Arc::new(watch::Sender::new(0)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct TrackedConnections {
+    next_id: u64,
+    draining: bool,
+    streams: HashMap<u64, TrackedStream>,
+}
+
+/// A connection registered with a [`ConnectionTracker`], forgotten when dropped.
+struct Tracked {
+    tracker: ConnectionTracker,
+    id: u64,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.tracker.remove(self.id);
+    }
+}
+
+struct TrackedStream {
+    stream: TcpStream,
+    idle: bool,
+}
+
+impl ConnectionTracker {
+    /// Records a connection until the returned registration is dropped.
+    fn register(&self, stream: &TcpStream) -> Tracked {
+        self.open.send_modify(|open| *open += 1);
+        let mut connections = self.inner.lock().unwrap();
+        let id = connections.next_id;
+        connections.next_id += 1;
+
+        // A connection we can't clone simply can't be force-closed
+        if let Ok(stream) = stream.try_clone() {
+            connections.streams.insert(
+                id,
+                TrackedStream {
+                    stream,
+                    idle: false,
+                },
+            );
+        }
+        Tracked {
+            tracker: self.clone(),
+            id,
+        }
+    }
+
+    /// Forgets a connection once it has been handled.
+    fn remove(&self, id: u64) {
+        self.inner.lock().unwrap().streams.remove(&id);
+        self.open.send_modify(|open| *open -= 1);
+    }
+
+    /// Marks whether a connection is waiting for its next request.
+    ///
+    /// Idle connections are closed as soon as draining starts.
+    fn set_idle(&self, id: u64, idle: bool) {
+        let mut connections = self.inner.lock().unwrap();
+        if idle && connections.draining {
+            if let Some(tracked) = connections.streams.get(&id) {
+                let _ = tracked.stream.shutdown(Shutdown::Both);
+            }
+        } else if let Some(tracked) = connections.streams.get_mut(&id) {
+            tracked.idle = idle;
+        }
+    }
+
+    /// Stops keep-alive and closes connections that are waiting for a new request.
+    fn start_draining(&self) {
+        {
+            let mut connections = self.inner.lock().unwrap();
+            connections.draining = true;
+            for tracked in connections.streams.values().filter(|tracked| tracked.idle) {
+                let _ = tracked.stream.shutdown(Shutdown::Both);
+            }
+        }
+        // Wake anyone waiting for draining to finish, in case nothing is open
+        self.open.send_modify(|_| {});
+    }
+
+    /// Waits until draining has started and every connection has closed.
+    async fn drained(&self) {
+        let mut open = self.open.subscribe();
+        // The sender lives as long as `self`, so this can't fail
+        let _ = open.wait_for(|&open| open == 0 && self.is_draining()).await;
+    }
+
+    /// Returns whether the server is draining, either on request or while shutting down.
+    fn is_draining(&self) -> bool {
+        self.inner.lock().unwrap().draining
+    }
+
+    /// Shuts down every tracked connection, unblocking any pending reads or writes.
+    fn close_all(&self) {
+        for (_, tracked) in self.inner.lock().unwrap().streams.drain() {
+            let _ = tracked.stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// Resolves once the peer closes `stream`, checking every `DISCONNECT_POLL_INTERVAL`.
+///
+/// Only meant for while a request is being handled: a client that half-closes its end after
+/// sending a request is treated as gone.
+async fn disconnected(stream: &TcpStream) {
+    let mut probe = [0; 1];
+    loop {
+        tokio::time::sleep(DISCONNECT_POLL_INTERVAL).await;
+
+        // Peek without blocking; pipelined bytes mean the client is still there
+        let closed = match stream.set_nonblocking(true) {
+            Ok(()) => {
+                let peeked = stream.peek(&mut probe);
+                let _ = stream.set_nonblocking(false);
+                match peeked {
+                    Ok(0) => true,
+                    Ok(_) => false,
+                    Err(e) => e.kind() != io::ErrorKind::WouldBlock,
+                }
+            }
+            Err(_) => false,
+        };
+        if closed {
+            return;
+        }
+    }
+}
+
+/// Waits for the next connection on a non-blocking listener without blocking the runtime.
+async fn accept(listener: &TcpListener) -> io::Result<(TcpStream, SocketAddr)> {
+    loop {
+        match listener.accept() {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                tokio::time::sleep(ACCEPT_POLL_INTERVAL).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+// Helper to create a server with a router and middleware
+pub fn new_server(
+    address: &str,
+    router: Router,
+) -> Server<impl Service<Response = Response, Error = String> + Send + Clone + 'static> {
+    // Create a service with middleware
+    let service = ServiceBuilder::new(router)
+        .layer(AccessLogLayer::new(LogFormat::Combined, StdoutWriter))
+        .layer(CorsLayer)
+        .service();
+
+    Server::new(address, service)
+}
+
+/// Creates a plain-HTTP server that redirects every request to the same URL over HTTPS.
+///
+/// Run it next to the TLS server, typically on port 80. Host, path and query are preserved;
+/// `GET` and `HEAD` requests get `301 Moved Permanently` and other methods
+/// `308 Permanent Redirect`, so clients repeat them with the same method and body.
+///
+/// # Arguments
+///
+/// * `address` - The address to listen on for plain HTTP.
+/// * `https_port` - The port HTTPS is served on; omitted from the URL when it's 443.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::server::https_redirect_server;
+/// # async fn example() -> Result<(), String> {
+/// let redirect = https_redirect_server("0.0.0.0:80", 443).spawn()?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn https_redirect_server(
+    address: &str,
+    https_port: u16,
+) -> Server<impl Service<Response = Response, Error = String> + Send + Clone + 'static> {
+    let service =
+        service_fn(move |request: Request| async move { Ok(https_redirect(&request, https_port)) });
+
+    Server::new(address, service)
+}
+
+/// Builds the redirect to the HTTPS equivalent of `request`.
+pub(crate) fn https_redirect(request: &Request, https_port: u16) -> Response {
+    let Some(host) = request.header("Host") else {
+        return error_response(StatusCode::BadRequest);
+    };
+
+    // Drop any port from the host, keeping the brackets of an IPv6 literal
+    let host = match host.find(']') {
+        Some(end) => &host[..=end],
+        None => host.split(':').next().unwrap_or(host),
+    };
+    let authority = match https_port {
+        443 => host.to_string(),
+        port => format!("{}:{}", host, port),
+    };
+    let query = request
+        .raw_query
+        .as_ref()
+        .map(|query| format!("?{}", query))
+        .unwrap_or_default();
+
+    let status_code = match request.method {
+        Method::Get | Method::Head => StatusCode::MovedPermanently,
+        _ => StatusCode::PermanentRedirect,
+    };
+    let mut response = Response::new(status_code);
+    response.headers.insert(
+        "Location".to_string(),
+        format!("https://{}{}{}", authority, request.path, query),
+    );
+    response.set_body(Vec::new());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::*;
+    use crate::http::sse::{Event, Sse};
+    use crate::testing::TestServer;
+
+    /// Sends raw bytes on a new connection and returns everything the server sends back.
+    async fn exchange(addr: SocketAddr, raw: impl Into<Vec<u8>>) -> String {
+        let raw = raw.into();
+        tokio::task::spawn_blocking(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(10)))
+                .unwrap();
+            stream.write_all(&raw).unwrap();
+            let mut response = Vec::new();
+            let _ = stream.read_to_end(&mut response);
+            String::from_utf8_lossy(&response).into_owned()
+        })
+        .await
+        .unwrap()
+    }
+
+    fn router() -> Router {
+        async fn ok(_request: Request) -> Result<Response, String> {
+            Ok(error_response(StatusCode::OK))
+        }
+        async fn admin(_request: Request) -> Result<Response, String> {
+            let mut response = Response::new(StatusCode::OK);
+            response.set_body(b"admin".to_vec());
+            Ok(response)
+        }
+        async fn panics(_request: Request) -> Result<Response, String> {
+            panic!("handler bug");
+        }
+        async fn events(_request: Request) -> Result<Response, String> {
+            let events = ["1", "2"].map(|data| Event::new().data(data));
+            Ok(Sse::new(futures::stream::iter(events)).into_response())
+        }
+        Router::new()
+            .get("/", ok)
+            .post("/", ok)
+            .get("/admin", admin)
+            .get("/panic", panics)
+            .get("/events", events)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn chunks_streams_for_http_1_1_clients() {
+        let server = TestServer::spawn(router()).unwrap();
+        let response = exchange(
+            server.addr(),
+            "GET /events HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(response.ends_with("9\r\ndata: 2\n\n\r\n0\r\n\r\n"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn streams_to_http_1_0_clients_without_chunking() {
+        let server = TestServer::spawn(router()).unwrap();
+        let response = exchange(
+            server.addr(),
+            "GET /events HTTP/1.0\r\nConnection: keep-alive\r\n\r\n",
+        )
+        .await;
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains("Connection: close"));
+        assert!(!head.contains("Transfer-Encoding"));
+        // The body ends when the server closes the connection
+        assert_eq!(body, "data: 1\n\ndata: 2\n\n");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rejects_transfer_encoding_with_content_length() {
+        let server = TestServer::spawn(router()).unwrap();
+        let response = exchange(
+            server.addr(),
+            b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\nContent-Length: 4\r\n\r\n\
+              0\r\n\r\nGET /admin HTTP/1.1\r\nHost: a\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+        assert_eq!(response.matches("HTTP/1.1 ").count(), 1, "{}", response);
+        assert!(!response.contains("admin"), "{}", response);
+        server.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn refuses_chunked_request_bodies() {
+        let server = TestServer::spawn(router()).unwrap();
+        let response = exchange(
+            server.addr(),
+            b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n\
+              0\r\n\r\nGET /admin HTTP/1.1\r\nHost: a\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 501"), "{}", response);
+        assert_eq!(response.matches("HTTP/1.1 ").count(), 1, "{}", response);
+        server.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rejects_conflicting_content_length() {
+        let server = TestServer::spawn(router()).unwrap();
+        let duplicate = exchange(
+            server.addr(),
+            b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 0\r\nContent-Length: 30\r\n\r\n",
+        )
+        .await;
+        assert!(duplicate.starts_with("HTTP/1.1 400"), "{}", duplicate);
+        let signed = exchange(
+            server.addr(),
+            b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: +0\r\n\r\n",
+        )
+        .await;
+        assert!(signed.starts_with("HTTP/1.1 400"), "{}", signed);
+        server.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn serves_pipelined_requests_framed_by_content_length() {
+        let server = TestServer::spawn(router()).unwrap();
+        let response = exchange(
+            server.addr(),
+            b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 4\r\n\r\nbody\
+              GET /admin HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert_eq!(response.matches("HTTP/1.1 200").count(), 2, "{}", response);
+        assert!(response.ends_with("admin"), "{}", response);
+        server.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn answers_unknown_methods_with_bad_request() {
+        let drain = DrainControl::new();
+        let server = Server::new("127.0.0.1:0", router())
+            .with_drain_control(drain.clone())
+            .spawn()
+            .unwrap();
+        let response = exchange(server.local_addr(), b"FOO / HTTP/1.1\r\nHost: a\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+
+        drain.drain();
+        let drained = tokio::time::timeout(Duration::from_secs(3), drain.drained()).await;
+        assert!(drained.is_ok(), "connection stayed registered");
+        server.stop();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn drains_after_a_handler_panics() {
+        let drain = DrainControl::new();
+        let server = Server::new("127.0.0.1:0", router())
+            .with_drain_control(drain.clone())
+            .spawn()
+            .unwrap();
+        let response = exchange(
+            server.local_addr(),
+            b"GET /panic HTTP/1.1\r\nHost: a\r\n\r\n",
+        )
+        .await;
+        assert!(response.is_empty(), "{}", response);
+
+        drain.drain();
+        let drained = tokio::time::timeout(Duration::from_secs(3), drain.drained()).await;
+        assert!(drained.is_ok(), "connection stayed registered");
+        server.stop();
+    }
+
+    fn limited_server() -> ServerHandle {
+        let config = ServerConfig {
+            max_request_size: 1024,
+            header_read_timeout: Duration::from_millis(200),
+            ..ServerConfig::default()
+        };
+        Server::new("127.0.0.1:0", router())
+            .with_config(config)
+            .spawn()
+            .unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn refuses_oversized_headers() {
+        let server = limited_server();
+        let mut raw = b"GET / HTTP/1.1\r\nHost: a\r\nX-Padding: ".to_vec();
+        raw.resize(2048, b'a');
+        raw.extend_from_slice(b"\r\n\r\n");
+        let response = exchange(server.local_addr(), raw).await;
+        assert!(response.starts_with("HTTP/1.1 413"), "{}", response);
+        server.stop();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn refuses_oversized_bodies_before_reading_them() {
+        let server = limited_server();
+        let response = exchange(
+            server.local_addr(),
+            b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 4096\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 413"), "{}", response);
+        let overflowing = exchange(
+            server.local_addr(),
+            b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 99999999999999999999999\r\n\r\n",
+        )
+        .await;
+        assert!(overflowing.starts_with("HTTP/1.1 413"), "{}", overflowing);
+        server.stop();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn times_out_incomplete_headers() {
+        let server = limited_server();
+        let response = exchange(server.local_addr(), b"GET / HTTP/1.1\r\nHost: a\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 408"), "{}", response);
+        server.stop();
+    }
+
+    #[test]
+    fn https_redirect_preserves_host_path_and_query() {
+        let request = Request::builder()
+            .path("/a/b?x=1&y=2")
+            .header("Host", "example.com:80")
+            .build();
+        let response = https_redirect(&request, 443);
+        assert_eq!(response.status_code, StatusCode::MovedPermanently);
+        assert_eq!(
+            response.headers["Location"],
+            "https://example.com/a/b?x=1&y=2"
+        );
+
+        let request = Request::builder()
+            .method(Method::Post)
+            .path("/form")
+            .header("Host", "[::1]:8080")
+            .build();
+        let response = https_redirect(&request, 8443);
+        assert_eq!(response.status_code, StatusCode::PermanentRedirect);
+        assert_eq!(response.headers["Location"], "https://[::1]:8443/form");
+
+        let response = https_redirect(&Request::builder().path("/").build(), 443);
+        assert_eq!(response.status_code, StatusCode::BadRequest);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn https_redirect_server_answers_every_request() {
+        let server = https_redirect_server("127.0.0.1:0", 443).spawn().unwrap();
+        let response = exchange(
+            server.local_addr(),
+            b"GET /docs?page=2 HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 301"), "{}", response);
+        assert!(
+            response.contains("Location: https://example.com/docs?page=2\r\n"),
+            "{}",
+            response
+        );
+        server.stop();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn drops_connections_from_banned_clients() {
+        let config = ServerConfig {
+            abuse: Some(AbusePolicy {
+                threshold: 2,
+                ..AbusePolicy::default()
+            }),
+            ..ServerConfig::default()
+        };
+        let server = Server::new("127.0.0.1:0", router())
+            .with_config(config)
+            .spawn()
+            .unwrap();
+        for _ in 0..2 {
+            let response = exchange(server.local_addr(), b"NOT HTTP\r\n\r\n").await;
+            assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+        }
+        let response = exchange(
+            server.local_addr(),
+            b"GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert_eq!(response, "");
+        server.stop();
+    }
+}
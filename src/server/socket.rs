@@ -0,0 +1,785 @@
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::process::{Child, Command};
+use std::time::Duration;
+
+/// Backlog used for listeners created outside of `std::net`.
+const LISTEN_BACKLOG: u32 = 1024;
+
+/// First file descriptor passed by systemd socket activation.
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+const SD_LISTEN_FDS_START: i32 = 3;
+
+/// Environment variable through which a server process hands its listening sockets to the next.
+///
+/// Holds the comma-separated descriptor numbers of the inherited listeners.
+const HANDOVER_FDS_VAR: &str = "HTTP_SERVER_LISTEN_FDS";
+
+/// Socket options applied to the listening socket and to accepted connections.
+#[derive(Debug, Clone, Default)]
+pub struct SocketOptions {
+    /// Disable Nagle's algorithm (`TCP_NODELAY`) on accepted connections.
+    pub nodelay: bool,
+    /// Enable TCP keepalive probes (`SO_KEEPALIVE`) on accepted connections.
+    pub keepalive: Option<TcpKeepalive>,
+    /// Kernel send buffer size (`SO_SNDBUF`) for accepted connections.
+    pub send_buffer_size: Option<usize>,
+    /// Kernel receive buffer size (`SO_RCVBUF`) for accepted connections.
+    pub recv_buffer_size: Option<usize>,
+    /// Maximum length of the queue of pending connections.
+    pub backlog: Option<u32>,
+    /// Whether IPv6 listeners accept only IPv6 connections (`IPV6_V6ONLY`), or `None` for the
+    /// system default. `Some(false)` lets a `[::]` listener serve IPv4 clients too.
+    pub ipv6_only: Option<bool>,
+}
+
+/// TCP keepalive probe timing.
+#[derive(Debug, Clone)]
+pub struct TcpKeepalive {
+    /// How long a connection must be idle before the first probe is sent.
+    pub idle: Duration,
+    /// Time between unanswered probes.
+    pub interval: Duration,
+    /// Number of unanswered probes before the connection is dropped.
+    pub retries: u32,
+}
+
+impl SocketOptions {
+    /// Returns whether any option needs the platform bindings in this module.
+    fn needs_sys(&self) -> bool {
+        self.keepalive.is_some()
+            || self.send_buffer_size.is_some()
+            || self.recv_buffer_size.is_some()
+            || self.backlog.is_some()
+    }
+}
+
+/// Resolves `address` to every address it names, e.g. both `127.0.0.1` and `::1` for
+/// `localhost`, without duplicates.
+pub(super) fn resolve(address: &str) -> io::Result<Vec<SocketAddr>> {
+    let mut addrs = Vec::new();
+    for addr in address.to_socket_addrs()? {
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    Ok(addrs)
+}
+
+/// Binds a listener to `addr`, applying `ipv6_only` to IPv6 addresses.
+///
+/// # Arguments
+///
+/// * `addr` - The address to bind.
+/// * `options` - Options to apply; only `ipv6_only` and the backlog are used for the listener.
+pub(super) fn bind(addr: SocketAddr, options: &SocketOptions) -> io::Result<TcpListener> {
+    match (addr, options.ipv6_only) {
+        (SocketAddr::V6(_), Some(only_v6)) => bind_v6_only(addr, only_v6, options),
+        _ => TcpListener::bind(addr),
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+fn bind_v6_only(
+    addr: SocketAddr,
+    only_v6: bool,
+    options: &SocketOptions,
+) -> io::Result<TcpListener> {
+    let socket = sys::Socket::new(&addr)?;
+    sys::set_option(&socket, sys::SOL_SOCKET, sys::SO_REUSEADDR, 1)?;
+    sys::set_option(&socket, sys::IPPROTO_IPV6, sys::IPV6_V6ONLY, only_v6 as i32)?;
+    socket.bind(&addr)?;
+    socket.listen(options.backlog.unwrap_or(LISTEN_BACKLOG))
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+)))]
+fn bind_v6_only(
+    _addr: SocketAddr,
+    _only_v6: bool,
+    _options: &SocketOptions,
+) -> io::Result<TcpListener> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Setting IPV6_V6ONLY is only supported on Linux on x86, ARM and RISC-V",
+    ))
+}
+
+/// Binds a listener with `SO_REUSEPORT` set, so several listeners can share one address.
+///
+/// # Arguments
+///
+/// * `addr` - The address to bind.
+/// * `options` - Options to apply; only `ipv6_only` and the backlog are used for the listener.
+///
+/// # Returns
+///
+/// The listening socket, or an `Unsupported` error on platforms other than Linux on x86, ARM
+/// and RISC-V.
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+pub(super) fn bind_reuse_port(
+    addr: SocketAddr,
+    options: &SocketOptions,
+) -> io::Result<TcpListener> {
+    let socket = sys::Socket::new(&addr)?;
+    sys::set_option(&socket, sys::SOL_SOCKET, sys::SO_REUSEADDR, 1)?;
+    sys::set_option(&socket, sys::SOL_SOCKET, sys::SO_REUSEPORT, 1)?;
+    if let (SocketAddr::V6(_), Some(only_v6)) = (addr, options.ipv6_only) {
+        sys::set_option(&socket, sys::IPPROTO_IPV6, sys::IPV6_V6ONLY, only_v6 as i32)?;
+    }
+    socket.bind(&addr)?;
+    socket.listen(options.backlog.unwrap_or(LISTEN_BACKLOG))
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+)))]
+pub(super) fn bind_reuse_port(
+    _addr: SocketAddr,
+    _options: &SocketOptions,
+) -> io::Result<TcpListener> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "SO_REUSEPORT listeners are only supported on Linux on x86, ARM and RISC-V",
+    ))
+}
+
+/// Takes over listening sockets passed in by systemd socket activation.
+///
+/// systemd sets `LISTEN_PID` to the activated process and `LISTEN_FDS` to the number of
+/// sockets, which start at file descriptor 3. Only the first call in a process gets the
+/// sockets. Unlike `sd_listen_fds(1)`, this leaves the variables set, since changing the
+/// environment while other threads may read it is unsound; child processes inherit them but
+/// are told apart by `LISTEN_PID`.
+///
+/// # Returns
+///
+/// The inherited listeners, or `None` if the process wasn't socket-activated or another server
+/// already took them.
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+pub(super) fn inherited_listeners() -> io::Result<Option<Vec<TcpListener>>> {
+    use std::os::fd::{FromRawFd, OwnedFd};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // The descriptors can only have one owner, or they'd be closed twice
+    static TAKEN: AtomicBool = AtomicBool::new(false);
+    if TAKEN.swap(true, Ordering::SeqCst) {
+        return Ok(None);
+    }
+
+    let for_us = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        .is_some_and(|pid| pid == std::process::id());
+    let count = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|fds| fds.parse::<i32>().ok())
+        .unwrap_or(0);
+    if !for_us || count <= 0 {
+        return Ok(None);
+    }
+
+    (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + count)
+        .map(|fd| {
+            // SAFETY: `LISTEN_PID` shows systemd handed these descriptors to this process, and
+            // `TAKEN` lets only one caller take ownership of them
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+            sys::set_cloexec(&fd)?;
+
+            // Make sure the descriptor really is a socket
+            let listener = TcpListener::from(fd);
+            listener.local_addr()?;
+            Ok(listener)
+        })
+        .collect::<io::Result<Vec<_>>>()
+        .map(Some)
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+)))]
+pub(super) fn inherited_listeners() -> io::Result<Option<Vec<TcpListener>>> {
+    Ok(None)
+}
+
+/// Takes over the listening sockets for `addr` handed down by a previous server process with
+/// [`spawn_with_listeners`].
+///
+/// The handed-over descriptors are read from the environment once per process, and each
+/// socket goes to the first server asking for its address. The variable is left set, since
+/// changing the environment while other threads may read it is unsound. Processes started
+/// from this one inherit it but not the sockets, which are close-on-exec here, so clear it
+/// with `Command::env_remove` when starting another server process other than through an
+/// upgrade.
+///
+/// # Returns
+///
+/// The inherited listeners for `addr`, or an empty list if none were handed over.
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+pub(super) fn handed_over_listeners(addr: SocketAddr) -> io::Result<Vec<TcpListener>> {
+    use std::sync::Mutex;
+
+    // Sockets not claimed by a server yet, or `None` before the environment has been read
+    static UNCLAIMED: Mutex<Option<Vec<TcpListener>>> = Mutex::new(None);
+
+    let mut unclaimed = UNCLAIMED.lock().unwrap();
+    let unclaimed = match &mut *unclaimed {
+        Some(listeners) => listeners,
+        None => unclaimed.insert(take_handed_over()?),
+    };
+    Ok(claim(unclaimed, addr))
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+)))]
+pub(super) fn handed_over_listeners(_addr: SocketAddr) -> io::Result<Vec<TcpListener>> {
+    Ok(Vec::new())
+}
+
+/// Removes the listeners bound to `addr` from `unclaimed` and returns them.
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+fn claim(unclaimed: &mut Vec<TcpListener>, addr: SocketAddr) -> Vec<TcpListener> {
+    let (claimed, rest) = std::mem::take(unclaimed)
+        .into_iter()
+        .partition(|listener| listener.local_addr().is_ok_and(|local| local == addr));
+    *unclaimed = rest;
+    claimed
+}
+
+/// Reads the descriptors handed down by the previous process and takes ownership of them.
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+fn take_handed_over() -> io::Result<Vec<TcpListener>> {
+    use std::os::fd::{FromRawFd, OwnedFd};
+
+    let Ok(fds) = std::env::var(HANDOVER_FDS_VAR) else {
+        return Ok(Vec::new());
+    };
+
+    fds.split(',')
+        .map(|fd| {
+            let fd = fd.trim().parse::<i32>().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Invalid descriptor in {}: {}", HANDOVER_FDS_VAR, fd),
+                )
+            })?;
+            // SAFETY: the previous process passed these descriptors to this one, and `UNCLAIMED`
+            // makes sure the variable naming them is read only once
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+            sys::set_cloexec(&fd)?;
+
+            // Make sure the descriptor really is a socket
+            let listener = TcpListener::from(fd);
+            listener.local_addr()?;
+            Ok(listener)
+        })
+        .collect()
+}
+
+/// Spawns `command` with `listeners` left open in the child and their descriptors listed in
+/// its environment, so a new server process can keep accepting on the same sockets.
+///
+/// The descriptors are briefly inheritable, so processes spawned concurrently by other threads
+/// may receive copies as well.
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+pub(super) fn spawn_with_listeners(
+    command: &mut Command,
+    listeners: &[TcpListener],
+) -> io::Result<Child> {
+    use std::os::fd::AsRawFd;
+
+    // Duplicates are closed in this process once the child has started
+    let inheritable = listeners
+        .iter()
+        .map(TcpListener::try_clone)
+        .collect::<io::Result<Vec<_>>>()?;
+    for listener in &inheritable {
+        sys::clear_cloexec(listener)?;
+    }
+
+    let fds = inheritable
+        .iter()
+        .map(|listener| listener.as_raw_fd().to_string())
+        .collect::<Vec<_>>();
+    command.env(HANDOVER_FDS_VAR, fds.join(",")).spawn()
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+)))]
+pub(super) fn spawn_with_listeners(
+    _command: &mut Command,
+    _listeners: &[TcpListener],
+) -> io::Result<Child> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Handing listeners to a new process is only supported on Linux on x86, ARM and RISC-V",
+    ))
+}
+
+/// Applies listener-level options to a socket bound by `std::net`.
+///
+/// Linux lets `listen` be called again on a listening socket, which is how the backlog is
+/// changed after the fact.
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+pub(super) fn configure_listener(
+    listener: &TcpListener,
+    options: &SocketOptions,
+) -> io::Result<()> {
+    if let Some(backlog) = options.backlog {
+        sys::listen(listener, backlog)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+)))]
+pub(super) fn configure_listener(
+    _listener: &TcpListener,
+    options: &SocketOptions,
+) -> io::Result<()> {
+    if options.needs_sys() {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Keepalive, buffer size and backlog options are only supported on Linux on x86, ARM \
+             and RISC-V",
+        ));
+    }
+    Ok(())
+}
+
+/// Applies connection-level options to an accepted stream.
+pub(super) fn configure_stream(stream: &TcpStream, options: &SocketOptions) -> io::Result<()> {
+    if options.nodelay {
+        stream.set_nodelay(true)?;
+    }
+    if options.needs_sys() {
+        configure_stream_sys(stream, options)?;
+    }
+    Ok(())
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+fn configure_stream_sys(stream: &TcpStream, options: &SocketOptions) -> io::Result<()> {
+    if let Some(keepalive) = &options.keepalive {
+        sys::set_option(stream, sys::SOL_SOCKET, sys::SO_KEEPALIVE, 1)?;
+        sys::set_option(
+            stream,
+            sys::IPPROTO_TCP,
+            sys::TCP_KEEPIDLE,
+            secs(keepalive.idle),
+        )?;
+        sys::set_option(
+            stream,
+            sys::IPPROTO_TCP,
+            sys::TCP_KEEPINTVL,
+            secs(keepalive.interval),
+        )?;
+        sys::set_option(
+            stream,
+            sys::IPPROTO_TCP,
+            sys::TCP_KEEPCNT,
+            keepalive.retries as i32,
+        )?;
+    }
+    if let Some(size) = options.send_buffer_size {
+        sys::set_option(stream, sys::SOL_SOCKET, sys::SO_SNDBUF, size as i32)?;
+    }
+    if let Some(size) = options.recv_buffer_size {
+        sys::set_option(stream, sys::SOL_SOCKET, sys::SO_RCVBUF, size as i32)?;
+    }
+    Ok(())
+}
+
+// Unsupported options are rejected when the listener is configured
+#[cfg(not(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+)))]
+fn configure_stream_sys(_stream: &TcpStream, _options: &SocketOptions) -> io::Result<()> {
+    Ok(())
+}
+
+/// Converts a duration to whole seconds for socket options, with a minimum of one.
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+fn secs(duration: Duration) -> i32 {
+    duration.as_secs().clamp(1, i32::MAX as u64) as i32
+}
+
+/// Minimal bindings to the socket calls `std::net` doesn't expose.
+///
+/// The constants are those of x86, ARM and RISC-V; other Linux architectures such as MIPS and
+/// SPARC number some of them differently, so they get the unsupported fallbacks instead.
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+mod sys {
+    use std::ffi::{c_int, c_void};
+    use std::io;
+    use std::net::{SocketAddr, TcpListener};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    const AF_INET: c_int = 2;
+    const AF_INET6: c_int = 10;
+    const SOCK_STREAM: c_int = 1;
+    const SOCK_CLOEXEC: c_int = 0o2000000;
+
+    pub const SOL_SOCKET: c_int = 1;
+    pub const SO_REUSEADDR: c_int = 2;
+    pub const SO_SNDBUF: c_int = 7;
+    pub const SO_RCVBUF: c_int = 8;
+    pub const SO_KEEPALIVE: c_int = 9;
+    pub const SO_REUSEPORT: c_int = 15;
+
+    const F_SETFD: c_int = 2;
+    const FD_CLOEXEC: c_int = 1;
+
+    pub const IPPROTO_IPV6: c_int = 41;
+    pub const IPV6_V6ONLY: c_int = 26;
+
+    pub const IPPROTO_TCP: c_int = 6;
+    pub const TCP_KEEPIDLE: c_int = 4;
+    pub const TCP_KEEPINTVL: c_int = 5;
+    pub const TCP_KEEPCNT: c_int = 6;
+
+    unsafe extern "C" {
+        fn socket(domain: c_int, ty: c_int, protocol: c_int) -> c_int;
+        fn setsockopt(
+            fd: c_int,
+            level: c_int,
+            name: c_int,
+            value: *const c_void,
+            len: u32,
+        ) -> c_int;
+        fn bind(fd: c_int, addr: *const c_void, len: u32) -> c_int;
+        #[link_name = "listen"]
+        fn sys_listen(fd: c_int, backlog: c_int) -> c_int;
+        fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+    }
+
+    #[repr(C)]
+    struct SockAddrIn {
+        family: u16,
+        port: u16,
+        addr: [u8; 4],
+        zero: [u8; 8],
+    }
+
+    #[repr(C)]
+    struct SockAddrIn6 {
+        family: u16,
+        port: u16,
+        flowinfo: u32,
+        addr: [u8; 16],
+        scope_id: u32,
+    }
+
+    /// A TCP socket that hasn't started listening yet.
+    pub struct Socket {
+        fd: OwnedFd,
+    }
+
+    /// Sets an integer socket option.
+    pub fn set_option(
+        socket: &impl AsRawFd,
+        level: c_int,
+        name: c_int,
+        value: c_int,
+    ) -> io::Result<()> {
+        check(unsafe {
+            setsockopt(
+                socket.as_raw_fd(),
+                level,
+                name,
+                &value as *const c_int as *const c_void,
+                size_of::<c_int>() as u32,
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Marks the socket as listening with the given backlog.
+    pub fn listen(socket: &impl AsRawFd, backlog: u32) -> io::Result<()> {
+        let backlog = backlog.min(c_int::MAX as u32) as c_int;
+        check(unsafe { sys_listen(socket.as_raw_fd(), backlog) })?;
+        Ok(())
+    }
+
+    /// Keeps the descriptor from leaking into child processes.
+    pub fn set_cloexec(fd: &impl AsRawFd) -> io::Result<()> {
+        check(unsafe { fcntl(fd.as_raw_fd(), F_SETFD, FD_CLOEXEC) })?;
+        Ok(())
+    }
+
+    /// Lets the descriptor be inherited by child processes.
+    pub fn clear_cloexec(fd: &impl AsRawFd) -> io::Result<()> {
+        check(unsafe { fcntl(fd.as_raw_fd(), F_SETFD, 0) })?;
+        Ok(())
+    }
+
+    /// Turns a `-1` return value into the current OS error.
+    fn check(result: c_int) -> io::Result<c_int> {
+        if result == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(result)
+        }
+    }
+
+    impl Socket {
+        /// Creates a TCP socket for the address family of `addr`.
+        pub fn new(addr: &SocketAddr) -> io::Result<Self> {
+            let domain = match addr {
+                SocketAddr::V4(_) => AF_INET,
+                SocketAddr::V6(_) => AF_INET6,
+            };
+            let fd = check(unsafe { socket(domain, SOCK_STREAM | SOCK_CLOEXEC, 0) })?;
+            Ok(Socket {
+                // SAFETY: `fd` was just returned by `socket` and is owned by nobody else
+                fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            })
+        }
+
+        /// Binds the socket to `addr`.
+        pub fn bind(&self, addr: &SocketAddr) -> io::Result<()> {
+            let result = match addr {
+                SocketAddr::V4(v4) => {
+                    let raw = SockAddrIn {
+                        family: AF_INET as u16,
+                        port: v4.port().to_be(),
+                        addr: v4.ip().octets(),
+                        zero: [0; 8],
+                    };
+                    unsafe {
+                        bind(
+                            self.fd.as_raw_fd(),
+                            &raw as *const SockAddrIn as *const c_void,
+                            size_of::<SockAddrIn>() as u32,
+                        )
+                    }
+                }
+                SocketAddr::V6(v6) => {
+                    let raw = SockAddrIn6 {
+                        family: AF_INET6 as u16,
+                        port: v6.port().to_be(),
+                        flowinfo: v6.flowinfo().to_be(),
+                        addr: v6.ip().octets(),
+                        scope_id: v6.scope_id(),
+                    };
+                    unsafe {
+                        bind(
+                            self.fd.as_raw_fd(),
+                            &raw as *const SockAddrIn6 as *const c_void,
+                            size_of::<SockAddrIn6>() as u32,
+                        )
+                    }
+                }
+            };
+            check(result)?;
+            Ok(())
+        }
+
+        /// Starts listening and hands the socket over to `std`.
+        pub fn listen(self, backlog: u32) -> io::Result<TcpListener> {
+            listen(&self, backlog)?;
+            Ok(TcpListener::from(self.fd))
+        }
+    }
+
+    impl AsRawFd for Socket {
+        fn as_raw_fd(&self) -> c_int {
+            self.fd.as_raw_fd()
+        }
+    }
+}
+
+#[cfg(all(
+    test,
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handed_over_sockets_are_claimed_once_by_address() {
+        let first = TcpListener::bind("127.0.0.1:0").unwrap();
+        let second = TcpListener::bind("127.0.0.1:0").unwrap();
+        let first_addr = first.local_addr().unwrap();
+        let second_addr = second.local_addr().unwrap();
+        let mut unclaimed = vec![first, second];
+
+        let claimed = claim(&mut unclaimed, first_addr);
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].local_addr().unwrap(), first_addr);
+        assert!(claim(&mut unclaimed, first_addr).is_empty());
+
+        // A server asking for an ephemeral port never gets a handed-over socket
+        assert!(claim(&mut unclaimed, "127.0.0.1:0".parse().unwrap()).is_empty());
+        assert_eq!(claim(&mut unclaimed, second_addr).len(), 1);
+        assert!(unclaimed.is_empty());
+    }
+}
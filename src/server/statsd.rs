@@ -0,0 +1,179 @@
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::metrics::MetricsSink;
+use crate::logging;
+
+/// Largest datagram sent, small enough to avoid fragmentation on common networks.
+const MAX_PACKET_SIZE: usize = 1432;
+
+/// A [`MetricsSink`] that sends samples over UDP in the StatsD line format, or in the
+/// DogStatsD dialect with tags.
+///
+/// Samples are buffered and sent in as few datagrams as fit, whenever the buffer fills up and
+/// on [`flush`](MetricsSink::flush). Plain StatsD has no tags, so they're only sent in the
+/// DogStatsD dialect. Sending is best effort: a missing agent loses samples without errors.
+///
+/// # Examples
+///
+/// ```
+/// # use std::{sync::Arc, time::Duration};
+/// # use http_server::{Router, Server};
+/// # use http_server::server::{ServerMetrics, metrics::MetricsSink, statsd::StatsdSink};
+/// # fn example(router: Router) -> Result<(), String> {
+/// # let metrics = ServerMetrics::new();
+/// let statsd = Arc::new(
+///     StatsdSink::new("127.0.0.1:8125", "myapp")?
+///         .dogstatsd()
+///         .tag("env", "production"),
+/// );
+/// let _exporter = metrics.export_to(statsd.clone(), Duration::from_secs(10))?;
+///
+/// // Request durations, as a histogram
+/// let server = Server::new("0.0.0.0:8080", router)
+///     .with_metrics(metrics)
+///     .on_response(move |event| {
+///         let status = event.status.map_or("none".to_string(), |status| status.to_string());
+///         statsd.timing("http.server.duration", event.duration, &[("status", &status)]);
+///     });
+/// # Ok(())
+/// # }
+/// ```
+pub struct StatsdSink {
+    socket: UdpSocket,
+    prefix: String,
+    dogstatsd: bool,
+    /// Formatted `|#tag:value,...` suffix of the global tags, empty if there are none.
+    global_tags: String,
+    buffer: Mutex<String>,
+}
+
+impl StatsdSink {
+    /// Creates a sink sending to the agent at `address`.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The agent's `host:port`, usually port 8125.
+    /// * `prefix` - Prepended to every metric name with a dot, e.g. the service name; may be
+    ///   empty.
+    ///
+    /// # Returns
+    ///
+    /// The sink, or an error if the address doesn't resolve or no socket could be opened.
+    pub fn new(address: &str, prefix: &str) -> Result<Self, String> {
+        let agent = address
+            .to_socket_addrs()
+            .map_err(|e| format!("Failed to resolve {}: {}", address, e))?
+            .next()
+            .ok_or_else(|| format!("No address found for {}", address))?;
+        let local = if agent.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(local)
+            .and_then(|socket| socket.connect(agent).map(|()| socket))
+            .map_err(|e| format!("Failed to open a socket to {}: {}", address, e))?;
+        socket
+            .set_nonblocking(true)
+            .map_err(|e| format!("Failed to configure the socket: {}", e))?;
+
+        Ok(StatsdSink {
+            socket,
+            prefix: sanitize(prefix),
+            dogstatsd: false,
+            global_tags: String::new(),
+            buffer: Mutex::new(String::new()),
+        })
+    }
+
+    /// Uses the DogStatsD dialect, which sends tags.
+    pub fn dogstatsd(mut self) -> Self {
+        self.dogstatsd = true;
+        self
+    }
+
+    /// Adds a tag sent with every sample, e.g. the environment. Only sent by DogStatsD.
+    pub fn tag(mut self, name: &str, value: &str) -> Self {
+        self.global_tags.push_str(&format!(
+            "{}{}:{}",
+            if self.global_tags.is_empty() { "" } else { "," },
+            sanitize(name),
+            sanitize(value)
+        ));
+        self
+    }
+
+    /// Formats a sample and adds it to the buffer, sending the buffer first if it's full.
+    fn send(&self, name: &str, value: &str, kind: &str, tags: &[(&str, &str)]) {
+        let mut line = String::new();
+        if !self.prefix.is_empty() {
+            line.push_str(&self.prefix);
+            line.push('.');
+        }
+        line.push_str(&format!("{}:{}|{}", sanitize(name), value, kind));
+        if self.dogstatsd && (!self.global_tags.is_empty() || !tags.is_empty()) {
+            let tags: Vec<String> = tags
+                .iter()
+                .map(|(name, value)| format!("{}:{}", sanitize(name), sanitize(value)))
+                .collect();
+            line.push_str("|#");
+            line.push_str(&self.global_tags);
+            if !self.global_tags.is_empty() && !tags.is_empty() {
+                line.push(',');
+            }
+            line.push_str(&tags.join(","));
+        }
+
+        let mut buffer = self.buffer.lock().unwrap();
+        if !buffer.is_empty() && buffer.len() + 1 + line.len() > MAX_PACKET_SIZE {
+            self.send_packet(&buffer);
+            buffer.clear();
+        }
+        if !buffer.is_empty() {
+            buffer.push('\n');
+        }
+        buffer.push_str(&line);
+    }
+
+    fn send_packet(&self, packet: &str) {
+        if let Err(e) = self.socket.send(packet.as_bytes()) {
+            logging::debug("statsd", "Failed to send metrics", &[("error", &e)]);
+        }
+    }
+}
+
+impl MetricsSink for StatsdSink {
+    fn gauge(&self, name: &str, value: u64, tags: &[(&str, &str)]) {
+        self.send(name, &value.to_string(), "g", tags);
+    }
+
+    fn count(&self, name: &str, delta: u64, tags: &[(&str, &str)]) {
+        self.send(name, &delta.to_string(), "c", tags);
+    }
+
+    fn timing(&self, name: &str, duration: Duration, tags: &[(&str, &str)]) {
+        let millis = format!("{:.3}", duration.as_secs_f64() * 1000.0);
+        self.send(name, &millis, "ms", tags);
+    }
+
+    fn flush(&self) {
+        let mut buffer = self.buffer.lock().unwrap();
+        if !buffer.is_empty() {
+            self.send_packet(&buffer);
+            buffer.clear();
+        }
+    }
+}
+
+/// Replaces characters with a meaning in the line format, and whitespace, with underscores.
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            ':' | '|' | '@' | '#' | ',' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect()
+}
@@ -0,0 +1,75 @@
+use std::net::TcpStream;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::oneshot;
+
+/// A connection taken over from the server after a `101 Switching Protocols` response.
+pub struct Upgraded {
+    /// The client connection, in blocking mode and without timeouts.
+    pub stream: TcpStream,
+    /// Bytes the client sent after the upgrade request that the server had already read.
+    pub buffered: Vec<u8>,
+}
+
+/// Resolves to the [`Upgraded`] connection once the server has sent a
+/// `101 Switching Protocols` response to the request.
+///
+/// The server adds one to the extensions of every request with a `Connection: upgrade` header.
+/// A handler clones it, spawns a task that awaits it, and returns the `101` response with the
+/// `Upgrade` and `Connection` headers it wants; from then on the connection speaks whatever
+/// protocol the task implements. Awaiting fails if the handler answers with anything other
+/// than `101`. Upgraded connections no longer count towards `max_connections`.
+///
+/// # Examples
+///
+/// ```
+/// # use std::net::TcpStream;
+/// # use http_server::{Request, Response, StatusCode, server::OnUpgrade};
+/// # fn echo(stream: TcpStream) -> std::io::Result<u64> {
+/// #     std::io::copy(&mut &stream, &mut &stream)
+/// # }
+/// async fn handle_upgrade(request: Request) -> Result<Response, String> {
+///     let on_upgrade = request.extensions.get::<OnUpgrade>().cloned().ok_or("Not an upgrade")?;
+///     tokio::spawn(async move {
+///         if let Ok(upgraded) = on_upgrade.await {
+///             tokio::task::spawn_blocking(move || echo(upgraded.stream));
+///         }
+///     });
+///
+///     let mut response = Response::new(StatusCode::SwitchingProtocols);
+///     response.headers.insert("Connection".to_string(), "upgrade".to_string());
+///     response.headers.insert("Upgrade".to_string(), "echo".to_string());
+///     Ok(response)
+/// }
+/// ```
+#[derive(Clone)]
+pub struct OnUpgrade {
+    receiver: Arc<Mutex<Option<oneshot::Receiver<Upgraded>>>>,
+}
+
+impl OnUpgrade {
+    /// Creates the handle given to the handler and the sender the server completes it with.
+    pub(super) fn new() -> (oneshot::Sender<Upgraded>, Self) {
+        let (sender, receiver) = oneshot::channel();
+        let on_upgrade = OnUpgrade {
+            receiver: Arc::new(Mutex::new(Some(receiver))),
+        };
+        (sender, on_upgrade)
+    }
+}
+
+impl IntoFuture for OnUpgrade {
+    type Output = Result<Upgraded, String>;
+    type IntoFuture = Pin<Box<dyn Future<Output = Result<Upgraded, String>> + Send>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        let receiver = self.receiver.lock().unwrap().take();
+        Box::pin(async move {
+            receiver
+                .ok_or_else(|| "Connection upgrade already taken".to_string())?
+                .await
+                .map_err(|_| "Connection was not upgraded".to_string())
+        })
+    }
+}
@@ -1,6 +1,9 @@
 use std::task::{Context, Poll};
 
-use crate::http::{request::Request, response::Response};
+use crate::http::{StatusCode, request::Request, response::Response};
+use crate::logging;
+use crate::middleware::{ConditionLayer, ConditionMiddleware};
+use crate::server::error_response;
 
 /// A trait representing an asynchronous service.
 pub trait Service {
@@ -88,12 +91,35 @@ impl<S> ServiceBuilder<S> {
         }
     }
 
+    /// Adds a layer that only applies to requests whose path starts with `path_prefix`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path_prefix` - The path prefix requests must start with, e.g. `/api/`.
+    /// * `layer` - The layer to be added.
+    ///
+    /// # Returns
+    ///
+    /// A new `ServiceBuilder` with the conditional layer added.
+    pub fn layer_when<L>(
+        self,
+        path_prefix: &str,
+        layer: L,
+    ) -> ServiceBuilder<ConditionMiddleware<L::Service, S>>
+    where
+        S: Clone,
+        L: Layer<S>,
+    {
+        self.layer(ConditionLayer::path_prefix(path_prefix, layer))
+    }
+
     pub fn service(self) -> S {
         self.service
     }
 }
 
 /// A service that handles requests using a function.
+#[derive(Clone)]
 pub struct HandlerService<F> {
     f: F,
 }
@@ -150,3 +176,53 @@ where
 {
     HandlerService { f }
 }
+
+/// Runs a single request through a service, without a server or sockets, e.g. to test a
+/// router together with its middleware.
+///
+/// Failures are turned into responses the way the server does: a service that isn't ready
+/// gives `503 Service Unavailable` and an error `500 Internal Server Error`. The request only
+/// carries the extensions given to it, so e.g. a [`ConnectInfo`](crate::server::ConnectInfo)
+/// has to be inserted by the caller for middleware that needs the client's address.
+///
+/// # Arguments
+///
+/// * `service` - The service to call.
+/// * `request` - The request to handle.
+///
+/// # Returns
+///
+/// The service's response.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Request, Response, Router, ServiceBuilder, StatusCode, service};
+/// # use http_server::middleware::request_id::RequestIdLayer;
+/// # async fn handle_user(_req: Request) -> Result<Response, String> {
+/// #     Ok(Response::new(StatusCode::OK))
+/// # }
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// # let router = Router::new().get("/users/:id", handle_user);
+/// let mut service = ServiceBuilder::new(router).layer(RequestIdLayer::new()).service();
+/// let response = service::oneshot(&mut service, Request::builder().path("/users/42").build()).await;
+/// assert!(response.headers.contains_key("X-Request-Id"));
+/// # }
+/// ```
+pub async fn oneshot<S>(service: &mut S, request: Request) -> Response
+where
+    S: Service<Response = Response, Error = String>,
+{
+    if let Err(e) = std::future::poll_fn(|cx| service.poll_ready(cx)).await {
+        logging::error("service", "Service not ready", &[("error", &e)]);
+        return error_response(StatusCode::ServiceUnavailable);
+    }
+    match service.call(request).await {
+        Ok(response) => response,
+        Err(e) => {
+            logging::error("service", "Error processing request", &[("error", &e)]);
+            error_response(StatusCode::InternalServerError)
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use tokio::fs;
+
+/// Builds URLs for static files with a content hash in the query string, so browsers fetch a
+/// file again as soon as it changes instead of reusing a stale copy.
+///
+/// Hashes are remembered per file and recomputed when its length or modification time
+/// changes, so edits show up on the next page render.
+///
+/// # Examples
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use http_server::static_files::StaticFiles;
+/// # async fn example() {
+/// let files = StaticFiles::new("public").dev_mode(true);
+/// let assets = Arc::new(files.asset_urls("/static"));
+///
+/// // While rendering a page
+/// let href = assets.url("css/app.css").await; // "/static/css/app.css?v=3f2a9c0d1e4b5a67"
+/// # }
+/// ```
+pub struct AssetUrls {
+    root: PathBuf,
+    prefix: String,
+    hashes: Mutex<HashMap<PathBuf, FileHash>>,
+}
+
+/// A file's content hash and the length and modification time it was computed for.
+struct FileHash {
+    len: u64,
+    modified: Option<SystemTime>,
+    hash: String,
+}
+
+impl AssetUrls {
+    /// Creates a helper for files under `root`, served at `prefix`.
+    pub(super) fn new(root: PathBuf, prefix: &str) -> Self {
+        AssetUrls {
+            root,
+            prefix: prefix.trim_end_matches('/').to_string(),
+            hashes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the URL of the file at `path`, relative to the root, with a `v` query parameter
+    /// derived from its contents.
+    ///
+    /// # Returns
+    ///
+    /// The versioned URL, or the plain URL if the file can't be read.
+    pub async fn url(&self, path: &str) -> String {
+        let path = path.trim_start_matches('/');
+        let url = format!("{}/{}", self.prefix, path);
+        match self.hash(Path::new(path)).await {
+            Some(hash) => format!("{}?v={}", url, hash),
+            None => url,
+        }
+    }
+
+    /// Returns the content hash of a file, reading it only if it changed since the last call.
+    async fn hash(&self, path: &Path) -> Option<String> {
+        // Only plain names, so the helper can't be used to probe outside the root
+        if !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return None;
+        }
+        let file_path = self.root.join(path);
+        let metadata = fs::metadata(&file_path).await.ok()?;
+        let modified = metadata.modified().ok();
+
+        if let Some(cached) = self.hashes.lock().unwrap().get(&file_path)
+            && cached.len == metadata.len()
+            && cached.modified == modified
+        {
+            return Some(cached.hash.clone());
+        }
+
+        let contents = fs::read(&file_path).await.ok()?;
+        let hash = format!("{:016x}", fnv1a(&contents));
+        self.hashes.lock().unwrap().insert(
+            file_path,
+            FileHash {
+                len: contents.len() as u64,
+                modified,
+                hash: hash.clone(),
+            },
+        );
+        Some(hash)
+    }
+}
+
+/// Hashes file contents with 64-bit FNV-1a, which is plenty to tell versions of a file apart.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
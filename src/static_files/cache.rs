@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// An in-memory cache of small files, evicting the least recently used ones.
+///
+/// Entries remember the length and modification time they were read with and are only
+/// returned while the file on disk still has both, so edits are picked up on the next request.
+pub(super) struct FileCache {
+    max_entry_size: u64,
+    max_total_bytes: u64,
+    inner: Mutex<CacheInner>,
+}
+
+struct CacheInner {
+    entries: HashMap<PathBuf, CacheEntry>,
+    total_bytes: u64,
+    /// Incremented on every access to order entries by recency.
+    clock: u64,
+}
+
+struct CacheEntry {
+    contents: Arc<Vec<u8>>,
+    len: u64,
+    modified: Option<SystemTime>,
+    last_used: u64,
+}
+
+impl FileCache {
+    /// Creates an empty cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_entry_size` - Files larger than this many bytes are never cached.
+    /// * `max_total_bytes` - The most bytes held at once, across all entries.
+    pub(super) fn new(max_entry_size: u64, max_total_bytes: u64) -> Self {
+        FileCache {
+            max_entry_size: max_entry_size.min(max_total_bytes),
+            max_total_bytes,
+            inner: Mutex::new(CacheInner {
+                entries: HashMap::new(),
+                total_bytes: 0,
+                clock: 0,
+            }),
+        }
+    }
+
+    /// Returns whether a file of `len` bytes is small enough to be cached.
+    pub(super) fn admits(&self, len: u64) -> bool {
+        len <= self.max_entry_size
+    }
+
+    /// Looks up the contents of `path`, if they are cached and still current.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The canonical path of the file.
+    /// * `len` - The file's current length.
+    /// * `modified` - The file's current modification time.
+    pub(super) fn get(
+        &self,
+        path: &Path,
+        len: u64,
+        modified: Option<SystemTime>,
+    ) -> Option<Arc<Vec<u8>>> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let clock = inner.clock;
+
+        let entry = inner.entries.get_mut(path)?;
+        if entry.len != len || entry.modified != modified {
+            // The file changed on disk; drop the stale copy
+            let stale = inner.entries.remove(path)?;
+            inner.total_bytes -= stale.len;
+            return None;
+        }
+        entry.last_used = clock;
+        Some(entry.contents.clone())
+    }
+
+    /// Caches the contents of `path`, evicting the least recently used entries to make room.
+    pub(super) fn insert(
+        &self,
+        path: PathBuf,
+        contents: Arc<Vec<u8>>,
+        modified: Option<SystemTime>,
+    ) {
+        let len = contents.len() as u64;
+        if !self.admits(len) {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let clock = inner.clock;
+
+        if let Some(replaced) = inner.entries.remove(&path) {
+            inner.total_bytes -= replaced.len;
+        }
+        while inner.total_bytes + len > self.max_total_bytes {
+            let Some(oldest) = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(path, _)| path.clone())
+            else {
+                break;
+            };
+            if let Some(evicted) = inner.entries.remove(&oldest) {
+                inner.total_bytes -= evicted.len;
+            }
+        }
+
+        inner.total_bytes += len;
+        inner.entries.insert(
+            path,
+            CacheEntry {
+                contents,
+                len,
+                modified,
+                last_used: clock,
+            },
+        );
+    }
+}
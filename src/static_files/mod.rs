@@ -0,0 +1,1318 @@
+mod assets;
+mod cache;
+mod range;
+
+pub use assets::AssetUrls;
+use cache::FileCache;
+pub(crate) use range::{ByteRange, Multipart, byte_range};
+
+use std::ffi::OsStr;
+use std::fs::Metadata;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use chrono::{DateTime, TimeDelta, Timelike, Utc};
+use serde_json::json;
+use tokio::fs::{self, File};
+
+use crate::fs_path::{self, PathError, percent_decode};
+use crate::http::{Request, Response, StatusCode};
+use crate::mime;
+
+/// Boxed future returned by [`StaticFiles::handler`].
+type ResponseFuture = Pin<Box<dyn Future<Output = Result<Response, String>> + Send>>;
+
+/// What the static file service does with paths that have a segment starting with `.`, such as
+/// `.env` or `.git/config`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HiddenFiles {
+    /// Answer `404 Not Found`, as if they didn't exist.
+    Hide,
+    /// Answer `403 Forbidden`.
+    Forbid,
+    /// Serve them like any other file.
+    Serve,
+}
+
+/// What the static file service does with symbolic links under its root.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symlinks {
+    /// Follow links wherever they point, including outside the root.
+    Follow,
+    /// Follow links only when their target is inside the root, and answer `403 Forbidden`
+    /// otherwise.
+    FollowWithinRoot,
+    /// Answer `403 Forbidden` for any path that goes through a link, and leave links out of
+    /// directory listings.
+    Reject,
+}
+
+/// Serves files from a directory on disk.
+///
+/// Request paths are resolved inside the root with the checks of [`fs_path::resolve`], so
+/// `..` segments, encoded traversal and, by default, symlinks can't reach files outside it.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Router, static_files::StaticFiles};
+/// let router = Router::new().get("/static/*", StaticFiles::new("public").handler());
+/// ```
+pub struct StaticFiles {
+    root: PathBuf,
+    hidden_files: HiddenFiles,
+    symlinks: Symlinks,
+    auto_index: bool,
+    index_file: Option<String>,
+    fallback: Option<String>,
+    fallback_excludes: Vec<String>,
+    precompressed: bool,
+    cache_rules: Vec<(String, String)>,
+    cache: Option<FileCache>,
+    languages: Vec<String>,
+    dev_mode: bool,
+}
+
+impl StaticFiles {
+    /// Creates a service that serves files under `root`, hiding dotfiles.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        StaticFiles {
+            root: root.into(),
+            hidden_files: HiddenFiles::Hide,
+            symlinks: Symlinks::FollowWithinRoot,
+            auto_index: false,
+            index_file: Some("index.html".to_string()),
+            fallback: None,
+            fallback_excludes: Vec::new(),
+            precompressed: false,
+            cache_rules: Vec::new(),
+            cache: None,
+            languages: Vec::new(),
+            dev_mode: false,
+        }
+    }
+
+    /// Sets how paths with a segment starting with `.` are handled.
+    pub fn hidden_files(mut self, policy: HiddenFiles) -> Self {
+        self.hidden_files = policy;
+        self
+    }
+
+    /// Sets how symbolic links under the root are handled, [`Symlinks::FollowWithinRoot`] by
+    /// default.
+    ///
+    /// Following links anywhere is convenient in development, where assets are often linked
+    /// in from elsewhere; rejecting them entirely suits hardened deployments.
+    pub fn symlinks(mut self, policy: Symlinks) -> Self {
+        self.symlinks = policy;
+        self
+    }
+
+    /// Enables listing the contents of directories.
+    ///
+    /// Listings are HTML, or JSON when the request asks for `application/json` in its `Accept`
+    /// header. Hidden entries are only listed when hidden files are served.
+    pub fn auto_index(mut self, enabled: bool) -> Self {
+        self.auto_index = enabled;
+        self
+    }
+
+    /// Sets the file served for requests to a directory, `index.html` by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The index file name, or `None` to never serve one.
+    pub fn index_file(mut self, name: Option<&str>) -> Self {
+        self.index_file = name.map(str::to_string);
+        self
+    }
+
+    /// Serves `path`, relative to the root, instead of `404 Not Found` for unknown paths.
+    ///
+    /// This is what single-page apps using HTML5 history routing need: deep links such as
+    /// `/settings/profile` load the app, which then routes on the client. Paths whose last
+    /// segment has an extension (missing assets like `app.js`) still get a 404.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::static_files::StaticFiles;
+    /// let app = StaticFiles::new("dist").spa_fallback("index.html").exclude_from_fallback("/api");
+    /// ```
+    pub fn spa_fallback(mut self, path: &str) -> Self {
+        self.fallback = Some(path.to_string());
+        self
+    }
+
+    /// Never serves the SPA fallback for request paths starting with `prefix`, so unknown API
+    /// endpoints keep answering 404.
+    pub fn exclude_from_fallback(mut self, prefix: &str) -> Self {
+        self.fallback_excludes.push(prefix.to_string());
+        self
+    }
+
+    /// Serves precompressed variants of files when the client accepts them.
+    ///
+    /// A request for `app.js` is answered with `app.js.br` or `app.js.gz`, if one exists next
+    /// to it and the client's `Accept-Encoding` allows it, with the matching
+    /// `Content-Encoding`. Brotli is preferred over gzip. Responses carry
+    /// `Vary: Accept-Encoding` so caches keep the variants apart.
+    pub fn precompressed(mut self, enabled: bool) -> Self {
+        self.precompressed = enabled;
+        self
+    }
+
+    /// Sends `Cache-Control: value` for files matching any of `patterns`.
+    ///
+    /// Patterns are comma-separated globs where `*` matches within a path segment, `**`
+    /// matches across segments and `?` matches one character. A pattern without a `/` is
+    /// matched against the file name, otherwise against the path relative to the root. Rules
+    /// are tried in the order they were added and the first match wins.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::static_files::StaticFiles;
+    /// let files = StaticFiles::new("dist")
+    ///     .cache_control("*.css, *.js", "max-age=31536000, immutable")
+    ///     .cache_control("*.html", "no-cache");
+    /// ```
+    pub fn cache_control(mut self, patterns: &str, value: &str) -> Self {
+        for pattern in patterns.split(',').map(str::trim) {
+            if !pattern.is_empty() {
+                self.cache_rules
+                    .push((pattern.to_string(), value.to_string()));
+            }
+        }
+        self
+    }
+
+    /// Serves language variants of files, chosen by the request's `Accept-Language`.
+    ///
+    /// Variants sit next to the file with the language before the extension: a request for
+    /// `index.html` is answered with `index.de.html` for a client preferring German, with
+    /// `Content-Language: de`. Without an acceptable variant the file itself is served, or the
+    /// variant for the first language when the file doesn't exist. Responses carry
+    /// `Vary: Accept-Language` so caches keep the variants apart.
+    ///
+    /// # Arguments
+    ///
+    /// * `languages` - The language tags variants exist for, the default one first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use http_server::static_files::StaticFiles;
+    /// let docs = StaticFiles::new("docs").languages(&["en", "de", "fr"]);
+    /// ```
+    pub fn languages(mut self, languages: &[&str]) -> Self {
+        self.languages = languages
+            .iter()
+            .map(|language| language.to_string())
+            .collect();
+        self
+    }
+
+    /// Keeps small, frequently requested files in memory instead of reading them from disk on
+    /// every request.
+    ///
+    /// Cached files are still checked against their length and modification time on each
+    /// request, so changes on disk are served right away. Precompressed variants are cached
+    /// separately from the files they belong to.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_entry_size` - Files larger than this many bytes are always read from disk.
+    /// * `max_total_bytes` - The most bytes cached at once; the least recently used files are
+    ///   evicted to stay within it.
+    pub fn cache(mut self, max_entry_size: u64, max_total_bytes: u64) -> Self {
+        self.cache = Some(FileCache::new(max_entry_size, max_total_bytes));
+        self
+    }
+
+    /// Turns off everything that lets browsers or the service reuse a stale copy, for use
+    /// during development.
+    ///
+    /// Responses are sent with `Cache-Control: no-store` instead of validators and caching
+    /// rules, conditional requests always get the full file, and the in-memory cache is
+    /// bypassed so every request reads the file as it is on disk now.
+    pub fn dev_mode(mut self, enabled: bool) -> Self {
+        self.dev_mode = enabled;
+        self
+    }
+
+    /// Creates a helper that builds cache-busting URLs for files served by this service.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The path this service is mounted at, e.g. `"/static"`.
+    pub fn asset_urls(&self, prefix: &str) -> AssetUrls {
+        AssetUrls::new(self.root.clone(), prefix)
+    }
+
+    /// Turns the service into a handler for mounting on a router.
+    ///
+    /// On a wildcard route the path matched by `*` is served; otherwise the whole request
+    /// path is.
+    pub fn handler(self) -> impl Fn(Request) -> ResponseFuture + Send + Sync + 'static {
+        let files = Arc::new(self);
+        move |request| {
+            let files = files.clone();
+            Box::pin(async move { Ok(files.serve(&request).await) })
+        }
+    }
+
+    /// Serves the file `request` refers to.
+    pub async fn serve(&self, request: &Request) -> Response {
+        let path = request
+            .param("*")
+            .map(String::as_str)
+            .unwrap_or(&request.path);
+
+        let response = self.serve_path(request, path).await;
+        if let Some(fallback) = &self.fallback
+            && response.status_code == StatusCode::NotFound
+            && self.uses_fallback(request)
+        {
+            return self.serve_path(request, fallback).await;
+        }
+        response
+    }
+
+    /// Serves the file or directory at `path`, relative to the root.
+    async fn serve_path(&self, request: &Request, path: &str) -> Response {
+        let (file_path, language) = match self.resolve(request, path).await {
+            Ok(resolved) => resolved,
+            Err(status_code) => return error_page(status_code),
+        };
+        if !is_dir(&file_path).await {
+            return self.respond_with_file(request, &file_path, language).await;
+        }
+
+        let mut index = None;
+        if let Some(name) = &self.index_file
+            && let Ok(root) = self.canonical_root().await
+            && let Ok((candidate, language)) = self
+                .select_variant(request, &root, &file_path.join(name))
+                .await
+            && is_file(&candidate).await
+        {
+            index = Some((candidate, language));
+        }
+        if index.is_none() && !self.auto_index {
+            return error_page(StatusCode::NotFound);
+        }
+        // Relative links in the index or listing only work from a URL ending in a slash
+        if !request.path.ends_with('/') {
+            return redirect_to_directory(request);
+        }
+
+        match index {
+            Some((index, language)) => self.respond_with_file(request, &index, language).await,
+            None => self.list_directory(request, &file_path).await,
+        }
+    }
+
+    /// Decides whether an unknown path gets the SPA fallback instead of a 404.
+    fn uses_fallback(&self, request: &Request) -> bool {
+        let excluded = self
+            .fallback_excludes
+            .iter()
+            .any(|prefix| request.path.starts_with(prefix.as_str()));
+        let looks_like_file = request
+            .path
+            .rsplit('/')
+            .next()
+            .is_some_and(|segment| segment.contains('.'));
+        !excluded && !looks_like_file
+    }
+
+    /// Maps a request path to a file inside the root.
+    ///
+    /// # Returns
+    ///
+    /// The canonical path of the file and the language of the variant chosen, or the status
+    /// code to answer with.
+    async fn resolve(
+        &self,
+        request: &Request,
+        path: &str,
+    ) -> Result<(PathBuf, Option<&str>), StatusCode> {
+        let relative = fs_path::relative(path).map_err(|e| path_status(&e))?;
+        let hidden_status = match self.hidden_files {
+            HiddenFiles::Hide => Some(StatusCode::NotFound),
+            HiddenFiles::Forbid => Some(StatusCode::Forbidden),
+            HiddenFiles::Serve => None,
+        };
+        if let Some(status_code) = hidden_status
+            && relative.iter().any(is_hidden)
+        {
+            return Err(status_code);
+        }
+
+        let root = self.canonical_root().await?;
+        let (file_path, language) = self
+            .select_variant(request, &root, &root.join(&relative))
+            .await?;
+
+        // A link such as `config -> .env` reaches a hidden file under a visible name, so check
+        // where it led too; outside the root, only the file's own name is ours to judge
+        if let Some(status_code) = hidden_status {
+            let hidden = match file_path.strip_prefix(&root) {
+                Ok(target) => target.iter().any(is_hidden),
+                Err(_) => file_path.file_name().is_some_and(is_hidden),
+            };
+            if hidden {
+                return Err(status_code);
+            }
+        }
+        Ok((file_path, language))
+    }
+
+    /// Picks the language variant of `path` that best suits the request, if languages are
+    /// configured.
+    ///
+    /// # Returns
+    ///
+    /// The canonical path to serve and the language of the variant, or `None` for `path`
+    /// itself.
+    async fn select_variant(
+        &self,
+        request: &Request,
+        root: &Path,
+        path: &Path,
+    ) -> Result<(PathBuf, Option<&str>), StatusCode> {
+        let Some(default) = self.languages.first() else {
+            return Ok((self.contain(root, path).await?, None));
+        };
+
+        let accept_language = request.header("Accept-Language").map_or("", |value| value);
+        for language in preferred_languages(accept_language, &self.languages) {
+            if let Ok(variant) = self.contain(root, &language_path(path, language)).await
+                && is_file(&variant).await
+            {
+                return Ok((variant, Some(language)));
+            }
+        }
+
+        match self.contain(root, path).await {
+            Ok(file_path) => Ok((file_path, None)),
+            Err(StatusCode::NotFound) => {
+                let variant = self.contain(root, &language_path(path, default)).await?;
+                Ok((variant, Some(default.as_str())))
+            }
+            Err(status_code) => Err(status_code),
+        }
+    }
+
+    /// Returns the canonical path of the root directory.
+    async fn canonical_root(&self) -> Result<PathBuf, StatusCode> {
+        fs::canonicalize(&self.root)
+            .await
+            .map_err(|_| StatusCode::NotFound)
+    }
+
+    /// Canonicalizes `path`, a path under `root`, enforcing the symlink policy.
+    ///
+    /// # Returns
+    ///
+    /// The canonical path, or the status code to answer with.
+    async fn contain(&self, root: &Path, path: &Path) -> Result<PathBuf, StatusCode> {
+        if self.symlinks == Symlinks::Reject {
+            let relative = path.strip_prefix(root).map_err(|_| StatusCode::Forbidden)?;
+            let mut current = root.to_path_buf();
+            for component in relative.components() {
+                current.push(component);
+                let metadata = fs::symlink_metadata(&current)
+                    .await
+                    .map_err(|e| status_for(&e))?;
+                if metadata.file_type().is_symlink() {
+                    return Err(StatusCode::Forbidden);
+                }
+            }
+        }
+
+        if self.symlinks == Symlinks::Follow {
+            return fs::canonicalize(path).await.map_err(|e| status_for(&e));
+        }
+        fs_path::contain(root, path)
+            .await
+            .map_err(|e| path_status(&e))
+    }
+
+    /// Builds the response for a resolved path.
+    ///
+    /// The response carries `ETag` and `Last-Modified` validators, and a conditional request
+    /// whose copy is still current gets `304 Not Modified` without the body.
+    async fn respond_with_file(
+        &self,
+        request: &Request,
+        file_path: &Path,
+        language: Option<&str>,
+    ) -> Response {
+        let (body_path, encoding) = match self.precompressed_variant(request, file_path).await {
+            Some((variant, encoding)) => (variant, Some(encoding)),
+            None => (file_path.to_path_buf(), None),
+        };
+
+        let metadata = match fs::metadata(&body_path).await {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => return error_page(StatusCode::NotFound),
+            Err(e) => return error_page(status_for(&e)),
+        };
+
+        let validators = Validators::new(&metadata, encoding);
+        if !self.dev_mode && validators.is_fresh(request) {
+            let mut response = Response::new(StatusCode::NotModified);
+            validators.apply(&mut response);
+            self.apply_encoding(&mut response, encoding);
+            self.apply_language(&mut response, language);
+            self.apply_cache_control(&mut response, file_path).await;
+            return response;
+        }
+
+        let len = metadata.len();
+        let range = match request.header("Range") {
+            Some(range) if validators.allows_range(request) => byte_range(range, len),
+            _ => ByteRange::Full,
+        };
+
+        let mut response = match range {
+            ByteRange::Full => Response::new(StatusCode::OK),
+            ByteRange::Partial(_) | ByteRange::Multiple(_) => {
+                Response::new(StatusCode::PartialContent)
+            }
+            ByteRange::Unsatisfiable => {
+                let mut response = error_page(StatusCode::RangeNotSatisfiable);
+                response
+                    .headers
+                    .insert("Content-Range".to_string(), format!("bytes */{}", len));
+                return response;
+            }
+        };
+        if !self.dev_mode {
+            validators.apply(&mut response);
+        }
+        self.apply_encoding(&mut response, encoding);
+        self.apply_language(&mut response, language);
+        self.apply_cache_control(&mut response, file_path).await;
+        let content_type = mime::from_path(file_path);
+        response
+            .headers
+            .insert("Accept-Ranges".to_string(), "bytes".to_string());
+
+        let mut multipart = None;
+        match range {
+            ByteRange::Partial(ref range) => {
+                response.headers.insert(
+                    "Content-Range".to_string(),
+                    format!("bytes {}-{}/{}", range.start, range.end - 1, len),
+                );
+                response.set_content_type(&content_type);
+            }
+            ByteRange::Multiple(ref ranges) => {
+                let body = Multipart::new(ranges.clone(), &content_type, len);
+                response.set_content_type(&body.content_type());
+                multipart = Some(body);
+            }
+            _ => response.set_content_type(&content_type),
+        }
+
+        if let Some(cache) = &self.cache
+            && !self.dev_mode
+            && cache.admits(len)
+        {
+            let contents = match self.cached_contents(cache, &body_path, &metadata).await {
+                Ok(contents) => contents,
+                Err(e) => return error_page(status_for(&e)),
+            };
+            match (range, multipart) {
+                (ByteRange::Partial(range), _) => {
+                    response.set_body(contents[range.start as usize..range.end as usize].to_vec())
+                }
+                (_, Some(multipart)) => response.set_body(multipart.to_bytes(&contents)),
+                _ => response.set_body(contents.to_vec()),
+            }
+            return response;
+        }
+
+        // The body is copied straight from the file to the socket, never buffered whole
+        let file = match File::open(&body_path).await {
+            Ok(file) => file.into_std().await,
+            Err(e) => return error_page(status_for(&e)),
+        };
+        match (range, multipart) {
+            (ByteRange::Partial(range), _) => response.set_file_range(file, range),
+            // Parts are read from the file as they are sent
+            (_, Some(multipart)) => response.set_stream(multipart.into_stream(file)),
+            _ => {
+                if let Err(e) = response.set_file(file) {
+                    return error_page(status_for(&e));
+                }
+            }
+        }
+        response
+    }
+
+    /// Returns the contents of a small file from the cache, reading and caching them on a miss.
+    async fn cached_contents(
+        &self,
+        cache: &FileCache,
+        path: &Path,
+        metadata: &Metadata,
+    ) -> io::Result<Arc<Vec<u8>>> {
+        let modified = metadata.modified().ok();
+        if let Some(contents) = cache.get(path, metadata.len(), modified) {
+            return Ok(contents);
+        }
+
+        let contents = Arc::new(fs::read(path).await?);
+        // A file modified mid-read may not match `metadata`, so it's served but not cached
+        if contents.len() as u64 == metadata.len() {
+            cache.insert(path.to_path_buf(), contents.clone(), modified);
+        }
+        Ok(contents)
+    }
+
+    /// Finds a precompressed variant of `file_path` the client accepts.
+    ///
+    /// # Returns
+    ///
+    /// The variant's path and its `Content-Encoding`, or `None` to serve the file itself.
+    async fn precompressed_variant(
+        &self,
+        request: &Request,
+        file_path: &Path,
+    ) -> Option<(PathBuf, &'static str)> {
+        if !self.precompressed {
+            return None;
+        }
+        let accept_encoding = request.header("Accept-Encoding")?;
+        let root = self.canonical_root().await.ok()?;
+
+        for (encoding, extension) in [("br", "br"), ("gzip", "gz")] {
+            if !accepts_encoding(accept_encoding, encoding) {
+                continue;
+            }
+            let mut variant = file_path.as_os_str().to_owned();
+            variant.push(".");
+            variant.push(extension);
+            // The variant may be a symlink too, so it gets the same checks
+            let Ok(variant) = self.contain(&root, Path::new(&variant)).await else {
+                continue;
+            };
+            if is_file(&variant).await {
+                return Some((variant, encoding));
+            }
+        }
+        None
+    }
+
+    /// Adds the headers describing which encoding of a file `response` carries.
+    fn apply_encoding(&self, response: &mut Response, encoding: Option<&str>) {
+        if !self.precompressed {
+            return;
+        }
+        add_vary(response, "Accept-Encoding");
+        if let Some(encoding) = encoding {
+            response
+                .headers
+                .insert("Content-Encoding".to_string(), encoding.to_string());
+        }
+    }
+
+    /// Adds the headers describing which language variant of a file `response` carries.
+    fn apply_language(&self, response: &mut Response, language: Option<&str>) {
+        if self.languages.is_empty() {
+            return;
+        }
+        add_vary(response, "Accept-Language");
+        if let Some(language) = language {
+            response
+                .headers
+                .insert("Content-Language".to_string(), language.to_string());
+        }
+    }
+
+    /// Adds the `Cache-Control` header of the first rule matching `file_path`, if any.
+    async fn apply_cache_control(&self, response: &mut Response, file_path: &Path) {
+        if self.dev_mode {
+            response
+                .headers
+                .insert("Cache-Control".to_string(), "no-store".to_string());
+            return;
+        }
+        if self.cache_rules.is_empty() {
+            return;
+        }
+        let name = file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        // Only patterns with a `/` need the path relative to the root
+        let mut relative = None;
+        if self
+            .cache_rules
+            .iter()
+            .any(|(pattern, _)| pattern.contains('/'))
+        {
+            relative = fs::canonicalize(&self.root).await.ok().and_then(|root| {
+                let relative = file_path.strip_prefix(root).ok()?;
+                let segments = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>();
+                Some(segments.join("/"))
+            });
+        }
+
+        let rule =
+            self.cache_rules
+                .iter()
+                .find(|(pattern, _)| match (pattern.contains('/'), &relative) {
+                    (false, _) => glob_matches(pattern.as_bytes(), name.as_bytes()),
+                    (true, Some(relative)) => {
+                        let pattern = pattern.trim_start_matches('/');
+                        glob_matches(pattern.as_bytes(), relative.as_bytes())
+                    }
+                    (true, None) => false,
+                });
+        if let Some((_, value)) = rule {
+            response
+                .headers
+                .insert("Cache-Control".to_string(), value.clone());
+        }
+    }
+
+    /// Builds the listing of a directory's contents.
+    async fn list_directory(&self, request: &Request, dir: &Path) -> Response {
+        let mut entries = match fs::read_dir(dir).await {
+            Ok(entries) => entries,
+            Err(e) => return error_page(status_for(&e)),
+        };
+
+        let mut listing = Vec::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if name.starts_with('.') && self.hidden_files != HiddenFiles::Serve {
+                continue;
+            }
+            let is_symlink = entry
+                .file_type()
+                .await
+                .is_ok_and(|file_type| file_type.is_symlink());
+            if is_symlink && self.symlinks == Symlinks::Reject {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            let modified = metadata.modified().ok().map(DateTime::<Utc>::from);
+            listing.push((name, metadata.is_dir(), metadata.len(), modified));
+        }
+        // Directories first, then by name
+        listing.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let wants_json = request
+            .header("Accept")
+            .is_some_and(|accept| accept.contains("application/json"));
+        let mut response = Response::new(StatusCode::OK);
+
+        if wants_json {
+            let entries = listing
+                .iter()
+                .map(|(name, is_dir, size, modified)| {
+                    json!({
+                        "name": name,
+                        "type": if *is_dir { "directory" } else { "file" },
+                        "size": size,
+                        "modified": modified.map(|time| time.to_rfc3339()),
+                    })
+                })
+                .collect::<Vec<_>>();
+            response.set_content_type("application/json");
+            response.set_body(
+                json!({ "path": request.path, "entries": entries })
+                    .to_string()
+                    .into_bytes(),
+            );
+            return response;
+        }
+
+        let title = html_escape(&percent_decode(&request.path).unwrap_or_default());
+        let mut html = format!(
+            "<html><head><title>Index of {0}</title></head><body><h1>Index of {0}</h1>\
+             <table><tr><th>Name</th><th>Size</th><th>Modified</th></tr>\
+             <tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>",
+            title
+        );
+        for (name, is_dir, size, modified) in &listing {
+            let suffix = if *is_dir { "/" } else { "" };
+            let size = if *is_dir {
+                "-".to_string()
+            } else {
+                size.to_string()
+            };
+            let modified = modified
+                .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default();
+            html.push_str(&format!(
+                "<tr><td><a href=\"{}{}\">{}{}</a></td><td>{}</td><td>{}</td></tr>",
+                percent_encode(name),
+                suffix,
+                html_escape(name),
+                suffix,
+                size,
+                modified
+            ));
+        }
+        html.push_str("</table></body></html>");
+
+        response.set_content_type("text/html");
+        response.set_body(html.into_bytes());
+        response
+    }
+}
+
+/// Cache validators for a file, derived from its size and modification time.
+struct Validators {
+    etag: String,
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl Validators {
+    /// Computes the validators for a file's current contents.
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` - The metadata of the file being sent.
+    /// * `encoding` - The `Content-Encoding` of a precompressed variant, which is added to the
+    ///   tag since `gzip -k` and similar tools keep the original's modification time.
+    fn new(metadata: &Metadata, encoding: Option<&str>) -> Self {
+        let modified = metadata.modified().ok();
+        let nanos = modified
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since_epoch| since_epoch.as_nanos());
+        // HTTP dates have one second resolution, so sub-second precision is dropped
+        let last_modified = modified
+            .map(DateTime::<Utc>::from)
+            .and_then(|time| time.with_nanosecond(0));
+
+        Validators {
+            etag: match encoding {
+                Some(encoding) => format!("\"{:x}-{:x}-{}\"", metadata.len(), nanos, encoding),
+                None => format!("\"{:x}-{:x}\"", metadata.len(), nanos),
+            },
+            last_modified,
+        }
+    }
+
+    /// Formats the modification time as an HTTP date.
+    fn last_modified_header(&self) -> Option<String> {
+        self.last_modified
+            .map(|time| time.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+    }
+
+    /// Adds the `ETag` and `Last-Modified` headers to `response`.
+    fn apply(&self, response: &mut Response) {
+        response
+            .headers
+            .insert("ETag".to_string(), self.etag.clone());
+        if let Some(last_modified) = self.last_modified_header() {
+            response
+                .headers
+                .insert("Last-Modified".to_string(), last_modified);
+        }
+    }
+
+    /// Decides whether a `Range` header may be honored, given the request's `If-Range`.
+    ///
+    /// A client resuming a download sends the validator of the copy it has; if the file has
+    /// changed since, the whole file is sent instead of a part that wouldn't fit.
+    fn allows_range(&self, request: &Request) -> bool {
+        let Some(if_range) = request.header("If-Range").map(|value| value.trim()) else {
+            return true;
+        };
+        // Ranges need a strong match, so weak tags never qualify
+        if if_range.starts_with('"') || if_range.starts_with("W/") {
+            return if_range == self.etag;
+        }
+
+        let Some(last_modified) = self.last_modified else {
+            return false;
+        };
+        // A file changed within the current second could change again without its date
+        // moving, so such a date is too weak to match
+        let now = Utc::now().with_nanosecond(0).unwrap_or_else(Utc::now);
+        if now - last_modified < TimeDelta::seconds(1) {
+            return false;
+        }
+        DateTime::parse_from_rfc2822(if_range).is_ok_and(|date| date == last_modified)
+    }
+
+    /// Decides whether the client's cached copy, as described by its conditional headers, is
+    /// still current.
+    ///
+    /// `If-None-Match` takes precedence; `If-Modified-Since` is only consulted without it.
+    fn is_fresh(&self, request: &Request) -> bool {
+        if let Some(if_none_match) = request.header("If-None-Match") {
+            return etag_matches(if_none_match, &self.etag);
+        }
+
+        let since = request
+            .header("If-Modified-Since")
+            .and_then(|date| DateTime::parse_from_rfc2822(date.trim()).ok());
+        match (since, self.last_modified) {
+            (Some(since), Some(last_modified)) => last_modified <= since,
+            _ => false,
+        }
+    }
+}
+
+/// Returns whether an `If-None-Match` header lists `etag`, or `*`.
+///
+/// Uses weak comparison, so a `W/` prefix doesn't stop a match.
+pub(crate) fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+/// Returns whether an `Accept-Encoding` header allows `encoding`.
+///
+/// An encoding is accepted when it, or `*`, is listed without `q=0`.
+pub(crate) fn accepts_encoding(header: &str, encoding: &str) -> bool {
+    let mut wildcard = false;
+    for item in header.split(',') {
+        let mut parts = item.split(';');
+        let name = parts.next().unwrap_or_default().trim();
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if name.eq_ignore_ascii_case(encoding) {
+            return quality > 0.0;
+        }
+        if name == "*" {
+            wildcard = quality > 0.0;
+        }
+    }
+    wildcard
+}
+
+/// Adds `header` to the `Vary` header of `response`.
+fn add_vary(response: &mut Response, header: &str) {
+    let vary = match response.headers.get("Vary") {
+        Some(vary) => format!("{}, {}", vary, header),
+        None => header.to_string(),
+    };
+    response.headers.insert("Vary".to_string(), vary);
+}
+
+/// Orders the configured languages by the client's `Accept-Language` preferences.
+///
+/// A range matches a language when they are equal or one is a prefix of the other at a `-`,
+/// so `en-US` selects `en` and `en` selects `en-GB`. Languages the client doesn't accept are
+/// left out.
+fn preferred_languages<'a>(header: &str, languages: &'a [String]) -> Vec<&'a str> {
+    let mut ranges = header
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let range = parts.next()?.trim();
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (!range.is_empty() && quality > 0.0).then_some((range, quality))
+        })
+        .collect::<Vec<_>>();
+    // Stable, so equally preferred ranges keep the client's order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut preferred = Vec::new();
+    for (range, _) in ranges {
+        for language in languages {
+            let matches = range == "*"
+                || range.eq_ignore_ascii_case(language)
+                || is_subtag_of(range, language)
+                || is_subtag_of(language, range);
+            if matches && !preferred.contains(&language.as_str()) {
+                preferred.push(language.as_str());
+            }
+        }
+    }
+    preferred
+}
+
+/// Returns whether `tag` is a more specific form of `prefix`, such as `en-US` of `en`.
+fn is_subtag_of(tag: &str, prefix: &str) -> bool {
+    tag.len() > prefix.len()
+        && tag.as_bytes()[prefix.len()] == b'-'
+        && tag[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+/// Returns the path of the `language` variant of `path`, e.g. `index.de.html` for
+/// `index.html`.
+fn language_path(path: &Path, language: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let variant = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => {
+            format!("{}.{}.{}", stem, language, extension)
+        }
+        _ => format!("{}.{}", name, language),
+    };
+    path.with_file_name(variant)
+}
+
+/// Matches `text` against a glob where `*` stays within a path segment, `**` crosses
+/// segments and `?` matches a single character other than `/`.
+fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => {
+            // `**/` also matches no directories at all
+            let rest_after_slash = rest.strip_prefix(b"/").unwrap_or(rest);
+            (0..=text.len()).any(|skip| {
+                glob_matches(rest, &text[skip..]) || glob_matches(rest_after_slash, &text[skip..])
+            })
+        }
+        [b'*', rest @ ..] => {
+            let segment_end = text.iter().position(|&c| c == b'/').unwrap_or(text.len());
+            (0..=segment_end).any(|skip| glob_matches(rest, &text[skip..]))
+        }
+        [b'?', rest @ ..] => {
+            matches!(text, [c, tail @ ..] if *c != b'/' && glob_matches(rest, tail))
+        }
+        [c, rest @ ..] => matches!(text, [t, tail @ ..] if t == c && glob_matches(rest, tail)),
+    }
+}
+
+/// Returns whether a path segment names a hidden file, such as `.env`.
+fn is_hidden(segment: &OsStr) -> bool {
+    segment.as_encoded_bytes().starts_with(b".")
+}
+
+/// Returns whether `path` is a directory, following symlinks.
+async fn is_dir(path: &Path) -> bool {
+    fs::metadata(path)
+        .await
+        .is_ok_and(|metadata| metadata.is_dir())
+}
+
+/// Returns whether `path` is a regular file, following symlinks.
+async fn is_file(path: &Path) -> bool {
+    fs::metadata(path)
+        .await
+        .is_ok_and(|metadata| metadata.is_file())
+}
+
+/// Redirects a directory request to the same path with a trailing slash.
+pub(crate) fn redirect_to_directory(request: &Request) -> Response {
+    let query = request
+        .raw_query
+        .as_ref()
+        .map(|query| format!("?{}", query))
+        .unwrap_or_default();
+    let mut response = Response::new(StatusCode::MovedPermanently);
+    response.headers.insert(
+        "Location".to_string(),
+        format!("{}/{}", request.path, query),
+    );
+    response.set_body(Vec::new());
+    response
+}
+
+/// Escapes text for inclusion in HTML.
+pub(crate) fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Encodes a file name for use as a URL path segment.
+fn percent_encode(name: &str) -> String {
+    name.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Maps a file system error to the status code to answer with.
+fn status_for(error: &io::Error) -> StatusCode {
+    match error.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::NotADirectory => StatusCode::NotFound,
+        io::ErrorKind::PermissionDenied => StatusCode::Forbidden,
+        _ => StatusCode::InternalServerError,
+    }
+}
+
+/// Maps a path that can't be resolved to the status code to answer with.
+fn path_status(error: &PathError) -> StatusCode {
+    match error {
+        PathError::Malformed => StatusCode::BadRequest,
+        PathError::Traversal | PathError::OutsideRoot => StatusCode::Forbidden,
+        PathError::Io(e) => status_for(e),
+    }
+}
+
+/// Builds a small HTML error page.
+pub(crate) fn error_page(status_code: StatusCode) -> Response {
+    let mut response = Response::new(status_code);
+    response.set_content_type("text/html");
+    response.set_body(
+        format!(
+            "<html><body><h1>{} - {}</h1></body></html>",
+            status_code as u16,
+            status_code.reason_phrase()
+        )
+        .into_bytes(),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::router::Router;
+
+    /// A directory tree under the system's temporary directory, removed when dropped.
+    struct Site {
+        base: PathBuf,
+    }
+
+    impl Site {
+        /// Creates `public/` with a page, a dotfile and a hidden directory, and a secret next to
+        /// it, outside the root.
+        fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let base = std::env::temp_dir().join(format!(
+                "static-files-test-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            let root = base.join("public");
+            std::fs::create_dir_all(root.join(".git")).unwrap();
+            std::fs::write(root.join("index.html"), "0123456789").unwrap();
+            std::fs::write(root.join(".env"), "SECRET=1").unwrap();
+            std::fs::write(root.join(".git/config"), "[core]").unwrap();
+            std::fs::write(base.join("secret.txt"), "secret").unwrap();
+            Site { base }
+        }
+
+        fn root(&self) -> PathBuf {
+            self.base.join("public")
+        }
+
+        #[cfg(unix)]
+        fn link(&self, target: &Path, name: &str) {
+            std::os::unix::fs::symlink(target, self.root().join(name)).unwrap();
+        }
+    }
+
+    impl Drop for Site {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.base);
+        }
+    }
+
+    fn router(files: StaticFiles) -> Router {
+        Router::new().get("/static/*", files.handler())
+    }
+
+    async fn get(router: &Router, path: &str, range: Option<&str>) -> Response {
+        let mut builder = Request::builder().path(path);
+        if let Some(range) = range {
+            builder = builder.header("Range", range);
+        }
+        router.oneshot(builder.build()).await
+    }
+
+    /// Returns the body as it would be sent, whether held in memory or in a file.
+    fn body(response: &Response) -> String {
+        let bytes = response.to_bytes();
+        String::from_utf8_lossy(&bytes[response.head_bytes().len()..]).into_owned()
+    }
+
+    #[tokio::test]
+    async fn refuses_traversal() {
+        let site = Site::new();
+        let router = router(StaticFiles::new(site.root()));
+        let response = get(&router, "/static/index.html", None).await;
+        assert_eq!(response.status_code, StatusCode::OK);
+        assert_eq!(body(&response), "0123456789");
+
+        for path in [
+            "/static/../secret.txt",
+            "/static/%2e%2e/secret.txt",
+            "/static/%2e%2e%2fsecret.txt",
+            "/static/..%5csecret.txt",
+            "/static/index.html%00",
+        ] {
+            let response = get(&router, path, None).await;
+            assert!(
+                matches!(
+                    response.status_code,
+                    StatusCode::BadRequest | StatusCode::Forbidden | StatusCode::NotFound
+                ),
+                "{} answered {:?}",
+                path,
+                response.status_code
+            );
+            assert!(!body(&response).contains("secret"), "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn applies_the_hidden_file_policy() {
+        let site = Site::new();
+        let hiding = router(StaticFiles::new(site.root()));
+        assert_eq!(
+            get(&hiding, "/static/.env", None).await.status_code,
+            StatusCode::NotFound
+        );
+        assert_eq!(
+            get(&hiding, "/static/.git/config", None).await.status_code,
+            StatusCode::NotFound
+        );
+        assert_eq!(
+            get(&hiding, "/static/%2eenv", None).await.status_code,
+            StatusCode::NotFound
+        );
+
+        let forbidding = router(StaticFiles::new(site.root()).hidden_files(HiddenFiles::Forbid));
+        assert_eq!(
+            get(&forbidding, "/static/.env", None).await.status_code,
+            StatusCode::Forbidden
+        );
+
+        let serving = router(StaticFiles::new(site.root()).hidden_files(HiddenFiles::Serve));
+        let response = get(&serving, "/static/.env", None).await;
+        assert_eq!(response.status_code, StatusCode::OK);
+        assert_eq!(body(&response), "SECRET=1");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn applies_the_symlink_policy() {
+        let site = Site::new();
+        site.link(&site.root().join("index.html"), "home.html");
+        site.link(&site.base.join("secret.txt"), "escape.txt");
+
+        let within = router(StaticFiles::new(site.root()));
+        assert_eq!(
+            get(&within, "/static/home.html", None).await.status_code,
+            StatusCode::OK
+        );
+        assert_eq!(
+            get(&within, "/static/escape.txt", None).await.status_code,
+            StatusCode::Forbidden
+        );
+
+        let rejecting = router(StaticFiles::new(site.root()).symlinks(Symlinks::Reject));
+        assert_eq!(
+            get(&rejecting, "/static/home.html", None).await.status_code,
+            StatusCode::Forbidden
+        );
+        assert_eq!(
+            get(&rejecting, "/static/index.html", None)
+                .await
+                .status_code,
+            StatusCode::OK
+        );
+
+        let following = router(StaticFiles::new(site.root()).symlinks(Symlinks::Follow));
+        let response = get(&following, "/static/escape.txt", None).await;
+        assert_eq!(response.status_code, StatusCode::OK);
+        assert_eq!(body(&response), "secret");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hides_dotfiles_reached_through_symlinks() {
+        let site = Site::new();
+        site.link(&site.root().join(".env"), "config");
+        site.link(&site.root().join(".git"), "repo");
+
+        for symlinks in [Symlinks::FollowWithinRoot, Symlinks::Follow] {
+            let router = router(StaticFiles::new(site.root()).symlinks(symlinks));
+            assert_eq!(
+                get(&router, "/static/config", None).await.status_code,
+                StatusCode::NotFound
+            );
+            assert_eq!(
+                get(&router, "/static/repo/config", None).await.status_code,
+                StatusCode::NotFound
+            );
+        }
+
+        let serving = router(StaticFiles::new(site.root()).hidden_files(HiddenFiles::Serve));
+        assert_eq!(
+            get(&serving, "/static/config", None).await.status_code,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn serves_byte_ranges() {
+        let site = Site::new();
+        let router = router(StaticFiles::new(site.root()));
+
+        let response = get(&router, "/static/index.html", Some("bytes=2-5")).await;
+        assert_eq!(response.status_code, StatusCode::PartialContent);
+        assert_eq!(response.headers["Content-Range"], "bytes 2-5/10");
+        assert_eq!(response.headers["Accept-Ranges"], "bytes");
+        assert_eq!(body(&response), "2345");
+
+        let response = get(&router, "/static/index.html", Some("bytes=-3")).await;
+        assert_eq!(response.headers["Content-Range"], "bytes 7-9/10");
+        assert_eq!(body(&response), "789");
+
+        let response = get(&router, "/static/index.html", Some("bytes=20-30")).await;
+        assert_eq!(response.status_code, StatusCode::RangeNotSatisfiable);
+        assert_eq!(response.headers["Content-Range"], "bytes */10");
+
+        // Malformed ranges are ignored rather than refused
+        let response = get(&router, "/static/index.html", Some("lines=1-2")).await;
+        assert_eq!(response.status_code, StatusCode::OK);
+        assert_eq!(body(&response), "0123456789");
+    }
+
+    #[tokio::test]
+    async fn serves_multiple_ranges_as_multipart() {
+        let site = Site::new();
+        let router = router(StaticFiles::new(site.root()).cache(1024, 4096));
+        let response = get(&router, "/static/index.html", Some("bytes=0-1, 8-9")).await;
+        assert_eq!(response.status_code, StatusCode::PartialContent);
+        let content_type = &response.headers["Content-Type"];
+        assert!(content_type.starts_with("multipart/byteranges; boundary="));
+        let body = body(&response);
+        assert!(
+            body.contains("Content-Range: bytes 0-1/10\r\n\r\n01\r\n"),
+            "{}",
+            body
+        );
+        assert!(
+            body.contains("Content-Range: bytes 8-9/10\r\n\r\n89\r\n"),
+            "{}",
+            body
+        );
+    }
+}
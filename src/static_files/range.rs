@@ -0,0 +1,224 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::Stream;
+
+/// Most ranges answered in one `multipart/byteranges` response; requests for more get the
+/// whole file, so a single request can't fan out into thousands of tiny parts.
+const MAX_RANGES: usize = 32;
+
+/// Size of the reads that feed a multipart body from disk.
+const READ_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// The part of a file a request asks for.
+pub(crate) enum ByteRange {
+    /// No usable range was given, so the whole file is sent.
+    Full,
+    /// The byte offsets to send.
+    Partial(Range<u64>),
+    /// Several byte ranges, sent as `multipart/byteranges`.
+    Multiple(Vec<Range<u64>>),
+    /// Every range lies entirely past the end of the file.
+    Unsatisfiable,
+}
+
+/// Parses a `Range` header against a file of `len` bytes.
+///
+/// Only the `bytes` unit is supported; other units, malformed headers, more than
+/// [`MAX_RANGES`] ranges and overlapping ranges fall back to the whole file, which the
+/// specification allows. Ranges past the end of the file are dropped.
+pub(crate) fn byte_range(header: &str, len: u64) -> ByteRange {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+
+    let mut ranges = Vec::new();
+    for spec in spec
+        .split(',')
+        .map(str::trim)
+        .filter(|spec| !spec.is_empty())
+    {
+        match parse_range(spec, len) {
+            Ok(Some(range)) => ranges.push(range),
+            Ok(None) => {}
+            Err(()) => return ByteRange::Full,
+        }
+    }
+    if ranges.len() > MAX_RANGES || overlaps(&ranges) {
+        return ByteRange::Full;
+    }
+
+    match ranges.len() {
+        0 => ByteRange::Unsatisfiable,
+        1 => ByteRange::Partial(ranges.remove(0)),
+        _ => ByteRange::Multiple(ranges),
+    }
+}
+
+/// Parses one range of a `Range` header.
+///
+/// # Returns
+///
+/// The range clamped to the file, `None` if it lies past the end of the file, or an error if
+/// it is malformed.
+fn parse_range(spec: &str, len: u64) -> Result<Option<Range<u64>>, ()> {
+    let (start, end) = spec.split_once('-').ok_or(())?;
+    let range = match (start.trim(), end.trim()) {
+        // `-500`: the last 500 bytes
+        ("", suffix) => match suffix.parse::<u64>().map_err(|_| ())? {
+            0 => return Ok(None),
+            suffix => len.saturating_sub(suffix)..len,
+        },
+        // `500-`: from byte 500 to the end
+        (start, "") => start.parse::<u64>().map_err(|_| ())?..len,
+        (start, end) => {
+            let start = start.parse::<u64>().map_err(|_| ())?;
+            let end = end.parse::<u64>().map_err(|_| ())?;
+            if start > end {
+                return Err(());
+            }
+            start..end.saturating_add(1).min(len)
+        }
+    };
+    Ok((range.start < len).then_some(range))
+}
+
+/// Returns whether any two ranges share a byte.
+fn overlaps(ranges: &[Range<u64>]) -> bool {
+    let mut sorted = ranges.to_vec();
+    sorted.sort_by_key(|range| range.start);
+    sorted.windows(2).any(|pair| pair[1].start < pair[0].end)
+}
+
+/// A `multipart/byteranges` body holding several ranges of one file.
+pub(crate) struct Multipart {
+    boundary: String,
+    part_content_type: String,
+    len: u64,
+    ranges: Vec<Range<u64>>,
+}
+
+impl Multipart {
+    /// Prepares a body for `ranges` of a file of `len` bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `ranges` - The byte ranges to send, in the order they were requested.
+    /// * `part_content_type` - The file's own content type, repeated in every part.
+    /// * `len` - The file's full length.
+    pub(crate) fn new(ranges: Vec<Range<u64>>, part_content_type: &str, len: u64) -> Self {
+        Multipart {
+            boundary: boundary(),
+            part_content_type: part_content_type.to_string(),
+            len,
+            ranges,
+        }
+    }
+
+    /// Returns the `Content-Type` of the whole response.
+    pub(crate) fn content_type(&self) -> String {
+        format!("multipart/byteranges; boundary={}", self.boundary)
+    }
+
+    /// Builds the body from a file's contents held in memory.
+    pub(crate) fn to_bytes(&self, contents: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for range in &self.ranges {
+            body.extend_from_slice(self.part_header(range).as_bytes());
+            body.extend_from_slice(&contents[range.start as usize..range.end as usize]);
+        }
+        body.extend_from_slice(self.closing().as_bytes());
+        body
+    }
+
+    /// Streams the body from an open file, reading each range in chunks as it is sent.
+    pub(crate) fn into_stream(self, file: File) -> impl Stream<Item = Vec<u8>> + Send + 'static {
+        let mut pieces = VecDeque::new();
+        for range in &self.ranges {
+            pieces.push_back(Piece::Text(self.part_header(range).into_bytes()));
+            pieces.push_back(Piece::File(range.clone()));
+        }
+        pieces.push_back(Piece::Text(self.closing().into_bytes()));
+
+        let file = Arc::new(file);
+        futures::stream::unfold(pieces, move |mut pieces| {
+            let file = file.clone();
+            async move {
+                loop {
+                    match pieces.pop_front()? {
+                        Piece::Text(text) => return Some((text, pieces)),
+                        Piece::File(range) if range.is_empty() => continue,
+                        Piece::File(range) => {
+                            let chunk = read_chunk(file, range.clone()).await?;
+                            pieces.push_front(Piece::File(
+                                range.start + chunk.len() as u64..range.end,
+                            ));
+                            return Some((chunk, pieces));
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Returns the delimiter and headers that start the part for `range`.
+    fn part_header(&self, range: &Range<u64>) -> String {
+        format!(
+            "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+            self.boundary,
+            self.part_content_type,
+            range.start,
+            range.end - 1,
+            self.len
+        )
+    }
+
+    /// Returns the delimiter that ends the body.
+    fn closing(&self) -> String {
+        format!("\r\n--{}--\r\n", self.boundary)
+    }
+}
+
+/// A piece of a streamed multipart body.
+enum Piece {
+    Text(Vec<u8>),
+    File(Range<u64>),
+}
+
+/// Reads the start of `range` from `file` without blocking the executor.
+///
+/// # Returns
+///
+/// Up to [`READ_CHUNK_SIZE`] bytes, or `None` if the read failed or the file got shorter.
+async fn read_chunk(file: Arc<File>, range: Range<u64>) -> Option<Vec<u8>> {
+    tokio::task::spawn_blocking(move || {
+        let mut file = &*file;
+        file.seek(SeekFrom::Start(range.start)).ok()?;
+        let mut chunk = Vec::new();
+        let want = (range.end - range.start).min(READ_CHUNK_SIZE);
+        file.take(want).read_to_end(&mut chunk).ok()?;
+        (!chunk.is_empty()).then_some(chunk)
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// Generates a boundary that is unique per response, so it is vanishingly unlikely to occur
+/// in the file's contents.
+fn boundary() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_nanos() as u64);
+    format!(
+        "byteranges_{:016x}{:08x}",
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
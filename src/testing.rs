@@ -0,0 +1,442 @@
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::http::{Method, Response, StatusCode};
+use crate::server::{Server, ServerHandle};
+use crate::service::Service;
+
+/// How long a test request may take before it fails instead of hanging the test.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long in-flight requests get to finish when a test server shuts down.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A server on an ephemeral local port, for integration tests.
+///
+/// The server runs on the current tokio runtime and is stopped when dropped; call
+/// [`shutdown`](TestServer::shutdown) to wait for it to finish instead.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{Request, Response, Router, StatusCode, testing::TestServer};
+/// # async fn handle_hello(request: Request) -> Result<Response, String> {
+/// #     let name = request.query_param("name").map_or("World", |name| name.as_str());
+/// #     let mut response = Response::new(StatusCode::OK);
+/// #     response.set_body(format!("Hello, {}!", name).into_bytes());
+/// #     Ok(response)
+/// # }
+/// #[tokio::test]
+/// async fn greets_by_name() {
+///     let server = TestServer::spawn(Router::new().get("/hello", handle_hello)).unwrap();
+///     let response = server.client().get("/hello?name=Ada").await.unwrap();
+///     response.assert_status(StatusCode::OK);
+///     assert_eq!(response.text(), "Hello, Ada!");
+///     server.shutdown().await.unwrap();
+/// }
+/// ```
+pub struct TestServer {
+    handle: Option<ServerHandle>,
+    addr: SocketAddr,
+}
+
+impl TestServer {
+    /// Starts serving `service`, e.g. a router or a service with middleware, on
+    /// `127.0.0.1` with a port picked by the OS. Must be called from within a tokio runtime.
+    ///
+    /// # Returns
+    ///
+    /// The running server, or an error if it couldn't bind.
+    pub fn spawn<S>(service: S) -> Result<Self, String>
+    where
+        S: Service<Response = Response, Error = String> + Send + Clone + 'static,
+        S::Future: Send + 'static,
+    {
+        let handle = Server::new("127.0.0.1:0", service).spawn()?;
+        Ok(TestServer {
+            addr: handle.local_addr(),
+            handle: Some(handle),
+        })
+    }
+
+    /// Returns the address the server listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the full URL of `path` on the server, e.g. for clients other than
+    /// [`TestClient`].
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    /// Returns a client sending requests to the server.
+    pub fn client(&self) -> TestClient {
+        TestClient::new(self.addr)
+    }
+
+    /// Stops the server, giving in-flight requests a few seconds to finish, and waits for it
+    /// to shut down.
+    pub async fn shutdown(mut self) -> Result<(), String> {
+        let Some(handle) = self.handle.take() else {
+            return Ok(());
+        };
+        handle.graceful_stop(SHUTDOWN_TIMEOUT);
+        handle.await
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(handle) = &self.handle {
+            handle.stop();
+        }
+    }
+}
+
+/// A minimal HTTP/1.1 client for tests, sending each request on a new connection.
+///
+/// Requests run on the blocking pool, so tests can await them on the runtime the server
+/// runs on.
+///
+/// # Examples
+///
+/// ```
+/// # use http_server::{StatusCode, testing::TestServer};
+/// # use serde::Deserialize;
+/// # use serde_json::json;
+/// # #[derive(Deserialize)]
+/// # struct User { name: String }
+/// # async fn example(server: TestServer) -> Result<(), String> {
+/// let client = server.client().header("Authorization", "Bearer test-token");
+/// let user: User = client
+///     .post_json("/users", &json!({"name": "Ada"}))
+///     .await?
+///     .assert_status(StatusCode::Created)
+///     .json();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TestClient {
+    addr: SocketAddr,
+    headers: Vec<(String, String)>,
+}
+
+impl TestClient {
+    /// Creates a client sending requests to the server at `addr`.
+    pub fn new(addr: SocketAddr) -> Self {
+        TestClient {
+            addr,
+            headers: Vec::new(),
+        }
+    }
+
+    /// Sends the header `name` with every request, e.g. credentials.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sends a `GET` request for `path`, which may include a query string.
+    pub async fn get(&self, path: &str) -> Result<TestResponse, String> {
+        self.request(Method::Get, path, &[], Vec::new()).await
+    }
+
+    /// Sends a `DELETE` request for `path`.
+    pub async fn delete(&self, path: &str) -> Result<TestResponse, String> {
+        self.request(Method::Delete, path, &[], Vec::new()).await
+    }
+
+    /// Sends a `POST` request with `value` as its JSON body.
+    pub async fn post_json<T: Serialize>(
+        &self,
+        path: &str,
+        value: &T,
+    ) -> Result<TestResponse, String> {
+        self.json(Method::Post, path, value).await
+    }
+
+    /// Sends a `PUT` request with `value` as its JSON body.
+    pub async fn put_json<T: Serialize>(
+        &self,
+        path: &str,
+        value: &T,
+    ) -> Result<TestResponse, String> {
+        self.json(Method::Put, path, value).await
+    }
+
+    /// Sends a request with `value` as its JSON body.
+    async fn json<T: Serialize>(
+        &self,
+        method: Method,
+        path: &str,
+        value: &T,
+    ) -> Result<TestResponse, String> {
+        let body =
+            serde_json::to_vec(value).map_err(|e| format!("Failed to serialize body: {}", e))?;
+        self.request(method, path, &[("Content-Type", "application/json")], body)
+            .await
+    }
+
+    /// Sends a request and reads the whole response.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The request method.
+    /// * `path` - The request target, e.g. `/users?limit=5`.
+    /// * `headers` - Headers sent in addition to the client's own; `Host`, `Content-Length`
+    ///   and `Connection` are filled in.
+    /// * `body` - The request body.
+    ///
+    /// # Returns
+    ///
+    /// The response, or an error if the connection failed or the response is malformed.
+    pub async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<TestResponse, String> {
+        let mut raw = format!("{} {} HTTP/1.1\r\nHost: {}\r\n", method, path, self.addr);
+        for (name, value) in self
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .chain(headers.iter().copied())
+        {
+            let _ = write!(raw, "{}: {}\r\n", name, value);
+        }
+        let _ = write!(
+            raw,
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        );
+        let mut raw = raw.into_bytes();
+        raw.extend_from_slice(&body);
+
+        let addr = self.addr;
+        let is_head = method == Method::Head;
+        tokio::task::spawn_blocking(move || exchange(addr, &raw, is_head))
+            .await
+            .map_err(|e| format!("Request task failed: {}", e))?
+    }
+}
+
+/// Sends a raw request on a new connection and reads the response until the server closes it.
+fn exchange(addr: SocketAddr, request: &[u8], is_head: bool) -> Result<TestResponse, String> {
+    let mut stream =
+        TcpStream::connect(addr).map_err(|e| format!("Failed to connect to {}: {}", addr, e))?;
+    stream
+        .set_read_timeout(Some(REQUEST_TIMEOUT))
+        .and_then(|()| stream.set_write_timeout(Some(REQUEST_TIMEOUT)))
+        .and_then(|()| stream.write_all(request))
+        .map_err(|e| format!("Failed to send request: {}", e))?;
+    let mut raw = Vec::new();
+    stream
+        .read_to_end(&mut raw)
+        .map_err(|e| format!("Failed to read response: {}", e))?;
+    TestResponse::parse(&raw, is_head)
+}
+
+/// A response received by a [`TestClient`].
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    pub status: u16,
+    /// The headers in the order received, so repeated ones like `Set-Cookie` are all kept.
+    pub headers: Vec<(String, String)>,
+    /// The body, with chunked transfer encoding removed.
+    pub body: Vec<u8>,
+}
+
+impl TestResponse {
+    /// Parses a complete raw response.
+    fn parse(raw: &[u8], is_head: bool) -> Result<Self, String> {
+        let head_len = raw
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .ok_or("Incomplete response head")?;
+        let head = std::str::from_utf8(&raw[..head_len])
+            .map_err(|_| "Response head isn't valid UTF-8".to_string())?;
+        let mut lines = head.split("\r\n");
+        let status = lines
+            .next()
+            .and_then(|line| line.split(' ').nth(1))
+            .and_then(|status| status.parse().ok())
+            .ok_or("Malformed status line")?;
+        let headers: Vec<(String, String)> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.to_string(), value.trim().to_string()))
+            .collect();
+
+        let mut response = TestResponse {
+            status,
+            headers,
+            body: Vec::new(),
+        };
+        if is_head {
+            return Ok(response);
+        }
+        let body = &raw[head_len + 4..];
+        let chunked = response
+            .header("Transfer-Encoding")
+            .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"));
+        response.body = if chunked {
+            dechunk(body)?
+        } else {
+            match response.header("Content-Length") {
+                Some(length) => {
+                    let length: usize = length.parse().map_err(|_| "Malformed Content-Length")?;
+                    body.get(..length)
+                        .ok_or("Truncated response body")?
+                        .to_vec()
+                }
+                None => body.to_vec(),
+            }
+        };
+        Ok(response)
+    }
+
+    /// Looks up a header by name, ignoring ASCII case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns every value of the header `name`, e.g. all `Set-Cookie` headers.
+    pub fn header_values(&self, name: &str) -> Vec<&str> {
+        self.headers
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// Returns the body as text, replacing invalid UTF-8.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Deserializes the JSON body.
+    ///
+    /// # Panics
+    ///
+    /// If the body isn't JSON of type `T`, showing the body.
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.body).unwrap_or_else(|e| {
+            panic!(
+                "Response body isn't the expected JSON: {}\nBody: {}",
+                e,
+                self.text()
+            )
+        })
+    }
+
+    /// Checks the response status.
+    ///
+    /// # Panics
+    ///
+    /// If the status isn't `expected`, showing the body.
+    pub fn assert_status(&self, expected: StatusCode) -> &Self {
+        assert_eq!(
+            self.status,
+            expected as u16,
+            "Unexpected status\nBody: {}",
+            self.text()
+        );
+        self
+    }
+
+    /// Checks that the header `name` has the value `expected`.
+    ///
+    /// # Panics
+    ///
+    /// If the header is missing or has another value.
+    pub fn assert_header(&self, name: &str, expected: &str) -> &Self {
+        assert_eq!(
+            self.header(name),
+            Some(expected),
+            "Unexpected {} header",
+            name
+        );
+        self
+    }
+}
+
+/// Removes chunked transfer encoding from a body.
+fn dechunk(mut body: &[u8]) -> Result<Vec<u8>, String> {
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = body
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or("Truncated chunk size")?;
+        let size = std::str::from_utf8(&body[..line_end])
+            .ok()
+            .and_then(|line| line.split(';').next())
+            .and_then(|size| usize::from_str_radix(size.trim(), 16).ok())
+            .ok_or("Malformed chunk size")?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(decoded);
+        }
+        decoded.extend_from_slice(body.get(..size).ok_or("Truncated chunk")?);
+        body = body.get(size + 2..).ok_or("Truncated chunk")?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::http::Request;
+    use crate::router::Router;
+
+    #[test]
+    fn parses_chunked_responses() {
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nSet-Cookie: a=1\r\n\
+                    Set-Cookie: b=2\r\n\r\n5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\n\r\n";
+        let response = TestResponse::parse(raw, false).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.text(), "hello world");
+        assert_eq!(response.header_values("set-cookie"), ["a=1", "b=2"]);
+        assert!(TestResponse::parse(b"HTTP/1.1 200 OK\r\n", false).is_err());
+        assert!(dechunk(b"5\r\nhel").is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn round_trips_json_through_a_server() {
+        async fn echo(request: Request) -> Result<Response, String> {
+            let mut response = Response::new(StatusCode::Created);
+            response.set_content_type("application/json");
+            response.set_body(request.body);
+            Ok(response)
+        }
+        let server = TestServer::spawn(Router::new().post("/echo", echo)).unwrap();
+        let body: Value = server
+            .client()
+            .post_json("/echo", &json!({"name": "Ada"}))
+            .await
+            .unwrap()
+            .assert_status(StatusCode::Created)
+            .assert_header("Content-Type", "application/json")
+            .json();
+        assert_eq!(body, json!({"name": "Ada"}));
+        server
+            .client()
+            .get("/missing")
+            .await
+            .unwrap()
+            .assert_status(StatusCode::NotFound);
+        server.shutdown().await.unwrap();
+    }
+}